@@ -196,6 +196,11 @@ fn get_dda_scale_factors(ray: ptr<function, Line>) -> vec3f {
     );
 }
 
+//crate::spatial::raytracing::float_error_tolerance_for
+fn float_error_tolerance_for(size: f32) -> f32 {
+    return max(FLOAT_ERROR_TOLERANCE, size * 1.1920929e-7 * 4.);
+}
+
 //crate::octree::raytracing::dda_step_to_next_sibling
 fn dda_step_to_next_sibling(
     ray: ptr<function, Line>,
@@ -221,14 +226,15 @@ fn dda_step_to_next_sibling(
     );
     *ray_current_distance = min(d.x, min(d.y, d.z));
 
+    let tolerance = float_error_tolerance_for(f32(octree_meta_data.octree_size));
     var result = vec3f(0., 0., 0.);
-    if abs(*ray_current_distance - d.x) < FLOAT_ERROR_TOLERANCE {
+    if abs(*ray_current_distance - d.x) < tolerance {
         result.x = sign((*ray).direction).x;
     }
-    if abs(*ray_current_distance - d.y) < FLOAT_ERROR_TOLERANCE {
+    if abs(*ray_current_distance - d.y) < tolerance {
         result.y = sign((*ray).direction).y;
     }
-    if abs(*ray_current_distance - d.z) < FLOAT_ERROR_TOLERANCE {
+    if abs(*ray_current_distance - d.z) < tolerance {
         result.z = sign((*ray).direction).z;
     }
     return result;
@@ -307,6 +313,12 @@ struct BrickHit{
     flat_index: u32,
 }
 
+// Number of rays cast, missed, and node and brick steps taken by the current invocation
+var<private> ray_count: u32 = 0u;
+var<private> ray_misses: u32 = 0u;
+var<private> ray_node_steps: u32 = 0u;
+var<private> ray_brick_steps: u32 = 0u;
+
 fn traverse_brick(
     ray: ptr<function, Line>,
     ray_current_distance: ptr<function,f32>,
@@ -337,6 +349,7 @@ fn traverse_brick(
     var safety = 0u;
     */// --- DEBUG ---
     loop{
+        ray_brick_steps += 1u;
         /*// +++ DEBUG +++
         safety += 1u;
         if(safety > u32(f32(dimension) * sqrt(30.))) {
@@ -563,6 +576,7 @@ fn get_by_ray(ray: ptr<function, Line>) -> OctreeRayIntersection {
         var safety = 0;
         */// --- DEBUG ---
         while(!node_stack_is_empty(node_stack_meta)) {
+            ray_node_steps += 1u;
             /*// +++ DEBUG +++
             safety += 1;
             if(f32(safety) > f32(octree_meta_data.octree_size) * sqrt(30.)) {
@@ -849,6 +863,105 @@ fn get_by_ray(ray: ptr<function, Line>) -> OctreeRayIntersection {
     return OctreeRayIntersection(false, vec4f(missing_data_color, 1.), 0, vec3f(0.), vec3f(0., 0., 1.));
 }
 
+// Cheap integer hash of a voxel position, based on PCG
+fn hash_voxel_position(position: vec3u) -> u32 {
+    var state = (position.x * 73856093u) ^ (position.y * 19349663u) ^ (position.z * 83492791u);
+    state = state * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+/// Provides the position of the voxel containing the given hit
+fn hit_voxel_position(collision_point: vec3f, impact_normal: vec3f) -> vec3u {
+    return vec3u(max(floor(collision_point - impact_normal * 0.5), vec3f(0.)));
+}
+
+/// Perturbs the albedo of the voxel containing the given hit
+fn vary_albedo(albedo: vec3f, voxel_position: vec3u) -> vec3f {
+    if 0. == octree_meta_data.albedo_variation {
+        return albedo;
+    }
+    let variation = f32(hash_voxel_position(voxel_position)) / f32(0xFFFFFFFFu) * 2. - 1.;
+    return clamp(albedo * (1. + variation * octree_meta_data.albedo_variation), vec3f(0.), vec3f(1.));
+}
+
+/// Modulates the albedo of foliage voxels as if they were swaying in the wind
+fn sway_albedo(albedo: vec3f, content: u32, voxel_position: vec3u) -> vec3f {
+    if 0 == (content & octree_meta_data.foliage_mask) {
+        return albedo;
+    }
+    // Neighbouring voxels sway with a slightly different phase, like a wave
+    let phase = f32(voxel_position.x + voxel_position.z) * 0.3
+        + f32(hash_voxel_position(voxel_position) % 16u) * 0.05;
+    let sway = sin(octree_meta_data.animation_time * 2. + phase);
+    return clamp(albedo * (1. + sway * 0.15), vec3f(0.), vec3f(1.));
+}
+
+/// Darkens parts of damaged voxels as cracks, covering more of the voxel the more damage it has
+fn crack_albedo(albedo: vec3f, content: u32, voxel_position: vec3u, collision_point: vec3f) -> vec3f {
+    if 0 == (content & octree_meta_data.damage_mask) {
+        return albedo;
+    }
+    let damage_shift = countTrailingZeros(octree_meta_data.damage_mask);
+    let max_damage = octree_meta_data.damage_mask >> damage_shift;
+    let damage = f32((content & octree_meta_data.damage_mask) >> damage_shift) / f32(max_damage);
+
+    // The voxel is divided into 4x4x4 cells, each of them cracking above a random damage
+    let cell = vec3u(clamp((collision_point - vec3f(voxel_position)) * 4., vec3f(0.), vec3f(3.)));
+    let crack_threshold = f32(hash_voxel_position(voxel_position * 4u + cell) % 256u) / 255.;
+    if crack_threshold < damage * 0.75 {
+        return albedo * 0.35;
+    }
+    return albedo;
+}
+
+/// The light arriving at the hit surface, baked into the user data of the voxel if available
+fn shade(content: u32, impact_normal: vec3f) -> f32 {
+    if 0 == octree_meta_data.light_mask {
+        return dot(impact_normal, vec3f(-0.5,0.5,-0.5)) / 2. + 0.5;
+    }
+    // The light is stored inverted, see crate::octree::raytracing::LightLayer
+    let light_shift = countTrailingZeros(octree_meta_data.light_mask);
+    let max_level = octree_meta_data.light_mask >> light_shift;
+    return 1. - f32((content & octree_meta_data.light_mask) >> light_shift) / f32(max_level);
+}
+
+const TONEMAPPER_REINHARD = 1u;
+const TONEMAPPER_ACES = 2u;
+
+/// Applies exposure, tonemapping and gamma correction to the rendered color
+fn tonemap(color: vec3f) -> vec3f {
+    var result = color * octree_meta_data.exposure;
+    if TONEMAPPER_REINHARD == octree_meta_data.tonemapper {
+        result = result / (result + vec3f(1.));
+    } else if TONEMAPPER_ACES == octree_meta_data.tonemapper {
+        result = (result * (2.51 * result + 0.03)) / (result * (2.43 * result + 0.59) + 0.14);
+    }
+    return pow(clamp(result, vec3f(0.), vec3f(1.)), vec3f(1. / octree_meta_data.gamma));
+}
+
+/// Maps the tonemapped color through the color grading LUT of the view
+fn grade(color: vec3f) -> vec3f {
+    let lut_size = vec3i(textureDimensions(color_grading_lut));
+    let position = clamp(color, vec3f(0.), vec3f(1.)) * vec3f(lut_size - vec3i(1));
+    let low = vec3i(floor(position));
+    let high = min(low + vec3i(1), lut_size - vec3i(1));
+    let t = position - vec3f(low);
+    let c000 = textureLoad(color_grading_lut, vec3i(low.x, low.y, low.z), 0).rgb;
+    let c100 = textureLoad(color_grading_lut, vec3i(high.x, low.y, low.z), 0).rgb;
+    let c010 = textureLoad(color_grading_lut, vec3i(low.x, high.y, low.z), 0).rgb;
+    let c110 = textureLoad(color_grading_lut, vec3i(high.x, high.y, low.z), 0).rgb;
+    let c001 = textureLoad(color_grading_lut, vec3i(low.x, low.y, high.z), 0).rgb;
+    let c101 = textureLoad(color_grading_lut, vec3i(high.x, low.y, high.z), 0).rgb;
+    let c011 = textureLoad(color_grading_lut, vec3i(low.x, high.y, high.z), 0).rgb;
+    let c111 = textureLoad(color_grading_lut, vec3i(high.x, high.y, high.z), 0).rgb;
+    return mix(
+        mix(mix(c000, c100, t.x), mix(c010, c110, t.x), t.y),
+        mix(mix(c001, c101, t.x), mix(c011, c111, t.x), t.y),
+        t.z
+    );
+}
+
 struct Voxelement {
     albedo_index: u32,
     content: u32,
@@ -870,12 +983,24 @@ struct OctreeMetaData {
     ambient_light_position: vec3f,
     octree_size: u32,
     voxel_brick_dim: u32,
+    albedo_variation: f32,
+    animation_time: f32,
+    foliage_mask: u32,
+    damage_mask: u32,
+    light_mask: u32,
+    exposure: f32,
+    gamma: f32,
+    tonemapper: u32,
+    collect_stats: u32,
 }
 
 struct Viewport {
     origin: vec3f,
     direction: vec3f,
+    up: vec3f,
     w_h_fov: vec3f,
+    pixel_jitter_x: f32,
+    pixel_jitter_y: f32,
 }
 
 @group(0) @binding(0)
@@ -887,6 +1012,16 @@ var<uniform> viewport: Viewport;
 @group(0) @binding(2)
 var<storage, read_write> node_requests: array<atomic<u32>>;
 
+@group(0) @binding(3)
+var color_grading_lut: texture_3d<f32>;
+
+// Counters of the current frame, see crate::octree::raytracing::bevy::types::SvxFrameStats
+@group(0) @binding(4)
+var<storage, read_write> frame_stats: array<atomic<u32>, 4>;
+
+// Counters of the current workgroup, summed into frame_stats once per workgroup
+var<workgroup> workgroup_stats: array<atomic<u32>, 4>;
+
 @group(1) @binding(0)
 var<uniform> octree_meta_data: OctreeMetaData;
 
@@ -909,36 +1044,71 @@ var<storage, read_write> color_palette: array<vec4f>;
 @compute @workgroup_size(8, 8, 1)
 fn update(
     @builtin(global_invocation_id) invocation_id: vec3<u32>,
-    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+    @builtin(local_invocation_index) local_invocation_index: u32,
 ) {
+    render_pixel(invocation_id);
+    if octree_meta_data.collect_stats != 0u {
+        atomicAdd(&workgroup_stats[0], ray_count);
+        atomicAdd(&workgroup_stats[1], ray_node_steps);
+        atomicAdd(&workgroup_stats[2], ray_brick_steps);
+        atomicAdd(&workgroup_stats[3], ray_misses);
+        workgroupBarrier();
+        if local_invocation_index == 0u {
+            for (var i = 0u; i < 4u; i++) {
+                atomicAdd(&frame_stats[i], atomicLoad(&workgroup_stats[i]));
+            }
+        }
+    }
+}
+
+fn render_pixel(invocation_id: vec3<u32>) {
+    // Workgroups are dispatched to cover the whole output, so some invocations may lie outside of it
+    let output_size = textureDimensions(output_texture);
+    if invocation_id.x >= output_size.x || invocation_id.y >= output_size.y {
+        return;
+    }
+    // Matches Viewport::ray_for_pixel on the CPU
+    let pixel = vec2f(
+        f32(invocation_id.x) + viewport.pixel_jitter_x,
+        f32(invocation_id.y) + viewport.pixel_jitter_y
+    );
+    let viewport_right = normalize(cross(viewport.up, viewport.direction));
     let ray_endpoint =
         (
             viewport.origin
             + (viewport.direction * viewport.w_h_fov.z)
-            - (
-                normalize(cross(vec3f(0., 1., 0.), viewport.direction))
-                * (viewport.w_h_fov.x / 2.)
-            )
-            - (vec3f(0., 1., 0.) * (viewport.w_h_fov.y / 2.))
+            - (viewport_right * (viewport.w_h_fov.x / 2.))
+            - (viewport.up * (viewport.w_h_fov.y / 2.))
         ) // Viewport bottom left
         + (
-            normalize(cross(vec3f(0., 1., 0.), viewport.direction))
-            * viewport.w_h_fov.x
-            * (f32(invocation_id.x) / f32(num_workgroups.x * 8))
+            viewport_right * viewport.w_h_fov.x
+            * (pixel.x / f32(output_size.x))
         ) // Viewport right direction
         + (
-            vec3f(0., 1., 0.) * viewport.w_h_fov.y
-            * (1. - (f32(invocation_id.y) / f32(num_workgroups.y * 8)))
+            viewport.up * viewport.w_h_fov.y
+            * (1. - (pixel.y / f32(output_size.y)))
         ) // Viewport up direction
         ;
     var ray = Line(ray_endpoint, normalize(ray_endpoint - viewport.origin));
     var rgb_result = vec3f(0.5,0.5,0.5);
     var ray_result = get_by_ray(&ray);
+    ray_count = 1u;
+    if !ray_result.hit {
+        ray_misses = 1u;
+    }
     if ray_result.hit == true {
+        let voxel_position = hit_voxel_position(ray_result.collision_point, ray_result.impact_normal);
         rgb_result = (
-            ray_result.albedo.rgb * (
-                dot(ray_result.impact_normal, vec3f(-0.5,0.5,-0.5)) / 2. + 0.5
-            )
+            crack_albedo(
+                sway_albedo(
+                    vary_albedo(ray_result.albedo.rgb, voxel_position),
+                    ray_result.content,
+                    voxel_position
+                ),
+                ray_result.content,
+                voxel_position,
+                ray_result.collision_point
+            ) * shade(ray_result.content, ray_result.impact_normal)
         ).rgb;
     } else {
         rgb_result = (rgb_result + ray_result.albedo.rgb) / 2.;
@@ -966,7 +1136,7 @@ fn update(
         rgb_result.b += 0.1; // Also color in the area of the octree
     }
     */// --- DEBUG ---
-    textureStore(output_texture, vec2u(invocation_id.xy), vec4f(rgb_result, 1.));
+    textureStore(output_texture, vec2u(invocation_id.xy), vec4f(grade(tonemap(rgb_result)), 1.));
 }
 
 //crate::spatial::math::offset_region
@@ -123,6 +123,26 @@ fn criterion_benchmark(c: &mut criterion::Criterion) {
             });
         });
     }
+
+    {
+        // Fragment the node pool with interleaved inserts and clears before saving
+        let tree_size = 128;
+        let mut tree = Octree::<Albedo, 4>::new(tree_size).ok().unwrap();
+        for i in 0..tree_size {
+            let position = V3c::new(i, (i * 7) % tree_size, (i * 13) % tree_size);
+            tree.insert(&position, 0x00ABCDEF.into()).ok().unwrap();
+            if 0 == i % 3 {
+                tree.clear(&position).ok().unwrap();
+            }
+        }
+        let bytes = tree.to_bytes();
+
+        c.bench_function("octree from_bytes after edits", |b| {
+            b.iter(|| {
+                let _tree_copy = Octree::<Albedo, 4>::from_bytes(bytes.clone()).ok().unwrap();
+            });
+        });
+    }
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,10 +1,10 @@
-#[cfg(feature = "raytracing")]
+#[cfg(feature = "cpu_render_window")]
 use rand::Rng;
 
-#[cfg(feature = "raytracing")]
-use shocovox_rs::octree::{raytracing::Ray, V3c};
+#[cfg(feature = "cpu_render_window")]
+use shocovox_rs::octree::{raytracing::Viewport, V3c};
 
-#[cfg(feature = "raytracing")]
+#[cfg(feature = "cpu_render_window")]
 #[show_image::main]
 fn main() {
     let voxel_color: Albedo = 0x645097FF.into();
@@ -75,23 +75,13 @@ fn main() {
         angle = angle + velos.x / 10.;
 
         // Set the viewport
-        let origin = V3c::new(angle.sin() * radius, radius, angle.cos() * radius);
-        let viewport_ray = Ray {
-            direction: (V3c::unit(0.) - origin).normalized(),
-            origin,
-        };
-        let viewport_up_direction = V3c::new(0., 1., 0.);
-        let viewport_right_direction = viewport_up_direction
-            .cross(viewport_ray.direction)
-            .normalized();
-        let viewport_width = 4.;
-        let viewport_height = 4.;
-        let viewport_fov = 3.;
-        let pixel_width = viewport_width as f32 / viewport_size_width as f32;
-        let pixel_height = viewport_height as f32 / viewport_size_height as f32;
-        let viewport_bottom_left = viewport_ray.origin + (viewport_ray.direction * viewport_fov)
-            - (viewport_up_direction * (viewport_height / 2.))
-            - (viewport_right_direction * (viewport_width / 2.));
+        let mut viewport = Viewport::default();
+        viewport.w_h_fov = V3c::new(4., 4., 3.);
+        viewport.look_at(
+            V3c::new(angle.sin() * radius, radius, angle.cos() * radius),
+            V3c::unit(0.),
+            V3c::new(0., 1., 0.),
+        );
 
         // define light
         let diffuse_light_normal = V3c::new(0., -1., 1.).normalized();
@@ -104,14 +94,10 @@ fn main() {
         for x in 0..viewport_size_width {
             for y in 0..viewport_size_height {
                 let actual_y_in_image = viewport_size_height - y - 1;
-                //from the origin of the camera to the current point of the viewport
-                let glass_point = viewport_bottom_left
-                    + viewport_right_direction * x as f32 * pixel_width
-                    + viewport_up_direction * y as f32 * pixel_height;
-                let ray = Ray {
-                    origin: viewport_ray.origin,
-                    direction: (glass_point - viewport_ray.origin).normalized(),
-                };
+                let ray = viewport.ray_for_pixel(
+                    [x, actual_y_in_image],
+                    [viewport_size_width, viewport_size_height],
+                );
 
                 use std::io::Write;
                 std::io::stdout().flush().ok().unwrap();
@@ -151,8 +137,8 @@ fn main() {
     }
 }
 
-#[cfg(not(feature = "raytracing"))]
+#[cfg(not(feature = "cpu_render_window"))]
 fn main() {
-    println!("You probably forgot to enable the raytracing feature!");
+    println!("You probably forgot to enable the cpu_render_window feature!");
     //nothing to do when the feature is not enabled
 }
@@ -9,7 +9,7 @@ use iyes_perf_ui::{
 
 #[cfg(feature = "bevy_wgpu")]
 use shocovox_rs::octree::{
-    raytracing::{OctreeGPUHost, Ray, SvxViewSet, Viewport},
+    raytracing::{OctreeGPUHost, SvxViewSet, Viewport},
     Albedo, V3c,
 };
 
@@ -100,17 +100,9 @@ fn setup(mut commands: Commands, images: ResMut<Assets<Image>>) {
 
     let mut host = OctreeGPUHost { tree };
     let mut views = SvxViewSet::default();
-    let output_texture = host.create_new_view(
-        &mut views,
-        45,
-        Viewport {
-            origin,
-            direction: (V3c::new(0., 0., 0.) - origin).normalized(),
-            w_h_fov: V3c::new(10., 10., 3.),
-        },
-        DISPLAY_RESOLUTION,
-        images,
-    );
+    let mut viewport = Viewport::default();
+    viewport.look_at(origin, V3c::new(0., 0., 0.), V3c::new(0., 1., 0.));
+    let output_texture = host.create_new_view(&mut views, 45, viewport, DISPLAY_RESOLUTION, images);
     commands.insert_resource(host);
     commands.insert_resource(views);
     commands.spawn(Sprite::from_image(output_texture));
@@ -147,13 +139,15 @@ fn rotate_camera(angles_query: Query<&mut DomePosition>, view_set: ResMut<SvxVie
     let (yaw, roll) = (angles_query.single().yaw, angles_query.single().roll);
     let radius = angles_query.single().radius;
     let mut tree_view = view_set.views[0].lock().unwrap();
-    tree_view.spyglass.viewport.origin = V3c::new(
-        radius / 2. + yaw.sin() * radius,
-        radius + roll.sin() * radius * 2.,
-        radius / 2. + yaw.cos() * radius,
+    tree_view.spyglass.viewport.look_at(
+        V3c::new(
+            radius / 2. + yaw.sin() * radius,
+            radius + roll.sin() * radius * 2.,
+            radius / 2. + yaw.cos() * radius,
+        ),
+        V3c::unit(radius / 2.),
+        V3c::new(0., 1., 0.),
     );
-    tree_view.spyglass.viewport.direction =
-        (V3c::unit(radius / 2.) - tree_view.spyglass.viewport.origin).normalized();
 }
 
 #[cfg(feature = "bevy_wgpu")]
@@ -175,18 +169,7 @@ fn handle_zoom(
     };
     if keys.pressed(KeyCode::Tab) {
         // Render the current view with CPU
-        let viewport_up_direction = V3c::new(0., 1., 0.);
-        let viewport_right_direction = viewport_up_direction
-            .cross(tree_view.spyglass.viewport.direction)
-            .normalized();
-        let pixel_width =
-            tree_view.spyglass.viewport.w_h_fov.x as f32 / DISPLAY_RESOLUTION[0] as f32;
-        let pixel_height =
-            tree_view.spyglass.viewport.w_h_fov.y as f32 / DISPLAY_RESOLUTION[1] as f32;
-        let viewport_bottom_left = tree_view.spyglass.viewport.origin
-            + (tree_view.spyglass.viewport.direction * tree_view.spyglass.viewport.w_h_fov.z)
-            - (viewport_up_direction * (tree_view.spyglass.viewport.w_h_fov.y / 2.))
-            - (viewport_right_direction * (tree_view.spyglass.viewport.w_h_fov.x / 2.));
+        let viewport = tree_view.spyglass.viewport;
 
         // define light
         let diffuse_light_normal = V3c::new(0., -1., 1.).normalized();
@@ -199,14 +182,7 @@ fn handle_zoom(
         for x in 0..DISPLAY_RESOLUTION[0] {
             for y in 0..DISPLAY_RESOLUTION[1] {
                 let actual_y_in_image = DISPLAY_RESOLUTION[1] - y - 1;
-                //from the origin of the camera to the current point of the viewport
-                let glass_point = viewport_bottom_left
-                    + viewport_right_direction * x as f32 * pixel_width
-                    + viewport_up_direction * y as f32 * pixel_height;
-                let ray = Ray {
-                    origin: tree_view.spyglass.viewport.origin,
-                    direction: (glass_point - tree_view.spyglass.viewport.origin).normalized(),
-                };
+                let ray = viewport.ray_for_pixel([x, actual_y_in_image], DISPLAY_RESOLUTION);
 
                 use std::io::Write;
                 std::io::stdout().flush().ok().unwrap();
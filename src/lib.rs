@@ -2,3 +2,4 @@ mod object_pool;
 mod spatial;
 
 pub mod octree;
+pub mod prelude;
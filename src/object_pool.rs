@@ -11,6 +11,20 @@ struct ReusableItem<T: Clone> {
     item: T,
 }
 
+/// Decides how much space is allocated when the pool runs out of reserved capacity
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum PoolGrowth {
+    /// Reserves less additional items the larger the pool is
+    #[default]
+    Adaptive,
+    /// No items are reserved beyond the explicitly requested capacity,
+    /// when it runs out the buffer grows by the default strategy of `Vec`
+    Exact,
+    /// Reserves space for the given number of items at once
+    Chunked(usize),
+}
+
 pub fn empty_marker() -> u32 {
     u32::MAX
 }
@@ -30,6 +44,12 @@ where
 }
 
 use bendy::decoding::{FromBencode, Object};
+
+/// The error of a list ending before all of its expected items were decoded, e.g. in truncated data
+pub(crate) fn missing_item() -> bendy::decoding::Error {
+    bendy::decoding::Error::missing_field("list item")
+}
+
 impl<T> FromBencode for ReusableItem<T>
 where
     T: Clone + FromBencode,
@@ -37,7 +57,7 @@ where
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                let reserved = match list.next_object()?.unwrap() {
+                let reserved = match list.next_object()?.ok_or_else(missing_item)? {
                     Object::Integer("0") => Ok(false),
                     Object::Integer("1") => Ok(true),
                     Object::Integer(i) => Err(bendy::decoding::Error::unexpected_token(
@@ -49,7 +69,7 @@ where
                         "Something else",
                     )),
                 }?;
-                let item = T::decode_bencode_object(list.next_object()?.unwrap())?;
+                let item = T::decode_bencode_object(list.next_object()?.ok_or_else(missing_item)?)?;
                 Ok(Self { item, reserved })
             }
             _ => Err(bendy::decoding::Error::unexpected_token(
@@ -71,6 +91,8 @@ where
 pub(crate) struct ObjectPool<T: Clone> {
     buffer: Vec<ReusableItem<T>>, // Pool of objects to be reused
     first_available: usize,       // the index of the first available item
+    growth: PoolGrowth,           // the strategy of reserving space for new items
+    reserved_count: usize,        // the number of items currently in use
 }
 
 impl<
@@ -94,17 +116,22 @@ where
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                let first_available = match list.next_object()?.unwrap() {
-                    Object::Integer(i) => Ok(i.parse::<usize>().ok().unwrap()),
+                let first_available = match list.next_object()?.ok_or_else(missing_item)? {
+                    Object::Integer(i) => i.parse::<usize>().map_err(|_| {
+                        bendy::decoding::Error::unexpected_token("int field first_available", i)
+                    }),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field first_available",
                         "Something else",
                     )),
                 }?;
-                let buffer = Vec::decode_bencode_object(list.next_object()?.unwrap())?;
+                let buffer: Vec<ReusableItem<T>> =
+                    Vec::decode_bencode_object(list.next_object()?.ok_or_else(missing_item)?)?;
                 Ok(Self {
                     first_available,
+                    reserved_count: buffer.iter().filter(|item| item.reserved).count(),
                     buffer,
+                    ..Default::default()
                 })
             }
             _ => Err(bendy::decoding::Error::unexpected_token(
@@ -127,6 +154,34 @@ where
         }
     }
 
+    pub(crate) fn set_growth(&mut self, growth: PoolGrowth) {
+        self.growth = growth;
+    }
+
+    /// Reserves space for exactly the given number of additional items
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.buffer.reserve_exact(additional);
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    pub(crate) fn set_first_available(&mut self, key: usize) {
+        self.first_available = key.min(self.buffer.len());
+    }
+
+    /// Appends an encoded item to the end of the buffer, so the pool can be decoded item by item
+    pub(crate) fn push_encoded(&mut self, bytes: &[u8]) -> Result<(), bendy::decoding::Error>
+    where
+        T: FromBencode,
+    {
+        let item = ReusableItem::from_bencode(bytes)?;
+        self.reserved_count += item.reserved as usize;
+        self.buffer.push(item);
+        Ok(())
+    }
+
     fn is_next_available(&mut self) -> bool {
         self.first_available + 1 < self.buffer.len()
             && !self.buffer[self.first_available + 1].reserved
@@ -148,6 +203,11 @@ where
         self.buffer.len()
     }
 
+    /// The number of items currently in use
+    pub(crate) fn reserved_count(&self) -> usize {
+        self.reserved_count
+    }
+
     pub(crate) fn push(&mut self, item: T) -> usize {
         let key = self.allocate();
         *self.get_mut(key) = item;
@@ -160,11 +220,21 @@ where
             self.first_available
         } else {
             // reserve place for additional items
-            let x = self.buffer.len().max(10) as f32;
-
-            // reserve less additional items the more the size of the buffer
-            self.buffer
-                .reserve(((100. * x.log10().powf(2.)) / x) as usize);
+            match self.growth {
+                PoolGrowth::Adaptive => {
+                    let x = self.buffer.len().max(10) as f32;
+
+                    // reserve less additional items the more the size of the buffer
+                    self.buffer
+                        .reserve(((100. * x.log10().powf(2.)) / x) as usize);
+                }
+                PoolGrowth::Exact => {}
+                PoolGrowth::Chunked(chunk_size) => {
+                    if self.buffer.len() == self.buffer.capacity() {
+                        self.buffer.reserve_exact(chunk_size.max(1));
+                    }
+                }
+            }
 
             // mark Node as reserved and return with the key
             self.buffer.push(ReusableItem {
@@ -174,6 +244,7 @@ where
 
             self.buffer.len() - 1
         };
+        self.reserved_count += 1;
         if self.is_next_available() {
             self.first_available += 1;
         }
@@ -183,6 +254,7 @@ where
     pub(crate) fn pop(&mut self, key: usize) -> Option<T> {
         if self.key_is_valid(key) {
             self.buffer[key].reserved = false;
+            self.reserved_count -= 1;
             self.first_available = self.first_available.min(key);
             Some(std::mem::take(&mut self.buffer[key].item))
         } else {
@@ -193,6 +265,7 @@ where
     pub(crate) fn free(&mut self, key: usize) -> bool {
         if self.key_is_valid(key) {
             self.buffer[key].reserved = false;
+            self.reserved_count -= 1;
             self.first_available = self.first_available.min(key);
             true
         } else {
@@ -217,11 +290,20 @@ where
     pub(crate) fn key_is_valid(&self, key: usize) -> bool {
         key < self.buffer.len() && self.buffer[key].reserved
     }
+
+    /// Removes the unused items from the end of the buffer and releases the excess capacity
+    pub(crate) fn trim(&mut self) {
+        while self.buffer.last().is_some_and(|item| !item.reserved) {
+            self.buffer.pop();
+        }
+        self.buffer.shrink_to_fit();
+        self.first_available = self.first_available.min(self.buffer.len());
+    }
 }
 
 #[cfg(test)]
 mod object_pool_tests {
-    use super::ObjectPool;
+    use super::{ObjectPool, PoolGrowth};
 
     #[test]
     fn test_push_pop_modify() {
@@ -260,4 +342,59 @@ mod object_pool_tests {
         pool.push(test_value * 3.);
         debug_assert!(*pool.get(key_1) == test_value * 3.); // the original key is reused to hold the latest value
     }
+
+    #[test]
+    fn test_reserved_count() {
+        let mut pool = ObjectPool::<f32>::with_capacity(3);
+        let key = pool.push(5.);
+        pool.push(6.);
+        pool.push(7.);
+        assert!(pool.reserved_count() == 3);
+
+        pool.free(key);
+        assert!(pool.reserved_count() == 2);
+        assert!(pool.len() == 3);
+
+        // Freeing an unused item does not change the count, reusing it does
+        assert!(!pool.free(key));
+        assert!(pool.pop(key).is_none());
+        assert!(pool.reserved_count() == 2);
+        assert!(pool.push(8.) == key);
+        assert!(pool.reserved_count() == 3);
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut pool = ObjectPool::<f32>::with_capacity(3);
+        let key_1 = pool.push(5.);
+        let key_2 = pool.push(6.);
+        let key_3 = pool.push(7.);
+        pool.free(key_1);
+        pool.free(key_3);
+        pool.trim();
+        assert!(pool.len() == 2); // Only trailing items are removed
+        assert!(*pool.get(key_2) == 6.);
+
+        pool.free(key_2);
+        pool.trim();
+        assert!(pool.len() == 0);
+        assert!(pool.push(8.) == 0);
+    }
+
+    #[test]
+    fn test_chunked_growth() {
+        let mut pool = ObjectPool::<f32>::with_capacity(0);
+        pool.set_growth(PoolGrowth::Chunked(16));
+        pool.push(5.);
+        assert!(pool.capacity() == 16);
+        for _ in 0..15 {
+            pool.push(6.);
+        }
+        assert!(pool.capacity() == 16);
+        pool.push(7.);
+        assert!(pool.capacity() == 32);
+
+        pool.reserve(100);
+        assert!(pool.capacity() >= 117);
+    }
 }
@@ -0,0 +1,559 @@
+use crate::octree::{
+    detail::{bound_contains, child_octant_for},
+    types::{BrickData, NodeContent},
+    Octree, V3c, VoxelData,
+};
+use crate::spatial::{
+    lut::BITMAP_MASK_FOR_OCTANT_LUT,
+    math::{flat_projection, BITMAP_DIMENSION},
+    Cube,
+};
+use std::{collections::HashMap, hash::Hash};
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Provides the occupancy bitmap of the node at the given depth containing the given position
+    /// The node is divided into 4x4x4 parts, each bit is set if the corresponding part contains voxels
+    /// Bits are indexed by `x + y * 4 + z * 16` inside the node
+    /// * `depth` - the level of the node, 0 being the root node, at most `depth()`
+    /// * Returns with None if there is no node at the given depth containing the position
+    pub fn occupied_bits_at(&self, position: &V3c<u32>, depth: u32) -> Option<u64> {
+        let position = V3c::<f32>::from(*position);
+        let mut current_bounds = Cube::root_bounds(self.octree_size as f32);
+        if !bound_contains(&current_bounds, &position) {
+            return None;
+        }
+        let mut current_node_key = Self::ROOT_NODE_KEY as usize;
+        for _ in 0..depth {
+            if !self.is_node_internal(current_node_key) {
+                return None;
+            }
+            let child_octant = child_octant_for(&current_bounds, &position);
+            let child_key = self.node_children[current_node_key][child_octant as u32] as usize;
+            if !self.nodes.key_is_valid(child_key) {
+                return None;
+            }
+            current_node_key = child_key;
+            current_bounds = Cube::child_bounds_for(&current_bounds, child_octant);
+        }
+        Some(self.stored_occupied_bits(current_node_key))
+    }
+
+    /// Collects the occupancy bitmaps of every non-empty node down to the given depth
+    /// The bitmaps are laid out as in `occupied_bits_at`
+    /// * `max_depth` - the deepest level to collect, 0 being the root node
+    /// * Returns with one map for each level containing nodes, from the minimum position
+    ///   of each node to its bitmap
+    pub fn occupancy_pyramid(&self, max_depth: u32) -> Vec<HashMap<V3c<u32>, u64>> {
+        let mut pyramid = Vec::new();
+        let mut current_level = vec![(
+            Self::ROOT_NODE_KEY as usize,
+            Cube::root_bounds(self.octree_size as f32),
+        )];
+        while !current_level.is_empty() && pyramid.len() <= max_depth as usize {
+            let mut bitmaps = HashMap::new();
+            let mut next_level = Vec::new();
+            for (node_key, bounds) in current_level {
+                let occupied_bits = self.stored_occupied_bits(node_key);
+                if 0 == occupied_bits {
+                    continue;
+                }
+                bitmaps.insert(V3c::<u32>::from(bounds.min_position), occupied_bits);
+                if self.is_node_internal(node_key) {
+                    for octant in 0..8u8 {
+                        let child_key = self.node_children[node_key][octant as u32] as usize;
+                        if self.nodes.key_is_valid(child_key) {
+                            next_level.push((child_key, Cube::child_bounds_for(&bounds, octant)));
+                        }
+                    }
+                }
+            }
+            pyramid.push(bitmaps);
+            current_level = next_level;
+        }
+        pyramid
+    }
+
+    /// Iterates over every voxel contained in the tree, skipping empty nodes and bricks
+    /// Voxels are provided together with their position, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (V3c<u32>, T)> + '_ {
+        self.filled_voxels_in(&V3c::unit(0), &V3c::unit(self.octree_size))
+    }
+
+    /// Iterates over every voxel inside the given region, only visiting nodes overlapping with it
+    /// Voxels are provided together with their position, in no particular order
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    pub fn iter_region(
+        &self,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+    ) -> impl Iterator<Item = (V3c<u32>, T)> + '_ {
+        self.filled_voxels_in(region_min, region_max)
+    }
+
+    /// Calls the given visitor with every voxel inside the given region and its position
+    /// Only nodes overlapping with the region are traversed, voxels are visited in no particular order
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    pub fn for_each_in<F>(&self, region_min: &V3c<u32>, region_max: &V3c<u32>, mut visitor: F)
+    where
+        F: FnMut(&V3c<u32>, &T),
+    {
+        for (position, voxel) in self.filled_voxels_in(region_min, region_max) {
+            visitor(&position, &voxel);
+        }
+    }
+
+    /// Provides a lazy iterator over the voxels inside the given region
+    fn filled_voxels_in(
+        &self,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+    ) -> FilledVoxels<'_, T, DIM> {
+        FilledVoxels {
+            tree: self,
+            region_min: V3c::<f32>::from(*region_min),
+            region_max: V3c::<f32>::from(*region_max),
+            pending: vec![PendingPart::Node(
+                Self::ROOT_NODE_KEY as usize,
+                Cube::root_bounds(self.octree_size as f32),
+            )],
+            cell: None,
+        }
+    }
+
+    /// Tells if there are no voxels inside the given region
+    /// Nodes are skipped based on their occupancy bitmaps, voxels are only read from the bricks
+    /// under occupied parts of leaf nodes partially overlapping with the region
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    pub fn is_region_empty(&self, region_min: &V3c<u32>, region_max: &V3c<u32>) -> bool {
+        let mut region_max = *region_max;
+        region_max.cut_each_component(&self.octree_size);
+        self.is_region_empty_in(
+            Self::ROOT_NODE_KEY as usize,
+            &Cube::root_bounds(self.octree_size as f32),
+            &V3c::<f32>::from(*region_min),
+            &V3c::<f32>::from(region_max),
+        )
+    }
+
+    fn is_region_empty_in(
+        &self,
+        node_key: usize,
+        node_bounds: &Cube,
+        region_min: &V3c<f32>,
+        region_max: &V3c<f32>,
+    ) -> bool {
+        if !self.nodes.key_is_valid(node_key)
+            || 0. >= node_bounds.overlap_volume(region_min, region_max)
+        {
+            return true;
+        }
+
+        // Collect the occupied parts of the node overlapping with the region
+        let occupied_bits = self.stored_occupied_bits(node_key);
+        let part_size = node_bounds.size / BITMAP_DIMENSION as f32;
+        let mut occupied_parts = Vec::new();
+        for x in 0..BITMAP_DIMENSION {
+            for y in 0..BITMAP_DIMENSION {
+                for z in 0..BITMAP_DIMENSION {
+                    if 0 == occupied_bits & (0x01 << flat_projection(x, y, z, BITMAP_DIMENSION)) {
+                        continue;
+                    }
+                    let part = Cube {
+                        min_position: node_bounds.min_position
+                            + V3c::new(x as f32, y as f32, z as f32) * part_size,
+                        size: part_size,
+                    };
+                    if 0. < part.overlap_volume(region_min, region_max) {
+                        occupied_parts.push(part);
+                    }
+                }
+            }
+        }
+        if occupied_parts.is_empty() {
+            return true;
+        }
+
+        if self.is_node_internal(node_key) {
+            return (0..8u8).all(|octant| {
+                self.is_region_empty_in(
+                    self.node_children[node_key][octant as u32] as usize,
+                    &node_bounds.child_bounds_for(octant),
+                    region_min,
+                    region_max,
+                )
+            });
+        }
+
+        // Leaf node: occupied parts completely inside the region contain voxels based on the bitmap,
+        // only the bricks under occupied parts partially overlapping with the region are checked
+        for part in occupied_parts {
+            let part_max = part.min_position + V3c::unit(part.size);
+            let overlap_min = V3c::new(
+                part.min_position.x.max(region_min.x),
+                part.min_position.y.max(region_min.y),
+                part.min_position.z.max(region_min.z),
+            );
+            let overlap_max = V3c::new(
+                part_max.x.min(region_max.x),
+                part_max.y.min(region_max.y),
+                part_max.z.min(region_max.z),
+            );
+            if overlap_min == part.min_position && overlap_max == part_max {
+                return false;
+            }
+            let occupied = match self.nodes.get(node_key) {
+                NodeContent::Leaf(bricks) => {
+                    let octant = child_octant_for(node_bounds, &part.min_position);
+                    Self::is_brick_occupied_in(
+                        &bricks[octant as usize],
+                        &node_bounds.child_bounds_for(octant),
+                        &overlap_min,
+                        &overlap_max,
+                    )
+                }
+                NodeContent::UniformLeaf(brick) => {
+                    Self::is_brick_occupied_in(brick, node_bounds, &overlap_min, &overlap_max)
+                }
+                NodeContent::Nothing | NodeContent::Internal(_) => false,
+            };
+            if occupied {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// True if the given brick contains voxels inside the given region
+    /// * `brick_bounds` - the bounds covered by the brick, one voxel of it might cover multiple positions
+    /// * `region_min` - the minimum position of the region, inside the bounds of the brick
+    /// * `region_max` - the maximum position of the region, exclusive, inside the bounds of the brick
+    fn is_brick_occupied_in(
+        brick: &BrickData<T, DIM>,
+        brick_bounds: &Cube,
+        region_min: &V3c<f32>,
+        region_max: &V3c<f32>,
+    ) -> bool {
+        match brick {
+            BrickData::Empty => false,
+            BrickData::Solid(voxel) => !voxel.is_empty(),
+            BrickData::Parted(voxels) => {
+                let voxel_size = brick_bounds.size / DIM as f32;
+                let index_of = |position: f32, brick_min: f32| {
+                    ((position - brick_min) / voxel_size).clamp(0., DIM as f32)
+                };
+                let min = V3c::new(
+                    index_of(region_min.x, brick_bounds.min_position.x).floor() as usize,
+                    index_of(region_min.y, brick_bounds.min_position.y).floor() as usize,
+                    index_of(region_min.z, brick_bounds.min_position.z).floor() as usize,
+                );
+                let max = V3c::new(
+                    index_of(region_max.x, brick_bounds.min_position.x).ceil() as usize,
+                    index_of(region_max.y, brick_bounds.min_position.y).ceil() as usize,
+                    index_of(region_max.z, brick_bounds.min_position.z).ceil() as usize,
+                );
+                (min.x..max.x).any(|x| {
+                    (min.y..max.y).any(|y| (min.z..max.z).any(|z| !voxels[x][y][z].is_empty()))
+                })
+            }
+        }
+    }
+
+    /// Provides the distance of the first occupied voxel from the origin along a cardinal direction
+    /// Empty parts of nodes are skipped based on their occupancy bitmaps
+    /// * `origin` - the position to start the scan from, it is checked too
+    /// * `direction` - unit vector along one of the axes, e.g. (0,-1,0) for a gravity check
+    /// * `max_len` - the number of voxels to check at most, including the origin
+    /// * Returns with the number of steps from the origin to the first occupied voxel,
+    ///   or None if there is none within `max_len` voxels inside the tree
+    pub fn scan_axis(&self, origin: &V3c<u32>, direction: &V3c<i32>, max_len: u32) -> Option<u32> {
+        debug_assert!(
+            1 == direction.x.abs() + direction.y.abs() + direction.z.abs(),
+            "Expected a cardinal direction instead of {:?}",
+            direction
+        );
+        let mut distance = 0;
+        while distance < max_len {
+            let position = V3c::<i64>::new(
+                origin.x as i64 + direction.x as i64 * distance as i64,
+                origin.y as i64 + direction.y as i64 * distance as i64,
+                origin.z as i64 + direction.z as i64 * distance as i64,
+            );
+            if position.x < 0
+                || position.y < 0
+                || position.z < 0
+                || position.x >= self.octree_size as i64
+                || position.y >= self.octree_size as i64
+                || position.z >= self.octree_size as i64
+            {
+                return None;
+            }
+            let position = V3c::new(position.x as u32, position.y as u32, position.z as u32);
+            let empty_steps = self.empty_steps_along(&position, direction);
+            if 0 == empty_steps {
+                return Some(distance);
+            }
+            distance = distance.saturating_add(empty_steps);
+        }
+        None
+    }
+
+    /// Provides the number of steps from the given position until the end of the
+    /// empty area containing it along the given direction, or 0 if the position is occupied
+    fn empty_steps_along(&self, position: &V3c<u32>, direction: &V3c<i32>) -> u32 {
+        // Provides the number of steps needed to leave the given area along the direction
+        let steps_to_leave = |area_min: &V3c<f32>, area_size: f32| -> u32 {
+            let (coordinate, area_min, step) = if 0 != direction.x {
+                (position.x as f32, area_min.x, direction.x)
+            } else if 0 != direction.y {
+                (position.y as f32, area_min.y, direction.y)
+            } else {
+                (position.z as f32, area_min.z, direction.z)
+            };
+            if 0 < step {
+                ((area_min + area_size).ceil() - coordinate).max(1.) as u32
+            } else {
+                (coordinate - area_min.floor() + 1.).max(1.) as u32
+            }
+        };
+
+        let position_f32 = V3c::<f32>::from(*position);
+        let mut current_bounds = Cube::root_bounds(self.octree_size as f32);
+        let mut current_node_key = Self::ROOT_NODE_KEY as usize;
+        loop {
+            if !self.nodes.key_is_valid(current_node_key) {
+                return steps_to_leave(&current_bounds.min_position, current_bounds.size);
+            }
+            let part_size = current_bounds.size / BITMAP_DIMENSION as f32;
+            let part_index = V3c::new(
+                ((position_f32.x - current_bounds.min_position.x) / part_size) as usize,
+                ((position_f32.y - current_bounds.min_position.y) / part_size) as usize,
+                ((position_f32.z - current_bounds.min_position.z) / part_size) as usize,
+            );
+            let part_bit = flat_projection(
+                part_index.x.min(BITMAP_DIMENSION - 1),
+                part_index.y.min(BITMAP_DIMENSION - 1),
+                part_index.z.min(BITMAP_DIMENSION - 1),
+                BITMAP_DIMENSION,
+            );
+            if 0 == self.stored_occupied_bits(current_node_key) & (0x01 << part_bit) {
+                let part_min = current_bounds.min_position
+                    + V3c::new(
+                        part_index.x as f32,
+                        part_index.y as f32,
+                        part_index.z as f32,
+                    ) * part_size;
+                return steps_to_leave(&part_min, part_size);
+            }
+            if !self.is_node_internal(current_node_key) {
+                return if self.get(position).is_some() { 0 } else { 1 };
+            }
+            let child_octant = child_octant_for(&current_bounds, &position_f32);
+            current_node_key = self.node_children[current_node_key][child_octant as u32] as usize;
+            current_bounds = Cube::child_bounds_for(&current_bounds, child_octant);
+        }
+    }
+
+    /// Counts the number of voxels for each distinct voxel data contained in the tree
+    pub fn histogram(&self) -> HashMap<T, usize>
+    where
+        T: Hash,
+    {
+        self.histogram_in(&V3c::unit(0), &V3c::unit(self.octree_size))
+    }
+
+    /// Counts the number of voxels for each distinct voxel data inside the given region
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    pub fn histogram_in(&self, region_min: &V3c<u32>, region_max: &V3c<u32>) -> HashMap<T, usize>
+    where
+        T: Hash,
+    {
+        let region_min = V3c::<f32>::from(*region_min);
+        let region_max = V3c::<f32>::from(*region_max);
+        let mut result = HashMap::new();
+        self.for_each_filled_cell_in(&region_min, &region_max, &mut |bounds, voxel| {
+            // Solid cells might cover multiple voxels, only the part inside the region is counted
+            *result.entry(*voxel).or_insert(0) +=
+                bounds.overlap_volume(&region_min, &region_max).round() as usize;
+        });
+        result
+    }
+}
+
+/// A part of the tree still to be visited by `FilledVoxels`
+enum PendingPart<'a, T, const DIM: usize>
+where
+    T: Clone + PartialEq + VoxelData,
+{
+    /// A node with its bounds
+    Node(usize, Cube),
+
+    /// A brick with its bounds, and the index of its next voxel to visit
+    Brick(&'a BrickData<T, DIM>, Cube, usize),
+}
+
+/// The part of a filled cell inside the visited region: its minimum and maximum position,
+/// the next position to provide inside it, and its content
+type CellCursor<T> = (V3c<u32>, V3c<u32>, V3c<u32>, T);
+
+/// Iterates over the voxels of a tree inside a region, without collecting them beforehand
+/// Nodes are traversed with an explicit stack, children and bricks are skipped
+/// where the occupancy bitmap of their parent is empty
+struct FilledVoxels<'a, T, const DIM: usize>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    tree: &'a Octree<T, DIM>,
+    region_min: V3c<f32>,
+    region_max: V3c<f32>,
+    pending: Vec<PendingPart<'a, T, DIM>>,
+    cell: Option<CellCursor<T>>,
+}
+
+impl<'a, T, const DIM: usize> FilledVoxels<'a, T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Starts providing the voxels of the given filled cell inside the region, if they overlap
+    fn enter_cell(&mut self, bounds: &Cube, voxel: T) {
+        if 0. >= bounds.overlap_volume(&self.region_min, &self.region_max) {
+            return;
+        }
+        let cell_max = bounds.min_position + V3c::unit(bounds.size);
+        let min = V3c::new(
+            bounds.min_position.x.max(self.region_min.x),
+            bounds.min_position.y.max(self.region_min.y),
+            bounds.min_position.z.max(self.region_min.z),
+        );
+        let max = V3c::new(
+            cell_max.x.min(self.region_max.x),
+            cell_max.y.min(self.region_max.y),
+            cell_max.z.min(self.region_max.z),
+        );
+        let min = V3c::<u32>::from(min);
+        self.cell = Some((min, V3c::<u32>::from(max), min, voxel));
+    }
+
+    /// Schedules the children of the given node, skipping the ones with no occupied bits
+    fn enter_node(&mut self, node_key: usize, node_bounds: Cube) {
+        let tree = self.tree;
+        if !tree.nodes.key_is_valid(node_key)
+            || 0. >= node_bounds.overlap_volume(&self.region_min, &self.region_max)
+        {
+            return;
+        }
+        let occupied_bits = tree.stored_occupied_bits(node_key);
+        if 0 == occupied_bits {
+            return;
+        }
+        match tree.nodes.get(node_key) {
+            NodeContent::Nothing => {}
+            NodeContent::Internal(_) => {
+                for octant in 0..8u8 {
+                    if 0 != occupied_bits & BITMAP_MASK_FOR_OCTANT_LUT[octant as usize] {
+                        self.pending.push(PendingPart::Node(
+                            tree.node_children[node_key][octant as u32] as usize,
+                            node_bounds.child_bounds_for(octant),
+                        ));
+                    }
+                }
+            }
+            NodeContent::Leaf(bricks) => {
+                for (octant, brick) in bricks.iter().enumerate() {
+                    if 0 != occupied_bits & BITMAP_MASK_FOR_OCTANT_LUT[octant] {
+                        self.pending.push(PendingPart::Brick(
+                            brick,
+                            node_bounds.child_bounds_for(octant as u8),
+                            0,
+                        ));
+                    }
+                }
+            }
+            NodeContent::UniformLeaf(brick) => {
+                self.pending.push(PendingPart::Brick(brick, node_bounds, 0));
+            }
+        }
+    }
+
+    /// Starts providing the next filled voxel of the given brick starting from the given index,
+    /// and schedules the rest of the brick
+    fn enter_brick(
+        &mut self,
+        brick: &'a BrickData<T, DIM>,
+        brick_bounds: Cube,
+        first_index: usize,
+    ) {
+        if 0. >= brick_bounds.overlap_volume(&self.region_min, &self.region_max) {
+            return;
+        }
+        match brick {
+            BrickData::Empty => {}
+            BrickData::Solid(voxel) => {
+                if !voxel.is_empty() {
+                    self.enter_cell(&brick_bounds, *voxel);
+                }
+            }
+            BrickData::Parted(voxels) => {
+                let voxel_size = brick_bounds.size / DIM as f32;
+                for index in first_index..(DIM * DIM * DIM) {
+                    let (x, y, z) = (index % DIM, (index / DIM) % DIM, index / (DIM * DIM));
+                    if voxels[x][y][z].is_empty() {
+                        continue;
+                    }
+                    let voxel_bounds = Cube {
+                        min_position: brick_bounds.min_position
+                            + V3c::new(x as f32, y as f32, z as f32) * voxel_size,
+                        size: voxel_size,
+                    };
+                    if 0. < voxel_bounds.overlap_volume(&self.region_min, &self.region_max) {
+                        self.pending
+                            .push(PendingPart::Brick(brick, brick_bounds, index + 1));
+                        self.enter_cell(&voxel_bounds, voxels[x][y][z]);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T, const DIM: usize> Iterator for FilledVoxels<'_, T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    type Item = (V3c<u32>, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((min, max, next, voxel)) = &mut self.cell {
+                let position = *next;
+                next.z += 1;
+                if next.z >= max.z {
+                    next.z = min.z;
+                    next.y += 1;
+                    if next.y >= max.y {
+                        next.y = min.y;
+                        next.x += 1;
+                    }
+                }
+                let voxel = *voxel;
+                if next.x >= max.x {
+                    self.cell = None;
+                }
+                return Some((position, voxel));
+            }
+            match self.pending.pop()? {
+                PendingPart::Node(node_key, node_bounds) => self.enter_node(node_key, node_bounds),
+                PendingPart::Brick(brick, brick_bounds, first_index) => {
+                    self.enter_brick(brick, brick_bounds, first_index)
+                }
+            }
+        }
+    }
+}
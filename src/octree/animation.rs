@@ -0,0 +1,88 @@
+use crate::octree::{types::OctreeError, Octree, VoxelData};
+
+/// A sequence of trees displayed one after another, each for its own duration
+/// With the GPU renderer, it is played by the `SvxAnimation` resource
+#[derive(Default, Clone)]
+pub struct VoxelAnimation<T, const DIM: usize = 1>
+where
+    T: Default + Clone + PartialEq + VoxelData,
+{
+    frames: Vec<(Octree<T, DIM>, f32)>,
+    pub looping: bool,
+}
+
+impl<T, const DIM: usize> VoxelAnimation<T, DIM>
+where
+    T: Default + Clone + PartialEq + VoxelData,
+{
+    pub fn new(looping: bool) -> Self {
+        Self {
+            frames: Vec::new(),
+            looping,
+        }
+    }
+
+    /// Appends a frame to the end of the animation
+    /// * `tree` - the contents of the frame, it must be the same size as the other frames
+    /// * `duration` - the time the frame is displayed for, must be larger, than 0
+    pub fn add_frame(&mut self, tree: Octree<T, DIM>, duration: f32) -> Result<(), OctreeError> {
+        if duration <= 0. {
+            return Err(OctreeError::InvalidStructure(
+                format!("Frame duration must be larger, than 0 instead of {duration}").into(),
+            ));
+        }
+        if let Some((first, _)) = self.frames.first() {
+            if first.octree_size != tree.octree_size {
+                return Err(OctreeError::InvalidStructure(
+                    format!(
+                        "Animation frames must be of size {} instead of {}",
+                        first.octree_size, tree.octree_size
+                    )
+                    .into(),
+                ));
+            }
+        }
+        self.frames.push((tree, duration));
+        Ok(())
+    }
+
+    /// The number of frames inside the animation
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The time it takes to play every frame of the animation once
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|(_, duration)| duration).sum()
+    }
+
+    /// Provides the index of the frame displayed at the given time since the start of the animation
+    /// Returns with None if there are no frames, or if the animation is over
+    pub fn frame_index_at(&self, time: f32) -> Option<usize> {
+        if self.frames.is_empty() || time < 0. {
+            return None;
+        }
+        let mut time = time;
+        if self.looping {
+            time %= self.duration();
+        }
+        let mut frame_end = 0.;
+        for (index, (_, duration)) in self.frames.iter().enumerate() {
+            frame_end += duration;
+            if time < frame_end {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    /// Provides the frame displayed at the given time since the start of the animation
+    pub fn frame_at(&self, time: f32) -> Option<&Octree<T, DIM>> {
+        self.frame_index_at(time).map(|index| &self.frames[index].0)
+    }
+
+    /// Exchanges the contents of the frame at the given index with the given tree
+    pub(crate) fn swap_frame(&mut self, index: usize, tree: &mut Octree<T, DIM>) {
+        std::mem::swap(&mut self.frames[index].0, tree);
+    }
+}
@@ -0,0 +1,355 @@
+use crate::octree::{types::BrickChecksums, Octree, V3c, VoxelData};
+use crate::spatial::math::flat_projection;
+use std::collections::HashMap;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// 64 bit FNV-1a hash of the given bytes, continuing from the given hash
+/// Its results do not depend on the platform or the version of the library, so they can be saved
+/// FNV-1a is used instead of xxhash to keep the library free of a hashing dependency;
+/// it is slower on large inputs, but checksums are calculated from runs of equal voxels, which are short
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Calculates the checksum of a brick from the runs of equal voxels inside it
+/// Runs need to be maximal and ordered by `flat_projection`, so the checksum only depends on the voxels
+fn checksum_of_runs<T: VoxelData>(runs: impl Iterator<Item = (Option<T>, usize)>) -> u64 {
+    runs.fold(FNV_OFFSET_BASIS, |hash, (voxel, length)| {
+        let hash = match voxel {
+            None => fnv1a(hash, &[0]),
+            Some(voxel) => {
+                let albedo = voxel.albedo();
+                let hash = fnv1a(hash, &[1, albedo.r, albedo.g, albedo.b, albedo.a]);
+                fnv1a(hash, &voxel.user_data().to_le_bytes())
+            }
+        };
+        fnv1a(hash, &(length as u64).to_le_bytes())
+    })
+}
+
+/// Calculates the checksum of a brick from its voxels, ordered by `flat_projection`
+fn checksum_of_voxels<T: PartialEq + Copy + VoxelData>(voxels: &[Option<T>]) -> u64 {
+    let mut runs: Vec<(Option<T>, usize)> = Vec::new();
+    for voxel in voxels {
+        match runs.last_mut() {
+            Some((run_voxel, length)) if run_voxel == voxel => *length += 1,
+            _ => runs.push((*voxel, 1)),
+        }
+    }
+    checksum_of_runs(runs.into_iter())
+}
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Clone + Copy + PartialEq + VoxelData,
+{
+    /// Calculates the checksum of every brick containing voxels inside the given brick aligned region
+    /// Bricks covered by one solid cell are hashed in one step, others are collected voxel by voxel
+    pub(crate) fn checksums_in(
+        &self,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+    ) -> HashMap<V3c<u32>, u64> {
+        let brick_volume = DIM * DIM * DIM;
+        let mut checksums = HashMap::new();
+        let mut parted_bricks = HashMap::<V3c<u32>, Vec<Option<T>>>::new();
+        self.for_each_filled_cell_in(
+            &V3c::<f32>::from(*region_min),
+            &V3c::<f32>::from(*region_max),
+            &mut |bounds, voxel| {
+                let cell_min = V3c::<u32>::from(bounds.min_position);
+                let cell_max = cell_min + V3c::unit(bounds.size as u32);
+                let min = V3c::new(
+                    cell_min.x.max(region_min.x),
+                    cell_min.y.max(region_min.y),
+                    cell_min.z.max(region_min.z),
+                );
+                let max = V3c::new(
+                    cell_max.x.min(region_max.x),
+                    cell_max.y.min(region_max.y),
+                    cell_max.z.min(region_max.z),
+                );
+                let first_brick = V3c::new(
+                    min.x - min.x % DIM as u32,
+                    min.y - min.y % DIM as u32,
+                    min.z - min.z % DIM as u32,
+                );
+                for brick_x in (first_brick.x..max.x).step_by(DIM) {
+                    for brick_y in (first_brick.y..max.y).step_by(DIM) {
+                        for brick_z in (first_brick.z..max.z).step_by(DIM) {
+                            let brick_min = V3c::new(brick_x, brick_y, brick_z);
+                            let brick_max = brick_min + V3c::unit(DIM as u32);
+                            if min.x <= brick_min.x
+                                && min.y <= brick_min.y
+                                && min.z <= brick_min.z
+                                && brick_max.x <= max.x
+                                && brick_max.y <= max.y
+                                && brick_max.z <= max.z
+                            {
+                                checksums.insert(
+                                    brick_min,
+                                    checksum_of_runs(std::iter::once((Some(*voxel), brick_volume))),
+                                );
+                                continue;
+                            }
+                            let brick = parted_bricks
+                                .entry(brick_min)
+                                .or_insert_with(|| vec![None; brick_volume]);
+                            for x in min.x.max(brick_min.x)..max.x.min(brick_max.x) {
+                                for y in min.y.max(brick_min.y)..max.y.min(brick_max.y) {
+                                    for z in min.z.max(brick_min.z)..max.z.min(brick_max.z) {
+                                        brick[flat_projection(
+                                            (x - brick_min.x) as usize,
+                                            (y - brick_min.y) as usize,
+                                            (z - brick_min.z) as usize,
+                                            DIM,
+                                        )] = Some(*voxel);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+        );
+        for (brick_min, voxels) in parted_bricks {
+            checksums.insert(brick_min, checksum_of_voxels(&voxels));
+        }
+        checksums
+    }
+
+    /// Calculates a checksum for every brick sized part of the tree containing voxels
+    /// The checksums only depend on the voxels, not on how they are stored inside the tree,
+    /// the position of the brick, the platform or the version of the library, so they can be
+    /// compared between different versions of the tree, e.g. before a save and after a load, to detect corruption.
+    /// Takes time proportional to the number of voxels inside parted bricks.
+    /// * Returns with the checksum of each brick, by the minimum position of the brick
+    pub fn brick_checksums(&self) -> HashMap<V3c<u32>, u64> {
+        self.checksums_in(&V3c::unit(0), &V3c::unit(self.octree_size))
+    }
+
+    /// Compares the contents of the tree to previously calculated brick checksums
+    /// * `expected` - brick checksums calculated with `brick_checksums`
+    /// * Returns with the minimum position of every brick not matching the expected checksum
+    pub fn verify_checksums(&self, expected: &HashMap<V3c<u32>, u64>) -> Vec<V3c<u32>> {
+        let actual = self.brick_checksums();
+        let mut mismatches = actual
+            .iter()
+            .filter(|(origin, checksum)| expected.get(*origin) != Some(*checksum))
+            .map(|(origin, _)| *origin)
+            .chain(
+                expected
+                    .keys()
+                    .filter(|origin| !actual.contains_key(*origin))
+                    .copied(),
+            )
+            .collect::<Vec<_>>();
+        mismatches.sort_unstable_by_key(|origin| (origin.x, origin.y, origin.z));
+        mismatches
+    }
+
+    /// Starts to maintain the checksum of every brick on each write, and to save them together with the tree
+    /// Does nothing if checksums are already maintained
+    pub fn enable_checksums(&mut self) {
+        if self.checksums.is_none() {
+            self.checksums = Some(BrickChecksums {
+                values: self.brick_checksums(),
+                ..Default::default()
+            });
+        }
+    }
+
+    /// Stops maintaining brick checksums, they are also not saved with the tree anymore
+    pub fn disable_checksums(&mut self) {
+        self.checksums = None;
+    }
+
+    pub fn checksums_enabled(&self) -> bool {
+        self.checksums.is_some()
+    }
+
+    /// Compares the contents of the tree to the maintained brick checksums
+    /// Bricks found corrupted while they were written are also reported, but only once
+    /// * Returns with the minimum position of every corrupted brick, empty if checksums are not enabled
+    pub fn verify_stored_checksums(&mut self) -> Vec<V3c<u32>> {
+        self.update_pending_checksums();
+        let Some(checksums) = &self.checksums else {
+            return Vec::new();
+        };
+
+        // Bricks written inside the current batch of updates can not be verified yet
+        let mut mismatches = self
+            .verify_checksums(&checksums.values)
+            .into_iter()
+            .filter(|origin| !checksums.pending.contains(origin))
+            .chain(checksums.corrupted.iter().copied())
+            .collect::<Vec<_>>();
+        mismatches.sort_unstable_by_key(|origin| (origin.x, origin.y, origin.z));
+        mismatches.dedup();
+        if let Some(checksums) = &mut self.checksums {
+            checksums.corrupted.clear();
+        }
+        mismatches
+    }
+
+    /// Provides the maintained brick checksums, with the outdated ones recalculated
+    pub(crate) fn current_checksums(&self) -> Option<HashMap<V3c<u32>, u64>> {
+        let checksums = self.checksums.as_ref()?;
+        let mut values = checksums.values.clone();
+        for origin in &checksums.pending {
+            match self
+                .checksums_in(origin, &(*origin + V3c::unit(DIM as u32)))
+                .remove(origin)
+            {
+                Some(checksum) => values.insert(*origin, checksum),
+                None => values.remove(origin),
+            };
+        }
+        Some(values)
+    }
+
+    /// Provides the brick aligned region an update of the given size at the given position may modify
+    fn update_envelope(&self, position: &V3c<u32>, size: u32) -> (V3c<u32>, V3c<u32>) {
+        // Updates up to the brick dimension stay inside the target brick,
+        // larger ones may fill the whole parent of the node matching their size
+        let mut envelope_size = DIM as u32;
+        if envelope_size < size {
+            while envelope_size < self.octree_size && envelope_size * 2 <= size {
+                envelope_size *= 2;
+            }
+            envelope_size = (envelope_size * 2).min(self.octree_size);
+        }
+        let min = V3c::new(
+            position.x - position.x % envelope_size,
+            position.y - position.y % envelope_size,
+            position.z - position.z % envelope_size,
+        );
+        (min, min + V3c::unit(envelope_size))
+    }
+
+    /// Verifies the bricks an update of the given size at the given position may modify,
+    /// and marks them to have their checksums updated after the update
+    /// * `position` - the position of the update, must be contained within the tree
+    pub(crate) fn checksums_before_update(&mut self, position: &V3c<u32>, size: u32) {
+        if self.checksums.is_some() || self.modified_regions.is_some() {
+            let (envelope_min, envelope_max) = self.update_envelope(position, size);
+            self.checksums_before_update_in(&envelope_min, &envelope_max);
+        }
+    }
+
+    /// Verifies the bricks overlapping the given region before they are modified,
+    /// and marks them to have their checksums updated after the update
+    /// The region is also recorded as modified, while modifications are tracked
+    /// * `region_min` - the minimum position of the region, must be contained within the tree
+    /// * `region_max` - the maximum position of the region, exclusive, not larger than the size of the tree
+    pub(crate) fn checksums_before_update_in(
+        &mut self,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+    ) {
+        self.record_modified_region(region_min, region_max);
+        let Some(mut checksums) = self.checksums.take() else {
+            return;
+        };
+        let bricks_min = V3c::new(
+            region_min.x - region_min.x % DIM as u32,
+            region_min.y - region_min.y % DIM as u32,
+            region_min.z - region_min.z % DIM as u32,
+        );
+        let bricks_max = V3c::new(
+            region_max.x.next_multiple_of(DIM as u32),
+            region_max.y.next_multiple_of(DIM as u32),
+            region_max.z.next_multiple_of(DIM as u32),
+        );
+        for x in (bricks_min.x..bricks_max.x).step_by(DIM) {
+            for y in (bricks_min.y..bricks_max.y).step_by(DIM) {
+                for z in (bricks_min.z..bricks_max.z).step_by(DIM) {
+                    let origin = V3c::new(x, y, z);
+                    if !checksums.pending.insert(origin) {
+                        // Already written since its checksum was last updated, no need to hash it again
+                        continue;
+                    }
+                    let actual = self.checksums_in(&origin, &(origin + V3c::unit(DIM as u32)));
+                    if checksums.values.get(&origin) != actual.get(&origin) {
+                        checksums.corrupted.insert(origin);
+                    }
+                }
+            }
+        }
+        self.checksums = Some(checksums);
+    }
+
+    /// Recalculates the checksums of the written bricks, unless a batch of updates is running
+    pub(crate) fn update_pending_checksums(&mut self) {
+        let Some(mut checksums) = self.checksums.take() else {
+            return;
+        };
+        if !checksums.deferred {
+            for origin in std::mem::take(&mut checksums.pending) {
+                match self
+                    .checksums_in(&origin, &(origin + V3c::unit(DIM as u32)))
+                    .remove(&origin)
+                {
+                    Some(checksum) => checksums.values.insert(origin, checksum),
+                    None => checksums.values.remove(&origin),
+                };
+            }
+        }
+        self.checksums = Some(checksums);
+    }
+
+    /// Sets whether checksums are updated after each write, or only once the running batch of updates is done
+    /// * Returns with the previous setting
+    pub(crate) fn defer_checksums(&mut self, deferred: bool) -> bool {
+        match &mut self.checksums {
+            Some(checksums) => std::mem::replace(&mut checksums.deferred, deferred),
+            None => false,
+        }
+    }
+
+    /// Moves every maintained checksum by the given offset, after the contents of the tree were moved
+    /// The whole tree is recorded as modified, while modifications are tracked
+    /// * `offset` - applied to the minimum position of every brick, its components need to be divisible by DIM
+    /// * `towards_origin` - true if the offset is subtracted from the positions instead of added to them,
+    /// bricks which would be moved below the origin are dropped
+    pub(crate) fn move_checksums(&mut self, offset: &V3c<u32>, towards_origin: bool) {
+        self.record_modified_region(&V3c::unit(0), &V3c::unit(self.octree_size));
+        let Some(checksums) = &mut self.checksums else {
+            return;
+        };
+        let moved = |origin: V3c<u32>| {
+            if !towards_origin {
+                Some(origin + *offset)
+            } else if offset.x <= origin.x && offset.y <= origin.y && offset.z <= origin.z {
+                Some(origin - *offset)
+            } else {
+                None
+            }
+        };
+        checksums.values = checksums
+            .values
+            .drain()
+            .filter_map(|(origin, checksum)| Some((moved(origin)?, checksum)))
+            .collect();
+        checksums.corrupted = checksums.corrupted.drain().filter_map(moved).collect();
+        checksums.pending = checksums.pending.drain().filter_map(moved).collect();
+    }
+
+    /// Recalculates every maintained checksum, after the contents of the tree were rearranged
+    /// Bricks corrupted before the rearrangement can not be detected afterwards
+    /// The whole tree is recorded as modified, while modifications are tracked
+    pub(crate) fn rebuild_checksums(&mut self) {
+        self.record_modified_region(&V3c::unit(0), &V3c::unit(self.octree_size));
+        if self.checksums.is_some() {
+            let values = self.brick_checksums();
+            if let Some(checksums) = &mut self.checksums {
+                checksums.values = values;
+                checksums.pending.clear();
+            }
+        }
+    }
+}
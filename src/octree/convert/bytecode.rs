@@ -1,11 +1,16 @@
-use crate::object_pool::ObjectPool;
+use crate::object_pool::{empty_marker, missing_item, ObjectPool};
 use crate::octree::{
-    types::{BrickData, NodeChildren, NodeChildrenArray, NodeContent},
-    Albedo, Octree, VoxelData,
+    types::{BrickChecksums, BrickData, NodeChildren, NodeChildrenArray, NodeContent},
+    Albedo, Octree, OctreeHeader, OctreeLoadError, V3c, VoxelData,
 };
 use bendy::{
-    decoding::{FromBencode, ListDecoder, Object},
-    encoding::{Encoder, Error as BencodeError, SingleItemEncoder, ToBencode},
+    decoding::{Decoder, FromBencode, ListDecoder, Object},
+    encoding::{AsString, Encoder, Error as BencodeError, SingleItemEncoder, ToBencode},
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{BufRead, BufReader, Read, Write},
 };
 
 ///####################################################################################
@@ -45,17 +50,18 @@ where
 {
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
-            Object::Bytes(b) => {
-                debug_assert_eq!(
-                    String::from_utf8(b.to_vec())
-                        .unwrap_or("".to_string())
-                        .as_str(),
-                    "#b"
-                );
-                Ok(BrickData::Empty)
-            }
+            Object::Bytes(b) => match String::from_utf8(b.to_vec())
+                .unwrap_or("".to_string())
+                .as_str()
+            {
+                "#b" => Ok(BrickData::Empty),
+                misc => Err(bendy::decoding::Error::unexpected_token(
+                    "The empty BrickData identifier #b",
+                    "The string ".to_owned() + misc,
+                )),
+            },
             Object::List(mut list) => {
-                let is_solid = match list.next_object()?.unwrap() {
+                let is_solid = match list.next_object()?.ok_or_else(missing_item)? {
                     Object::Bytes(b) => {
                         match String::from_utf8(b.to_vec())
                             .unwrap_or("".to_string())
@@ -81,7 +87,7 @@ where
                     for z in 0..DIM {
                         for y in 0..DIM {
                             for x in 0..DIM {
-                                brick_data[x][y][z] = Self::decode_single(&mut list).unwrap();
+                                brick_data[x][y][z] = Self::decode_single(&mut list)?;
                             }
                         }
                     }
@@ -110,36 +116,46 @@ where
     }
 
     fn decode_single(list: &mut ListDecoder<'obj, 'ser>) -> Result<T, bendy::decoding::Error> {
-        let r = match list.next_object()?.unwrap() {
-            Object::Integer(i) => Ok(i.parse::<u8>().ok().unwrap()),
+        let r = match list.next_object()?.ok_or_else(missing_item)? {
+            Object::Integer(i) => i
+                .parse::<u8>()
+                .map_err(|_| bendy::decoding::Error::unexpected_token("u8 color component", i)),
             _ => Err(bendy::decoding::Error::unexpected_token(
                 "int field red color component",
                 "Something else",
             )),
         }?;
-        let g = match list.next_object()?.unwrap() {
-            Object::Integer(i) => Ok(i.parse::<u8>().ok().unwrap()),
+        let g = match list.next_object()?.ok_or_else(missing_item)? {
+            Object::Integer(i) => i
+                .parse::<u8>()
+                .map_err(|_| bendy::decoding::Error::unexpected_token("u8 color component", i)),
             _ => Err(bendy::decoding::Error::unexpected_token(
                 "int field green color component",
                 "Something else",
             )),
         }?;
-        let b = match list.next_object()?.unwrap() {
-            Object::Integer(i) => Ok(i.parse::<u8>().ok().unwrap()),
+        let b = match list.next_object()?.ok_or_else(missing_item)? {
+            Object::Integer(i) => i
+                .parse::<u8>()
+                .map_err(|_| bendy::decoding::Error::unexpected_token("u8 color component", i)),
             _ => Err(bendy::decoding::Error::unexpected_token(
                 "int field blue color component",
                 "Something else",
             )),
         }?;
-        let a = match list.next_object()?.unwrap() {
-            Object::Integer(i) => Ok(i.parse::<u8>().ok().unwrap()),
+        let a = match list.next_object()?.ok_or_else(missing_item)? {
+            Object::Integer(i) => i
+                .parse::<u8>()
+                .map_err(|_| bendy::decoding::Error::unexpected_token("u8 color component", i)),
             _ => Err(bendy::decoding::Error::unexpected_token(
                 "int field alpha color component",
                 "Something else",
             )),
         }?;
-        let user_data = match list.next_object()?.unwrap() {
-            Object::Integer(i) => i.parse::<u32>().ok().unwrap(),
+        let user_data = match list.next_object()?.ok_or_else(missing_item)? {
+            Object::Integer(i) => i
+                .parse::<u32>()
+                .map_err(|_| bendy::decoding::Error::unexpected_token("u32 user data", i))?,
             _ => 0,
         };
         let albedo = Albedo::default()
@@ -192,7 +208,7 @@ where
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                let (is_leaf, is_uniform) = match list.next_object()?.unwrap() {
+                let (is_leaf, is_uniform) = match list.next_object()?.ok_or_else(missing_item)? {
                     Object::Bytes(b) => {
                         match String::from_utf8(b.to_vec())
                             .unwrap_or("".to_string())
@@ -224,8 +240,15 @@ where
 
                 if !is_leaf && !is_uniform {
                     let occupied_bits;
-                    match list.next_object()?.unwrap() {
-                        Object::Integer(i) => occupied_bits = i.parse::<u64>().ok().unwrap(),
+                    match list.next_object()?.ok_or_else(missing_item)? {
+                        Object::Integer(i) => {
+                            occupied_bits = i.parse::<u64>().map_err(|_| {
+                                bendy::decoding::Error::unexpected_token(
+                                    "u64 Internal Node Occupancy bitmap",
+                                    i,
+                                )
+                            })?
+                        }
                         _ => {
                             return Err(bendy::decoding::Error::unexpected_token(
                                 "int field for Internal Node Occupancy bitmap",
@@ -247,30 +270,52 @@ where
                         BrickData::Empty,
                         BrickData::Empty,
                     ];
-                    leaf_data[0] = BrickData::decode_bencode_object(list.next_object()?.unwrap())?;
-                    leaf_data[1] = BrickData::decode_bencode_object(list.next_object()?.unwrap())?;
-                    leaf_data[2] = BrickData::decode_bencode_object(list.next_object()?.unwrap())?;
-                    leaf_data[3] = BrickData::decode_bencode_object(list.next_object()?.unwrap())?;
-                    leaf_data[4] = BrickData::decode_bencode_object(list.next_object()?.unwrap())?;
-                    leaf_data[5] = BrickData::decode_bencode_object(list.next_object()?.unwrap())?;
-                    leaf_data[6] = BrickData::decode_bencode_object(list.next_object()?.unwrap())?;
-                    leaf_data[7] = BrickData::decode_bencode_object(list.next_object()?.unwrap())?;
+                    leaf_data[0] = BrickData::decode_bencode_object(
+                        list.next_object()?.ok_or_else(missing_item)?,
+                    )?;
+                    leaf_data[1] = BrickData::decode_bencode_object(
+                        list.next_object()?.ok_or_else(missing_item)?,
+                    )?;
+                    leaf_data[2] = BrickData::decode_bencode_object(
+                        list.next_object()?.ok_or_else(missing_item)?,
+                    )?;
+                    leaf_data[3] = BrickData::decode_bencode_object(
+                        list.next_object()?.ok_or_else(missing_item)?,
+                    )?;
+                    leaf_data[4] = BrickData::decode_bencode_object(
+                        list.next_object()?.ok_or_else(missing_item)?,
+                    )?;
+                    leaf_data[5] = BrickData::decode_bencode_object(
+                        list.next_object()?.ok_or_else(missing_item)?,
+                    )?;
+                    leaf_data[6] = BrickData::decode_bencode_object(
+                        list.next_object()?.ok_or_else(missing_item)?,
+                    )?;
+                    leaf_data[7] = BrickData::decode_bencode_object(
+                        list.next_object()?.ok_or_else(missing_item)?,
+                    )?;
                     return Ok(NodeContent::Leaf(leaf_data));
                 }
 
                 if is_leaf && is_uniform {
                     return Ok(NodeContent::UniformLeaf(BrickData::decode_bencode_object(
-                        list.next_object()?.unwrap(),
+                        list.next_object()?.ok_or_else(missing_item)?,
                     )?));
                 }
                 panic!(
                     "The logical combination of !is_leaf and is_uniform should never be reached"
                 );
             }
-            Object::Bytes(b) => {
-                assert!(String::from_utf8(b.to_vec()).unwrap_or("".to_string()) == "#");
-                Ok(NodeContent::Nothing)
-            }
+            Object::Bytes(b) => match String::from_utf8(b.to_vec())
+                .unwrap_or("".to_string())
+                .as_str()
+            {
+                "#" => Ok(NodeContent::Nothing),
+                misc => Err(bendy::decoding::Error::unexpected_token(
+                    "The empty NodeContent identifier #",
+                    "The string ".to_owned() + misc,
+                )),
+            },
             _ => Err(bendy::decoding::Error::unexpected_token(
                 "A NodeContent Object, either a List or a ByteString",
                 "Something else",
@@ -311,51 +356,101 @@ impl ToBencode for NodeChildren<u32> {
 
 impl FromBencode for NodeChildren<u32> {
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
-        use crate::object_pool::empty_marker;
-        match data {
-            Object::List(mut list) => {
-                let marker = String::decode_bencode_object(list.next_object()?.unwrap())?;
-                match marker.as_str() {
-                    "##c##" => {
-                        let mut c = Vec::new();
-                        for _ in 0..8 {
-                            c.push(
-                                u32::decode_bencode_object(list.next_object()?.unwrap())
-                                    .ok()
-                                    .unwrap(),
-                            );
+        decode_node_children(data, None)
+    }
+}
+
+/// Encodes the children of a node with their keys relative to the key of the node,
+/// which is smaller than the key of any of its children in depth first order
+struct RelativeChildren<'a>(&'a NodeChildren<u32>, u32);
+
+impl ToBencode for RelativeChildren<'_> {
+    const MAX_DEPTH: usize = 2;
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        let NodeChildrenArray::Children(c) = &self.0.content else {
+            return self.0.encode(encoder);
+        };
+        encoder.emit_list(|e| {
+            e.emit_str("##r##")?;
+            for child_key in c {
+                if *child_key == empty_marker() {
+                    e.emit(*child_key)?;
+                } else {
+                    debug_assert!(self.1 < *child_key);
+                    e.emit(child_key - self.1)?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Decodes the children of a node, child keys relative to the node are converted to keys of the pool
+/// * `node_key` - the key of the node in the pool, children with relative keys can only be decoded with it
+fn decode_node_children(
+    data: Object,
+    node_key: Option<u32>,
+) -> Result<NodeChildren<u32>, bendy::decoding::Error> {
+    match data {
+        Object::List(mut list) => {
+            let marker =
+                String::decode_bencode_object(list.next_object()?.ok_or_else(missing_item)?)?;
+            match marker.as_str() {
+                "##c##" | "##r##" => {
+                    let mut c = [empty_marker(); 8];
+                    for child_key in c.iter_mut() {
+                        *child_key = u32::decode_bencode_object(
+                            list.next_object()?.ok_or_else(missing_item)?,
+                        )?;
+                        if "##r##" == marker && *child_key != empty_marker() {
+                            let Some(node_key) = node_key else {
+                                return Err(bendy::decoding::Error::unexpected_token(
+                                    "Children with absolute keys",
+                                    "Children with keys relative to an unknown node",
+                                ));
+                            };
+                            *child_key = node_key
+                                .checked_add(*child_key)
+                                .filter(|child_key| *child_key != empty_marker())
+                                .ok_or_else(|| {
+                                    bendy::decoding::Error::unexpected_token(
+                                        "A relative child key inside the node pool",
+                                        format!("{child_key} relative to node {node_key}"),
+                                    )
+                                })?;
                         }
-                        Ok(NodeChildren {
-                            empty_marker: empty_marker(),
-                            content: NodeChildrenArray::Children(c.try_into().ok().unwrap()),
-                        })
                     }
-                    "##b##" => Ok(NodeChildren {
+                    Ok(NodeChildren {
                         empty_marker: empty_marker(),
-                        content: NodeChildrenArray::OccupancyBitmap(u64::decode_bencode_object(
-                            list.next_object()?.unwrap(),
-                        )?),
-                    }),
-                    s => Err(bendy::decoding::Error::unexpected_token(
-                        "A NodeChildren marker, either ##b##, ##bs## or ##c##",
-                        s,
-                    )),
+                        content: NodeChildrenArray::Children(c),
+                    })
                 }
+                "##b##" => Ok(NodeChildren {
+                    empty_marker: empty_marker(),
+                    content: NodeChildrenArray::OccupancyBitmap(u64::decode_bencode_object(
+                        list.next_object()?.ok_or_else(missing_item)?,
+                    )?),
+                }),
+                s => Err(bendy::decoding::Error::unexpected_token(
+                    "A NodeChildren marker, either ##b##, ##c## or ##r##",
+                    s,
+                )),
             }
-            Object::Bytes(b) => {
-                debug_assert_eq!(
-                    String::from_utf8(b.to_vec())
-                        .unwrap_or("".to_string())
-                        .as_str(),
-                    "##x##"
-                );
-                Ok(NodeChildren::new(empty_marker()))
-            }
-            _ => Err(bendy::decoding::Error::unexpected_token(
-                "A NodeChildren Object, Either a List or a ByteString",
-                "Something else",
-            )),
         }
+        Object::Bytes(b) => match String::from_utf8(b.to_vec())
+            .unwrap_or("".to_string())
+            .as_str()
+        {
+            "##x##" => Ok(NodeChildren::new(empty_marker())),
+            misc => Err(bendy::decoding::Error::unexpected_token(
+                "The empty NodeChildren marker ##x##",
+                "The string ".to_owned() + misc,
+            )),
+        },
+        _ => Err(bendy::decoding::Error::unexpected_token(
+            "A NodeChildren Object, Either a List or a ByteString",
+            "Something else",
+        )),
     }
 }
 
@@ -364,19 +459,171 @@ impl FromBencode for NodeChildren<u32> {
 ///####################################################################################
 impl<T, const DIM: usize> ToBencode for Octree<T, DIM>
 where
-    T: Default + Clone + PartialEq + VoxelData,
+    T: Default + Clone + Copy + PartialEq + VoxelData,
 {
     const MAX_DEPTH: usize = 10;
     fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        let (nodes, node_children) = self.depth_first_ordered();
         encoder.emit_list(|e| {
             e.emit_int(self.auto_simplify as u8)?;
             e.emit_int(self.octree_size)?;
-            e.emit(&self.nodes)?;
-            e.emit(&self.node_children)
+            e.emit(&nodes)?;
+            e.emit_list(|e| {
+                for (node_key, children) in node_children.iter().enumerate() {
+                    e.emit(RelativeChildren(children, node_key as u32))?;
+                }
+                Ok(())
+            })?;
+            e.emit(EncodedMetadata(&self.metadata))?;
+            if let Some(checksums) = self.current_checksums() {
+                e.emit(EncodedChecksums(&checksums))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Encodes the metadata of a tree as a dictionary
+struct EncodedMetadata<'a>(&'a BTreeMap<String, Vec<u8>>);
+
+impl ToBencode for EncodedMetadata<'_> {
+    const MAX_DEPTH: usize = 1;
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        encoder.emit_dict(|mut d| {
+            for (key, value) in self.0 {
+                d.emit_pair(key.as_bytes(), AsString(value))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Encodes brick checksums as a list of `[x, y, z, checksum]` lists, ordered by position
+struct EncodedChecksums<'a>(&'a HashMap<V3c<u32>, u64>);
+
+impl ToBencode for EncodedChecksums<'_> {
+    const MAX_DEPTH: usize = 2;
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        let mut origins = self.0.keys().collect::<Vec<_>>();
+        origins.sort_unstable_by_key(|origin| (origin.x, origin.y, origin.z));
+        encoder.emit_list(|e| {
+            for origin in origins {
+                e.emit(EncodedChecksum(origin, self.0[origin]))?;
+            }
+            Ok(())
         })
     }
 }
 
+/// Encodes the checksum of one brick as `[x, y, z, checksum]`
+struct EncodedChecksum<'a>(&'a V3c<u32>, u64);
+
+impl ToBencode for EncodedChecksum<'_> {
+    const MAX_DEPTH: usize = 1;
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        encoder.emit_list(|e| {
+            e.emit_int(self.0.x)?;
+            e.emit_int(self.0.y)?;
+            e.emit_int(self.0.z)?;
+            e.emit_int(self.1)
+        })
+    }
+}
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Clone + PartialEq + VoxelData,
+{
+    /// Provides a copy of the nodes of the tree in depth first order, without unused node slots
+    /// Nodes are saved in this order, so the loaded pool is filled sequentially,
+    /// and the nodes visited together during traversal are stored close to each other
+    fn depth_first_ordered(&self) -> (ObjectPool<NodeContent<T, DIM>>, Vec<NodeChildren<u32>>) {
+        let (order, new_keys) = self.depth_first_order();
+        let mut nodes = ObjectPool::with_capacity(order.len());
+        let mut node_children = Vec::with_capacity(order.len());
+        for node_key in order {
+            nodes.push(self.nodes.get(node_key).clone());
+            node_children.push(self.children_with_new_keys(node_key, &new_keys));
+        }
+        nodes.set_first_available(node_children.len());
+        (nodes, node_children)
+    }
+
+    /// Provides the keys of the valid nodes in depth first order, and the new key of every node in that order
+    fn depth_first_order(&self) -> (Vec<usize>, Vec<u32>) {
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut new_keys = vec![empty_marker(); self.nodes.len()];
+        let mut node_stack = vec![Self::ROOT_NODE_KEY as usize];
+        while let Some(node_key) = node_stack.pop() {
+            new_keys[node_key] = order.len() as u32;
+            order.push(node_key);
+            if let NodeChildrenArray::Children(children) = &self.node_children[node_key].content {
+                // Children are pushed in reverse, so they are visited in the order of their octants
+                for child_key in children.iter().rev() {
+                    if self.nodes.key_is_valid(*child_key as usize) {
+                        node_stack.push(*child_key as usize);
+                    }
+                }
+            }
+        }
+        (order, new_keys)
+    }
+
+    /// Provides the children of the given node, referenced by the keys of the depth first order
+    fn children_with_new_keys(&self, node_key: usize, new_keys: &[u32]) -> NodeChildren<u32> {
+        let mut children = self.node_children[node_key];
+        if let NodeChildrenArray::Children(child_keys) = &mut children.content {
+            for child_key in child_keys.iter_mut() {
+                if self.nodes.key_is_valid(*child_key as usize) {
+                    *child_key = new_keys[*child_key as usize];
+                } else {
+                    *child_key = empty_marker();
+                }
+            }
+        }
+        children
+    }
+}
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Checks the consistency of a decoded tree, so invalid data is not used as a tree
+    /// * Returns with the description of the first found problem
+    pub(crate) fn validate_structure(&self) -> Result<(), String> {
+        if DIM >= self.octree_size as usize
+            || !(self.octree_size / DIM as u32).is_power_of_two()
+            || 0 != self.octree_size % DIM as u32
+        {
+            return Err(format!(
+                "Invalid tree size {} for brick dimension {DIM}",
+                self.octree_size
+            ));
+        }
+        if !self.nodes.key_is_valid(Self::ROOT_NODE_KEY as usize) {
+            return Err("Missing root node".to_string());
+        }
+        if self.node_children.len() < self.nodes.len() {
+            return Err(format!(
+                "Children are stored for {} nodes out of {}",
+                self.node_children.len(),
+                self.nodes.len()
+            ));
+        }
+        for node_key in 0..self.nodes.len() {
+            if let NodeChildrenArray::Children(children) = &self.node_children[node_key].content {
+                if let Some(child_key) = children.iter().find(|child_key| {
+                    **child_key != empty_marker() && !self.nodes.key_is_valid(**child_key as usize)
+                }) {
+                    return Err(format!("Node {node_key} has invalid child {child_key}"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 impl<T, const DIM: usize> FromBencode for Octree<T, DIM>
 where
     T: Eq + Default + Clone + Copy + VoxelData,
@@ -384,7 +631,7 @@ where
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                let auto_simplify = match list.next_object()?.unwrap() {
+                let auto_simplify = match list.next_object()?.ok_or_else(missing_item)? {
                     Object::Integer("0") => Ok(false),
                     Object::Integer("1") => Ok(true),
                     Object::Integer(i) => Err(bendy::decoding::Error::unexpected_token(
@@ -397,25 +644,662 @@ where
                     )),
                 }?;
 
-                let root_size = match list.next_object()?.unwrap() {
-                    Object::Integer(i) => Ok(i.parse::<u32>().ok().unwrap()),
+                let root_size = match list.next_object()?.ok_or_else(missing_item)? {
+                    Object::Integer(i) => i.parse::<u32>().map_err(|_| {
+                        bendy::decoding::Error::unexpected_token("int field root_size", i)
+                    }),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field root_size",
                         "Something else",
                     )),
                 }?;
                 let nodes = ObjectPool::<NodeContent<T, DIM>>::decode_bencode_object(
-                    list.next_object()?.unwrap(),
+                    list.next_object()?.ok_or_else(missing_item)?,
                 )?;
-                let node_children = Vec::decode_bencode_object(list.next_object()?.unwrap())?;
+                let mut node_children = Vec::with_capacity(nodes.len());
+                match list.next_object()?.ok_or_else(missing_item)? {
+                    Object::List(mut children_list) => {
+                        while let Some(children) = children_list.next_object()? {
+                            let node_key = node_children.len() as u32;
+                            node_children.push(decode_node_children(children, Some(node_key))?);
+                        }
+                    }
+                    _ => {
+                        return Err(bendy::decoding::Error::unexpected_token(
+                            "List of NodeChildren",
+                            "not List",
+                        ))
+                    }
+                }
+
+                let metadata = decode_metadata(list.next_object()?)?;
+                let checksums = decode_checksums(list.next_object()?)?;
                 Ok(Self {
                     auto_simplify,
                     octree_size: root_size,
                     nodes,
                     node_children,
+                    metadata,
+                    checksums,
+                    modified_regions: None,
                 })
             }
             _ => Err(bendy::decoding::Error::unexpected_token("List", "not List")),
         }
     }
 }
+
+/// Decodes the metadata of a tree
+/// Metadata is optional, as trees saved before it was introduced do not have it
+fn decode_metadata(
+    data: Option<Object>,
+) -> Result<BTreeMap<String, Vec<u8>>, bendy::decoding::Error> {
+    let mut metadata = BTreeMap::new();
+    match data {
+        None => {}
+        Some(Object::Dict(mut dict)) => {
+            while let Some((key, value)) = dict.next_pair()? {
+                let key = String::from_utf8(key.to_vec()).map_err(|_| {
+                    bendy::decoding::Error::unexpected_token("UTF-8 metadata key", "invalid UTF-8")
+                })?;
+                let value = match value {
+                    Object::Bytes(bytes) => Ok(bytes.to_vec()),
+                    _ => Err(bendy::decoding::Error::unexpected_token(
+                        "byte string metadata value",
+                        "Something else",
+                    )),
+                }?;
+                metadata.insert(key, value);
+            }
+        }
+        Some(_) => {
+            return Err(bendy::decoding::Error::unexpected_token(
+                "Dictionary of metadata",
+                "Something else",
+            ))
+        }
+    }
+    Ok(metadata)
+}
+
+/// Decodes the next item of the given list as an integer
+fn decode_int<N: std::str::FromStr>(
+    list: &mut ListDecoder,
+    field: &str,
+) -> Result<N, bendy::decoding::Error> {
+    match list.next_object()? {
+        Some(Object::Integer(i)) => i
+            .parse::<N>()
+            .map_err(|_| bendy::decoding::Error::unexpected_token(field, i)),
+        _ => Err(bendy::decoding::Error::unexpected_token(
+            field,
+            "Something else",
+        )),
+    }
+}
+
+/// Decodes the brick checksums of a tree
+/// Checksums are optional, as they are only saved for trees maintaining them
+fn decode_checksums(
+    data: Option<Object>,
+) -> Result<Option<BrickChecksums>, bendy::decoding::Error> {
+    let Some(data) = data else {
+        return Ok(None);
+    };
+    let Object::List(mut list) = data else {
+        return Err(bendy::decoding::Error::unexpected_token(
+            "List of brick checksums",
+            "Something else",
+        ));
+    };
+    let mut values = HashMap::new();
+    while let Some(entry) = list.next_object()? {
+        let Object::List(mut entry) = entry else {
+            return Err(bendy::decoding::Error::unexpected_token(
+                "List of brick position and checksum",
+                "Something else",
+            ));
+        };
+        let origin = V3c::new(
+            decode_int::<u32>(&mut entry, "u32 brick position")?,
+            decode_int::<u32>(&mut entry, "u32 brick position")?,
+            decode_int::<u32>(&mut entry, "u32 brick position")?,
+        );
+        let checksum = decode_int::<u64>(&mut entry, "u64 brick checksum")?;
+        values.insert(origin, checksum);
+    }
+    Ok(Some(BrickChecksums {
+        values,
+        ..Default::default()
+    }))
+}
+
+///####################################################################################
+/// Format version
+///####################################################################################
+/// Marks the byte representation of a tree, it is followed by the format version
+const FORMAT_MAGIC: &[u8; 4] = b"SVOX";
+
+/// The version of the byte layout written by this version of the library
+/// It needs to be increased with every layout change, together with a migration step in `migrate_payload`
+/// * Version 2 added the header block between the format version and the encoded tree
+/// * Version 3 stores the keys of child nodes relative to the key of their parent
+pub(crate) const FORMAT_VERSION: u32 = 3;
+
+/// The first format version with a header block, see `Octree::encoded_header`
+const HEADER_BLOCK_VERSION: u32 = 2;
+
+/// The length of the fixed size fields of the header block, which are followed by the metadata
+const HEADER_FIXED_LENGTH: usize = 4 + 4 + 1 + 8 + 8 + 4 + 4 + 4;
+
+/// Prepends the magic number, the format version and the header block to the encoded tree
+/// The encoded tree may be compressed, the header block never is, so it can be read on its own
+pub(crate) fn with_format_header(header: &[u8], payload: Vec<u8>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(FORMAT_MAGIC.len() + 4 + header.len() + payload.len());
+    bytes.extend_from_slice(FORMAT_MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(header);
+    bytes.extend(payload);
+    bytes
+}
+
+/// Separates the format version from the encoded tree
+/// Bytes without the magic number were saved before the format was versioned, they are treated as version 0
+fn split_format_header(bytes: &[u8]) -> Result<(u32, &[u8]), OctreeLoadError> {
+    let Some(versioned) = bytes.strip_prefix(FORMAT_MAGIC) else {
+        return Ok((0, bytes));
+    };
+    let Some((version, payload)) = versioned.split_first_chunk::<4>() else {
+        return Err(OctreeLoadError::Decode(
+            "Data ended before the format version".to_string(),
+        ));
+    };
+    Ok((u32::from_le_bytes(*version), payload))
+}
+
+/// Converts the encoded tree of the given format version to the current layout
+fn migrate_payload(version: u32, payload: &[u8]) -> Result<Cow<[u8]>, OctreeLoadError> {
+    match version {
+        // Version 0 only differs in the missing format header
+        0 | 1 => Ok(Cow::Borrowed(payload)),
+        // The header block is skipped, the encoded tree after it may be compressed
+        // Relative child keys are marked in the encoded tree, so it is decoded the same way
+        HEADER_BLOCK_VERSION..=FORMAT_VERSION => {
+            let mut reader = payload;
+            OctreeHeader::decode(&mut reader)?;
+            decompressed(reader)
+        }
+        found => Err(OctreeLoadError::VersionMismatch {
+            found,
+            supported: FORMAT_VERSION,
+        }),
+    }
+}
+
+/// Marks the LZ4 compressed byte representation of a tree
+const COMPRESSED_MAGIC: &[u8; 4] = b"SVXZ";
+
+/// Compresses the byte representation of a tree
+#[cfg(feature = "compression")]
+pub(crate) fn compressed(bytes: &[u8]) -> Vec<u8> {
+    let mut result = COMPRESSED_MAGIC.to_vec();
+    result.extend(lz4_flex::compress_prepend_size(bytes));
+    result
+}
+
+/// Provides the uncompressed byte representation of a tree, bytes without compression are returned as is
+pub(crate) fn decompressed(bytes: &[u8]) -> Result<Cow<[u8]>, OctreeLoadError> {
+    let Some(compressed) = bytes.strip_prefix(COMPRESSED_MAGIC) else {
+        return Ok(Cow::Borrowed(bytes));
+    };
+
+    #[cfg(feature = "compression")]
+    {
+        lz4_flex::decompress_size_prepended(compressed)
+            .map(Cow::Owned)
+            .map_err(|error| OctreeLoadError::Decode(format!("{error:?}")))
+    }
+
+    #[cfg(not(feature = "compression"))]
+    {
+        let _ = compressed;
+        Err(OctreeLoadError::Decode(
+            "Compressed data can only be loaded with the compression feature".to_string(),
+        ))
+    }
+}
+
+/// Provides the encoded tree in the current layout from bytes of any supported format version
+pub(crate) fn current_payload(bytes: &[u8]) -> Result<Cow<[u8]>, OctreeLoadError> {
+    let (version, payload) = split_format_header(bytes)?;
+    migrate_payload(version, payload)
+}
+
+///####################################################################################
+/// Streaming
+///####################################################################################
+/// Reads bencode objects one by one, so the whole input doesn't need to be in memory at once
+struct BencodeReader<R: BufRead> {
+    reader: R,
+}
+
+impl<R: BufRead> BencodeReader<R> {
+    fn peek(&mut self) -> Result<u8, OctreeLoadError> {
+        self.reader.fill_buf()?.first().copied().ok_or_else(|| {
+            OctreeLoadError::Decode("Data ended before the end of the tree".to_string())
+        })
+    }
+
+    fn read_byte(&mut self) -> Result<u8, OctreeLoadError> {
+        let byte = self.peek()?;
+        self.reader.consume(1);
+        Ok(byte)
+    }
+
+    fn expect(&mut self, token: u8) -> Result<(), OctreeLoadError> {
+        let byte = self.read_byte()?;
+        if byte != token {
+            return Err(OctreeLoadError::Decode(format!(
+                "Expected {} instead of {}",
+                token as char, byte as char
+            )));
+        }
+        Ok(())
+    }
+
+    /// Reads the next object, including every object nested in it
+    fn read_object(&mut self) -> Result<Vec<u8>, OctreeLoadError> {
+        let mut object = Vec::new();
+        let mut depth = 0;
+        loop {
+            let token = self.read_byte()?;
+            object.push(token);
+            match token {
+                b'i' => {
+                    while b'e' != *object.last().unwrap() {
+                        object.push(self.read_byte()?);
+                    }
+                }
+                b'l' | b'd' => depth += 1,
+                b'e' if 0 < depth => depth -= 1,
+                b'0'..=b'9' => {
+                    let mut length = (token - b'0') as u64;
+                    loop {
+                        let byte = self.read_byte()?;
+                        object.push(byte);
+                        match byte {
+                            b':' => break,
+                            b'0'..=b'9' => {
+                                length = length
+                                    .checked_mul(10)
+                                    .and_then(|length| length.checked_add((byte - b'0') as u64))
+                                    .ok_or_else(|| {
+                                        OctreeLoadError::Decode("Invalid string length".to_string())
+                                    })?;
+                            }
+                            _ => {
+                                return Err(OctreeLoadError::Decode(format!(
+                                    "Unexpected {} in string length",
+                                    byte as char
+                                )))
+                            }
+                        }
+                    }
+                    let expected_length = object.len() as u64 + length;
+                    (&mut self.reader).take(length).read_to_end(&mut object)?;
+                    if object.len() as u64 != expected_length {
+                        return Err(OctreeLoadError::Decode(
+                            "Data ended inside a string".to_string(),
+                        ));
+                    }
+                }
+                _ => {
+                    return Err(OctreeLoadError::Decode(format!(
+                        "Unexpected {} at the start of an object",
+                        token as char
+                    )))
+                }
+            }
+            if 0 == depth {
+                return Ok(object);
+            }
+        }
+    }
+
+    fn read_int<N: std::str::FromStr>(&mut self, field: &str) -> Result<N, OctreeLoadError> {
+        let object = self.read_object()?;
+        object
+            .strip_prefix(b"i")
+            .and_then(|int| int.strip_suffix(b"e"))
+            .and_then(|int| std::str::from_utf8(int).ok())
+            .and_then(|int| int.parse::<N>().ok())
+            .ok_or_else(|| OctreeLoadError::Decode(format!("Invalid int field {field}")))
+    }
+}
+
+fn decode_error(error: bendy::decoding::Error) -> OctreeLoadError {
+    OctreeLoadError::Decode(format!("{error:?}"))
+}
+
+fn encode_error(error: BencodeError) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{error:?}"))
+}
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Provides the header block of the byte representation of the tree, see `OctreeHeader::decode`
+    pub(crate) fn encoded_header(&self) -> Vec<u8> {
+        let mut voxel_count = 0;
+        let mut colors = HashSet::new();
+        let mut user_data = HashSet::new();
+        self.for_each_filled_cell_in(
+            &V3c::unit(0.),
+            &V3c::unit(self.octree_size as f32),
+            &mut |bounds, voxel| {
+                voxel_count += (bounds.size as u64).pow(3);
+                colors.insert(voxel.albedo());
+                user_data.insert(voxel.user_data());
+            },
+        );
+        let metadata = EncodedMetadata(&self.metadata)
+            .to_bencode()
+            .expect("Expected metadata to be encodable");
+
+        let mut header = Vec::with_capacity(HEADER_FIXED_LENGTH + metadata.len());
+        header.extend_from_slice(&self.octree_size.to_le_bytes());
+        header.extend_from_slice(&(DIM as u32).to_le_bytes());
+        header.push(self.auto_simplify as u8);
+        header.extend_from_slice(&voxel_count.to_le_bytes());
+        header.extend_from_slice(&(self.node_count() as u64).to_le_bytes());
+        header.extend_from_slice(&(colors.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(user_data.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        header.extend(metadata);
+        header
+    }
+
+    /// Writes the byte representation of the tree node by node into the given writer
+    /// The written bytes are the same as the result of `to_bytes`, without storing all of them at once
+    pub fn save_to_writer(&self, writer: &mut impl Write) -> Result<(), std::io::Error> {
+        let (order, new_keys) = self.depth_first_order();
+        writer.write_all(FORMAT_MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&self.encoded_header())?;
+        let auto_simplify = self.auto_simplify as u8;
+        write!(writer, "li{auto_simplify}ei{}e", self.octree_size)?;
+
+        // Node pool, all of the written nodes are in use
+        write!(writer, "li{}el", order.len())?;
+        for node_key in &order {
+            let node = self
+                .nodes
+                .get(*node_key)
+                .to_bencode()
+                .map_err(encode_error)?;
+            writer.write_all(b"li1e")?;
+            writer.write_all(&node)?;
+            writer.write_all(b"e")?;
+        }
+        writer.write_all(b"ee")?;
+
+        writer.write_all(b"l")?;
+        for (new_key, node_key) in order.iter().enumerate() {
+            let children = self.children_with_new_keys(*node_key, &new_keys);
+            writer.write_all(
+                &RelativeChildren(&children, new_key as u32)
+                    .to_bencode()
+                    .map_err(encode_error)?,
+            )?;
+        }
+        writer.write_all(b"e")?;
+
+        writer.write_all(
+            &EncodedMetadata(&self.metadata)
+                .to_bencode()
+                .map_err(encode_error)?,
+        )?;
+        if let Some(checksums) = self.current_checksums() {
+            writer.write_all(
+                &EncodedChecksums(&checksums)
+                    .to_bencode()
+                    .map_err(encode_error)?,
+            )?;
+        }
+        writer.write_all(b"e")
+    }
+
+    /// Reads a tree from the given reader node by node, without storing all of its bytes at once
+    /// Compressed data, or data which needs to be migrated from an older format version, is read at once
+    pub fn load_from_reader(reader: &mut impl Read) -> Result<Self, OctreeLoadError> {
+        let mut reader = BufReader::new(reader);
+
+        // Data saved before the format was versioned starts with the tree itself
+        if b'l' != *reader.fill_buf()?.first().unwrap_or(&0) {
+            let mut magic = [0; 4];
+            let mut version = [0; 4];
+            reader.read_exact(&mut magic)?;
+            let mut bytes = magic.to_vec();
+            if FORMAT_MAGIC == &magic {
+                reader.read_exact(&mut version)?;
+                bytes.extend_from_slice(&version);
+            }
+            if FORMAT_MAGIC != &magic || FORMAT_VERSION != u32::from_le_bytes(version) {
+                reader.read_to_end(&mut bytes)?;
+                return Self::from_bytes(bytes);
+            }
+            OctreeHeader::decode(&mut reader)?;
+
+            // The encoded tree is compressed
+            if b'l' != *reader.fill_buf()?.first().unwrap_or(&0) {
+                let mut compressed = Vec::new();
+                reader.read_to_end(&mut compressed)?;
+                let tree = Self::from_bencode(&decompressed(&compressed)?).map_err(decode_error)?;
+                tree.validate_structure()
+                    .map_err(OctreeLoadError::InvalidStructure)?;
+                return Ok(tree);
+            }
+        }
+
+        let mut reader = BencodeReader { reader };
+        reader.expect(b'l')?;
+        let auto_simplify = match reader.read_object()?.as_slice() {
+            b"i0e" => false,
+            b"i1e" => true,
+            _ => {
+                return Err(OctreeLoadError::Decode(
+                    "Invalid boolean field auto_simplify".to_string(),
+                ))
+            }
+        };
+        let octree_size = reader.read_int::<u32>("root_size")?;
+
+        reader.expect(b'l')?;
+        let first_available = reader.read_int::<usize>("first_available")?;
+        let mut nodes = ObjectPool::<NodeContent<T, DIM>>::with_capacity(0);
+        reader.expect(b'l')?;
+        while b'e' != reader.peek()? {
+            nodes
+                .push_encoded(&reader.read_object()?)
+                .map_err(decode_error)?;
+        }
+        reader.expect(b'e')?;
+        reader.expect(b'e')?;
+        nodes.set_first_available(first_available);
+
+        let mut node_children = Vec::with_capacity(nodes.len());
+        reader.expect(b'l')?;
+        while b'e' != reader.peek()? {
+            let object = reader.read_object()?;
+            let mut decoder = Decoder::new(&object);
+            let node_key = node_children.len() as u32;
+            node_children.push(
+                decode_node_children(
+                    decoder
+                        .next_object()
+                        .map_err(decode_error)?
+                        .ok_or_else(missing_item)
+                        .map_err(decode_error)?,
+                    Some(node_key),
+                )
+                .map_err(decode_error)?,
+            );
+        }
+        reader.expect(b'e')?;
+
+        // Metadata is optional, as trees saved before it was introduced do not have it
+        let metadata = if b'e' == reader.peek()? {
+            BTreeMap::new()
+        } else {
+            let object = reader.read_object()?;
+            let mut decoder = Decoder::new(&object);
+            decode_metadata(decoder.next_object().map_err(decode_error)?).map_err(decode_error)?
+        };
+
+        // Checksums are only saved for trees maintaining them
+        let checksums = if b'e' == reader.peek()? {
+            None
+        } else {
+            let object = reader.read_object()?;
+            let mut decoder = Decoder::new(&object);
+            decode_checksums(decoder.next_object().map_err(decode_error)?).map_err(decode_error)?
+        };
+        reader.expect(b'e')?;
+
+        let tree = Self {
+            auto_simplify,
+            octree_size,
+            nodes,
+            node_children,
+            metadata,
+            checksums,
+            modified_regions: None,
+        };
+        tree.validate_structure()
+            .map_err(OctreeLoadError::InvalidStructure)?;
+        Ok(tree)
+    }
+}
+
+///####################################################################################
+/// OctreeHeader
+///####################################################################################
+impl OctreeHeader {
+    /// Reads the header of the tree saved to the given file path, without decoding its nodes
+    /// Only the header block is read from trees saved with format version 2 or later,
+    /// older trees are read whole
+    pub fn read(path: &str) -> Result<Self, OctreeLoadError> {
+        let mut reader = BufReader::new(std::fs::File::open(path)?);
+        let mut bytes = Vec::new();
+        (&mut reader)
+            .take((FORMAT_MAGIC.len() + 4) as u64)
+            .read_to_end(&mut bytes)?;
+        match split_format_header(&bytes) {
+            Ok((version, _)) if (HEADER_BLOCK_VERSION..=FORMAT_VERSION).contains(&version) => {
+                let mut header = Self::decode(&mut reader)?;
+                header.format_version = version;
+                Ok(header)
+            }
+            _ => {
+                reader.read_to_end(&mut bytes)?;
+                Self::from_bytes(&bytes)
+            }
+        }
+    }
+
+    /// Parses the header from the byte representation of a tree, skipping node contents
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OctreeLoadError> {
+        let bytes = decompressed(bytes)?;
+        let (format_version, mut payload) = split_format_header(&bytes)?;
+        let mut header = if (HEADER_BLOCK_VERSION..=FORMAT_VERSION).contains(&format_version) {
+            Self::decode(&mut payload)?
+        } else {
+            Self::decode_payload(&current_payload(&bytes)?)
+                .map_err(|error| OctreeLoadError::Decode(format!("{error:?}")))?
+        };
+        header.format_version = format_version;
+        Ok(header)
+    }
+
+    /// Reads the header block written before the encoded tree, see `Octree::encoded_header`
+    /// The block starts with the fixed size fields, all of them little endian:
+    /// size: u32, brick_dim: u32, auto_simplify: u8, voxel_count: u64, node_count: u64,
+    /// color_palette_size: u32, user_data_palette_size: u32, metadata length: u32
+    /// These are followed by the encoded metadata of the given length
+    fn decode(reader: &mut impl Read) -> Result<Self, OctreeLoadError> {
+        let mut fixed = [0; HEADER_FIXED_LENGTH];
+        reader.read_exact(&mut fixed)?;
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(fixed[offset..offset + 4].try_into().unwrap());
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(fixed[offset..offset + 8].try_into().unwrap());
+        let mut metadata = vec![0; u32_at(33) as usize];
+        reader.read_exact(&mut metadata)?;
+        let mut decoder = Decoder::new(&metadata);
+        let metadata =
+            decode_metadata(decoder.next_object().map_err(decode_error)?).map_err(decode_error)?;
+        Ok(Self {
+            size: u32_at(0),
+            brick_dim: Some(u32_at(4)),
+            auto_simplify: 0 != fixed[8],
+            voxel_count: Some(u64_at(9)),
+            node_count: u64_at(17) as usize,
+            color_palette_size: Some(u32_at(25) as usize),
+            user_data_palette_size: Some(u32_at(29) as usize),
+            format_version: FORMAT_VERSION,
+            metadata,
+        })
+    }
+
+    fn decode_payload(bytes: &[u8]) -> Result<Self, bendy::decoding::Error> {
+        let mut decoder = Decoder::new(bytes);
+        let Some(Object::List(mut list)) = decoder.next_object()? else {
+            return Err(bendy::decoding::Error::unexpected_token("List", "not List"));
+        };
+        let auto_simplify = match list.next_object()? {
+            Some(Object::Integer(i)) => Ok("0" != i),
+            _ => Err(bendy::decoding::Error::unexpected_token(
+                "boolean field auto_simplify",
+                "Something else",
+            )),
+        }?;
+        let size = match list.next_object()? {
+            Some(Object::Integer(i)) => i
+                .parse::<u32>()
+                .map_err(|_| bendy::decoding::Error::unexpected_token("int field root_size", i)),
+            _ => Err(bendy::decoding::Error::unexpected_token(
+                "int field root_size",
+                "Something else",
+            )),
+        }?;
+
+        // Only the reserved flag of each item in the node pool is read, contents are skipped
+        let mut node_count = 0;
+        if let Some(Object::List(mut pool)) = list.next_object()? {
+            pool.next_object()?; // first_available
+            if let Some(Object::List(mut items)) = pool.next_object()? {
+                while let Some(item) = items.next_object()? {
+                    if let Object::List(mut item) = item {
+                        if let Some(Object::Integer("1")) = item.next_object()? {
+                            node_count += 1;
+                        }
+                    }
+                }
+            }
+        }
+        list.next_object()?; // node_children
+        let metadata = decode_metadata(list.next_object()?)?;
+        Ok(Self {
+            size,
+            brick_dim: None,
+            auto_simplify,
+            voxel_count: None,
+            node_count,
+            color_palette_size: None,
+            user_data_palette_size: None,
+            format_version: FORMAT_VERSION,
+            metadata,
+        })
+    }
+}
@@ -0,0 +1,122 @@
+use crate::octree::{types::OctreeError, Octree, V3c, VoxelData};
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Creates a tree from a dense array of voxels, e.g. the output of a noise generator
+    /// The array is copied into the tree one brick at a time, row by row
+    /// The size of the tree is the smallest valid size containing the whole array
+    /// * `data` - the voxels of the array, with x being the fastest changing component, and z the slowest
+    /// * `dimensions` - the extent of the array in each dimension
+    pub fn from_dense_slice(data: &[Option<T>], dimensions: V3c<u32>) -> Result<Self, OctreeError> {
+        let length = dimensions.x as usize * dimensions.y as usize * dimensions.z as usize;
+        if 0 == length || data.len() != length {
+            return Err(OctreeError::InvalidStructure(
+                format!(
+                    "Dense array of {} voxels doesn't match dimensions {:?}",
+                    data.len(),
+                    dimensions
+                )
+                .into(),
+            ));
+        }
+        let extent = dimensions.x.max(dimensions.y).max(dimensions.z);
+        let Some(size) = Self::valid_sizes_around(extent, DIM as u32).1 else {
+            return Err(OctreeError::InvalidStructure(
+                format!("Array extent {extent} is too large for a tree").into(),
+            ));
+        };
+
+        let mut tree = Self::new(size)?;
+        let dimensions = V3c::<usize>::from(dimensions);
+        let mut brick = vec![T::default(); DIM * DIM * DIM];
+        tree.update_batch(|tree| {
+            for brick_z in (0..dimensions.z).step_by(DIM) {
+                for brick_y in (0..dimensions.y).step_by(DIM) {
+                    for brick_x in (0..dimensions.x).step_by(DIM) {
+                        // Parts of the brick outside the array are left empty
+                        brick.fill(T::default());
+                        let row_length = DIM.min(dimensions.x - brick_x);
+                        for z in 0..DIM.min(dimensions.z - brick_z) {
+                            for y in 0..DIM.min(dimensions.y - brick_y) {
+                                let source_start = brick_x
+                                    + dimensions.x * ((brick_y + y) + dimensions.y * (brick_z + z));
+                                let target_start = DIM * (y + DIM * z);
+                                for (target, source) in brick
+                                    [target_start..(target_start + row_length)]
+                                    .iter_mut()
+                                    .zip(&data[source_start..(source_start + row_length)])
+                                {
+                                    *target = source.unwrap_or_default();
+                                }
+                            }
+                        }
+                        if brick.iter().all(|voxel| voxel.is_empty()) {
+                            continue;
+                        }
+                        tree.insert_brick(
+                            &V3c::new(brick_x as u32, brick_y as u32, brick_z as u32),
+                            &brick,
+                        )?;
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        Ok(tree)
+    }
+
+    /// Copies the voxels of the given region into a dense array
+    /// Filled parts of the tree are copied into the array row by row
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    /// * Returns with the voxels of the region, with x being the fastest changing component, and z the slowest
+    ///   positions without voxels, or outside the tree are None
+    pub fn to_dense_region(&self, region_min: &V3c<u32>, region_max: &V3c<u32>) -> Vec<Option<T>> {
+        let dimensions = V3c::new(
+            region_max.x.saturating_sub(region_min.x) as usize,
+            region_max.y.saturating_sub(region_min.y) as usize,
+            region_max.z.saturating_sub(region_min.z) as usize,
+        );
+        let mut dense = vec![None; dimensions.x * dimensions.y * dimensions.z];
+        if dense.is_empty() {
+            return dense;
+        }
+        let mut tree_region_max = *region_max;
+        tree_region_max.cut_each_component(&self.octree_size);
+        self.for_each_filled_cell_in(
+            &V3c::<f32>::from(*region_min),
+            &V3c::<f32>::from(tree_region_max),
+            &mut |bounds, voxel| {
+                let cell_min = V3c::<u32>::from(bounds.min_position);
+                let cell_max = cell_min + V3c::unit(bounds.size as u32);
+                let overlap_min = V3c::new(
+                    cell_min.x.max(region_min.x),
+                    cell_min.y.max(region_min.y),
+                    cell_min.z.max(region_min.z),
+                );
+                let overlap_max = V3c::new(
+                    cell_max.x.min(tree_region_max.x),
+                    cell_max.y.min(tree_region_max.y),
+                    cell_max.z.min(tree_region_max.z),
+                );
+                if overlap_max.x <= overlap_min.x
+                    || overlap_max.y <= overlap_min.y
+                    || overlap_max.z <= overlap_min.z
+                {
+                    return;
+                }
+                let min = V3c::<usize>::from(overlap_min - *region_min);
+                let max = V3c::<usize>::from(overlap_max - *region_min);
+                for z in min.z..max.z {
+                    for y in min.y..max.y {
+                        let row_start = dimensions.x * (y + dimensions.y * z);
+                        dense[(row_start + min.x)..(row_start + max.x)].fill(Some(*voxel));
+                    }
+                }
+            },
+        );
+        dense
+    }
+}
@@ -0,0 +1,166 @@
+use crate::octree::{
+    types::{OctreeError, OctreeLoadError},
+    Albedo, Octree, V3c, VoxelData,
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+};
+
+/// The number of bytes one record takes up inside the log
+const RECORD_SIZE: usize = 25;
+const INSERT_TAG: u8 = 1;
+const CLEAR_TAG: u8 = 2;
+
+/// A single modification of a tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditRecord {
+    Insert {
+        position: V3c<u32>,
+        size: u32,
+        albedo: Albedo,
+        user_data: u32,
+    },
+    Clear {
+        position: V3c<u32>,
+        size: u32,
+    },
+}
+
+impl EditRecord {
+    fn to_bytes(self) -> [u8; RECORD_SIZE] {
+        let (tag, position, size, albedo, user_data) = match self {
+            EditRecord::Insert {
+                position,
+                size,
+                albedo,
+                user_data,
+            } => (INSERT_TAG, position, size, albedo, user_data),
+            EditRecord::Clear { position, size } => {
+                (CLEAR_TAG, position, size, Albedo::default(), 0)
+            }
+        };
+        let mut bytes = [0u8; RECORD_SIZE];
+        bytes[0] = tag;
+        for (i, value) in [position.x, position.y, position.z, size, user_data]
+            .iter()
+            .enumerate()
+        {
+            bytes[(1 + i * 4)..(5 + i * 4)].copy_from_slice(&value.to_le_bytes());
+        }
+        bytes[21..25].copy_from_slice(&[albedo.r, albedo.g, albedo.b, albedo.a]);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let value = |i: usize| {
+            u32::from_le_bytes([
+                bytes[1 + i * 4],
+                bytes[2 + i * 4],
+                bytes[3 + i * 4],
+                bytes[4 + i * 4],
+            ])
+        };
+        let position = V3c::new(value(0), value(1), value(2));
+        match bytes[0] {
+            INSERT_TAG => Some(EditRecord::Insert {
+                position,
+                size: value(3),
+                albedo: Albedo {
+                    r: bytes[21],
+                    g: bytes[22],
+                    b: bytes[23],
+                    a: bytes[24],
+                },
+                user_data: value(4),
+            }),
+            CLEAR_TAG => Some(EditRecord::Clear {
+                position,
+                size: value(3),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Append only log of the modifications done to a tree since its last full save
+/// Replaying the log onto the last saved tree restores the edits done after the save
+pub struct EditLog {
+    file: File,
+}
+
+impl EditLog {
+    /// Opens the log at the given path for appending, creating it if it doesn't exist
+    pub fn open(path: &str) -> Result<Self, std::io::Error> {
+        Ok(Self {
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+        })
+    }
+
+    /// Appends the given record to the end of the log
+    pub fn append(&mut self, record: &EditRecord) -> Result<(), std::io::Error> {
+        self.file.write_all(&record.to_bytes())
+    }
+
+    /// Makes sure every appended record reached the disk
+    pub fn sync(&mut self) -> Result<(), std::io::Error> {
+        self.file.sync_data()
+    }
+
+    /// Empties the log, e.g. after the tree was fully saved
+    pub fn truncate(&mut self) -> Result<(), std::io::Error> {
+        self.file.set_len(0)
+    }
+
+    /// Reads every complete record from the log at the given path
+    /// An incomplete record at the end of the log, e.g. from a crash during writing, is ignored
+    pub fn read(path: &str) -> Result<Vec<EditRecord>, std::io::Error> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        bytes
+            .chunks_exact(RECORD_SIZE)
+            .map(|record| {
+                EditRecord::from_bytes(record).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid edit record type: {}", record[0]),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Applies the given modifications to the tree in order
+    pub fn apply_edits(&mut self, records: &[EditRecord]) -> Result<(), OctreeError> {
+        for record in records {
+            match record {
+                EditRecord::Insert {
+                    position,
+                    size,
+                    albedo,
+                    user_data,
+                } => self.insert_at_lod(position, *size, T::new(*albedo, *user_data))?,
+                EditRecord::Clear { position, size } => self.clear_at_lod(position, *size)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the tree saved at the given path, and replays the edit log at the other path onto it
+    pub fn load_with_edit_log(path: &str, edit_log_path: &str) -> Result<Self, std::io::Error> {
+        let mut tree = Self::load(path).map_err(|error| match error {
+            OctreeLoadError::Io(error) => error,
+            error => std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{error:?}")),
+        })?;
+        tree.apply_edits(&EditLog::read(edit_log_path)?)
+            .map_err(|error| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{error:?}"))
+            })?;
+        Ok(tree)
+    }
+}
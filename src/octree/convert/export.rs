@@ -0,0 +1,82 @@
+use crate::octree::{Octree, V3c, VoxelData};
+use std::io::Write;
+
+/// The columns of each exported voxel record
+const RECORD_COLUMNS: [&str; 8] = ["x", "y", "z", "r", "g", "b", "a", "data"];
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Collects one (x, y, z, r, g, b, a, data) record for each voxel in the tree
+    /// Records are ordered by their position, x being the slowest changing coordinate
+    pub fn voxel_records(&self) -> Vec<[u32; 8]> {
+        let mut records = Vec::new();
+        self.for_each_filled_cell_in(
+            &V3c::unit(0.),
+            &V3c::unit(self.octree_size as f32),
+            &mut |bounds, voxel| {
+                let min = V3c::<u32>::from(bounds.min_position);
+                let size = bounds.size as u32;
+                let albedo = voxel.albedo();
+                for x in min.x..(min.x + size) {
+                    for y in min.y..(min.y + size) {
+                        for z in min.z..(min.z + size) {
+                            records.push([
+                                x,
+                                y,
+                                z,
+                                albedo.r as u32,
+                                albedo.g as u32,
+                                albedo.b as u32,
+                                albedo.a as u32,
+                                voxel.user_data(),
+                            ]);
+                        }
+                    }
+                }
+            },
+        );
+        records.sort_unstable();
+        records
+    }
+
+    /// Writes every voxel of the tree as a line of comma separated values, with a header line
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writeln!(writer, "{}", RECORD_COLUMNS.join(","))?;
+        for record in self.voxel_records() {
+            let fields = record.map(|field| field.to_string());
+            writeln!(writer, "{}", fields.join(","))?;
+        }
+        Ok(())
+    }
+
+    /// Writes every voxel of the tree in the NumPy .npy format
+    /// The stored array is of shape (voxel count, 8) with little endian u32 elements,
+    /// with the columns in the same order as in the csv export
+    pub fn write_npy<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let records = self.voxel_records();
+        let mut header = format!(
+            "{{'descr': '<u4', 'fortran_order': False, 'shape': ({}, {}), }}",
+            records.len(),
+            RECORD_COLUMNS.len()
+        );
+        // magic string, version and header length takes 10 bytes,
+        // the whole header needs to be aligned to 64 bytes, ending with a newline
+        let header_len = (10 + header.len() + 1).div_ceil(64) * 64 - 10;
+        while header.len() < header_len - 1 {
+            header.push(' ');
+        }
+        header.push('\n');
+
+        writer.write_all(b"\x93NUMPY\x01\x00")?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+        for record in records {
+            for field in record {
+                writer.write_all(&field.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+use crate::octree::{meshing::VoxelMesh, Octree, V3c, VoxelData};
+use std::io::Write;
+
+/// Identifies the binary glTF container, and the types of its chunks
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_VERSION: u32 = 2;
+const GLB_JSON_CHUNK: u32 = 0x4E4F534A;
+const GLB_BIN_CHUNK: u32 = 0x004E4942;
+
+/// The size of the regions the tree is meshed in, each in one pass
+const GLB_REGION_SIZE: u32 = 512;
+
+/// Component and target types of the glTF buffers
+const GLTF_FLOAT: u32 = 5126;
+const GLTF_UNSIGNED_BYTE: u32 = 5121;
+const GLTF_UNSIGNED_INT: u32 = 5125;
+const GLTF_ARRAY_BUFFER: u32 = 34962;
+const GLTF_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Writes a chunk of the binary glTF container, padded to 4 bytes
+fn write_glb_chunk<W: Write>(
+    writer: &mut W,
+    chunk_type: u32,
+    content: &[u8],
+    padding: u8,
+) -> std::io::Result<()> {
+    let padding_length = (4 - content.len() % 4) % 4;
+    writer.write_all(&((content.len() + padding_length) as u32).to_le_bytes())?;
+    writer.write_all(&chunk_type.to_le_bytes())?;
+    writer.write_all(content)?;
+    writer.write_all(&vec![padding; padding_length])
+}
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Writes the contents of the tree as a binary glTF(.glb) scene, e.g. to inspect it in Blender
+    /// The voxels are meshed with `extract_greedy_mesh`, their albedo is stored as vertex colors
+    /// The scene contains a single mesh, or no mesh at all in case the tree is empty
+    pub fn write_glb<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        // The tree is meshed region by region, so memory usage depends on the surface of the tree,
+        // faces between the regions are hidden by the voxels of the neighbouring regions
+        let mut mesh = VoxelMesh::default();
+        let region_size = self.octree_size.min(GLB_REGION_SIZE);
+        for z in (0..self.octree_size).step_by(region_size as usize) {
+            for y in (0..self.octree_size).step_by(region_size as usize) {
+                for x in (0..self.octree_size).step_by(region_size as usize) {
+                    let region_min = V3c::new(x, y, z);
+                    let region_mesh = self.extract_greedy_mesh_occluded_by(
+                        &region_min,
+                        &(region_min + V3c::unit(region_size)),
+                        &V3c::unit(0),
+                        &V3c::unit(self.octree_size),
+                    );
+                    let first_vertex = mesh.positions.len() as u32;
+                    mesh.positions.extend(region_mesh.positions);
+                    mesh.colors.extend(region_mesh.colors);
+                    mesh.indices
+                        .extend(region_mesh.indices.iter().map(|index| first_vertex + index));
+                }
+            }
+        }
+
+        // Vertex positions, vertex colors and indices are stored in one buffer after each other
+        let mut buffer = Vec::with_capacity(mesh.positions.len() * 16 + mesh.indices.len() * 4);
+        for position in mesh.positions.iter() {
+            for component in [position.x, position.y, position.z] {
+                buffer.extend_from_slice(&component.to_le_bytes());
+            }
+        }
+        for color in mesh.colors.iter() {
+            buffer.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+        for index in mesh.indices.iter() {
+            buffer.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let json = if mesh.indices.is_empty() {
+            "{\"asset\":{\"version\":\"2.0\",\"generator\":\"shocovox\"},\
+            \"scene\":0,\"scenes\":[{\"nodes\":[]}]}"
+                .to_string()
+        } else {
+            let vertex_count = mesh.positions.len();
+            let positions_length = vertex_count * 12;
+            let colors_length = vertex_count * 4;
+            let (min, max) = mesh.positions.iter().fold(
+                (V3c::unit(f32::MAX), V3c::unit(f32::MIN)),
+                |(min, max), position| {
+                    (
+                        V3c::new(
+                            min.x.min(position.x),
+                            min.y.min(position.y),
+                            min.z.min(position.z),
+                        ),
+                        V3c::new(
+                            max.x.max(position.x),
+                            max.y.max(position.y),
+                            max.z.max(position.z),
+                        ),
+                    )
+                },
+            );
+            format!(
+                "{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"shocovox\"}},\
+                \"scene\":0,\"scenes\":[{{\"nodes\":[0]}}],\"nodes\":[{{\"mesh\":0}}],\
+                \"meshes\":[{{\"primitives\":[{{\"attributes\":{{\"POSITION\":0,\"COLOR_0\":1}},\"indices\":2}}]}}],\
+                \"buffers\":[{{\"byteLength\":{}}}],\
+                \"bufferViews\":[\
+                {{\"buffer\":0,\"byteOffset\":0,\"byteLength\":{positions_length},\"target\":{GLTF_ARRAY_BUFFER}}},\
+                {{\"buffer\":0,\"byteOffset\":{positions_length},\"byteLength\":{colors_length},\"target\":{GLTF_ARRAY_BUFFER}}},\
+                {{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{},\"target\":{GLTF_ELEMENT_ARRAY_BUFFER}}}],\
+                \"accessors\":[\
+                {{\"bufferView\":0,\"componentType\":{GLTF_FLOAT},\"count\":{vertex_count},\"type\":\"VEC3\",\
+                \"min\":[{},{},{}],\"max\":[{},{},{}]}},\
+                {{\"bufferView\":1,\"componentType\":{GLTF_UNSIGNED_BYTE},\"normalized\":true,\"count\":{vertex_count},\"type\":\"VEC4\"}},\
+                {{\"bufferView\":2,\"componentType\":{GLTF_UNSIGNED_INT},\"count\":{},\"type\":\"SCALAR\"}}]}}",
+                buffer.len(),
+                positions_length + colors_length,
+                mesh.indices.len() * 4,
+                min.x,
+                min.y,
+                min.z,
+                max.x,
+                max.y,
+                max.z,
+                mesh.indices.len(),
+            )
+        };
+
+        let json_length = json.len().div_ceil(4) * 4;
+        let mut total_length = 12 + 8 + json_length;
+        if !buffer.is_empty() {
+            total_length += 8 + buffer.len();
+        }
+        writer.write_all(GLB_MAGIC)?;
+        writer.write_all(&GLB_VERSION.to_le_bytes())?;
+        writer.write_all(&(total_length as u32).to_le_bytes())?;
+        write_glb_chunk(&mut writer, GLB_JSON_CHUNK, json.as_bytes(), b' ')?;
+        if !buffer.is_empty() {
+            write_glb_chunk(&mut writer, GLB_BIN_CHUNK, &buffer, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Saves the contents of the tree as a binary glTF(.glb) file, see `write_glb`
+    pub fn save_glb(&self, path: &str) -> std::io::Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_glb(&mut writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod gltf_tests {
+    use crate::octree::{Albedo, Octree, V3c};
+
+    #[test]
+    fn test_glb_export() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+        tree.insert(&V3c::new(1, 1, 1), red).ok().unwrap();
+        let mut bytes = Vec::new();
+        tree.write_glb(&mut bytes).ok().unwrap();
+
+        assert!(&bytes[0..4] == b"glTF");
+        assert!(read_u32(&bytes, 4) == 2);
+        assert!(read_u32(&bytes, 8) as usize == bytes.len());
+
+        // The binary chunk follows the JSON chunk, containing the cube made of 6 quads
+        let json_length = read_u32(&bytes, 12) as usize;
+        assert!(json_length % 4 == 0);
+        let json = std::str::from_utf8(&bytes[20..20 + json_length]).unwrap();
+        assert!(json.contains("\"COLOR_0\":1"));
+        let bin_length = read_u32(&bytes, 20 + json_length) as usize;
+        assert!(bin_length == 24 * 12 + 24 * 4 + 36 * 4);
+        assert!(bytes.len() == 28 + json_length + bin_length);
+        let colors_start = 28 + json_length + 24 * 12;
+        assert!(bytes[colors_start..colors_start + 4] == [0xFF, 0x00, 0x00, 0xFF]);
+
+        let empty_tree = Octree::<Albedo>::new(4).ok().unwrap();
+        let mut empty_bytes = Vec::new();
+        empty_tree.write_glb(&mut empty_bytes).ok().unwrap();
+        assert!(empty_bytes.len() == 20 + read_u32(&empty_bytes, 12) as usize);
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ])
+    }
+}
@@ -0,0 +1,149 @@
+use crate::octree::{types::OctreeError, Octree, V3c, VoxelData};
+use crate::spatial::lut::OCTANT_OFFSET_REGION_LUT;
+
+/// The voxels of the terrain columns, indexed by their depth below the surface
+struct ColumnEntries<T> {
+    entries: Vec<T>,
+    /// The first depth after each depth with a different entry
+    run_ends: Vec<usize>,
+}
+
+impl<T: PartialEq> ColumnEntries<T> {
+    fn new(entries: Vec<T>) -> Self {
+        let mut run_ends = vec![entries.len(); entries.len()];
+        for depth in (0..entries.len().saturating_sub(1)).rev() {
+            if entries[depth] == entries[depth + 1] {
+                run_ends[depth] = run_ends[depth + 1];
+            } else {
+                run_ends[depth] = depth + 1;
+            }
+        }
+        Self { entries, run_ends }
+    }
+
+    /// Provides the entry shared by every depth in the given inclusive range, if there is one
+    fn uniform_between(&self, min_depth: usize, max_depth: usize) -> Option<&T> {
+        (max_depth < self.run_ends[min_depth]).then(|| &self.entries[min_depth])
+    }
+}
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Creates a terrain from the given heightmap, filling each column up to its height
+    /// Parts of the terrain below the surface sharing the same voxel are set in bulk, instead of voxel by voxel
+    /// The size of the tree is the smallest valid size containing the whole terrain
+    /// * `heights` - the height of each column, row by row along the x axis, rows following each other along z
+    /// * `width` - the number of columns in one row, along the x axis
+    /// * `depth` - the number of rows, along the z axis
+    /// * `scale` - the number of voxels one unit of height is converted to
+    /// * `entry_fn` - provides the voxel at the given depth below the surface, 0 being the topmost voxel of a column
+    pub fn from_heightmap<F>(
+        heights: &[u16],
+        width: usize,
+        depth: usize,
+        scale: f32,
+        entry_fn: F,
+    ) -> Result<Self, OctreeError>
+    where
+        F: Fn(u32) -> T,
+    {
+        if 0 == width || 0 == depth || heights.len() != width * depth {
+            return Err(OctreeError::InvalidStructure(
+                format!(
+                    "Heightmap of {} heights can't be split into {depth} rows of {width}",
+                    heights.len()
+                )
+                .into(),
+            ));
+        }
+        let column_heights = heights
+            .iter()
+            .map(|height| (*height as f32 * scale).round().max(0.) as u32)
+            .collect::<Vec<u32>>();
+        let max_height = column_heights.iter().copied().max().unwrap_or(0);
+        let extent = (width as u32).max(depth as u32).max(max_height);
+        let Some(size) = Self::valid_sizes_around(extent, DIM as u32).1 else {
+            return Err(OctreeError::InvalidStructure(
+                format!("Terrain extent {extent} is too large for a tree").into(),
+            ));
+        };
+        let columns = ColumnEntries::new((0..max_height).map(entry_fn).collect());
+        let mut tree = Self::new(size)?;
+        tree.update_batch(|tree| {
+            tree.fill_heightmap_cube(&V3c::unit(0), size, &column_heights, width, depth, &columns)
+        })?;
+        Ok(tree)
+    }
+
+    /// Fills the part of the terrain inside the given cube
+    fn fill_heightmap_cube(
+        &mut self,
+        cube_min: &V3c<u32>,
+        cube_size: u32,
+        column_heights: &[u32],
+        width: usize,
+        depth: usize,
+        columns: &ColumnEntries<T>,
+    ) -> Result<(), OctreeError> {
+        let cube_max = *cube_min + V3c::unit(cube_size);
+        let x_range = cube_min.x as usize..(cube_max.x as usize).min(width);
+        let z_range = cube_min.z as usize..(cube_max.z as usize).min(depth);
+        if x_range.is_empty() || z_range.is_empty() {
+            return Ok(());
+        }
+        let (min_height, max_height) = z_range
+            .clone()
+            .flat_map(|z| {
+                column_heights[(z * width + x_range.start)..(z * width + x_range.end)].iter()
+            })
+            .fold((u32::MAX, 0), |(min, max), height| {
+                (min.min(*height), max.max(*height))
+            });
+        if max_height <= cube_min.y {
+            return Ok(());
+        }
+
+        // Cubes fully below the surface are set at once, if each voxel inside them is the same
+        let covered = x_range.len() == cube_size as usize && z_range.len() == cube_size as usize;
+        if covered && cube_max.y <= min_height && DIM as u32 <= cube_size {
+            let min_depth = (min_height - cube_max.y) as usize;
+            let max_depth = (max_height - 1 - cube_min.y) as usize;
+            if let Some(entry) = columns.uniform_between(min_depth, max_depth) {
+                if entry.is_empty() {
+                    return Ok(());
+                }
+                return self.insert_at_lod(cube_min, cube_size, *entry);
+            }
+        }
+
+        if cube_size <= DIM as u32 {
+            for z in z_range {
+                for x in x_range.clone() {
+                    let height = column_heights[z * width + x];
+                    for y in cube_min.y..cube_max.y.min(height) {
+                        let entry = columns.entries[(height - 1 - y) as usize];
+                        if !entry.is_empty() {
+                            self.insert(&V3c::new(x as u32, y, z as u32), entry)?;
+                        }
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        let child_size = cube_size / 2;
+        for offset in OCTANT_OFFSET_REGION_LUT.iter() {
+            self.fill_heightmap_cube(
+                &(*cube_min + V3c::<u32>::from(*offset) * child_size),
+                child_size,
+                column_heights,
+                width,
+                depth,
+                columns,
+            )?;
+        }
+        Ok(())
+    }
+}
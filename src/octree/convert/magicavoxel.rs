@@ -4,6 +4,7 @@ use crate::{
 };
 use dot_vox::{Color, DotVoxData, Model, SceneNode, Size, Voxel};
 use nalgebra::Matrix3;
+use std::collections::HashMap;
 
 impl From<Albedo> for Color {
     fn from(color: Albedo) -> Self {
@@ -134,12 +135,15 @@ fn iterate_vox_tree<F: FnMut(&Model, &V3c<i32>, &Matrix3<i8>)>(vox_tree: &DotVox
                 child,
                 layer_id: _,
             } => {
+                // The translation of a nested transform is relative to the orientation of its parents
                 let translation = if let Some(t) = frames[0].attributes.get("_t") {
                     translation
-                        + t.split(" ")
-                            .map(|x| x.parse().expect("Not an integer!"))
-                            .collect::<Vec<i32>>()
-                            .into()
+                        + V3c::<i32>::from(
+                            t.split(" ")
+                                .map(|x| x.parse().expect("Not an integer!"))
+                                .collect::<Vec<i32>>(),
+                        )
+                        .clone_transformed(&rotation)
                 } else {
                     translation
                 };
@@ -193,61 +197,106 @@ fn iterate_vox_tree<F: FnMut(&Model, &V3c<i32>, &Matrix3<i8>)>(vox_tree: &DotVox
     }
 }
 
+/// Provides the minimum and maximum position of the models placed in the scene, in left handed Y up coordinates
+fn scene_bounds(vox_tree: &DotVoxData) -> (V3c<i32>, V3c<i32>) {
+    let mut min_position_lyup = V3c::<i32>::new(0, 0, 0);
+    let mut max_position_lyup = V3c::<i32>::new(0, 0, 0);
+    iterate_vox_tree(vox_tree, |model, position, orientation| {
+        let model_size_half_lyup = convert_coordinate(
+            V3c::from(model.size).clone_transformed(orientation),
+            CoordinateSystemType::RZUP,
+            CoordinateSystemType::LYUP,
+        ) / 2;
+
+        // If the index is negative, then it is calculated
+        // as model[size - i - 1][..][..], instead of model[i][..][..]
+        // So one needs to be added in every dimension where the index is below 0
+        let position = convert_coordinate(
+            *position,
+            CoordinateSystemType::RZUP,
+            CoordinateSystemType::LYUP,
+        ) + V3c::new(
+            if model_size_half_lyup.x < 0 { -1 } else { 0 },
+            if model_size_half_lyup.y < 0 { -1 } else { 0 },
+            if model_size_half_lyup.z < 0 { -1 } else { 0 },
+        );
+
+        min_position_lyup.x = min_position_lyup
+            .x
+            .min(position.x - model_size_half_lyup.x)
+            .min(position.x + model_size_half_lyup.x);
+        min_position_lyup.y = min_position_lyup
+            .y
+            .min(position.y - model_size_half_lyup.y)
+            .min(position.y + model_size_half_lyup.y);
+        min_position_lyup.z = min_position_lyup
+            .z
+            .min(position.z - model_size_half_lyup.z)
+            .min(position.z + model_size_half_lyup.z);
+
+        max_position_lyup.x = max_position_lyup
+            .x
+            .max(position.x + model_size_half_lyup.x)
+            .max(position.x - model_size_half_lyup.x);
+        max_position_lyup.y = max_position_lyup
+            .y
+            .max(position.y + model_size_half_lyup.y)
+            .max(position.y - model_size_half_lyup.y);
+        max_position_lyup.z = max_position_lyup
+            .z
+            .max(position.z + model_size_half_lyup.z)
+            .max(position.z - model_size_half_lyup.z);
+    });
+    (min_position_lyup, max_position_lyup)
+}
+
+/// Calls the given function with every voxel of the model placed into the scene
+/// * `min_position_lyup` - the minimum position of the scene, voxel positions are provided relative to it
+/// * `fun` - called with the position of the voxel in left handed Y up coordinates, and the palette index of its color
+fn for_each_placed_voxel<F: FnMut(V3c<i32>, u8)>(
+    model: &Model,
+    position: &V3c<i32>,
+    orientation: &Matrix3<i8>,
+    min_position_lyup: &V3c<i32>,
+    mut fun: F,
+) {
+    let model_size_lyup = convert_coordinate(
+        V3c::from(model.size).clone_transformed(orientation),
+        CoordinateSystemType::RZUP,
+        CoordinateSystemType::LYUP,
+    );
+    let position_lyup = convert_coordinate(
+        *position,
+        CoordinateSystemType::RZUP,
+        CoordinateSystemType::LYUP,
+    );
+
+    let current_position = position_lyup - *min_position_lyup - (model_size_lyup / 2)
+        + V3c::new(
+            if model_size_lyup.x < 0 { -1 } else { 0 },
+            if model_size_lyup.y < 0 { -1 } else { 0 },
+            if model_size_lyup.z < 0 { -1 } else { 0 },
+        );
+
+    for voxel in &model.voxels {
+        let voxel_position = convert_coordinate(
+            V3c::from(*voxel).clone_transformed(orientation),
+            CoordinateSystemType::RZUP,
+            CoordinateSystemType::LYUP,
+        );
+        fun(current_position + voxel_position, voxel.i);
+    }
+}
+
 impl<T, const DIM: usize> Octree<T, DIM>
 where
     T: Default + Eq + Clone + Copy + VoxelData,
 {
+    /// Loads every model of the given .vox file into a single tree, each placed by the transforms of the scene
     pub fn load_vox_file(filename: &str) -> Result<Self, &'static str> {
         let vox_tree = dot_vox::load(filename)?;
 
-        let mut min_position_lyup = V3c::<i32>::new(0, 0, 0);
-        let mut max_position_lyup = V3c::<i32>::new(0, 0, 0);
-        iterate_vox_tree(&vox_tree, |model, position, orientation| {
-            let model_size_half_lyup = convert_coordinate(
-                V3c::from(model.size).clone_transformed(orientation),
-                CoordinateSystemType::RZUP,
-                CoordinateSystemType::LYUP,
-            ) / 2;
-
-            // If the index is negative, then it is calculated
-            // as model[size - i - 1][..][..], instead of model[i][..][..]
-            // So one needs to be added in every dimension where the index is below 0
-            let position = convert_coordinate(
-                *position,
-                CoordinateSystemType::RZUP,
-                CoordinateSystemType::LYUP,
-            ) + V3c::new(
-                if model_size_half_lyup.x < 0 { -1 } else { 0 },
-                if model_size_half_lyup.y < 0 { -1 } else { 0 },
-                if model_size_half_lyup.z < 0 { -1 } else { 0 },
-            );
-
-            min_position_lyup.x = min_position_lyup
-                .x
-                .min(position.x - model_size_half_lyup.x)
-                .min(position.x + model_size_half_lyup.x);
-            min_position_lyup.y = min_position_lyup
-                .y
-                .min(position.y - model_size_half_lyup.y)
-                .min(position.y + model_size_half_lyup.y);
-            min_position_lyup.z = min_position_lyup
-                .z
-                .min(position.z - model_size_half_lyup.z)
-                .min(position.z + model_size_half_lyup.z);
-
-            max_position_lyup.x = max_position_lyup
-                .x
-                .max(position.x + model_size_half_lyup.x)
-                .max(position.x - model_size_half_lyup.x);
-            max_position_lyup.y = max_position_lyup
-                .y
-                .max(position.y + model_size_half_lyup.y)
-                .max(position.y - model_size_half_lyup.y);
-            max_position_lyup.z = max_position_lyup
-                .z
-                .max(position.z + model_size_half_lyup.z)
-                .max(position.z - model_size_half_lyup.z);
-        });
+        let (min_position_lyup, mut max_position_lyup) = scene_bounds(&vox_tree);
         max_position_lyup -= min_position_lyup;
         let max_dimension = max_position_lyup
             .x
@@ -257,51 +306,254 @@ where
         let max_dimension = 2_u32.pow(max_dimension);
         let mut shocovox_octree = Octree::<T, DIM>::new(max_dimension).ok().unwrap();
         iterate_vox_tree(&vox_tree, |model, position, orientation| {
-            let model_size_lyup = convert_coordinate(
-                V3c::from(model.size).clone_transformed(orientation),
-                CoordinateSystemType::RZUP,
-                CoordinateSystemType::LYUP,
+            for_each_placed_voxel(
+                model,
+                position,
+                orientation,
+                &min_position_lyup,
+                |voxel_position, color_index| {
+                    shocovox_octree
+                        .insert(
+                            &V3c::<u32>::from(voxel_position),
+                            T::new(vox_tree.palette[color_index as usize].into(), 0),
+                        )
+                        .ok()
+                        .unwrap();
+                },
             );
-            let position = *position;
-            let position_lyup = convert_coordinate(
+        });
+        Ok(shocovox_octree)
+    }
+
+    /// Loads every model placed in the scene of the given .vox file into a separate tree
+    /// * Returns with the trees of the models in the order of the scene, each together with its minimum position
+    ///   The positions are in the same space as the contents of the tree loaded by `load_vox_file`
+    pub fn load_vox_models(filename: &str) -> Result<Vec<(V3c<u32>, Self)>, &'static str> {
+        let vox_tree = dot_vox::load(filename)?;
+        let (min_position_lyup, _) = scene_bounds(&vox_tree);
+        let mut placed_models = Vec::new();
+        iterate_vox_tree(&vox_tree, |model, position, orientation| {
+            let mut voxels = Vec::with_capacity(model.voxels.len());
+            for_each_placed_voxel(
+                model,
                 position,
-                CoordinateSystemType::RZUP,
-                CoordinateSystemType::LYUP,
+                orientation,
+                &min_position_lyup,
+                |voxel_position, color_index| voxels.push((voxel_position, color_index)),
             );
+            placed_models.push(voxels);
+        });
 
-            let current_position = position_lyup - min_position_lyup - (model_size_lyup / 2)
-                + V3c::new(
-                    if model_size_lyup.x < 0 { -1 } else { 0 },
-                    if model_size_lyup.y < 0 { -1 } else { 0 },
-                    if model_size_lyup.z < 0 { -1 } else { 0 },
-                );
-
-            let mut vmin = V3c::unit(max_dimension);
-            let mut vmax = V3c::unit(0u32);
-            for voxel in &model.voxels {
-                let voxel_position = convert_coordinate(
-                    V3c::from(*voxel).clone_transformed(orientation),
-                    CoordinateSystemType::RZUP,
-                    CoordinateSystemType::LYUP,
-                );
-                let cpos = current_position + voxel_position;
-                if cpos.length() < vmin.length() {
-                    vmin = cpos.into();
-                }
-                if cpos.length() > vmax.length() {
-                    vmax = cpos.into();
-                }
+        let mut models = Vec::with_capacity(placed_models.len());
+        for voxels in placed_models {
+            let Some(model_min) = voxels
+                .iter()
+                .map(|(position, _)| *position)
+                .reduce(|a, b| V3c::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)))
+            else {
+                continue;
+            };
+            let extent = voxels.iter().fold(0, |extent, (position, _)| {
+                let offset = *position - model_min;
+                extent.max(offset.x).max(offset.y).max(offset.z) as u32 + 1
+            });
+            let size = Self::valid_sizes_around(extent, DIM as u32)
+                .1
+                .ok_or("Model is too large for a tree")?;
+            let mut tree = Self::new(size).map_err(|_| "Unable to create tree for model")?;
+            for (position, color_index) in voxels {
+                tree.insert(
+                    &V3c::<u32>::from(position - model_min),
+                    T::new(vox_tree.palette[color_index as usize].into(), 0),
+                )
+                .map_err(|_| "Unable to insert voxel of model")?;
+            }
+            models.push((V3c::<u32>::from(model_min), tree));
+        }
+        Ok(models)
+    }
+}
+
+/// The maximum size of a model in each dimension inside a .vox file
+const VOX_MODEL_SIZE: u32 = 256;
+
+/// The number of colors a .vox palette can be used with, index 0 is reserved for empty voxels
+const VOX_PALETTE_SIZE: usize = 255;
 
-                shocovox_octree
-                    .insert(
-                        &V3c::<u32>::from(current_position + voxel_position),
-                        T::new(vox_tree.palette[voxel.i as usize].into(), 0),
-                    )
-                    .ok()
-                    .unwrap();
+/// Writes the given chunk with its content and children into the output
+fn write_vox_chunk(output: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    output.extend_from_slice(id);
+    output.extend_from_slice(&(content.len() as i32).to_le_bytes());
+    output.extend_from_slice(&(children.len() as i32).to_le_bytes());
+    output.extend_from_slice(content);
+    output.extend_from_slice(children);
+}
+
+/// Writes a dictionary of a scene graph node into the output
+fn write_vox_dict(output: &mut Vec<u8>, entries: &[(&str, String)]) {
+    output.extend_from_slice(&(entries.len() as i32).to_le_bytes());
+    for (key, value) in entries {
+        for string in [key.as_bytes(), value.as_bytes()] {
+            output.extend_from_slice(&(string.len() as i32).to_le_bytes());
+            output.extend_from_slice(string);
+        }
+    }
+}
+
+/// Collects the colors of the voxels into a .vox palette
+/// When there are more colors than what fits into the palette, the closest color is used for the rest
+struct VoxPalette {
+    colors: Vec<Albedo>,
+    indices: HashMap<Albedo, u8>,
+}
+
+impl VoxPalette {
+    /// Provides the index of the given color in the file, which is 1 larger than its position in the palette
+    fn index_of(&mut self, color: Albedo) -> u8 {
+        if let Some(index) = self.indices.get(&color) {
+            return *index;
+        }
+        let index = if self.colors.len() < VOX_PALETTE_SIZE {
+            self.colors.push(color);
+            self.colors.len() as u8
+        } else {
+            let distance = |other: &Albedo| {
+                let channel = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+                channel(color.r, other.r)
+                    + channel(color.g, other.g)
+                    + channel(color.b, other.b)
+                    + channel(color.a, other.a)
+            };
+            let closest = (0..self.colors.len())
+                .min_by_key(|i| distance(&self.colors[*i]))
+                .unwrap();
+            closest as u8 + 1
+        };
+        self.indices.insert(color, index);
+        index
+    }
+}
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Saves the contents of the tree into a MagicaVoxel .vox file
+    /// Trees larger than what fits into a single model are split into multiple models placed next to each other
+    /// Only the albedo of the voxels are saved, with at most 255 different colors
+    pub fn save_magicavoxel(&self, path: &str) -> Result<(), std::io::Error> {
+        let model_size = self.octree_size.min(VOX_MODEL_SIZE);
+        let models_per_dimension = self.octree_size.div_ceil(model_size);
+        let mut palette = VoxPalette {
+            colors: Vec::new(),
+            indices: HashMap::new(),
+        };
+
+        // Models are stored as SIZE and XYZI chunk pairs, each placed into the scene with its translation
+        let mut model_chunks = Vec::new();
+        let mut translations = Vec::new();
+        for x in 0..models_per_dimension {
+            for y in 0..models_per_dimension {
+                for z in 0..models_per_dimension {
+                    let model_min = V3c::new(x, y, z) * model_size;
+                    let mut voxels = Vec::new();
+                    self.for_each_in(
+                        &model_min,
+                        &(model_min + V3c::unit(model_size)),
+                        |position, voxel| {
+                            let albedo = voxel.albedo();
+                            if albedo.is_transparent() {
+                                return;
+                            }
+                            let position = convert_coordinate(
+                                V3c::<i32>::from(*position - model_min),
+                                CoordinateSystemType::LYUP,
+                                CoordinateSystemType::RZUP,
+                            );
+                            voxels.extend_from_slice(&[
+                                position.x as u8,
+                                position.y as u8,
+                                position.z as u8,
+                                palette.index_of(albedo),
+                            ]);
+                        },
+                    );
+                    if voxels.is_empty() {
+                        continue;
+                    }
+
+                    let mut size = Vec::new();
+                    for _ in 0..3 {
+                        size.extend_from_slice(&(model_size as i32).to_le_bytes());
+                    }
+                    write_vox_chunk(&mut model_chunks, b"SIZE", &size, &[]);
+                    let mut xyzi = ((voxels.len() / 4) as i32).to_le_bytes().to_vec();
+                    xyzi.extend(voxels);
+                    write_vox_chunk(&mut model_chunks, b"XYZI", &xyzi, &[]);
+
+                    // The translation of a model in the scene is the position of its center
+                    let center = convert_coordinate(
+                        V3c::<i32>::from(model_min + V3c::unit(model_size / 2)),
+                        CoordinateSystemType::LYUP,
+                        CoordinateSystemType::RZUP,
+                    );
+                    translations.push(center);
+                }
             }
-        });
-        Ok(shocovox_octree)
+        }
+
+        // Scene graph: root transform -> group -> (transform -> shape) for each model
+        let mut scene_chunks = Vec::new();
+        let group_id = 1;
+        let write_transform = |output: &mut Vec<u8>, node_id: i32, child_id: i32, t: String| {
+            let mut content = Vec::new();
+            content.extend_from_slice(&node_id.to_le_bytes());
+            write_vox_dict(&mut content, &[]);
+            content.extend_from_slice(&child_id.to_le_bytes());
+            content.extend_from_slice(&(-1_i32).to_le_bytes()); // reserved
+            content.extend_from_slice(&(-1_i32).to_le_bytes()); // layer
+            content.extend_from_slice(&1_i32.to_le_bytes()); // frame count
+            write_vox_dict(&mut content, &[("_t", t)]);
+            write_vox_chunk(output, b"nTRN", &content, &[]);
+        };
+        write_transform(&mut scene_chunks, 0, group_id, "0 0 0".to_string());
+        let mut group_content = group_id.to_le_bytes().to_vec();
+        write_vox_dict(&mut group_content, &[]);
+        group_content.extend_from_slice(&(translations.len() as i32).to_le_bytes());
+        for model_id in 0..translations.len() as i32 {
+            group_content.extend_from_slice(&(group_id + 1 + model_id * 2).to_le_bytes());
+        }
+        write_vox_chunk(&mut scene_chunks, b"nGRP", &group_content, &[]);
+        for (model_id, center) in translations.iter().enumerate() {
+            let transform_id = group_id + 1 + model_id as i32 * 2;
+            write_transform(
+                &mut scene_chunks,
+                transform_id,
+                transform_id + 1,
+                format!("{} {} {}", center.x, center.y, center.z),
+            );
+            let mut content = Vec::new();
+            content.extend_from_slice(&(transform_id + 1).to_le_bytes());
+            write_vox_dict(&mut content, &[]);
+            content.extend_from_slice(&1_i32.to_le_bytes()); // model count
+            content.extend_from_slice(&(model_id as i32).to_le_bytes());
+            write_vox_dict(&mut content, &[]);
+            write_vox_chunk(&mut scene_chunks, b"nSHP", &content, &[]);
+        }
+
+        let mut colors = Vec::with_capacity(256 * 4);
+        for i in 0..256 {
+            let color = palette.colors.get(i).copied().unwrap_or_default();
+            colors.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+        let mut children = model_chunks;
+        children.extend(scene_chunks);
+        write_vox_chunk(&mut children, b"RGBA", &colors, &[]);
+
+        let mut output = b"VOX ".to_vec();
+        output.extend_from_slice(&150_i32.to_le_bytes());
+        write_vox_chunk(&mut output, b"MAIN", &[], &children);
+        std::fs::write(path, output)
     }
 }
 
@@ -332,4 +584,67 @@ mod octree_tests {
         assert!(parsed_example.m23 == -1);
         assert!(parsed_example.m31 == -1);
     }
+
+    #[test]
+    fn test_magicavoxel_export() {
+        use crate::octree::{Albedo, Octree, V3c};
+        let red: Albedo = 0xFF0000FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree = Octree::<Albedo>::new(8).ok().unwrap();
+        tree.insert(&V3c::new(0, 0, 0), red).ok().unwrap();
+        tree.insert(&V3c::new(5, 3, 1), blue).ok().unwrap();
+        tree.insert(&V3c::new(7, 7, 7), red).ok().unwrap();
+        tree.save_magicavoxel("test_junk_magicavoxel_export.vox")
+            .ok()
+            .unwrap();
+
+        let loaded = Octree::<Albedo>::load_vox_file("test_junk_magicavoxel_export.vox")
+            .ok()
+            .unwrap();
+        assert!(loaded.get_size() == 8);
+        assert!(loaded.get(&V3c::new(0, 0, 0)) == Some(&red));
+        assert!(loaded.get(&V3c::new(5, 3, 1)) == Some(&blue));
+        assert!(loaded.get(&V3c::new(7, 7, 7)) == Some(&red));
+        assert!(loaded.get(&V3c::new(1, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_magicavoxel_load_models() {
+        use crate::octree::{Albedo, Octree, V3c};
+        let red: Albedo = 0xFF0000FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree = Octree::<Albedo>::new(512).ok().unwrap();
+        tree.insert(&V3c::new(0, 0, 0), red).ok().unwrap();
+        tree.insert(&V3c::new(300, 2, 5), blue).ok().unwrap();
+        tree.insert(&V3c::new(301, 2, 5), red).ok().unwrap();
+        tree.save_magicavoxel("test_junk_magicavoxel_models.vox")
+            .ok()
+            .unwrap();
+
+        // Every model is placed by its transform into the same tree
+        let loaded = Octree::<Albedo>::load_vox_file("test_junk_magicavoxel_models.vox")
+            .ok()
+            .unwrap();
+        assert!(loaded.get(&V3c::new(0, 0, 0)) == Some(&red));
+        assert!(loaded.get(&V3c::new(300, 2, 5)) == Some(&blue));
+        assert!(loaded.get(&V3c::new(301, 2, 5)) == Some(&red));
+
+        // Each model is loaded into its own tree, together with its position
+        let models = Octree::<Albedo>::load_vox_models("test_junk_magicavoxel_models.vox")
+            .ok()
+            .unwrap();
+        assert!(models.len() == 2);
+        let (_, first) = models
+            .iter()
+            .find(|(position, _)| *position == V3c::new(0, 0, 0))
+            .unwrap();
+        assert!(first.get(&V3c::new(0, 0, 0)) == Some(&red));
+        let (_, second) = models
+            .iter()
+            .find(|(position, _)| *position == V3c::new(300, 2, 5))
+            .unwrap();
+        assert!(second.get_size() == 2);
+        assert!(second.get(&V3c::new(0, 0, 0)) == Some(&blue));
+        assert!(second.get(&V3c::new(1, 0, 0)) == Some(&red));
+    }
 }
@@ -1,7 +1,20 @@
-mod bytecode;
+pub(crate) mod bytecode;
+mod dense;
+pub(crate) mod edit_log;
+mod export;
+mod heightmap;
+pub(crate) mod point_cloud;
+pub(crate) mod volume;
+mod voxelize;
 
 #[cfg(test)]
 mod tests;
 
 #[cfg(feature = "dot_vox_support")]
 mod magicavoxel;
+
+#[cfg(feature = "gltf")]
+mod gltf;
+
+#[cfg(feature = "schematic")]
+pub(crate) mod schematic;
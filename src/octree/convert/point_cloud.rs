@@ -0,0 +1,460 @@
+use crate::octree::{types::OctreeError, Albedo, Octree, V3c, VoxelData};
+use std::{
+    collections::HashMap,
+    io::{BufRead, Read},
+};
+
+/// The maximum number of points read from a file before they are binned
+const POINT_CHUNK_SIZE: usize = 1 << 16;
+
+/// The size of the LAS header before version 1.3, and since version 1.4
+const LAS_MIN_HEADER_SIZE: usize = 227;
+const LAS_1_4_HEADER_SIZE: usize = 375;
+
+fn invalid_data(message: String) -> OctreeError {
+    OctreeError::InvalidStructure(message.into())
+}
+
+/// Reads the next line of a PLY file, or None at the end of it
+fn read_ply_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, OctreeError> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => Ok(None),
+        Ok(_) => Ok(Some(line)),
+        Err(error) => Err(invalid_data(format!("Unable to read PLY data: {error}"))),
+    }
+}
+
+/// The encoding of the elements after the header of a PLY file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// The type of a scalar PLY property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyScalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyScalar {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Self::I8),
+            "uchar" | "uint8" => Some(Self::U8),
+            "short" | "int16" => Some(Self::I16),
+            "ushort" | "uint16" => Some(Self::U16),
+            "int" | "int32" => Some(Self::I32),
+            "uint" | "uint32" => Some(Self::U32),
+            "float" | "float32" => Some(Self::F32),
+            "double" | "float64" => Some(Self::F64),
+            _ => None,
+        }
+    }
+
+    /// The number of bytes the property takes up in binary files
+    fn size(&self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    /// Reads the property from the start of the given bytes
+    fn read(&self, bytes: &[u8], big_endian: bool) -> f64 {
+        fn array<const N: usize>(bytes: &[u8], big_endian: bool) -> [u8; N] {
+            let mut array = [0; N];
+            array.copy_from_slice(&bytes[..N]);
+            if big_endian {
+                array.reverse();
+            }
+            array
+        }
+        match self {
+            Self::I8 => bytes[0] as i8 as f64,
+            Self::U8 => bytes[0] as f64,
+            Self::I16 => i16::from_le_bytes(array(bytes, big_endian)) as f64,
+            Self::U16 => u16::from_le_bytes(array(bytes, big_endian)) as f64,
+            Self::I32 => i32::from_le_bytes(array(bytes, big_endian)) as f64,
+            Self::U32 => u32::from_le_bytes(array(bytes, big_endian)) as f64,
+            Self::F32 => f32::from_le_bytes(array(bytes, big_endian)) as f64,
+            Self::F64 => f64::from_le_bytes(array(bytes, big_endian)),
+        }
+    }
+}
+
+/// Decides the color of a voxel containing multiple points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointColorMode {
+    /// The average of the colors of the contained points
+    Average,
+    /// The most frequent color among the contained points
+    Mode,
+}
+
+#[derive(Debug, Default, Clone)]
+struct PointBin {
+    channel_sums: [u64; 4],
+    point_count: u64,
+    color_counts: HashMap<Albedo, u64>,
+}
+
+/// Collects colored points into voxels of a chosen size
+/// Points can be added in multiple batches, memory usage depends only on the
+/// number of occupied voxels, so arbitrarily large scans can be streamed through it
+#[derive(Debug, Clone)]
+pub struct PointCloudBinner {
+    origin: V3c<f32>,
+    voxel_size: f32,
+    color_mode: PointColorMode,
+    bins: HashMap<(u32, u32, u32), PointBin>,
+}
+
+impl PointCloudBinner {
+    /// * `origin` - the point mapped to the minimum corner of the tree
+    /// * `voxel_size` - the edge length of one voxel in the units of the points
+    /// * `color_mode` - how to combine the colors of points inside the same voxel
+    pub fn new(origin: V3c<f32>, voxel_size: f32, color_mode: PointColorMode) -> Self {
+        debug_assert!(0. < voxel_size);
+        Self {
+            origin,
+            voxel_size,
+            color_mode,
+            bins: HashMap::new(),
+        }
+    }
+
+    /// The number of voxels containing at least one point
+    pub fn len(&self) -> usize {
+        self.bins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bins.is_empty()
+    }
+
+    /// Adds the given points to the voxels they fall into
+    /// Points below the origin in any dimension are ignored
+    pub fn add_points<I>(&mut self, points: I)
+    where
+        I: IntoIterator<Item = (V3c<f32>, Albedo)>,
+    {
+        for (position, color) in points {
+            let voxel = (position - self.origin) / self.voxel_size;
+            if voxel.x < 0. || voxel.y < 0. || voxel.z < 0. {
+                continue;
+            }
+            let bin = self
+                .bins
+                .entry((voxel.x as u32, voxel.y as u32, voxel.z as u32))
+                .or_default();
+            match self.color_mode {
+                PointColorMode::Average => {
+                    bin.channel_sums[0] += color.r as u64;
+                    bin.channel_sums[1] += color.g as u64;
+                    bin.channel_sums[2] += color.b as u64;
+                    bin.channel_sums[3] += color.a as u64;
+                }
+                PointColorMode::Mode => {
+                    *bin.color_counts.entry(color).or_insert(0) += 1;
+                }
+            }
+            bin.point_count += 1;
+        }
+    }
+
+    /// Adds the vertices of a PLY file to the voxels they fall into
+    /// Both ASCII and binary files are supported, vertices are binned in chunks while they are read,
+    /// so in case of an error the vertices before it are already added
+    /// Vertices need to be the first element of the file and to have x, y and z properties,
+    /// colors are read from the red, green, blue and alpha properties when available,
+    /// otherwise they are white
+    /// * Returns with the number of points read
+    pub fn add_ply_points<R: BufRead>(&mut self, mut reader: R) -> Result<usize, OctreeError> {
+        if read_ply_line(&mut reader)?.as_deref().map(str::trim) != Some("ply") {
+            return Err(invalid_data("Missing PLY header".into()));
+        }
+        let mut format = None;
+        let mut vertex_count = 0;
+        let mut vertex_properties = Vec::new();
+        let mut reading_vertex_element = false;
+        loop {
+            let Some(line) = read_ply_line(&mut reader)? else {
+                return Err(invalid_data("PLY header is not closed".into()));
+            };
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words.as_slice() {
+                ["end_header"] => break,
+                ["format", name, ..] => {
+                    format = Some(match *name {
+                        "ascii" => PlyFormat::Ascii,
+                        "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                        "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                        _ => return Err(invalid_data(format!("Unsupported PLY format: {name}"))),
+                    });
+                }
+                ["element", name, count] => {
+                    if reading_vertex_element || 0 < vertex_count {
+                        // Elements after the vertices are not needed
+                        reading_vertex_element = false;
+                        continue;
+                    }
+                    if *name != "vertex" {
+                        return Err(invalid_data(
+                            "Vertices must be the first PLY element".into(),
+                        ));
+                    }
+                    reading_vertex_element = true;
+                    vertex_count = count
+                        .parse::<usize>()
+                        .map_err(|_| invalid_data(format!("Invalid vertex count: {count}")))?;
+                }
+                ["property", "list", ..] if reading_vertex_element => {
+                    return Err(invalid_data(
+                        "List properties of PLY vertices are not supported".into(),
+                    ));
+                }
+                ["property", scalar, name] if reading_vertex_element => {
+                    let Some(scalar) = PlyScalar::parse(scalar) else {
+                        return Err(invalid_data(format!("Unknown PLY property type: {scalar}")));
+                    };
+                    vertex_properties.push((name.to_string(), scalar));
+                }
+                _ => {}
+            }
+        }
+        let Some(format) = format else {
+            return Err(invalid_data("Missing PLY format".into()));
+        };
+        let property_index = |name: &str| vertex_properties.iter().position(|(p, _)| p == name);
+        let (Some(x), Some(y), Some(z)) = (
+            property_index("x"),
+            property_index("y"),
+            property_index("z"),
+        ) else {
+            return Err(invalid_data(
+                "PLY vertices must have x, y and z properties".into(),
+            ));
+        };
+        let channels = [
+            property_index("red"),
+            property_index("green"),
+            property_index("blue"),
+            property_index("alpha"),
+        ];
+
+        let mut record = vec![
+            0;
+            vertex_properties
+                .iter()
+                .map(|(_, scalar)| scalar.size())
+                .sum::<usize>()
+        ];
+        let mut values = Vec::with_capacity(vertex_properties.len());
+        let mut points = Vec::new();
+        for _ in 0..vertex_count {
+            values.clear();
+            if PlyFormat::Ascii == format {
+                let Some(line) = read_ply_line(&mut reader)? else {
+                    return Err(invalid_data(
+                        "PLY data ended before all vertices were read".into(),
+                    ));
+                };
+                for value in line.split_whitespace() {
+                    values.push(
+                        value.parse::<f64>().map_err(|error| {
+                            invalid_data(format!("Invalid PLY vertex: {error}"))
+                        })?,
+                    );
+                }
+                if values.len() < vertex_properties.len() {
+                    return Err(invalid_data(format!("Incomplete PLY vertex: {line}")));
+                }
+            } else {
+                reader.read_exact(&mut record).map_err(|_| {
+                    invalid_data("PLY data ended before all vertices were read".into())
+                })?;
+                let big_endian = matches!(format, PlyFormat::BinaryBigEndian);
+                let mut offset = 0;
+                for (_, scalar) in vertex_properties.iter() {
+                    values.push(scalar.read(&record[offset..], big_endian));
+                    offset += scalar.size();
+                }
+            }
+            let channel = |index: Option<usize>| index.map_or(255, |i| values[i] as u8);
+            points.push((
+                V3c::new(values[x] as f32, values[y] as f32, values[z] as f32),
+                Albedo {
+                    r: channel(channels[0]),
+                    g: channel(channels[1]),
+                    b: channel(channels[2]),
+                    a: channel(channels[3]),
+                },
+            ));
+            if POINT_CHUNK_SIZE <= points.len() {
+                self.add_points(points.drain(..));
+            }
+        }
+        self.add_points(points);
+        Ok(vertex_count)
+    }
+
+    /// Adds the points of an uncompressed LAS file to the voxels they fall into
+    /// Points are binned in chunks while they are read, so in case of an error the points before it are already added
+    /// Colors are read from the point formats storing them, otherwise points are white
+    /// Compressed LAZ files are not supported
+    /// * Returns with the number of points read
+    pub fn add_las_points<R: Read>(&mut self, mut reader: R) -> Result<usize, OctreeError> {
+        let ended = |_| invalid_data("LAS data ended before all points were read".into());
+        let mut header = vec![0; LAS_MIN_HEADER_SIZE];
+        reader.read_exact(&mut header).map_err(ended)?;
+        if &header[0..4] != b"LASF" {
+            return Err(invalid_data("Missing LAS signature".into()));
+        }
+        let u16_at =
+            |bytes: &[u8], offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let u32_at = |bytes: &[u8], offset: usize| {
+            u32::from_le_bytes(std::array::from_fn(|i| bytes[offset + i]))
+        };
+        let f64_at = |bytes: &[u8], offset: usize| {
+            f64::from_le_bytes(std::array::from_fn(|i| bytes[offset + i]))
+        };
+        let header_size = u16_at(&header, 94) as usize;
+        let point_offset = u32_at(&header, 96) as usize;
+        let point_format = header[104];
+        let record_length = u16_at(&header, 105) as usize;
+        if header_size < LAS_MIN_HEADER_SIZE || point_offset < header_size {
+            return Err(invalid_data("Invalid LAS header size".into()));
+        }
+        if 0 != point_format & 0xC0 {
+            return Err(invalid_data(
+                "Compressed LAS points are not supported".into(),
+            ));
+        }
+        header.resize(header_size, 0);
+        reader
+            .read_exact(&mut header[LAS_MIN_HEADER_SIZE..])
+            .map_err(ended)?;
+
+        // Files since version 1.4 may only store the number of points in a 64 bit field
+        let mut point_count = u32_at(&header, 107) as u64;
+        if 0 == point_count && LAS_1_4_HEADER_SIZE <= header_size {
+            point_count = u32_at(&header, 247) as u64 | ((u32_at(&header, 251) as u64) << 32);
+        }
+        let scale = V3c::new(
+            f64_at(&header, 131),
+            f64_at(&header, 139),
+            f64_at(&header, 147),
+        );
+        let offset = V3c::new(
+            f64_at(&header, 155),
+            f64_at(&header, 163),
+            f64_at(&header, 171),
+        );
+        let color_offset = match point_format {
+            2 => Some(20),
+            3 | 5 => Some(28),
+            7 | 8 | 10 => Some(30),
+            _ => None,
+        };
+        if record_length < color_offset.map_or(12, |color_offset| color_offset + 6) {
+            return Err(invalid_data(format!(
+                "LAS point records of {record_length} bytes are too short for point format {point_format}"
+            )));
+        }
+
+        // Variable length records between the header and the points are not needed
+        let skipped = std::io::copy(
+            &mut (&mut reader).take((point_offset - header_size) as u64),
+            &mut std::io::sink(),
+        )
+        .map_err(ended)?;
+        if skipped < (point_offset - header_size) as u64 {
+            return Err(invalid_data("LAS data ended before the points".into()));
+        }
+
+        let mut record = vec![0; record_length];
+        let mut points = Vec::new();
+        for _ in 0..point_count {
+            reader.read_exact(&mut record).map_err(ended)?;
+            let coordinate = |index: usize, scale: f64, offset: f64| {
+                (u32_at(&record, index * 4) as i32 as f64 * scale + offset) as f32
+            };
+            let channel = |index: usize| {
+                color_offset.map_or(255, |o| (u16_at(&record, o + index * 2) >> 8) as u8)
+            };
+            points.push((
+                V3c::new(
+                    coordinate(0, scale.x, offset.x),
+                    coordinate(1, scale.y, offset.y),
+                    coordinate(2, scale.z, offset.z),
+                ),
+                Albedo {
+                    r: channel(0),
+                    g: channel(1),
+                    b: channel(2),
+                    a: 255,
+                },
+            ));
+            if POINT_CHUNK_SIZE <= points.len() {
+                self.add_points(points.drain(..));
+            }
+        }
+        self.add_points(points);
+        Ok(point_count as usize)
+    }
+
+    /// Inserts a voxel into the tree for every occupied bin
+    /// Voxels outside the bounds of the tree are skipped
+    /// * Returns with the number of skipped voxels
+    pub fn write_into<T, const DIM: usize>(
+        &self,
+        tree: &mut Octree<T, DIM>,
+    ) -> Result<usize, OctreeError>
+    where
+        T: Default + Eq + Clone + Copy + VoxelData,
+    {
+        let mut skipped = 0;
+        let size = tree.octree_size;
+        tree.insert_batch(self.bins.iter().filter_map(|((x, y, z), bin)| {
+            if *x >= size || *y >= size || *z >= size {
+                skipped += 1;
+                return None;
+            }
+            Some((V3c::new(*x, *y, *z), T::new(self.color_of(bin), 0)))
+        }))?;
+        Ok(skipped)
+    }
+
+    fn color_of(&self, bin: &PointBin) -> Albedo {
+        match self.color_mode {
+            PointColorMode::Average => {
+                let average = |channel: usize| (bin.channel_sums[channel] / bin.point_count) as u8;
+                Albedo {
+                    r: average(0),
+                    g: average(1),
+                    b: average(2),
+                    a: average(3),
+                }
+            }
+            PointColorMode::Mode => bin
+                .color_counts
+                .iter()
+                // Ties are resolved by the color value to be deterministic
+                .max_by_key(|(color, count)| (**count, color.r, color.g, color.b, color.a))
+                .map(|(color, _)| *color)
+                .unwrap_or_default(),
+        }
+    }
+}
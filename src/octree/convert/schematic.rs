@@ -0,0 +1,652 @@
+use crate::octree::{types::OctreeLoadError, Albedo, Octree, V3c, VoxelData};
+use std::{collections::HashMap, io::Read};
+
+/// A block of an imported Minecraft schematic
+/// The id is the index of the block state in the list of block states provided by the import
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlockId {
+    pub id: u32,
+    pub albedo: Albedo,
+}
+
+impl VoxelData for BlockId {
+    fn new(albedo: Albedo, id: u32) -> Self {
+        Self { id, albedo }
+    }
+
+    fn albedo(&self) -> Albedo {
+        self.albedo
+    }
+
+    fn user_data(&self) -> u32 {
+        self.id
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Maps the blocks of a schematic to the colors they are displayed with
+/// Blocks are looked up by their full block state e.g. "minecraft:oak_log[axis=y]" first,
+/// then by their name without the properties e.g. "minecraft:oak_log"
+#[derive(Debug, Clone, Default)]
+pub struct BlockColors {
+    pub colors: HashMap<String, Albedo>,
+    /// The color of blocks missing from the table, these blocks are not imported when None
+    pub fallback: Option<Albedo>,
+}
+
+/// Blocks never imported into the tree
+const AIR_BLOCKS: [&str; 3] = ["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+impl BlockColors {
+    /// Provides the color of the given block state, or None if it is not to be imported
+    fn color_of(&self, block_state: &str) -> Option<Albedo> {
+        let name = block_state.split('[').next().unwrap_or(block_state);
+        if AIR_BLOCKS.contains(&name) {
+            return None;
+        }
+        self.colors
+            .get(block_state)
+            .or_else(|| self.colors.get(name))
+            .copied()
+            .or(self.fallback)
+    }
+}
+
+///####################################################################################
+/// NBT
+///####################################################################################
+
+/// A value of the Named Binary Tag format schematics are stored in
+#[derive(Debug, Clone)]
+enum Nbt {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Nbt>),
+    Compound(HashMap<String, Nbt>),
+    LongArray(Vec<i64>),
+    /// Values not used by the import: floating point numbers and int arrays
+    Other,
+}
+
+fn decode_error(message: &str) -> OctreeLoadError {
+    OctreeLoadError::Decode(message.to_string())
+}
+
+impl Nbt {
+    fn get(&self, key: &str) -> Option<&Nbt> {
+        match self {
+            Nbt::Compound(entries) => entries.get(key),
+            _ => None,
+        }
+    }
+
+    /// Provides the value of an integer tag of any size
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Nbt::Byte(value) => Some(*value as i64),
+            Nbt::Short(value) => Some(*value as i64),
+            Nbt::Int(value) => Some(*value as i64),
+            Nbt::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Provides the integer value under the given key of a compound tag
+    fn int_at(&self, key: &str) -> Result<i64, OctreeLoadError> {
+        self.get(key)
+            .and_then(Nbt::as_int)
+            .ok_or_else(|| decode_error(&format!("Missing integer tag {key}")))
+    }
+
+    /// Provides the x, y and z integer values of a compound tag
+    fn vector_at(&self, key: &str) -> Result<V3c<i32>, OctreeLoadError> {
+        let vector = self
+            .get(key)
+            .ok_or_else(|| decode_error(&format!("Missing vector tag {key}")))?;
+        Ok(V3c::new(
+            vector.int_at("x")? as i32,
+            vector.int_at("y")? as i32,
+            vector.int_at("z")? as i32,
+        ))
+    }
+}
+
+/// Reads big endian NBT values from a byte slice
+struct NbtReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl NbtReader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], OctreeLoadError> {
+        if self.bytes.len() < count {
+            return Err(decode_error("Unexpected end of NBT data"));
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], OctreeLoadError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn read_length(&mut self) -> Result<usize, OctreeLoadError> {
+        let length = i32::from_be_bytes(self.read_array()?);
+        usize::try_from(length).map_err(|_| decode_error("Negative NBT length"))
+    }
+
+    fn read_string(&mut self) -> Result<String, OctreeLoadError> {
+        let length = u16::from_be_bytes(self.read_array()?) as usize;
+        String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| decode_error("Invalid NBT string"))
+    }
+
+    /// Reads the root tag of the data, which is a named compound
+    fn read_root(&mut self) -> Result<Nbt, OctreeLoadError> {
+        let tag = self.read_array::<1>()?[0];
+        if 10 != tag {
+            return Err(decode_error("NBT data doesn't start with a compound"));
+        }
+        self.read_string()?;
+        self.read_payload(tag, 0)
+    }
+
+    fn read_payload(&mut self, tag: u8, depth: usize) -> Result<Nbt, OctreeLoadError> {
+        if 512 < depth {
+            return Err(decode_error("NBT data is nested too deep"));
+        }
+        Ok(match tag {
+            1 => Nbt::Byte(i8::from_be_bytes(self.read_array()?)),
+            2 => Nbt::Short(i16::from_be_bytes(self.read_array()?)),
+            3 => Nbt::Int(i32::from_be_bytes(self.read_array()?)),
+            4 => Nbt::Long(i64::from_be_bytes(self.read_array()?)),
+            5 => {
+                self.take(4)?;
+                Nbt::Other
+            }
+            6 => {
+                self.take(8)?;
+                Nbt::Other
+            }
+            7 => {
+                let length = self.read_length()?;
+                Nbt::ByteArray(self.take(length)?.iter().map(|b| *b as i8).collect())
+            }
+            8 => Nbt::String(self.read_string()?),
+            9 => {
+                let item_tag = self.read_array::<1>()?[0];
+                let length = self.read_length()?;
+                let mut items = Vec::with_capacity(length.min(self.bytes.len()));
+                for _ in 0..length {
+                    items.push(self.read_payload(item_tag, depth + 1)?);
+                }
+                Nbt::List(items)
+            }
+            10 => {
+                let mut entries = HashMap::new();
+                loop {
+                    let entry_tag = self.read_array::<1>()?[0];
+                    if 0 == entry_tag {
+                        break;
+                    }
+                    let name = self.read_string()?;
+                    entries.insert(name, self.read_payload(entry_tag, depth + 1)?);
+                }
+                Nbt::Compound(entries)
+            }
+            11 => {
+                let length = self.read_length()?;
+                self.take(length.saturating_mul(4))?;
+                Nbt::Other
+            }
+            12 => {
+                let length = self.read_length()?;
+                let bytes = self.take(length.saturating_mul(8))?;
+                Nbt::LongArray(
+                    bytes
+                        .chunks_exact(8)
+                        .map(|b| i64::from_be_bytes(b.try_into().unwrap()))
+                        .collect(),
+                )
+            }
+            _ => return Err(decode_error(&format!("Unknown NBT tag {tag}"))),
+        })
+    }
+}
+
+///####################################################################################
+/// Schematic import
+///####################################################################################
+
+/// The blocks of a schematic, with positions relative to the minimum corner of the schematic
+struct SchematicBlocks {
+    block_states: Vec<String>,
+    /// Position, and index inside the block states
+    blocks: Vec<(V3c<i32>, u32)>,
+}
+
+/// Collects the blocks of a Sponge schematic(.schem), version 2 or 3
+fn sponge_blocks(root: &Nbt) -> Result<SchematicBlocks, OctreeLoadError> {
+    // Version 3 nests the schematic into a compound, and the blocks into another one
+    let schematic = root.get("Schematic").unwrap_or(root);
+    let (palette, data) = match schematic.get("Blocks") {
+        Some(blocks) => (blocks.get("Palette"), blocks.get("Data")),
+        None => (schematic.get("Palette"), schematic.get("BlockData")),
+    };
+    let Some(Nbt::Compound(palette)) = palette else {
+        return Err(decode_error("Missing block palette"));
+    };
+    let Some(Nbt::ByteArray(data)) = data else {
+        return Err(decode_error("Missing block data"));
+    };
+    let width = schematic.int_at("Width")? as u16 as usize;
+    let height = schematic.int_at("Height")? as u16 as usize;
+    let length = schematic.int_at("Length")? as u16 as usize;
+
+    let mut block_states = vec![String::new(); palette.len()];
+    for (block_state, index) in palette {
+        let index = index
+            .as_int()
+            .and_then(|index| usize::try_from(index).ok())
+            .filter(|index| *index < block_states.len())
+            .ok_or_else(|| decode_error("Invalid block palette index"))?;
+        block_states[index] = block_state.clone();
+    }
+
+    // Block indices are stored as varints, ordered by x, then z, then y
+    let mut blocks = Vec::new();
+    let mut bytes = data.iter().map(|b| *b as u8);
+    for index in 0..(width * height * length) {
+        let mut value = 0_u32;
+        let mut shift = 0;
+        loop {
+            let byte = bytes
+                .next()
+                .ok_or_else(|| decode_error("Block data is shorter than the schematic"))?;
+            value |= ((byte & 0x7F) as u32) << shift;
+            if 0 == byte & 0x80 {
+                break;
+            }
+            shift += 7;
+            if 28 < shift {
+                return Err(decode_error("Invalid varint in block data"));
+            }
+        }
+        if value as usize >= block_states.len() {
+            return Err(decode_error("Invalid block palette index"));
+        }
+        let x = index % width;
+        let z = (index / width) % length;
+        let y = index / (width * length);
+        blocks.push((V3c::new(x as i32, y as i32, z as i32), value));
+    }
+    Ok(SchematicBlocks {
+        block_states,
+        blocks,
+    })
+}
+
+/// Provides the block state string of a litematic palette entry, e.g. "minecraft:oak_log[axis=y]"
+fn litematic_block_state(entry: &Nbt) -> Result<String, OctreeLoadError> {
+    let Some(Nbt::String(name)) = entry.get("Name") else {
+        return Err(decode_error("Missing block name"));
+    };
+    let Some(Nbt::Compound(properties)) = entry.get("Properties") else {
+        return Ok(name.clone());
+    };
+    let mut properties = properties
+        .iter()
+        .filter_map(|(key, value)| match value {
+            Nbt::String(value) => Some(format!("{key}={value}")),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    properties.sort();
+    Ok(format!("{name}[{}]", properties.join(",")))
+}
+
+/// Collects the blocks of every region of a Litematica schematic(.litematic)
+fn litematic_blocks(root: &Nbt) -> Result<SchematicBlocks, OctreeLoadError> {
+    let Some(Nbt::Compound(regions)) = root.get("Regions") else {
+        return Err(decode_error("Missing litematic regions"));
+    };
+    let mut block_states = Vec::new();
+    let mut state_indices = HashMap::new();
+    let mut blocks = Vec::new();
+    for region in regions.values() {
+        let position = region.vector_at("Position")?;
+        let size = region.vector_at("Size")?;
+        let Some(Nbt::List(palette)) = region.get("BlockStatePalette") else {
+            return Err(decode_error("Missing region palette"));
+        };
+        let Some(Nbt::LongArray(states)) = region.get("BlockStates") else {
+            return Err(decode_error("Missing region block states"));
+        };
+
+        // Palettes of the regions are merged into one list
+        let mut palette_indices = Vec::with_capacity(palette.len());
+        for entry in palette {
+            let block_state = litematic_block_state(entry)?;
+            let index = *state_indices.entry(block_state.clone()).or_insert_with(|| {
+                block_states.push(block_state);
+                block_states.len() as u32 - 1
+            });
+            palette_indices.push(index);
+        }
+
+        // Regions with negative size extend from their position towards the negative direction
+        let origin = position
+            + V3c::new(
+                if size.x < 0 { size.x + 1 } else { 0 },
+                if size.y < 0 { size.y + 1 } else { 0 },
+                if size.z < 0 { size.z + 1 } else { 0 },
+            );
+        let extent = V3c::<usize>::new(
+            size.x.unsigned_abs() as usize,
+            size.y.unsigned_abs() as usize,
+            size.z.unsigned_abs() as usize,
+        );
+
+        // Block states are packed tightly into the longs, values may span two longs
+        let bits = (usize::BITS - palette.len().saturating_sub(1).leading_zeros()).max(2) as usize;
+        let mask = (1_u64 << bits) - 1;
+        let Some(volume) = extent
+            .x
+            .checked_mul(extent.y)
+            .and_then(|area| area.checked_mul(extent.z))
+        else {
+            return Err(decode_error("Region is too large"));
+        };
+        for index in 0..volume {
+            let start_bit = index * bits;
+            let (long_index, bit_offset) = (start_bit / 64, start_bit % 64);
+            let Some(low) = states.get(long_index) else {
+                return Err(decode_error("Block states are shorter than the region"));
+            };
+            let mut value = (*low as u64) >> bit_offset;
+            if 64 < bit_offset + bits {
+                let Some(high) = states.get(long_index + 1) else {
+                    return Err(decode_error("Block states are shorter than the region"));
+                };
+                value |= (*high as u64) << (64 - bit_offset);
+            }
+            let Some(block) = palette_indices.get((value & mask) as usize) else {
+                return Err(decode_error("Invalid block state index"));
+            };
+            let x = index % extent.x;
+            let z = (index / extent.x) % extent.z;
+            let y = index / (extent.x * extent.z);
+            blocks.push((origin + V3c::new(x as i32, y as i32, z as i32), *block));
+        }
+    }
+    Ok(SchematicBlocks {
+        block_states,
+        blocks,
+    })
+}
+
+impl<const DIM: usize> Octree<BlockId, DIM> {
+    /// Loads a Sponge(.schem) or Litematica(.litematic) schematic, see `from_schematic_bytes`
+    pub fn load_schematic(
+        path: &str,
+        colors: &BlockColors,
+    ) -> Result<(Self, Vec<String>), OctreeLoadError> {
+        Self::from_schematic_bytes(&std::fs::read(path)?, colors)
+    }
+
+    /// Creates a tree from the contents of a Sponge(.schem) or Litematica(.litematic) schematic
+    /// The data may be gzip compressed, as the files usually are
+    /// Minecraft uses right handed coordinates, so the z axis is mirrored inside the tree
+    /// * `colors` - the colors of the blocks, blocks without color are not imported
+    /// * Returns with the tree, and the block states the ids of the voxels refer to
+    pub fn from_schematic_bytes(
+        bytes: &[u8],
+        colors: &BlockColors,
+    ) -> Result<(Self, Vec<String>), OctreeLoadError> {
+        let decompressed;
+        let bytes = if bytes.starts_with(&[0x1F, 0x8B]) {
+            let mut data = Vec::new();
+            flate2::read::GzDecoder::new(bytes).read_to_end(&mut data)?;
+            decompressed = data;
+            decompressed.as_slice()
+        } else {
+            bytes
+        };
+        let root = NbtReader { bytes }.read_root()?;
+        let schematic = if root.get("Regions").is_some() {
+            litematic_blocks(&root)?
+        } else {
+            sponge_blocks(&root)?
+        };
+
+        let block_colors = schematic
+            .block_states
+            .iter()
+            .map(|block_state| colors.color_of(block_state))
+            .collect::<Vec<Option<Albedo>>>();
+        let Some((min, max)) = schematic
+            .blocks
+            .iter()
+            .filter(|(_, block)| block_colors[*block as usize].is_some())
+            .map(|(position, _)| (*position, *position))
+            .reduce(|(min, max), (position, _)| {
+                (
+                    V3c::new(
+                        min.x.min(position.x),
+                        min.y.min(position.y),
+                        min.z.min(position.z),
+                    ),
+                    V3c::new(
+                        max.x.max(position.x),
+                        max.y.max(position.y),
+                        max.z.max(position.z),
+                    ),
+                )
+            })
+        else {
+            return Ok((
+                Self::new(DIM as u32 * 2).ok().unwrap(),
+                schematic.block_states,
+            ));
+        };
+        let extent = max - min + V3c::unit(1);
+        let extent = extent.x.max(extent.y).max(extent.z) as u32;
+        let size = Self::valid_sizes_around(extent, DIM as u32)
+            .1
+            .ok_or_else(|| decode_error("Schematic is too large for a tree"))?;
+        let mut tree = Self::new(size).map_err(|error| decode_error(&format!("{error:?}")))?;
+        tree.insert_batch(schematic.blocks.iter().filter_map(|(position, block)| {
+            let albedo = block_colors[*block as usize]?;
+            let position = *position - min;
+            Some((
+                V3c::new(
+                    position.x as u32,
+                    position.y as u32,
+                    (max.z - min.z - position.z) as u32,
+                ),
+                BlockId { id: *block, albedo },
+            ))
+        }))
+        .map_err(|error| decode_error(&format!("{error:?}")))?;
+        Ok((tree, schematic.block_states))
+    }
+}
+
+#[cfg(test)]
+mod schematic_tests {
+    use super::{BlockColors, BlockId};
+    use crate::octree::{Albedo, Octree, V3c};
+    use std::collections::HashMap;
+
+    /// Encodes a named NBT tag with the given payload
+    fn named(tag: u8, name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![tag];
+        bytes.extend_from_slice(&string(name));
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut bytes = (value.len() as u16).to_be_bytes().to_vec();
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    fn compound(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = entries.concat();
+        bytes.push(0);
+        bytes
+    }
+
+    fn vector(x: i32, y: i32, z: i32) -> Vec<u8> {
+        compound(&[
+            named(3, "x", &x.to_be_bytes()),
+            named(3, "y", &y.to_be_bytes()),
+            named(3, "z", &z.to_be_bytes()),
+        ])
+    }
+
+    fn colors() -> BlockColors {
+        BlockColors {
+            colors: HashMap::from([
+                ("minecraft:stone".to_string(), 0x808080FF.into()),
+                ("minecraft:oak_log".to_string(), 0x8B5A2BFF.into()),
+            ]),
+            fallback: None,
+        }
+    }
+
+    #[test]
+    fn test_sponge_schematic_import() {
+        let stone: Albedo = 0x808080FF.into();
+        let log: Albedo = 0x8B5A2BFF.into();
+
+        // 2x1x2 blocks, ordered by x, then z, then y
+        let schematic = named(
+            10,
+            "Schematic",
+            &compound(&[
+                named(2, "Width", &2_i16.to_be_bytes()),
+                named(2, "Height", &1_i16.to_be_bytes()),
+                named(2, "Length", &2_i16.to_be_bytes()),
+                named(
+                    10,
+                    "Palette",
+                    &compound(&[
+                        named(3, "minecraft:air", &0_i32.to_be_bytes()),
+                        named(3, "minecraft:stone", &1_i32.to_be_bytes()),
+                        named(3, "minecraft:oak_log[axis=y]", &2_i32.to_be_bytes()),
+                    ]),
+                ),
+                named(5, "Unused", &1_f32.to_be_bytes()),
+                named(7, "BlockData", &[0, 0, 0, 4, 1, 0, 2, 1]),
+            ]),
+        );
+        let (tree, block_states) = Octree::<BlockId>::from_schematic_bytes(&schematic, &colors())
+            .ok()
+            .unwrap();
+        assert!(block_states[2] == "minecraft:oak_log[axis=y]");
+        assert!(tree.get_size() == 2);
+
+        // The z axis is mirrored
+        assert!(
+            tree.get(&V3c::new(0, 0, 1))
+                == Some(&BlockId {
+                    id: 1,
+                    albedo: stone
+                })
+        );
+        assert!(tree.get(&V3c::new(0, 0, 0)) == Some(&BlockId { id: 2, albedo: log }));
+        assert!(
+            tree.get(&V3c::new(1, 0, 0))
+                == Some(&BlockId {
+                    id: 1,
+                    albedo: stone
+                })
+        );
+        assert!(tree.get(&V3c::new(1, 0, 1)).is_none());
+
+        assert!(Octree::<BlockId>::from_schematic_bytes(&schematic[..20], &colors()).is_err());
+
+        // Block indices outside of the palette
+        let mut invalid_index = schematic.clone();
+        let last_block = invalid_index.len() - 2;
+        invalid_index[last_block] = 3;
+        assert!(Octree::<BlockId>::from_schematic_bytes(&invalid_index, &colors()).is_err());
+    }
+
+    #[test]
+    fn test_litematic_import() {
+        let stone: Albedo = 0x808080FF.into();
+        let log: Albedo = 0x8B5A2BFF.into();
+
+        // 2x2x1 blocks, packed into 2 bits each, ordered by x, then z, then y
+        let block_states: i64 = 1 | (2 << 4) | (1 << 6);
+        let mut palette = vec![10];
+        palette.extend_from_slice(&3_i32.to_be_bytes());
+        palette.extend(compound(&[named(8, "Name", &string("minecraft:air"))]));
+        palette.extend(compound(&[named(8, "Name", &string("minecraft:stone"))]));
+        palette.extend(compound(&[
+            named(8, "Name", &string("minecraft:oak_log")),
+            named(
+                10,
+                "Properties",
+                &compound(&[named(8, "axis", &string("y"))]),
+            ),
+        ]));
+        let mut states = 1_i32.to_be_bytes().to_vec();
+        states.extend_from_slice(&block_states.to_be_bytes());
+        let litematic = named(
+            10,
+            "",
+            &compound(&[named(
+                10,
+                "Regions",
+                &compound(&[named(
+                    10,
+                    "main",
+                    &compound(&[
+                        named(10, "Position", &vector(0, 0, 0)),
+                        named(10, "Size", &vector(2, 2, 1)),
+                        named(9, "BlockStatePalette", &palette),
+                        named(12, "BlockStates", &states),
+                    ]),
+                )]),
+            )]),
+        );
+        let (tree, block_states) = Octree::<BlockId>::from_schematic_bytes(&litematic, &colors())
+            .ok()
+            .unwrap();
+        assert!(
+            block_states
+                == vec![
+                    "minecraft:air".to_string(),
+                    "minecraft:stone".to_string(),
+                    "minecraft:oak_log[axis=y]".to_string(),
+                ]
+        );
+        assert!(
+            tree.get(&V3c::new(0, 0, 0))
+                == Some(&BlockId {
+                    id: 1,
+                    albedo: stone
+                })
+        );
+        assert!(tree.get(&V3c::new(0, 1, 0)) == Some(&BlockId { id: 2, albedo: log }));
+        assert!(
+            tree.get(&V3c::new(1, 1, 0))
+                == Some(&BlockId {
+                    id: 1,
+                    albedo: stone
+                })
+        );
+        assert!(tree.get(&V3c::new(1, 0, 0)).is_none());
+    }
+}
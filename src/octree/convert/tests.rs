@@ -3,7 +3,7 @@ use crate::octree::types::{BrickData, NodeContent};
 use bendy::{decoding::FromBencode, encoding::ToBencode};
 
 use crate::object_pool::empty_marker;
-use crate::octree::{types::NodeChildren, Octree, V3c};
+use crate::octree::{types::NodeChildren, Octree, OctreeLoadError, V3c};
 
 #[test]
 fn test_node_brickdata_serialization() {
@@ -130,6 +130,211 @@ fn test_node_children_serialization() {
     assert!(deserialized_node_children_bitmap == node_children_bitmap);
 }
 
+#[test]
+fn test_octree_nodes_are_saved_in_depth_first_order() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree = Octree::<Albedo>::new(8).ok().unwrap();
+    tree.insert(&V3c::new(7, 7, 7), red).ok().unwrap();
+    tree.insert(&V3c::new(0, 0, 0), red).ok().unwrap();
+    tree.insert(&V3c::new(5, 1, 2), red).ok().unwrap();
+    tree.clear(&V3c::new(7, 7, 7)).ok().unwrap();
+
+    let tree_copy = Octree::<Albedo>::from_bytes(tree.to_bytes()).ok().unwrap();
+    assert!(tree_copy.node_count() == tree.node_count());
+    assert!(tree_copy.nodes.len() == tree_copy.node_count());
+    assert!(tree_copy.get(&V3c::new(0, 0, 0)) == Some(&red));
+    assert!(tree_copy.get(&V3c::new(5, 1, 2)) == Some(&red));
+    assert!(tree_copy.get(&V3c::new(7, 7, 7)).is_none());
+
+    // The first child of every internal node directly follows it
+    for node_key in 0..tree_copy.nodes.len() {
+        if let NodeChildrenArray::Children(children) = tree_copy.node_children[node_key].content {
+            let first_child = children.iter().find(|key| **key != empty_marker());
+            assert!(first_child == Some(&(node_key as u32 + 1)));
+        }
+    }
+
+    // Child keys are saved relative to their parent
+    let bytes = tree.to_bytes();
+    assert!(bytes.windows(5).any(|marker| marker == b"##r##"));
+    assert!(!bytes.windows(5).any(|marker| marker == b"##c##"));
+
+    // Absolute child keys of earlier versions are still read
+    let mut legacy_bytes = b"li1ei8e".to_vec();
+    legacy_bytes.extend(tree_copy.nodes.to_bencode().ok().unwrap());
+    legacy_bytes.extend(tree_copy.node_children.to_bencode().ok().unwrap());
+    legacy_bytes.extend(b"dee");
+    assert!(legacy_bytes.windows(5).any(|marker| marker == b"##c##"));
+    let legacy_tree = Octree::<Albedo>::from_bytes(legacy_bytes).ok().unwrap();
+    assert!(legacy_tree.get(&V3c::new(0, 0, 0)) == Some(&red));
+    assert!(legacy_tree.get(&V3c::new(5, 1, 2)) == Some(&red));
+}
+
+#[test]
+fn test_octree_load_errors() {
+    let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+    tree.insert(&V3c::new(1, 2, 3), 0xFF0000FF.into())
+        .ok()
+        .unwrap();
+    let bytes = tree.to_bytes();
+
+    let truncated = bytes[..bytes.len() / 2].to_vec();
+    assert!(matches!(
+        Octree::<Albedo>::from_bytes(truncated),
+        Err(OctreeLoadError::Decode(_))
+    ));
+    assert!(matches!(
+        Octree::<Albedo>::from_bytes(b"li1ei4ee".to_vec()),
+        Err(OctreeLoadError::Decode(_))
+    ));
+
+    // A tree of size 4 can not be loaded with a brick dimension of 4
+    let empty_tree_bytes = Octree::<Albedo>::new(4).ok().unwrap().to_bytes();
+    assert!(matches!(
+        Octree::<Albedo, 4>::from_bytes(empty_tree_bytes),
+        Err(OctreeLoadError::InvalidStructure(_))
+    ));
+
+    assert!(matches!(
+        Octree::<Albedo>::load("test_junk_octree_which_does_not_exist"),
+        Err(OctreeLoadError::Io(_))
+    ));
+    let tree_copy = Octree::<Albedo>::from_bytes_unchecked(bytes);
+    assert!(tree_copy.get(&V3c::new(1, 2, 3)).is_some());
+}
+
+#[test]
+fn test_octree_format_version() {
+    use crate::octree::convert::bytecode::FORMAT_VERSION;
+    use crate::octree::OctreeHeader;
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+    tree.insert(&V3c::new(1, 2, 3), red).ok().unwrap();
+    let bytes = tree.to_bytes();
+    assert!(bytes.starts_with(b"SVOX"));
+    assert_eq!(
+        OctreeHeader::from_bytes(&bytes).ok().unwrap().format_version,
+        FORMAT_VERSION
+    );
+
+    // Trees saved before the format was versioned have no header
+    let legacy_bytes = tree.to_bencode().ok().unwrap();
+    assert_eq!(
+        OctreeHeader::from_bytes(&legacy_bytes)
+            .ok()
+            .unwrap()
+            .format_version,
+        0
+    );
+    let legacy_tree = Octree::<Albedo>::from_bytes(legacy_bytes).ok().unwrap();
+    assert_eq!(legacy_tree.get(&V3c::new(1, 2, 3)), Some(&red));
+
+    let mut future_bytes = bytes.clone();
+    future_bytes[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        Octree::<Albedo>::from_bytes(future_bytes),
+        Err(OctreeLoadError::VersionMismatch { found, supported })
+            if found == FORMAT_VERSION + 1 && supported == FORMAT_VERSION
+    ));
+    assert!(matches!(
+        Octree::<Albedo>::from_bytes(b"SVOX".to_vec()),
+        Err(OctreeLoadError::Decode(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "compression")]
+fn test_octree_compression() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+    tree.fill_region(&V3c::new(0, 0, 0), &V3c::new(16, 8, 16), red)
+        .ok()
+        .unwrap();
+    tree.insert(&V3c::new(1, 9, 3), red).ok().unwrap();
+
+    let bytes = tree.to_bytes();
+    let compressed_bytes = tree.to_compressed_bytes();
+    assert!(compressed_bytes.len() < bytes.len());
+
+    let tree_copy = Octree::<Albedo, 2>::from_bytes(compressed_bytes)
+        .ok()
+        .unwrap();
+    assert_eq!(tree_copy.to_bytes(), bytes);
+
+    tree.save("test_junk_octree_compressed").ok().unwrap();
+    let tree_copy = Octree::<Albedo, 2>::load("test_junk_octree_compressed")
+        .ok()
+        .unwrap();
+    assert_eq!(tree_copy.get(&V3c::new(1, 9, 3)), Some(&red));
+    assert_eq!(tree_copy.get(&V3c::new(1, 10, 3)), None);
+}
+
+#[test]
+fn test_octree_streaming_serialization() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+    tree.insert(&V3c::new(1, 2, 3), red).ok().unwrap();
+    tree.insert(&V3c::new(12, 9, 5), red).ok().unwrap();
+    tree.metadata_mut()
+        .insert("author".to_string(), b"shocovox".to_vec());
+
+    let mut bytes = Vec::new();
+    tree.save_to_writer(&mut bytes).ok().unwrap();
+    assert_eq!(bytes, tree.to_bytes());
+
+    let tree_copy = Octree::<Albedo, 2>::load_from_reader(&mut bytes.as_slice())
+        .ok()
+        .unwrap();
+    assert_eq!(tree_copy.to_bytes(), bytes);
+
+    // Trees saved before the format was versioned are also read node by node
+    let legacy_bytes = tree.to_bencode().ok().unwrap();
+    let tree_copy = Octree::<Albedo, 2>::load_from_reader(&mut legacy_bytes.as_slice())
+        .ok()
+        .unwrap();
+    assert_eq!(tree_copy.get(&V3c::new(12, 9, 5)), Some(&red));
+
+    let truncated = &bytes[..bytes.len() - 3];
+    assert!(matches!(
+        Octree::<Albedo, 2>::load_from_reader(&mut &truncated[..]),
+        Err(OctreeLoadError::Decode(_))
+    ));
+}
+
+#[test]
+fn test_octree_load_errors_on_invalid_fields() {
+    // A tree of size 4 with a single node, and no children
+    let tree_bytes_with_node =
+        |node: &str| format!("li1ei4eli1elli1e{node}eeel5:##x##edee").into_bytes();
+
+    // Non-numeric user data
+    assert!(
+        Octree::<Albedo>::from_bytes(tree_bytes_with_node("l4:##u#l3:#b#i1ei1ei1ei1eixeee"))
+            .is_err()
+    );
+    assert!(
+        Octree::<Albedo>::from_bytes(tree_bytes_with_node("l4:##u#l3:#b#i1ei1ei1ei1ei-5eee"))
+            .is_err()
+    );
+
+    // Out of range user data and occupancy bitmap
+    assert!(Octree::<Albedo>::from_bytes(tree_bytes_with_node(
+        "l4:##u#l3:#b#i1ei1ei1ei1ei4294967296eee"
+    ))
+    .is_err());
+    assert!(
+        Octree::<Albedo>::from_bytes(tree_bytes_with_node("l2:##i18446744073709551616ee")).is_err()
+    );
+
+    // Wrong markers
+    assert!(Octree::<Albedo>::from_bytes(tree_bytes_with_node("2:#x")).is_err());
+    assert!(Octree::<Albedo>::from_bytes(tree_bytes_with_node("l4:##u#2:#xe")).is_err());
+    assert!(
+        Octree::<Albedo>::from_bytes("li1ei4eli1elli1e1:#eeel5:##y##edee".as_bytes().to_vec())
+            .is_err()
+    );
+}
+
 #[test]
 fn test_octree_file_io() {
     let red: Albedo = 0xFF0000FF.into();
@@ -179,7 +384,7 @@ fn test_big_octree_serialize() {
     }
 
     let serialized = tree.to_bytes();
-    let deserialized = Octree::<Albedo>::from_bytes(serialized);
+    let deserialized = Octree::<Albedo>::from_bytes(serialized).ok().unwrap();
 
     for x in FILL_RANGE_START..TREE_SIZE {
         for y in FILL_RANGE_START..TREE_SIZE {
@@ -200,7 +405,7 @@ fn test_small_octree_serialize_where_dim_is_1() {
     tree.insert(&V3c::new(0, 0, 0), 1.into()).ok().unwrap();
 
     let serialized = tree.to_bytes();
-    let deserialized = Octree::<Albedo>::from_bytes(serialized);
+    let deserialized = Octree::<Albedo>::from_bytes(serialized).ok().unwrap();
     let item_at_000 = deserialized.get(&V3c::new(0, 0, 0));
     assert!(
         item_at_000.is_some_and(|v| *v == 1.into()),
@@ -227,7 +432,7 @@ fn test_octree_serialize_where_dim_is_1() {
     }
 
     let serialized = tree.to_bytes();
-    let deserialized = Octree::<Albedo>::from_bytes(serialized);
+    let deserialized = Octree::<Albedo>::from_bytes(serialized).ok().unwrap();
 
     for x in 0..TREE_SIZE {
         for y in 0..TREE_SIZE {
@@ -258,7 +463,7 @@ fn test_octree_serialize_where_dim_is_2() {
     }
 
     let serialized = tree.to_bytes();
-    let deserialized = Octree::<Albedo, 2>::from_bytes(serialized);
+    let deserialized = Octree::<Albedo, 2>::from_bytes(serialized).ok().unwrap();
 
     for x in 0..4 {
         for y in 0..4 {
@@ -287,7 +492,7 @@ fn test_big_octree_serialize_where_dim_is_2() {
     }
 
     let serialized = tree.to_bytes();
-    let deserialized = Octree::<Albedo, 2>::from_bytes(serialized);
+    let deserialized = Octree::<Albedo, 2>::from_bytes(serialized).ok().unwrap();
 
     for x in 100..128 {
         for y in 100..128 {
@@ -300,3 +505,656 @@ fn test_big_octree_serialize_where_dim_is_2() {
         }
     }
 }
+
+#[test]
+fn test_voxelize_cube_mesh() {
+    use crate::octree::{VoxelCoverage, VoxelFill};
+    let positions = [
+        V3c::new(2.5, 2.5, 2.5),
+        V3c::new(5.5, 2.5, 2.5),
+        V3c::new(2.5, 5.5, 2.5),
+        V3c::new(5.5, 5.5, 2.5),
+        V3c::new(2.5, 2.5, 5.5),
+        V3c::new(5.5, 2.5, 5.5),
+        V3c::new(2.5, 5.5, 5.5),
+        V3c::new(5.5, 5.5, 5.5),
+    ];
+    let indices = [
+        0, 1, 3, 0, 3, 2, // bottom
+        4, 5, 7, 4, 7, 6, // top
+        0, 1, 5, 0, 5, 4, // front
+        2, 3, 7, 2, 7, 6, // back
+        0, 2, 6, 0, 6, 4, // left
+        1, 3, 7, 1, 7, 5, // right
+    ];
+    let red: Albedo = 0xFF0000FF.into();
+    let colors = [red; 8];
+    let count_voxels = |tree: &Octree<Albedo>| {
+        let mut count = 0;
+        for x in 0..8 {
+            for y in 0..8 {
+                for z in 0..8 {
+                    if let Some(voxel) = tree.get(&V3c::new(x, y, z)) {
+                        assert!(*voxel == red);
+                        assert!((2..6).contains(&x) && (2..6).contains(&y) && (2..6).contains(&z));
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
+    };
+
+    let mut shell = Octree::<Albedo>::new(8).ok().unwrap();
+    shell
+        .voxelize(
+            &positions,
+            Some(&colors),
+            &indices,
+            VoxelFill::Shell,
+            VoxelCoverage::Conservative,
+        )
+        .ok()
+        .unwrap();
+    assert!(count_voxels(&shell) == 4 * 4 * 4 - 2 * 2 * 2);
+    assert!(shell.get(&V3c::new(3, 3, 3)).is_none());
+
+    let mut solid = Octree::<Albedo>::new(8).ok().unwrap();
+    solid
+        .voxelize(
+            &positions,
+            Some(&colors),
+            &indices,
+            VoxelFill::Solid,
+            VoxelCoverage::Conservative,
+        )
+        .ok()
+        .unwrap();
+    assert!(count_voxels(&solid) == 4 * 4 * 4);
+
+    let mut tree = Octree::<Albedo>::new(8).ok().unwrap();
+    assert!(tree
+        .voxelize(
+            &positions,
+            None,
+            &[0, 1],
+            VoxelFill::Shell,
+            VoxelCoverage::Conservative,
+        )
+        .is_err());
+
+    // Only the bounds of the mesh are filled, wherever it is inside the tree
+    let offset = V3c::new(10., 20., 0.);
+    let moved_positions = positions.map(|position| position + offset);
+    let mut tree = Octree::<Albedo>::new(32).ok().unwrap();
+    tree.voxelize(
+        &moved_positions,
+        Some(&colors),
+        &indices,
+        VoxelFill::Solid,
+        VoxelCoverage::Conservative,
+    )
+    .ok()
+    .unwrap();
+    let mut count = 0;
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                if tree.get(&V3c::new(x, y, z)).is_some() {
+                    assert!((12..16).contains(&x) && (22..26).contains(&y) && (2..6).contains(&z));
+                    count += 1;
+                }
+            }
+        }
+    }
+    assert!(count == 4 * 4 * 4);
+}
+
+#[test]
+fn test_voxelize_coverage() {
+    use crate::octree::{VoxelCoverage, VoxelFill};
+    // A diagonal plane crossing the whole tree
+    let positions = [
+        V3c::new(0., 0., 0.),
+        V3c::new(8., 8., 0.),
+        V3c::new(0., 0., 8.),
+        V3c::new(8., 8., 8.),
+    ];
+    let indices = [0, 1, 3, 0, 3, 2];
+    let count_voxels = |coverage| {
+        let mut tree = Octree::<Albedo>::new(8).ok().unwrap();
+        tree.voxelize(&positions, None, &indices, VoxelFill::Shell, coverage)
+            .ok()
+            .unwrap();
+        let mut count = 0;
+        for x in 0..8 {
+            for y in 0..8 {
+                for z in 0..8 {
+                    if tree.get(&V3c::new(x, y, z)).is_some() {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
+    };
+    let conservative_count = count_voxels(VoxelCoverage::Conservative);
+    let separating_count = count_voxels(VoxelCoverage::SixSeparating);
+    assert!(separating_count == 8 * 8);
+    assert!(conservative_count > separating_count);
+}
+
+#[test]
+fn test_point_cloud_binning() {
+    use crate::octree::{PointCloudBinner, PointColorMode};
+    let red: Albedo = 0xFF0000FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    let points = [
+        (V3c::new(10.1, 10.1, 10.1), red),
+        (V3c::new(10.4, 10.2, 10.3), red),
+        (V3c::new(10.2, 10.4, 10.1), blue),
+        (V3c::new(11.1, 10.1, 10.1), blue),
+        (V3c::new(9.9, 10.1, 10.1), blue), // below the origin
+        (V3c::new(14.1, 10.1, 10.1), blue), // outside the tree
+    ];
+
+    let mut binner = PointCloudBinner::new(V3c::new(10., 10., 10.), 0.5, PointColorMode::Mode);
+    binner.add_points(points);
+    assert!(binner.len() == 3);
+    let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+    assert!(binner.write_into(&mut tree).ok().unwrap() == 1);
+    assert!(*tree.get(&V3c::new(0, 0, 0)).unwrap() == red);
+    assert!(*tree.get(&V3c::new(2, 0, 0)).unwrap() == blue);
+
+    let mut binner = PointCloudBinner::new(V3c::new(10., 10., 10.), 0.5, PointColorMode::Average);
+    binner.add_points(points);
+    let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+    binner.write_into(&mut tree).ok().unwrap();
+    assert!(*tree.get(&V3c::new(0, 0, 0)).unwrap() == Albedo::from(0xAA0055FF));
+}
+
+#[test]
+fn test_point_cloud_from_ply() {
+    use crate::octree::{PointCloudBinner, PointColorMode};
+    let ply = "ply
+format ascii 1.0
+comment generated for testing
+element vertex 2
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 0
+property list uchar int vertex_indices
+end_header
+0.5 0.5 0.5 255 0 0
+1.5 2.5 0.5 0 255 0
+";
+    let mut binner = PointCloudBinner::new(V3c::new(0., 0., 0.), 1., PointColorMode::Average);
+    assert!(binner.add_ply_points(ply.as_bytes()).ok().unwrap() == 2);
+    let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+    binner.write_into(&mut tree).ok().unwrap();
+    assert!(*tree.get(&V3c::new(0, 0, 0)).unwrap() == Albedo::from(0xFF0000FF));
+    assert!(*tree.get(&V3c::new(1, 2, 0)).unwrap() == Albedo::from(0x00FF00FF));
+
+    let mut binner = PointCloudBinner::new(V3c::new(0., 0., 0.), 1., PointColorMode::Average);
+    assert!(binner
+        .add_ply_points("ply\nformat binary_little_endian 1.0\nend_header\n".as_bytes())
+        .is_err());
+    assert!(binner
+        .add_ply_points(
+            "ply\nformat ascii 1.0\nelement vertex 18446744073709551615\nproperty float x\nproperty float y\nproperty float z\nend_header\n"
+                .as_bytes()
+        )
+        .is_err());
+    assert!(binner.is_empty());
+}
+
+#[test]
+fn test_point_cloud_from_binary_ply() {
+    use crate::octree::{PointCloudBinner, PointColorMode};
+    let header = |format: &str| {
+        format!(
+            "ply
+format {format} 1.0
+element vertex 1
+property float x
+property double y
+property short z
+property uchar red
+property uchar green
+property uchar blue
+end_header
+"
+        )
+        .into_bytes()
+    };
+    let mut little_endian = header("binary_little_endian");
+    little_endian.extend_from_slice(&1.5f32.to_le_bytes());
+    little_endian.extend_from_slice(&0.5f64.to_le_bytes());
+    little_endian.extend_from_slice(&2i16.to_le_bytes());
+    little_endian.extend_from_slice(&[0, 0, 255]);
+    let mut big_endian = header("binary_big_endian");
+    big_endian.extend_from_slice(&2.5f32.to_be_bytes());
+    big_endian.extend_from_slice(&0.5f64.to_be_bytes());
+    big_endian.extend_from_slice(&3i16.to_be_bytes());
+    big_endian.extend_from_slice(&[255, 0, 0]);
+
+    let mut binner = PointCloudBinner::new(V3c::new(0., 0., 0.), 1., PointColorMode::Average);
+    assert!(
+        binner
+            .add_ply_points(little_endian.as_slice())
+            .ok()
+            .unwrap()
+            == 1
+    );
+    assert!(binner.add_ply_points(big_endian.as_slice()).ok().unwrap() == 1);
+    let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+    binner.write_into(&mut tree).ok().unwrap();
+    assert!(*tree.get(&V3c::new(1, 0, 2)).unwrap() == Albedo::from(0x0000FFFF));
+    assert!(*tree.get(&V3c::new(2, 0, 3)).unwrap() == Albedo::from(0xFF0000FF));
+
+    // Truncated vertex data
+    let truncated = &little_endian[..little_endian.len() - 1];
+    assert!(binner.add_ply_points(truncated).is_err());
+}
+
+#[test]
+fn test_point_cloud_from_las() {
+    use crate::octree::{PointCloudBinner, PointColorMode};
+    let mut las = vec![0u8; 227];
+    las[0..4].copy_from_slice(b"LASF");
+    las[94..96].copy_from_slice(&227u16.to_le_bytes());
+    las[96..100].copy_from_slice(&227u32.to_le_bytes());
+    las[104] = 2;
+    las[105..107].copy_from_slice(&26u16.to_le_bytes());
+    las[107..111].copy_from_slice(&2u32.to_le_bytes());
+    for scale_offset in [131, 139, 147] {
+        las[scale_offset..(scale_offset + 8)].copy_from_slice(&0.01f64.to_le_bytes());
+    }
+    las[155..163].copy_from_slice(&1f64.to_le_bytes());
+    for (position, color) in [
+        ([50i32, 50, 250], [0u16, 0, 0xFF00]),
+        ([150, 250, 50], [0xFF00, 0, 0]),
+    ] {
+        let mut record = vec![0u8; 26];
+        for (i, coordinate) in position.iter().enumerate() {
+            record[(i * 4)..(i * 4 + 4)].copy_from_slice(&coordinate.to_le_bytes());
+        }
+        for (i, channel) in color.iter().enumerate() {
+            record[(20 + i * 2)..(22 + i * 2)].copy_from_slice(&channel.to_le_bytes());
+        }
+        las.extend(record);
+    }
+
+    let mut binner = PointCloudBinner::new(V3c::new(0., 0., 0.), 1., PointColorMode::Average);
+    assert!(binner.add_las_points(las.as_slice()).ok().unwrap() == 2);
+    let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+    binner.write_into(&mut tree).ok().unwrap();
+    assert!(*tree.get(&V3c::new(1, 0, 2)).unwrap() == Albedo::from(0x0000FFFF));
+    assert!(*tree.get(&V3c::new(2, 2, 0)).unwrap() == Albedo::from(0xFF0000FF));
+
+    // Compressed points and truncated data are rejected
+    let mut compressed = las.clone();
+    compressed[104] = 0x80 | 2;
+    assert!(binner.add_las_points(compressed.as_slice()).is_err());
+    assert!(binner.add_las_points(&las[..las.len() - 1]).is_err());
+}
+
+#[test]
+fn test_octree_from_slices() {
+    use crate::octree::IntensityWindow;
+    let window = IntensityWindow::from_center_width(150, 100);
+    assert!(window.albedo_of(99).is_none());
+    assert!(window.albedo_of(100) == Some(Albedo::from(0x000000FF)));
+    assert!(window.albedo_of(150).unwrap().r == 128);
+    assert!(window.albedo_of(1000) == Some(Albedo::from(0xFFFFFFFF)));
+
+    // 3 slices of 3x2 intensities
+    let slices = vec![vec![0, 100, 200, 0, 0, 0], vec![0; 6], vec![0, 0, 0, 0, 0, 150]];
+    let tree = Octree::<Albedo>::from_slices(&slices, 3, &window)
+        .ok()
+        .unwrap();
+    assert!(tree.get(&V3c::new(0, 0, 0)).is_none());
+    assert!(*tree.get(&V3c::new(1, 0, 0)).unwrap() == Albedo::from(0x000000FF));
+    assert!(*tree.get(&V3c::new(2, 0, 0)).unwrap() == Albedo::from(0xFFFFFFFF));
+    assert!(tree.get(&V3c::new(2, 1, 2)).unwrap().r == 128);
+    assert!(tree.get(&V3c::new(2, 1, 1)).is_none());
+
+    assert!(Octree::<Albedo>::from_slices(&slices, 4, &window).is_err());
+    assert!(Octree::<Albedo>::from_slices(&[], 4, &window).is_err());
+}
+
+#[test]
+fn test_octree_from_heightmap() {
+    let grass: Albedo = 0x00FF00FF.into();
+    let stone: Albedo = 0x808080FF.into();
+    let entry = |depth: u32| if 0 == depth { grass } else { stone };
+
+    // A 16x16 plateau of height 6, with a peak of height 12 in one corner
+    let mut heights = vec![3; 16 * 16];
+    heights[15 * 16 + 15] = 6;
+    let tree = Octree::<Albedo, 2>::from_heightmap(&heights, 16, 16, 2., entry)
+        .ok()
+        .unwrap();
+    assert!(tree.get_size() == 16);
+    assert!(tree.get(&V3c::new(0, 0, 0)) == Some(&stone));
+    assert!(tree.get(&V3c::new(7, 4, 9)) == Some(&stone));
+    assert!(tree.get(&V3c::new(7, 5, 9)) == Some(&grass));
+    assert!(tree.get(&V3c::new(7, 6, 9)).is_none());
+    assert!(tree.get(&V3c::new(15, 10, 15)) == Some(&stone));
+    assert!(tree.get(&V3c::new(15, 11, 15)) == Some(&grass));
+    assert!(tree.get(&V3c::new(15, 12, 15)).is_none());
+    assert!(tree.get(&V3c::new(14, 11, 15)).is_none());
+
+    // Every voxel of the terrain is set, without any additional ones
+    let mut voxel_count = 0;
+    tree.for_each_in(&V3c::unit(0), &V3c::unit(16), |_, _| voxel_count += 1);
+    assert!(voxel_count == 16 * 16 * 6 + 6);
+
+    assert!(Octree::<Albedo>::from_heightmap(&heights, 15, 16, 1., entry).is_err());
+    assert!(Octree::<Albedo>::from_heightmap(&[], 0, 0, 1., entry).is_err());
+}
+
+#[test]
+fn test_octree_dense_conversion() {
+    let red: Albedo = 0xFF0000FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    let dimensions = V3c::new(5, 2, 3);
+    let dense = (0..30)
+        .map(|i| match i % 3 {
+            0 => Some(red),
+            1 => Some(blue),
+            _ => None,
+        })
+        .collect::<Vec<Option<Albedo>>>();
+    let tree = Octree::<Albedo, 2>::from_dense_slice(&dense, dimensions)
+        .ok()
+        .unwrap();
+    assert!(tree.get_size() == 8);
+    assert!(tree.get(&V3c::new(0, 0, 0)) == Some(&red));
+    assert!(tree.get(&V3c::new(1, 0, 0)) == Some(&blue));
+    assert!(tree.get(&V3c::new(2, 0, 0)).is_none());
+    assert!(tree.get(&V3c::new(4, 1, 2)).is_none()); // index 29
+    assert!(tree.get(&V3c::new(3, 1, 2)) == Some(&blue)); // index 28
+    assert!(tree.get(&V3c::new(5, 0, 0)).is_none());
+
+    // The same array is copied back from the tree
+    assert!(tree.to_dense_region(&V3c::new(0, 0, 0), &dimensions) == dense);
+
+    // Positions outside the tree are empty
+    let region = tree.to_dense_region(&V3c::new(4, 1, 2), &V3c::new(10, 2, 3));
+    assert!(region.len() == 6);
+    assert!(region.iter().all(|voxel| voxel.is_none()));
+    let region = tree.to_dense_region(&V3c::new(3, 1, 2), &V3c::new(4, 2, 3));
+    assert!(region == vec![Some(blue)]);
+
+    assert!(Octree::<Albedo>::from_dense_slice(&dense, V3c::new(5, 2, 2)).is_err());
+    assert!(Octree::<Albedo>::from_dense_slice(&[], V3c::new(0, 0, 0)).is_err());
+}
+
+#[test]
+fn test_export_voxel_records() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+    tree.insert_at_lod(&V3c::new(2, 2, 2), 2, red).ok().unwrap();
+    tree.insert(&V3c::new(0, 1, 0), 0x00FF0080.into())
+        .ok()
+        .unwrap();
+
+    let records = tree.voxel_records();
+    assert!(records.len() == 9);
+    assert!(records[0] == [0, 1, 0, 0, 255, 0, 128, 0]);
+    assert!(records[1] == [2, 2, 2, 255, 0, 0, 255, 0]);
+    assert!(records[8] == [3, 3, 3, 255, 0, 0, 255, 0]);
+
+    let mut csv = Vec::new();
+    tree.write_csv(&mut csv).ok().unwrap();
+    let csv = String::from_utf8(csv).ok().unwrap();
+    let mut lines = csv.lines();
+    assert!(lines.next() == Some("x,y,z,r,g,b,a,data"));
+    assert!(lines.next() == Some("0,1,0,0,255,0,128,0"));
+    assert!(lines.count() == 8);
+
+    let mut npy = Vec::new();
+    tree.write_npy(&mut npy).ok().unwrap();
+    assert!(npy.starts_with(b"\x93NUMPY\x01\x00"));
+    let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+    assert!(0 == (10 + header_len) % 64);
+    assert!(npy[10 + header_len - 1] == b'\n');
+    let header = String::from_utf8(npy[10..(10 + header_len)].to_vec())
+        .ok()
+        .unwrap();
+    assert!(header.contains("'shape': (9, 8)"));
+    assert!(npy.len() == 10 + header_len + 9 * 8 * 4);
+    assert!(npy[10 + header_len + 4] == 1); // y of the first record
+}
+
+#[test]
+fn test_region_save_and_load() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree = Octree::<Albedo>::new(8).ok().unwrap();
+    tree.insert(&V3c::new(2, 2, 2), red).ok().unwrap();
+    tree.insert(&V3c::new(4, 3, 2), red).ok().unwrap();
+    tree.insert(&V3c::new(6, 6, 6), red).ok().unwrap(); // outside the saved region
+    tree.save_region("test_junk_octree_region", &V3c::new(2, 2, 2), &V3c::new(5, 4, 3))
+        .ok()
+        .unwrap();
+
+    let mut destination = Octree::<Albedo>::new(8).ok().unwrap();
+    destination.insert(&V3c::new(1, 0, 0), green).ok().unwrap(); // overwritten by the region
+    destination.insert(&V3c::new(5, 5, 5), green).ok().unwrap(); // outside the loaded region
+    destination
+        .load_region_into("test_junk_octree_region", &V3c::new(0, 0, 0))
+        .ok()
+        .unwrap();
+    assert!(*destination.get(&V3c::new(0, 0, 0)).unwrap() == red);
+    assert!(*destination.get(&V3c::new(2, 1, 0)).unwrap() == red);
+    assert!(destination.get(&V3c::new(1, 0, 0)).is_none());
+    assert!(destination.get(&V3c::new(4, 4, 4)).is_none());
+    assert!(*destination.get(&V3c::new(5, 5, 5)).unwrap() == green);
+
+    // Parts outside of the destination tree are ignored
+    destination
+        .load_region_into("test_junk_octree_region", &V3c::new(6, 6, 6))
+        .ok()
+        .unwrap();
+    assert!(*destination.get(&V3c::new(6, 6, 6)).unwrap() == red);
+}
+
+#[test]
+fn test_edit_log_replay() {
+    use crate::octree::{EditLog, EditRecord};
+    use std::io::Write;
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+    tree.insert(&V3c::new(3, 3, 3), red).ok().unwrap();
+    tree.save("test_junk_octree_with_log").ok().unwrap();
+
+    let mut log = EditLog::open("test_junk_octree_edit_log").ok().unwrap();
+    log.truncate().ok().unwrap();
+    let records = [
+        EditRecord::Insert {
+            position: V3c::new(0, 0, 0),
+            size: 2,
+            albedo: red,
+            user_data: 5,
+        },
+        EditRecord::Clear {
+            position: V3c::new(1, 1, 1),
+            size: 1,
+        },
+        EditRecord::Clear {
+            position: V3c::new(3, 3, 3),
+            size: 1,
+        },
+    ];
+    for record in records.iter() {
+        log.append(record).ok().unwrap();
+    }
+    log.sync().ok().unwrap();
+    assert!(EditLog::read("test_junk_octree_edit_log").ok().unwrap() == records);
+
+    // A record torn by a crash is ignored
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open("test_junk_octree_edit_log")
+        .ok()
+        .unwrap()
+        .write_all(&[1, 0, 0])
+        .ok()
+        .unwrap();
+
+    let restored = Octree::<Albedo>::load_with_edit_log(
+        "test_junk_octree_with_log",
+        "test_junk_octree_edit_log",
+    )
+    .ok()
+    .unwrap();
+    assert!(*restored.get(&V3c::new(0, 0, 0)).unwrap() == red);
+    assert!(*restored.get(&V3c::new(1, 0, 1)).unwrap() == red);
+    assert!(restored.get(&V3c::new(1, 1, 1)).is_none());
+    assert!(restored.get(&V3c::new(3, 3, 3)).is_none());
+}
+
+#[test]
+fn test_octree_metadata_is_preserved() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+    tree.insert(&V3c::new(1, 2, 3), red).ok().unwrap();
+    tree.metadata_mut()
+        .insert("author".to_string(), b"shocovox".to_vec());
+    tree.metadata_mut()
+        .insert("seed".to_string(), 42u64.to_le_bytes().to_vec());
+
+    tree.save("test_junk_octree_metadata").ok().unwrap();
+    let tree_copy = Octree::<Albedo>::load("test_junk_octree_metadata")
+        .ok()
+        .unwrap();
+
+    assert_eq!(tree.metadata(), tree_copy.metadata());
+    assert_eq!(
+        tree_copy.metadata().get("author"),
+        Some(&b"shocovox".to_vec())
+    );
+    assert_eq!(tree_copy.get(&V3c::new(1, 2, 3)), Some(&red));
+}
+
+#[test]
+fn test_octree_checksums_are_preserved() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree = Octree::<Albedo, 2>::new(8).ok().unwrap();
+    tree.insert(&V3c::new(1, 2, 3), red).ok().unwrap();
+    let unchecked_bytes = tree.to_bytes();
+    assert!(!Octree::<Albedo, 2>::from_bytes(unchecked_bytes.clone())
+        .ok()
+        .unwrap()
+        .checksums_enabled());
+
+    tree.enable_checksums();
+    tree.insert(&V3c::new(6, 5, 4), red).ok().unwrap();
+    let bytes = tree.to_bytes();
+    assert!(bytes.len() > unchecked_bytes.len());
+    let mut streamed_bytes = Vec::new();
+    tree.save_to_writer(&mut streamed_bytes).ok().unwrap();
+    assert_eq!(streamed_bytes, bytes);
+
+    let mut tree_copy = Octree::<Albedo, 2>::from_bytes(bytes.clone()).ok().unwrap();
+    assert!(tree_copy.checksums_enabled());
+    assert!(tree_copy.verify_stored_checksums().is_empty());
+    let mut tree_copy = Octree::<Albedo, 2>::load_from_reader(&mut bytes.as_slice())
+        .ok()
+        .unwrap();
+    assert!(tree_copy.verify_stored_checksums().is_empty());
+
+    // Contents not matching the saved checksums are detected after loading
+    tree.checksums
+        .as_mut()
+        .unwrap()
+        .values
+        .insert(V3c::new(6, 4, 4), 0);
+    let mut tree_copy = Octree::<Albedo, 2>::from_bytes(tree.to_bytes())
+        .ok()
+        .unwrap();
+    assert_eq!(tree_copy.verify_stored_checksums(), vec![V3c::new(6, 4, 4)]);
+}
+
+#[test]
+fn test_octree_header_read() {
+    use crate::octree::OctreeHeader;
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+    tree.insert(&V3c::new(1, 2, 3), red).ok().unwrap();
+    tree.insert(&V3c::new(12, 9, 5), red).ok().unwrap();
+    tree.metadata_mut()
+        .insert("author".to_string(), b"shocovox".to_vec());
+    tree.save("test_junk_octree_header").ok().unwrap();
+
+    let header = OctreeHeader::read("test_junk_octree_header").ok().unwrap();
+    assert_eq!(header.size, 16);
+    assert_eq!(header.brick_dim, Some(2));
+    assert!(header.auto_simplify);
+    assert_eq!(header.voxel_count, Some(2));
+    assert_eq!(header.node_count, tree.node_count());
+    assert_eq!(header.color_palette_size, Some(1));
+    assert_eq!(header.user_data_palette_size, Some(1));
+    assert_eq!(&header.metadata, tree.metadata());
+    assert_eq!(
+        OctreeHeader::from_bytes(&tree.to_bytes()).ok().unwrap(),
+        header
+    );
+
+    // Only the header block is read, the encoded tree after it is not needed
+    let bytes = tree.to_bytes();
+    let header_length = bytes.len() - tree.to_bencode().ok().unwrap().len();
+    std::fs::write("test_junk_octree_header", &bytes[..header_length]).unwrap();
+    assert_eq!(
+        OctreeHeader::read("test_junk_octree_header").ok().unwrap(),
+        header
+    );
+
+    // Solid parts of the tree are counted voxel by voxel
+    tree.fill_region(&V3c::new(8, 8, 8), &V3c::new(16, 16, 16), 0x00FF00FF.into())
+        .ok()
+        .unwrap();
+    let header = OctreeHeader::from_bytes(&tree.to_bytes()).ok().unwrap();
+    assert_eq!(header.voxel_count, Some(8 * 8 * 8 + 2));
+    assert_eq!(header.color_palette_size, Some(2));
+
+    // Statistics are not available for trees saved before the header block was introduced
+    let legacy_header = OctreeHeader::from_bytes(&tree.to_bencode().ok().unwrap())
+        .ok()
+        .unwrap();
+    assert_eq!(legacy_header.size, 16);
+    assert_eq!(legacy_header.voxel_count, None);
+    assert_eq!(legacy_header.brick_dim, None);
+}
+
+#[test]
+fn test_serialization_is_byte_exact_across_platforms() {
+    // Bencode stores every number as decimal text, so the bytes do not depend on endianness
+    let red: Albedo = 0xFF0000FF.into();
+    let brick = BrickData::<Albedo, 1>::Solid(red);
+    assert_eq!(
+        brick.to_bencode().ok().unwrap(),
+        b"l3:#b#i255ei0ei0ei255ei0ee".to_vec()
+    );
+
+    let node_children = NodeChildren {
+        empty_marker: empty_marker(),
+        content: NodeChildrenArray::OccupancyBitmap(0x8000000000000001),
+    };
+    assert_eq!(
+        node_children.to_bencode().ok().unwrap(),
+        b"l5:##b##i9223372036854775809ee".to_vec()
+    );
+    assert_eq!(
+        NodeChildren::from_bencode(b"l5:##b##i9223372036854775809ee")
+            .ok()
+            .unwrap(),
+        node_children
+    );
+}
@@ -0,0 +1,96 @@
+use crate::octree::{types::OctreeError, Albedo, Octree, V3c, VoxelData};
+
+/// Intensity range of a volume to display, in the units of the source data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntensityWindow {
+    /// Intensities below this value are left empty
+    pub min: u16,
+    /// Intensities above this value are displayed with full brightness
+    pub max: u16,
+}
+
+impl IntensityWindow {
+    /// Creates the window from its center and width, as used by radiology viewers
+    pub fn from_center_width(center: u16, width: u16) -> Self {
+        Self {
+            min: center.saturating_sub(width / 2),
+            max: center.saturating_add(width / 2),
+        }
+    }
+
+    /// The grayscale color the intensity is displayed with, or None if it is below the window
+    pub fn albedo_of(&self, intensity: u16) -> Option<Albedo> {
+        if intensity < self.min {
+            return None;
+        }
+        let range = self.max.saturating_sub(self.min).max(1) as f32;
+        let level = intensity.min(self.max).saturating_sub(self.min) as f32 / range;
+        let level = (level * 255.).round() as u8;
+        Some(Albedo {
+            r: level,
+            g: level,
+            b: level,
+            a: 255,
+        })
+    }
+}
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Creates a tree from a stack of grayscale images, e.g. slices of a medical volume
+    /// Each slice becomes one layer along the z axis, the user data of each voxel is
+    /// the raw intensity, while its albedo is decided by the given window.
+    /// The tree is simplified only once, after every slice is inserted
+    /// The size of the tree is the smallest valid size containing the whole volume
+    /// * `slices` - the intensities of each slice, row by row
+    /// * `width` - the number of intensities in one row of a slice
+    /// * `window` - the displayed range of intensities
+    pub fn from_slices(
+        slices: &[Vec<u16>],
+        width: usize,
+        window: &IntensityWindow,
+    ) -> Result<Self, OctreeError> {
+        let Some(first_slice) = slices.first() else {
+            return Err(OctreeError::InvalidStructure("No slices provided".into()));
+        };
+        if 0 == width || 0 == first_slice.len() || 0 != first_slice.len() % width {
+            return Err(OctreeError::InvalidStructure(
+                format!(
+                    "Slice of {} intensities can't be split into rows of {width}",
+                    first_slice.len()
+                )
+                .into(),
+            ));
+        }
+        if slices.iter().any(|slice| slice.len() != first_slice.len()) {
+            return Err(OctreeError::InvalidStructure(
+                "Every slice must be of the same size".into(),
+            ));
+        }
+
+        let height = first_slice.len() / width;
+        let extent = width.max(height).max(slices.len()) as u32;
+        let Some(size) = Self::valid_sizes_around(extent, DIM as u32).1 else {
+            return Err(OctreeError::InvalidStructure(
+                format!("Volume extent {extent} is too large for a tree").into(),
+            ));
+        };
+        let mut tree = Self::new(size)?;
+        tree.update_batch(|tree| {
+            for (z, slice) in slices.iter().enumerate() {
+                for (index, intensity) in slice.iter().enumerate() {
+                    if let Some(albedo) = window.albedo_of(*intensity) {
+                        tree.insert(
+                            &V3c::new((index % width) as u32, (index / width) as u32, z as u32),
+                            T::new(albedo, *intensity as u32),
+                        )?;
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        Ok(tree)
+    }
+}
@@ -0,0 +1,297 @@
+use crate::octree::{
+    types::{OctreeError, VoxelCoverage, VoxelFill},
+    Albedo, Octree, V3c, VoxelData,
+};
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Inserts the voxels covered by the given indexed triangle mesh into the tree
+    /// Positions are in voxel units relative to the origin of the tree,
+    /// parts of the mesh outside the bounds of the tree are ignored
+    /// * `positions` - the vertices of the mesh
+    /// * `colors` - optional color for each vertex, each triangle gets the average of its vertices
+    /// * `indices` - three vertex indices for each triangle
+    /// * `fill` - whether to insert only the surface or the enclosed volume too
+    /// * `coverage` - which voxels are counted as part of the surface
+    pub fn voxelize(
+        &mut self,
+        positions: &[V3c<f32>],
+        colors: Option<&[Albedo]>,
+        indices: &[u32],
+        fill: VoxelFill,
+        coverage: VoxelCoverage,
+    ) -> Result<(), OctreeError> {
+        if 0 != indices.len() % 3 {
+            return Err(OctreeError::InvalidStructure(
+                format!("Index count must be divisible by 3 instead of {}", indices.len()).into(),
+            ));
+        }
+        if let Some(index) = indices.iter().find(|i| **i as usize >= positions.len()) {
+            return Err(OctreeError::InvalidStructure(
+                format!("Vertex index {index} out of bounds").into(),
+            ));
+        }
+        if colors.is_some_and(|colors| colors.len() != positions.len()) {
+            return Err(OctreeError::InvalidStructure(
+                "There must be one color for each vertex".into(),
+            ));
+        }
+
+        let triangles = indices
+            .chunks(3)
+            .map(|triangle| {
+                (
+                    [
+                        positions[triangle[0] as usize],
+                        positions[triangle[1] as usize],
+                        positions[triangle[2] as usize],
+                    ],
+                    triangle_color(colors, triangle),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        self.update_batch(|tree| {
+            for (triangle, color) in triangles.iter() {
+                let (min_voxel, max_voxel) = match tree.voxels_around(triangle) {
+                    Some(bounds) => bounds,
+                    None => continue,
+                };
+                for x in min_voxel.x..=max_voxel.x {
+                    for y in min_voxel.y..=max_voxel.y {
+                        for z in min_voxel.z..=max_voxel.z {
+                            let voxel_center =
+                                V3c::new(x as f32, y as f32, z as f32) + V3c::unit(0.5);
+                            if triangle_overlaps_voxel(triangle, &voxel_center, coverage) {
+                                tree.insert(&V3c::new(x, y, z), T::new(*color, 0))?;
+                            }
+                        }
+                    }
+                }
+            }
+
+            if VoxelFill::Solid == fill {
+                tree.fill_enclosed_voxels(&triangles)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Provides the range of voxels inside the tree the given triangle might touch
+    fn voxels_around(&self, triangle: &[V3c<f32>; 3]) -> Option<(V3c<u32>, V3c<u32>)> {
+        let min = V3c::new(
+            triangle[0].x.min(triangle[1].x).min(triangle[2].x),
+            triangle[0].y.min(triangle[1].y).min(triangle[2].y),
+            triangle[0].z.min(triangle[1].z).min(triangle[2].z),
+        );
+        let max = V3c::new(
+            triangle[0].x.max(triangle[1].x).max(triangle[2].x),
+            triangle[0].y.max(triangle[1].y).max(triangle[2].y),
+            triangle[0].z.max(triangle[1].z).max(triangle[2].z),
+        );
+        let size = self.octree_size as f32;
+        if max.x < 0.
+            || max.y < 0.
+            || max.z < 0.
+            || min.x >= size
+            || min.y >= size
+            || min.z >= size
+        {
+            return None;
+        }
+        let last = self.octree_size - 1;
+        Some((
+            V3c::new(
+                min.x.max(0.) as u32,
+                min.y.max(0.) as u32,
+                min.z.max(0.) as u32,
+            )
+            .cut_each_component(&last),
+            V3c::new(max.x as u32, max.y as u32, max.z as u32).cut_each_component(&last),
+        ))
+    }
+
+    /// Inserts every empty voxel between the entry and exit points of the mesh along the z axis
+    /// The inserted voxels get the color of the triangle where the mesh is entered
+    fn fill_enclosed_voxels(
+        &mut self,
+        triangles: &[([V3c<f32>; 3], Albedo)],
+    ) -> Result<(), OctreeError> {
+        // Only the columns inside the bounds of the mesh are scanned,
+        // each of them checking only the triangles above or below it
+        let columns = triangles
+            .iter()
+            .map(|(triangle, _)| columns_around(triangle, self.octree_size))
+            .collect::<Vec<_>>();
+        if columns.iter().all(Option::is_none) {
+            return Ok(());
+        }
+        let mut mesh_min = (u32::MAX, u32::MAX);
+        let mut mesh_max = (0, 0);
+        for (min, max) in columns.iter().flatten() {
+            mesh_min = (mesh_min.0.min(min.0), mesh_min.1.min(min.1));
+            mesh_max = (mesh_max.0.max(max.0), mesh_max.1.max(max.1));
+        }
+        let width = (mesh_max.0 - mesh_min.0 + 1) as usize;
+        let height = (mesh_max.1 - mesh_min.1 + 1) as usize;
+        let mut buckets = vec![Vec::new(); width * height];
+        for (triangle_index, (min, max)) in columns
+            .iter()
+            .enumerate()
+            .filter_map(|(i, columns)| columns.map(|columns| (i, columns)))
+        {
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    buckets[(x - mesh_min.0) as usize + (y - mesh_min.1) as usize * width]
+                        .push(triangle_index);
+                }
+            }
+        }
+
+        for x in mesh_min.0..=mesh_max.0 {
+            for y in mesh_min.1..=mesh_max.1 {
+                // Sample slightly off the voxel center so the column doesn't hit
+                // the shared edges of neighbouring triangles twice
+                let column = (x as f32 + 0.5001, y as f32 + 0.5002);
+                let bucket =
+                    &buckets[(x - mesh_min.0) as usize + (y - mesh_min.1) as usize * width];
+                let mut crossings = bucket
+                    .iter()
+                    .filter_map(|triangle_index| {
+                        let (triangle, color) = &triangles[*triangle_index];
+                        z_crossing_of(triangle, column).map(|z| (z, *color))
+                    })
+                    .collect::<Vec<_>>();
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for span in crossings.chunks_exact(2) {
+                    let first_z = (span[0].0 - 0.5).ceil().max(0.);
+                    let last_z = (span[1].0 - 0.5).floor();
+                    if last_z < first_z {
+                        continue;
+                    }
+                    for z in first_z as u32..=(last_z as u32).min(self.octree_size - 1) {
+                        let position = V3c::new(x, y, z);
+                        if self.get(&position).is_none() {
+                            self.insert(&position, T::new(span[0].1, 0))?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Provides the range of voxel columns parallel to the z axis inside the tree,
+/// which the given triangle might cross, as the minimum and maximum (x, y) coordinates
+fn columns_around(triangle: &[V3c<f32>; 3], octree_size: u32) -> Option<((u32, u32), (u32, u32))> {
+    let min_x = triangle[0].x.min(triangle[1].x).min(triangle[2].x);
+    let min_y = triangle[0].y.min(triangle[1].y).min(triangle[2].y);
+    let max_x = triangle[0].x.max(triangle[1].x).max(triangle[2].x);
+    let max_y = triangle[0].y.max(triangle[1].y).max(triangle[2].y);
+    let size = octree_size as f32;
+    if max_x < 0. || max_y < 0. || min_x >= size || min_y >= size {
+        return None;
+    }
+    let last = octree_size - 1;
+    Some((
+        (min_x.max(0.) as u32, min_y.max(0.) as u32),
+        ((max_x as u32).min(last), (max_y as u32).min(last)),
+    ))
+}
+
+/// The average color of the vertices of the given triangle, or white in case there are no colors
+fn triangle_color(colors: Option<&[Albedo]>, triangle: &[u32]) -> Albedo {
+    let Some(colors) = colors else {
+        return 0xFFFFFFFF.into();
+    };
+    let channel_average = |channel: fn(&Albedo) -> u8| {
+        (triangle
+            .iter()
+            .map(|i| channel(&colors[*i as usize]) as u32)
+            .sum::<u32>()
+            / 3) as u8
+    };
+    Albedo {
+        r: channel_average(|c| c.r),
+        g: channel_average(|c| c.g),
+        b: channel_average(|c| c.b),
+        a: channel_average(|c| c.a),
+    }
+}
+
+/// Separating axis test between the triangle and the unit voxel centered at the given position
+/// based on: Tomas Akenine-Möller: Fast 3D Triangle-Box Overlap Testing
+fn triangle_overlaps_voxel(
+    triangle: &[V3c<f32>; 3],
+    voxel_center: &V3c<f32>,
+    coverage: VoxelCoverage,
+) -> bool {
+    const HALF_SIZE: f32 = 0.5;
+    let vertices = [
+        triangle[0] - *voxel_center,
+        triangle[1] - *voxel_center,
+        triangle[2] - *voxel_center,
+    ];
+
+    // Bounding box of the triangle against the voxel
+    let separated_on = |component: fn(&V3c<f32>) -> f32| {
+        let projections = vertices.map(|v| component(&v));
+        projections.iter().all(|p| *p > HALF_SIZE) || projections.iter().all(|p| *p < -HALF_SIZE)
+    };
+    if separated_on(|v| v.x) || separated_on(|v| v.y) || separated_on(|v| v.z) {
+        return false;
+    }
+
+    // Plane of the triangle against the voxel
+    let edges = [
+        vertices[1] - vertices[0],
+        vertices[2] - vertices[1],
+        vertices[0] - vertices[2],
+    ];
+    let normal = edges[0].cross(edges[1]);
+    let plane_reach = match coverage {
+        VoxelCoverage::Conservative => normal.x.abs() + normal.y.abs() + normal.z.abs(),
+        VoxelCoverage::SixSeparating => normal.x.abs().max(normal.y.abs()).max(normal.z.abs()),
+    } * HALF_SIZE;
+    if normal.dot(&vertices[0]).abs() > plane_reach {
+        return false;
+    }
+
+    // Cross products of the edges and the voxel axes
+    for edge in edges.iter() {
+        for axis in [
+            V3c::new(1., 0., 0.),
+            V3c::new(0., 1., 0.),
+            V3c::new(0., 0., 1.),
+        ] {
+            let axis = axis.cross(*edge);
+            let reach = (axis.x.abs() + axis.y.abs() + axis.z.abs()) * HALF_SIZE;
+            let projections = vertices.map(|v| axis.dot(&v));
+            if projections.iter().all(|p| *p > reach) || projections.iter().all(|p| *p < -reach) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Provides the z coordinate where the line parallel to the z axis
+/// going through the given (x, y) point crosses the triangle, if it does
+fn z_crossing_of(triangle: &[V3c<f32>; 3], column: (f32, f32)) -> Option<f32> {
+    let [a, b, c] = triangle;
+    let area = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+    if 0. == area {
+        // Triangle is parallel to the z axis
+        return None;
+    }
+    let weight_b = ((column.0 - a.x) * (c.y - a.y) - (c.x - a.x) * (column.1 - a.y)) / area;
+    let weight_c = ((b.x - a.x) * (column.1 - a.y) - (column.0 - a.x) * (b.y - a.y)) / area;
+    let weight_a = 1. - weight_b - weight_c;
+    if weight_a < 0. || weight_b < 0. || weight_c < 0. {
+        return None;
+    }
+    Some(a.z * weight_a + b.z * weight_b + c.z * weight_c)
+}
@@ -0,0 +1,84 @@
+use crate::octree::{types::OctreeError, Octree, V3c, VoxelData};
+
+/// Describes the bits of the user data storing the damage of a voxel
+/// The remaining bits of the user data are kept intact, so other attributes can be stored next to it
+/// Voxels without damage have all of these bits cleared, and they break when the damage reaches `max_damage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DamageLayer {
+    shift: u32,
+    bits: u32,
+}
+
+impl DamageLayer {
+    /// Creates a layer stored in the given bits of the user data
+    /// * `shift` - the index of the lowest bit of the layer
+    /// * `bits` - the number of bits in the layer, at least 1, reaching at most the highest bit of the user data
+    pub fn new(shift: u32, bits: u32) -> Self {
+        debug_assert!(0 < bits && shift + bits <= u32::BITS);
+        Self { shift, bits }
+    }
+
+    /// The bits of the user data the layer is stored in, to be used in the renderer
+    pub fn mask(&self) -> u32 {
+        self.max_damage() << self.shift
+    }
+
+    /// The amount of damage voxels break at
+    pub fn max_damage(&self) -> u32 {
+        u32::MAX >> (u32::BITS - self.bits)
+    }
+
+    /// Provides the damage stored in the given user data
+    pub fn damage_of(&self, user_data: u32) -> u32 {
+        (user_data & self.mask()) >> self.shift
+    }
+
+    /// The remaining health of a voxel with the given user data, in range 0..=1
+    pub fn health_of(&self, user_data: u32) -> f32 {
+        1. - self.damage_of(user_data) as f32 / self.max_damage() as f32
+    }
+
+    /// Provides the given user data with its damage replaced, clamped to `max_damage`
+    pub fn with_damage(&self, user_data: u32, damage: u32) -> u32 {
+        (user_data & !self.mask()) | (damage.min(self.max_damage()) << self.shift)
+    }
+}
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Damages every voxel inside the given region, clearing the voxels breaking from it
+    /// The type of the voxels needs to store their user data for the damage to accumulate
+    /// * `layer` - the bits of the user data storing the damage
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    /// * `amount` - the damage to add to each voxel
+    /// * Returns with the destroyed voxels and their positions
+    pub fn apply_damage(
+        &mut self,
+        layer: &DamageLayer,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+        amount: u32,
+    ) -> Result<Vec<(V3c<u32>, T)>, OctreeError> {
+        let mut damaged = Vec::new();
+        let mut destroyed = Vec::new();
+        self.for_each_in(region_min, region_max, |position, voxel| {
+            let damage = layer.damage_of(voxel.user_data()).saturating_add(amount);
+            if damage >= layer.max_damage() {
+                destroyed.push((*position, *voxel));
+            } else {
+                damaged.push((
+                    *position,
+                    T::new(voxel.albedo(), layer.with_damage(voxel.user_data(), damage)),
+                ));
+            }
+        });
+        if 0 < amount {
+            self.insert_batch(damaged)?;
+        }
+        self.clear_batch(destroyed.iter().map(|(position, _)| *position))?;
+        Ok(destroyed)
+    }
+}
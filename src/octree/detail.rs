@@ -413,6 +413,136 @@ where
         }
     }
 
+    /// Calls the given function with every non-empty part of the tree intersecting with the given region.
+    /// Solid bricks are provided as one cell, while parted bricks are provided voxel by voxel.
+    /// * `region_min` - the minimum position of the region to visit
+    /// * `region_max` - the maximum position of the region to visit, exclusive
+    /// * `visitor` - called with the bounds of each filled cell, and its content
+    pub(crate) fn for_each_filled_cell_in<F>(
+        &self,
+        region_min: &V3c<f32>,
+        region_max: &V3c<f32>,
+        visitor: &mut F,
+    ) where
+        F: FnMut(&Cube, &T),
+    {
+        self.for_each_filled_brick_in(region_min, region_max, &mut |brick_bounds, brick| {
+            Self::visit_filled_cells_of_brick(brick, brick_bounds, region_min, region_max, visitor)
+        });
+    }
+
+    /// Calls the given function with every non-empty brick of the tree intersecting with the given region.
+    /// Bricks of uniform leaf nodes are provided with the bounds of the whole node.
+    /// * `region_min` - the minimum position of the region to visit
+    /// * `region_max` - the maximum position of the region to visit, exclusive
+    /// * `visitor` - called with the bounds of each filled brick, and its content
+    pub(crate) fn for_each_filled_brick_in<F>(
+        &self,
+        region_min: &V3c<f32>,
+        region_max: &V3c<f32>,
+        visitor: &mut F,
+    ) where
+        F: FnMut(&Cube, &BrickData<T, DIM>),
+    {
+        self.visit_filled_bricks_of(
+            Self::ROOT_NODE_KEY as usize,
+            &Cube::root_bounds(self.octree_size as f32),
+            region_min,
+            region_max,
+            visitor,
+        );
+    }
+
+    fn visit_filled_bricks_of<F>(
+        &self,
+        node_key: usize,
+        node_bounds: &Cube,
+        region_min: &V3c<f32>,
+        region_max: &V3c<f32>,
+        visitor: &mut F,
+    ) where
+        F: FnMut(&Cube, &BrickData<T, DIM>),
+    {
+        if !self.nodes.key_is_valid(node_key)
+            || 0. >= node_bounds.overlap_volume(region_min, region_max)
+        {
+            return;
+        }
+        match self.nodes.get(node_key) {
+            NodeContent::Nothing => {}
+            NodeContent::Internal(_) => {
+                for octant in 0..8u8 {
+                    let child_key = self.node_children[node_key][octant as u32] as usize;
+                    self.visit_filled_bricks_of(
+                        child_key,
+                        &node_bounds.child_bounds_for(octant),
+                        region_min,
+                        region_max,
+                        visitor,
+                    );
+                }
+            }
+            NodeContent::Leaf(bricks) => {
+                for (octant, brick) in bricks.iter().enumerate() {
+                    let brick_bounds = node_bounds.child_bounds_for(octant as u8);
+                    if !matches!(brick, BrickData::Empty)
+                        && 0. < brick_bounds.overlap_volume(region_min, region_max)
+                    {
+                        visitor(&brick_bounds, brick);
+                    }
+                }
+            }
+            NodeContent::UniformLeaf(brick) => {
+                if !matches!(brick, BrickData::Empty) {
+                    visitor(node_bounds, brick);
+                }
+            }
+        }
+    }
+
+    /// Calls the given function with every non-empty part of the given brick intersecting with the given region
+    pub(crate) fn visit_filled_cells_of_brick<F>(
+        brick: &BrickData<T, DIM>,
+        brick_bounds: &Cube,
+        region_min: &V3c<f32>,
+        region_max: &V3c<f32>,
+        visitor: &mut F,
+    ) where
+        F: FnMut(&Cube, &T),
+    {
+        if 0. >= brick_bounds.overlap_volume(region_min, region_max) {
+            return;
+        }
+        match brick {
+            BrickData::Empty => {}
+            BrickData::Solid(voxel) => {
+                if !voxel.is_empty() {
+                    visitor(brick_bounds, voxel);
+                }
+            }
+            BrickData::Parted(brick) => {
+                let voxel_size = brick_bounds.size / DIM as f32;
+                for x in 0..DIM {
+                    for y in 0..DIM {
+                        for z in 0..DIM {
+                            if brick[x][y][z].is_empty() {
+                                continue;
+                            }
+                            let voxel_bounds = Cube {
+                                min_position: brick_bounds.min_position
+                                    + V3c::new(x as f32, y as f32, z as f32) * voxel_size,
+                                size: voxel_size,
+                            };
+                            if 0. < voxel_bounds.overlap_volume(region_min, region_max) {
+                                visitor(&voxel_bounds, &brick[x][y][z]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     /// Stores the given occupied bits for the given node based on key
     pub(crate) fn store_occupied_bits(&mut self, node_key: usize, new_occupied_bits: u64) {
         match self.nodes.get_mut(node_key) {
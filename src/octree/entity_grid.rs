@@ -0,0 +1,149 @@
+use crate::octree::V3c;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+/// Broad phase index of entity bounding boxes, so the entities possibly touching an edited region
+/// of a tree can be found without a second spatial structure, and vice versa
+/// Entities are stored in the cubes of a uniform grid their bounding box overlaps with,
+/// the cubes being aligned with the nodes of the tree when their size is a valid node size
+#[derive(Debug, Clone)]
+pub struct EntityGrid<K> {
+    cell_size: u32,
+    cells: HashMap<V3c<i32>, Vec<K>>,
+    bounds: HashMap<K, (V3c<f32>, V3c<f32>)>,
+}
+
+impl<K> EntityGrid<K>
+where
+    K: Copy + Eq + Hash,
+{
+    /// Creates an empty grid
+    /// * `cell_size` - the size of the grid cubes in each dimension, at least 1, usually the brick dimension
+    pub fn new(cell_size: u32) -> Self {
+        Self {
+            cell_size: cell_size.max(1),
+            cells: HashMap::new(),
+            bounds: HashMap::new(),
+        }
+    }
+
+    pub fn cell_size(&self) -> u32 {
+        self.cell_size
+    }
+
+    /// The number of entities inside the grid
+    pub fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounds.is_empty()
+    }
+
+    /// Provides the bounding box of the given entity, if it is inside the grid
+    pub fn bounds_of(&self, entity: &K) -> Option<(V3c<f32>, V3c<f32>)> {
+        self.bounds.get(entity).copied()
+    }
+
+    /// Provides the grid cell containing the given point
+    fn cell_of(&self, point: &V3c<f32>) -> V3c<i32> {
+        let cell_size = self.cell_size as f32;
+        V3c::new(
+            (point.x / cell_size).floor() as i32,
+            (point.y / cell_size).floor() as i32,
+            (point.z / cell_size).floor() as i32,
+        )
+    }
+
+    /// Provides every grid cell overlapping with the given box
+    fn cells_between(&self, min: &V3c<f32>, max: &V3c<f32>) -> Vec<V3c<i32>> {
+        let min_cell = self.cell_of(min);
+        let max_cell = self.cell_of(max);
+        let mut cells = Vec::new();
+        for x in min_cell.x..=max_cell.x {
+            for y in min_cell.y..=max_cell.y {
+                for z in min_cell.z..=max_cell.z {
+                    cells.push(V3c::new(x, y, z));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Inserts the given entity into the grid, or moves it if it is already inside
+    /// * `min` - the minimum corner of the bounding box of the entity
+    /// * `max` - the maximum corner of the bounding box of the entity
+    pub fn insert(&mut self, entity: K, min: V3c<f32>, max: V3c<f32>) {
+        self.remove(&entity);
+        for cell in self.cells_between(&min, &max) {
+            self.cells.entry(cell).or_default().push(entity);
+        }
+        self.bounds.insert(entity, (min, max));
+    }
+
+    /// Removes the given entity from the grid
+    /// * Returns with true if the entity was inside the grid
+    pub fn remove(&mut self, entity: &K) -> bool {
+        let Some((min, max)) = self.bounds.remove(entity) else {
+            return false;
+        };
+        for cell in self.cells_between(&min, &max) {
+            if let Some(entities) = self.cells.get_mut(&cell) {
+                entities.retain(|stored| stored != entity);
+                if entities.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+        true
+    }
+
+    /// Collects the entities whose bounding box overlaps with the given box, each only once
+    pub fn entities_in(&self, min: &V3c<f32>, max: &V3c<f32>) -> Vec<K> {
+        let mut visited = HashSet::new();
+        let mut entities = Vec::new();
+        for cell in self.cells_between(min, max) {
+            for entity in self.cells.get(&cell).into_iter().flatten() {
+                if !visited.insert(*entity) {
+                    continue;
+                }
+                let (entity_min, entity_max) = self.bounds[entity];
+                if entity_min.x <= max.x
+                    && entity_min.y <= max.y
+                    && entity_min.z <= max.z
+                    && min.x <= entity_max.x
+                    && min.y <= entity_max.y
+                    && min.z <= entity_max.z
+                {
+                    entities.push(*entity);
+                }
+            }
+        }
+        entities
+    }
+
+    /// Collects the entities possibly touching the given region of voxels, e.g. after editing it
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    pub fn entities_touching(&self, region_min: &V3c<u32>, region_max: &V3c<u32>) -> Vec<K> {
+        self.entities_in(
+            &V3c::<f32>::from(*region_min),
+            &V3c::<f32>::from(*region_max),
+        )
+    }
+
+    /// Provides the minimum position of every grid cube the given entity overlaps with, inside the tree
+    /// e.g. to check if the tree has any voxels near the entity with `is_region_empty`
+    pub fn cells_of(&self, entity: &K) -> Vec<V3c<u32>> {
+        let Some((min, max)) = self.bounds.get(entity) else {
+            return Vec::new();
+        };
+        self.cells_between(min, max)
+            .into_iter()
+            .filter(|cell| 0 <= cell.x && 0 <= cell.y && 0 <= cell.z)
+            .map(|cell| V3c::<u32>::from(cell) * self.cell_size)
+            .collect()
+    }
+}
@@ -0,0 +1,229 @@
+use crate::object_pool::empty_marker;
+use crate::octree::{
+    types::{MergePolicy, NodeChildren, NodeChildrenArray, NodeContent, OctreeError},
+    Octree, V3c, VoxelData,
+};
+use crate::spatial::{
+    math::{set_occupancy_in_bitmap_64bits, BITMAP_DIMENSION},
+    Cube,
+};
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Copies the contents of the other tree into this one, aligned at their origins
+    /// Both trees are traversed together: nodes of the other tree are copied as a whole where this tree is empty,
+    /// and voxels are resolved one by one based on the given policy only where both trees contain leaves
+    /// * `other` - the tree to merge into this one, must not be larger, than this tree
+    /// * `policy` - decides the result where both trees contain a voxel
+    pub fn merge(&mut self, other: &Self, policy: MergePolicy) -> Result<(), OctreeError> {
+        if other.octree_size > self.octree_size {
+            return Err(OctreeError::InvalidStructure(
+                format!(
+                    "Can't merge tree of size {} into a tree of size {}",
+                    other.octree_size, self.octree_size
+                )
+                .into(),
+            ));
+        }
+
+        self.update_batch(|tree| {
+            tree.checksums_before_update_in(&V3c::unit(0), &V3c::unit(other.octree_size));
+
+            // Find the node matching the root of the other tree, its ancestors need their occupancy updated
+            let mut ancestors = Vec::new();
+            let mut node_key = Self::ROOT_NODE_KEY as usize;
+            let mut node_bounds = Cube::root_bounds(tree.octree_size as f32);
+            while node_bounds.size > other.octree_size as f32 {
+                match tree.nodes.get(node_key) {
+                    NodeContent::Nothing => {
+                        *tree.nodes.get_mut(node_key) = NodeContent::Internal(0);
+                        tree.node_children[node_key] = NodeChildren::new(empty_marker());
+                    }
+                    NodeContent::Leaf(_) | NodeContent::UniformLeaf(_) => {
+                        tree.subdivide_leaf_to_nodes(node_key, 0);
+                    }
+                    NodeContent::Internal(_) => {}
+                }
+                ancestors.push(node_key);
+                node_key = tree.child_for_merge(node_key, 0);
+                node_bounds = node_bounds.child_bounds_for(0);
+            }
+
+            tree.merge_node(
+                other,
+                node_key,
+                Self::ROOT_NODE_KEY as usize,
+                &node_bounds,
+                policy,
+            )?;
+            for ancestor_key in ancestors.into_iter().rev() {
+                tree.refresh_occupied_bits(ancestor_key);
+            }
+            Ok(())
+        })
+    }
+
+    /// Merges the node of the other tree into the node of this tree covering the same bounds
+    /// * `node_key` - the key of the node in this tree
+    /// * `other_key` - the key of the node in the other tree
+    /// * `node_bounds` - the bounds of both nodes
+    /// * `policy` - decides the result where both trees contain a voxel
+    fn merge_node(
+        &mut self,
+        other: &Self,
+        node_key: usize,
+        other_key: usize,
+        node_bounds: &Cube,
+        policy: MergePolicy,
+    ) -> Result<(), OctreeError> {
+        match (self.nodes.get(node_key), other.nodes.get(other_key)) {
+            (_, NodeContent::Nothing) => Ok(()),
+            (NodeContent::Nothing, _) => {
+                self.copy_node_from(other, node_key, other_key);
+                Ok(())
+            }
+            (NodeContent::Internal(_), NodeContent::Internal(_)) => {
+                for octant in 0..8 {
+                    let other_child_key = other.node_children[other_key][octant] as usize;
+                    if !other.nodes.key_is_valid(other_child_key)
+                        || matches!(other.nodes.get(other_child_key), NodeContent::Nothing)
+                    {
+                        continue;
+                    }
+                    let child_key = self.child_for_merge(node_key, octant);
+                    self.merge_node(
+                        other,
+                        child_key,
+                        other_child_key,
+                        &node_bounds.child_bounds_for(octant as u8),
+                        policy,
+                    )?;
+                }
+                self.refresh_occupied_bits(node_key);
+                Ok(())
+            }
+            _ => self.merge_cells_in(other, node_bounds, policy),
+        }
+    }
+
+    /// Provides the key of the child of the given internal node at the given octant, creating an empty one if needed
+    pub(crate) fn child_for_merge(&mut self, node_key: usize, octant: u32) -> usize {
+        let child_key = self.node_children[node_key][octant] as usize;
+        if self.nodes.key_is_valid(child_key) {
+            return child_key;
+        }
+        let child_key = self.nodes.push(NodeContent::Nothing);
+        self.node_children.resize(
+            self.node_children.len().max(self.nodes.len()),
+            NodeChildren::new(empty_marker()),
+        );
+        self.node_children[child_key] = NodeChildren::new(empty_marker());
+        self.node_children[node_key][octant] = child_key as u32;
+        child_key
+    }
+
+    /// Replaces the node under the given key with a copy of the node of the other tree, including its children
+    pub(crate) fn copy_node_from(&mut self, other: &Self, node_key: usize, other_key: usize) {
+        self.deallocate_children_of(node_key as u32);
+        *self.nodes.get_mut(node_key) = other.nodes.get(other_key).clone();
+        self.node_children[node_key] = match other.node_children[other_key].content {
+            NodeChildrenArray::Children(other_children) => {
+                let mut children = NodeChildren::new(empty_marker());
+                for (octant, other_child_key) in other_children.iter().enumerate() {
+                    if !other.nodes.key_is_valid(*other_child_key as usize) {
+                        continue;
+                    }
+                    let child_key = self.nodes.push(NodeContent::Nothing);
+                    self.node_children.resize(
+                        self.node_children.len().max(self.nodes.len()),
+                        NodeChildren::new(empty_marker()),
+                    );
+                    self.copy_node_from(other, child_key, *other_child_key as usize);
+                    children[octant as u32] = child_key as u32;
+                }
+                children
+            }
+            content => NodeChildren {
+                empty_marker: empty_marker(),
+                content,
+            },
+        };
+    }
+
+    /// Calculates the occupied bits of the given node from its content, and stores them
+    pub(crate) fn refresh_occupied_bits(&mut self, node_key: usize) {
+        let mut occupied_bits = 0;
+        for x in 0..BITMAP_DIMENSION {
+            for y in 0..BITMAP_DIMENSION {
+                for z in 0..BITMAP_DIMENSION {
+                    let index = V3c::new(x, y, z);
+                    if !self.should_bitmap_be_empty_at_index(node_key, &index) {
+                        set_occupancy_in_bitmap_64bits(
+                            &index,
+                            1,
+                            BITMAP_DIMENSION,
+                            true,
+                            &mut occupied_bits,
+                        );
+                    }
+                }
+            }
+        }
+        self.store_occupied_bits(node_key, occupied_bits);
+    }
+
+    /// Copies the filled cells of the other tree inside the given bounds into this tree
+    /// Filled cells are copied as a whole where this tree is empty,
+    /// conflicting voxels are resolved one by one based on the given policy
+    fn merge_cells_in(
+        &mut self,
+        other: &Self,
+        bounds: &Cube,
+        policy: MergePolicy,
+    ) -> Result<(), OctreeError> {
+        let mut cells = Vec::new();
+        other.for_each_filled_cell_in(
+            &bounds.min_position,
+            &(bounds.min_position + V3c::unit(bounds.size)),
+            &mut |bounds, voxel| cells.push((*bounds, *voxel)),
+        );
+        for (bounds, voxel) in cells {
+            let cell_min = V3c::<u32>::from(bounds.min_position);
+            let cell_size = bounds.size as u32;
+            if MergePolicy::Overwrite == policy || self.is_empty_inside(&bounds) {
+                self.insert_at_lod(&cell_min, cell_size, voxel)?;
+                continue;
+            }
+            for x in cell_min.x..(cell_min.x + cell_size) {
+                for y in cell_min.y..(cell_min.y + cell_size) {
+                    for z in cell_min.z..(cell_min.z + cell_size) {
+                        let position = V3c::new(x, y, z);
+                        let merged = match (self.get(&position), policy) {
+                            (None, _) | (Some(_), MergePolicy::Overwrite) => voxel,
+                            (Some(_), MergePolicy::KeepExisting) => continue,
+                            (Some(existing), MergePolicy::Blend) => T::new(
+                                existing.albedo().lerp(&voxel.albedo(), 0.5),
+                                voxel.user_data(),
+                            ),
+                        };
+                        self.insert(&position, merged)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// True if there are no filled voxels inside the given bounds
+    fn is_empty_inside(&self, bounds: &Cube) -> bool {
+        let mut empty = true;
+        self.for_each_filled_cell_in(
+            &bounds.min_position,
+            &(bounds.min_position + V3c::unit(bounds.size)),
+            &mut |_, _| empty = false,
+        );
+        empty
+    }
+}
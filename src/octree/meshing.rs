@@ -0,0 +1,518 @@
+use crate::octree::{Albedo, Octree, V3c, VoxelData};
+use crate::spatial::Cube;
+use std::collections::BTreeSet;
+
+/// Triangle mesh extracted from the contents of an octree, ready to be uploaded as vertex and index buffers
+#[derive(Debug, Default, Clone)]
+pub struct VoxelMesh {
+    /// Vertex positions in the coordinate space of the tree
+    pub positions: Vec<V3c<f32>>,
+    /// Vertex colors, one for each position
+    pub colors: Vec<Albedo>,
+    /// Vertex indices, every 3 of them describing a counter-clockwise triangle seen from outside
+    pub indices: Vec<u32>,
+}
+
+/// The density value separating the inside of the surface from the outside
+const SURFACE_DENSITY: f32 = 0.5;
+
+/// The edge length of the chunks greedy meshing collects small cells into
+const GREEDY_CHUNK_SIZE: u32 = 32;
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Extracts a smooth surface from the given region using the Surface Nets algorithm.
+    /// The density of each voxel is sampled at its center, and one vertex is placed
+    /// inside each cell of the sample grid the surface crosses.
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    pub fn extract_smooth_mesh(&self, region_min: &V3c<u32>, region_max: &V3c<u32>) -> VoxelMesh {
+        // The sample grid is extended by one voxel in each direction to close the surface
+        let origin = V3c::<i32>::from(*region_min) - V3c::unit(1);
+        let extent = V3c::<i32>::from(*region_max) - V3c::<i32>::from(*region_min) + V3c::unit(2);
+        if extent.x <= 2 || extent.y <= 2 || extent.z <= 2 {
+            return VoxelMesh::default();
+        }
+        let extent = V3c::<usize>::from(extent);
+        let sample_index = |x: usize, y: usize, z: usize| x + extent.x * (y + extent.y * z);
+        let mut samples = Vec::with_capacity(extent.x * extent.y * extent.z);
+        for z in 0..extent.z {
+            for y in 0..extent.y {
+                for x in 0..extent.x {
+                    let position = origin + V3c::new(x as i32, y as i32, z as i32);
+                    samples.push(self.sample_for_meshing(&position));
+                }
+            }
+        }
+
+        // Place one vertex into every cell crossed by the surface
+        let cells = extent - V3c::unit(1);
+        let cell_index = |x: usize, y: usize, z: usize| x + cells.x * (y + cells.y * z);
+        let mut cell_vertices = vec![u32::MAX; cells.x * cells.y * cells.z];
+        let mut mesh = VoxelMesh::default();
+        for z in 0..cells.z {
+            for y in 0..cells.y {
+                for x in 0..cells.x {
+                    let mut crossing_sum = V3c::unit(0.);
+                    let mut crossing_count = 0;
+                    let mut color = None;
+                    for (a, b) in CELL_EDGES {
+                        let (density_a, albedo_a) =
+                            samples[sample_index(x + a.0, y + a.1, z + a.2)];
+                        let (density_b, albedo_b) =
+                            samples[sample_index(x + b.0, y + b.1, z + b.2)];
+                        if (density_a > SURFACE_DENSITY) == (density_b > SURFACE_DENSITY) {
+                            continue;
+                        }
+                        let t = (SURFACE_DENSITY - density_a) / (density_b - density_a);
+                        let corner_a = V3c::new(a.0 as f32, a.1 as f32, a.2 as f32);
+                        let corner_b = V3c::new(b.0 as f32, b.1 as f32, b.2 as f32);
+                        crossing_sum += corner_a + (corner_b - corner_a) * t;
+                        crossing_count += 1;
+                        if color.is_none() {
+                            color = Some(if density_a > SURFACE_DENSITY {
+                                albedo_a
+                            } else {
+                                albedo_b
+                            });
+                        }
+                    }
+                    if 0 == crossing_count {
+                        continue;
+                    }
+                    // Sample points are at voxel centers, hence the additional half offset
+                    let cell_position = V3c::<f32>::from(origin)
+                        + V3c::new(x as f32, y as f32, z as f32)
+                        + V3c::unit(0.5);
+                    cell_vertices[cell_index(x, y, z)] = mesh.positions.len() as u32;
+                    mesh.positions
+                        .push(cell_position + crossing_sum * (1. / crossing_count as f32));
+                    mesh.colors.push(color.unwrap_or_default());
+                }
+            }
+        }
+
+        // Connect the vertices of the 4 cells around every grid edge crossed by the surface
+        for z in 0..extent.z {
+            for y in 0..extent.y {
+                for x in 0..extent.x {
+                    let inside = samples[sample_index(x, y, z)].0 > SURFACE_DENSITY;
+                    let sample = [x, y, z];
+                    for axis in 0..3 {
+                        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                        let mut next = sample;
+                        next[axis] += 1;
+                        if next[axis] >= [extent.x, extent.y, extent.z][axis]
+                            || 0 == sample[u]
+                            || 0 == sample[v]
+                            || sample[u] >= [cells.x, cells.y, cells.z][u]
+                            || sample[v] >= [cells.x, cells.y, cells.z][v]
+                            || sample[axis] >= [cells.x, cells.y, cells.z][axis]
+                        {
+                            continue;
+                        }
+                        let next_inside =
+                            samples[sample_index(next[0], next[1], next[2])].0 > SURFACE_DENSITY;
+                        if inside == next_inside {
+                            continue;
+                        }
+
+                        // The cells sharing the edge, in counter-clockwise order around the axis
+                        let mut quad = [sample; 4];
+                        quad[0][u] -= 1;
+                        quad[0][v] -= 1;
+                        quad[1][v] -= 1;
+                        quad[3][u] -= 1;
+                        let quad = quad.map(|c| cell_vertices[cell_index(c[0], c[1], c[2])]);
+                        debug_assert!(quad.iter().all(|vertex| *vertex != u32::MAX));
+                        let triangles = if inside {
+                            [quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]
+                        } else {
+                            [quad[0], quad[2], quad[1], quad[0], quad[3], quad[2]]
+                        };
+                        mesh.indices.extend_from_slice(&triangles);
+                    }
+                }
+            }
+        }
+        mesh
+    }
+
+    /// Extracts a blocky surface from the given region using greedy meshing.
+    /// Visible faces of the voxels are merged into as few quads as possible,
+    /// each quad covering faces of the same color, so every vertex belongs to a single quad.
+    /// Cells of the tree at least `GREEDY_CHUNK_SIZE` large are meshed on their own, so large solid
+    /// parts of the tree produce only a few quads; smaller cells are meshed chunk by chunk,
+    /// so memory usage depends on the size of a chunk instead of the size of the region.
+    /// Voxels outside the region are considered empty.
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    pub fn extract_greedy_mesh(&self, region_min: &V3c<u32>, region_max: &V3c<u32>) -> VoxelMesh {
+        self.extract_greedy_mesh_occluded_by(region_min, region_max, region_min, region_max)
+    }
+
+    /// Extracts a blocky surface from the given region using greedy meshing,
+    /// hiding faces covered by voxels of the given occluding region, which should contain the meshed region
+    /// Meshing neighbouring regions with the same occluding region produces no faces between the regions
+    pub(crate) fn extract_greedy_mesh_occluded_by(
+        &self,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+        occluder_min: &V3c<u32>,
+        occluder_max: &V3c<u32>,
+    ) -> VoxelMesh {
+        let region_max = V3c::new(
+            region_max.x.min(self.octree_size),
+            region_max.y.min(self.octree_size),
+            region_max.z.min(self.octree_size),
+        );
+        if region_max.x <= region_min.x
+            || region_max.y <= region_min.y
+            || region_max.z <= region_min.z
+        {
+            return VoxelMesh::default();
+        }
+        let region = (
+            [region_min.x, region_min.y, region_min.z],
+            [region_max.x, region_max.y, region_max.z],
+        );
+        let occluders = (
+            [occluder_min.x, occluder_min.y, occluder_min.z],
+            [
+                occluder_max.x.min(self.octree_size),
+                occluder_max.y.min(self.octree_size),
+                occluder_max.z.min(self.octree_size),
+            ],
+        );
+
+        // Cells are aligned to their size, so each small cell is inside a single chunk
+        let mut large_cells = Vec::new();
+        let mut chunks = BTreeSet::new();
+        self.for_each_filled_cell_in(
+            &V3c::<f32>::from(*region_min),
+            &V3c::<f32>::from(region_max),
+            &mut |bounds, voxel| {
+                let (cell_min, cell_max) = clipped_cell(bounds, &region);
+                if GREEDY_CHUNK_SIZE <= bounds.size as u32 {
+                    large_cells.push((cell_min, cell_max, voxel.albedo()));
+                } else {
+                    chunks.insert(cell_min.map(|component| component / GREEDY_CHUNK_SIZE));
+                }
+            },
+        );
+
+        let mut mesh = VoxelMesh::default();
+        for (cell_min, cell_max, color) in large_cells {
+            self.mesh_large_cell(&occluders, &cell_min, &cell_max, color, &mut mesh);
+        }
+        for chunk in chunks {
+            self.mesh_chunk(&region, &occluders, &chunk, &mut mesh);
+        }
+        mesh
+    }
+
+    /// Adds the visible parts of the faces of a large cell to the mesh
+    /// The faces are split along the edges of the cells in front of them,
+    /// and the uncovered parts are merged into quads
+    fn mesh_large_cell(
+        &self,
+        occluders: &([u32; 3], [u32; 3]),
+        cell_min: &[u32; 3],
+        cell_max: &[u32; 3],
+        color: Albedo,
+        mesh: &mut VoxelMesh,
+    ) {
+        for axis in 0..3 {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            for facing_forward in [false, true] {
+                let plane = if facing_forward {
+                    cell_max[axis]
+                } else {
+                    cell_min[axis]
+                };
+
+                // Collect the parts of the face covered by the layer of voxels in front of it
+                let mut covered = Vec::new();
+                let layer_occluding = if facing_forward {
+                    plane < occluders.1[axis]
+                } else {
+                    plane > occluders.0[axis]
+                };
+                if layer_occluding {
+                    let mut layer_min = *cell_min;
+                    layer_min[axis] = if facing_forward { plane } else { plane - 1 };
+                    let mut layer_max = *cell_max;
+                    layer_max[axis] = layer_min[axis] + 1;
+                    self.for_each_filled_cell_in(
+                        &V3c::new(layer_min[0], layer_min[1], layer_min[2]).into(),
+                        &V3c::new(layer_max[0], layer_max[1], layer_max[2]).into(),
+                        &mut |bounds, _| {
+                            let (min, max) = clipped_cell(bounds, &(layer_min, layer_max));
+                            covered.push(([min[u], min[v]], [max[u], max[v]]));
+                        },
+                    );
+                }
+
+                let mut u_edges = vec![cell_min[u], cell_max[u]];
+                let mut v_edges = vec![cell_min[v], cell_max[v]];
+                for (min, max) in covered.iter() {
+                    u_edges.extend_from_slice(&[min[0], max[0]]);
+                    v_edges.extend_from_slice(&[min[1], max[1]]);
+                }
+                u_edges.sort_unstable();
+                u_edges.dedup();
+                v_edges.sort_unstable();
+                v_edges.dedup();
+                let width = u_edges.len() - 1;
+                let height = v_edges.len() - 1;
+                let mut mask = vec![Some(color); width * height];
+                for (min, max) in covered.iter() {
+                    let u_range = u_edges.partition_point(|edge| *edge < min[0])
+                        ..u_edges.partition_point(|edge| *edge < max[0]);
+                    for j in v_edges.partition_point(|edge| *edge < min[1])
+                        ..v_edges.partition_point(|edge| *edge < max[1])
+                    {
+                        for i in u_range.clone() {
+                            mask[i + width * j] = None;
+                        }
+                    }
+                }
+                merge_mask_into_quads(&mut mask, width, height, |i, j, w, h, color| {
+                    push_quad(
+                        mesh,
+                        axis,
+                        plane,
+                        [u_edges[i], v_edges[j]],
+                        [u_edges[i + w], v_edges[j + h]],
+                        color,
+                        facing_forward,
+                    );
+                });
+            }
+        }
+    }
+
+    /// Adds the visible faces of the small cells inside the given chunk to the mesh
+    /// The voxels of the chunk are collected into a grid extended by one voxel in each direction,
+    /// so the faces hidden by voxels of neighbouring chunks are not added
+    fn mesh_chunk(
+        &self,
+        region: &([u32; 3], [u32; 3]),
+        occluders: &([u32; 3], [u32; 3]),
+        chunk: &[u32; 3],
+        mesh: &mut VoxelMesh,
+    ) {
+        let chunk_min: [u32; 3] =
+            std::array::from_fn(|i| (chunk[i] * GREEDY_CHUNK_SIZE).max(region.0[i]));
+        let chunk_max: [u32; 3] =
+            std::array::from_fn(|i| ((chunk[i] + 1) * GREEDY_CHUNK_SIZE).min(region.1[i]));
+        let grid_min: [u32; 3] =
+            std::array::from_fn(|i| chunk_min[i].saturating_sub(1).max(occluders.0[i]));
+        let grid_max: [u32; 3] = std::array::from_fn(|i| (chunk_max[i] + 1).min(occluders.1[i]));
+        let extent: [usize; 3] = std::array::from_fn(|i| (grid_max[i] - grid_min[i]) as usize);
+        let grid_index = |p: [usize; 3]| p[0] + extent[0] * (p[1] + extent[1] * p[2]);
+
+        // Each voxel of the grid stores its color, and whether its faces are meshed with this chunk
+        let mut grid = vec![None; extent[0] * extent[1] * extent[2]];
+        self.for_each_filled_cell_in(
+            &V3c::new(grid_min[0], grid_min[1], grid_min[2]).into(),
+            &V3c::new(grid_max[0], grid_max[1], grid_max[2]).into(),
+            &mut |bounds, voxel| {
+                let small = (bounds.size as u32) < GREEDY_CHUNK_SIZE;
+                let (min, max) = clipped_cell(bounds, &(grid_min, grid_max));
+                for z in min[2]..max[2] {
+                    for y in min[1]..max[1] {
+                        for x in min[0]..max[0] {
+                            let position = [x, y, z];
+                            let inside_chunk =
+                                (0..3).all(|i| (chunk_min[i]..chunk_max[i]).contains(&position[i]));
+                            grid[grid_index(std::array::from_fn(|i| {
+                                (position[i] - grid_min[i]) as usize
+                            }))] = Some((voxel.albedo(), small && inside_chunk));
+                        }
+                    }
+                }
+            },
+        );
+
+        let offset: [usize; 3] = std::array::from_fn(|i| (chunk_min[i] - grid_min[i]) as usize);
+        let chunk_extent: [usize; 3] =
+            std::array::from_fn(|i| (chunk_max[i] - chunk_min[i]) as usize);
+        for axis in 0..3 {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            for facing_forward in [false, true] {
+                let mut mask = vec![None; chunk_extent[u] * chunk_extent[v]];
+                for layer in 0..chunk_extent[axis] {
+                    // Mark the faces of the layer visible from the current direction
+                    for j in 0..chunk_extent[v] {
+                        for i in 0..chunk_extent[u] {
+                            let mut cell = offset;
+                            cell[axis] += layer;
+                            cell[u] += i;
+                            cell[v] += j;
+                            let neighbour = if facing_forward {
+                                cell[axis] + 1
+                            } else {
+                                cell[axis].wrapping_sub(1)
+                            };
+                            let neighbour_filled = neighbour < extent[axis] && {
+                                let mut neighbour_cell = cell;
+                                neighbour_cell[axis] = neighbour;
+                                grid[grid_index(neighbour_cell)].is_some()
+                            };
+                            mask[i + chunk_extent[u] * j] = match grid[grid_index(cell)] {
+                                Some((color, true)) if !neighbour_filled => Some(color),
+                                _ => None,
+                            };
+                        }
+                    }
+
+                    let plane = chunk_min[axis] + layer as u32 + facing_forward as u32;
+                    merge_mask_into_quads(
+                        &mut mask,
+                        chunk_extent[u],
+                        chunk_extent[v],
+                        |i, j, width, height, color| {
+                            push_quad(
+                                mesh,
+                                axis,
+                                plane,
+                                [chunk_min[u] + i as u32, chunk_min[v] + j as u32],
+                                [
+                                    chunk_min[u] + (i + width) as u32,
+                                    chunk_min[v] + (j + height) as u32,
+                                ],
+                                color,
+                                facing_forward,
+                            );
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Provides the density and color of the voxel at the given position
+    /// Positions outside the tree are considered empty
+    fn sample_for_meshing(&self, position: &V3c<i32>) -> (f32, Albedo) {
+        if position.x < 0 || position.y < 0 || position.z < 0 {
+            return (0., Albedo::default());
+        }
+        match self.get(&V3c::<u32>::from(*position)) {
+            Some(voxel) => (voxel.density(), voxel.albedo()),
+            None => (0., Albedo::default()),
+        }
+    }
+}
+
+/// Provides the part of the given cell inside the given box
+fn clipped_cell(bounds: &Cube, clip: &([u32; 3], [u32; 3])) -> ([u32; 3], [u32; 3]) {
+    let min = V3c::<u32>::from(bounds.min_position);
+    let max = min + V3c::unit(bounds.size as u32);
+    (
+        [
+            min.x.max(clip.0[0]),
+            min.y.max(clip.0[1]),
+            min.z.max(clip.0[2]),
+        ],
+        [
+            max.x.min(clip.1[0]),
+            max.y.min(clip.1[1]),
+            max.z.min(clip.1[2]),
+        ],
+    )
+}
+
+/// Merges the marked cells of the given mask into as few rectangles as possible, first along u, then along v
+/// Every rectangle covers cells of the same color, and is provided with its first cell, its size and its color
+/// The mask is cleared in the process
+fn merge_mask_into_quads<F>(mask: &mut [Option<Albedo>], width: usize, height: usize, mut quad: F)
+where
+    F: FnMut(usize, usize, usize, usize, Albedo),
+{
+    for j in 0..height {
+        let mut i = 0;
+        while i < width {
+            let Some(color) = mask[i + width * j] else {
+                i += 1;
+                continue;
+            };
+            let mut quad_width = 1;
+            while i + quad_width < width && mask[i + quad_width + width * j] == Some(color) {
+                quad_width += 1;
+            }
+            let mut quad_height = 1;
+            while j + quad_height < height
+                && (i..i + quad_width).all(|k| mask[k + width * (j + quad_height)] == Some(color))
+            {
+                quad_height += 1;
+            }
+            for row in j..j + quad_height {
+                for k in i..i + quad_width {
+                    mask[k + width * row] = None;
+                }
+            }
+            quad(i, j, quad_width, quad_height, color);
+            i += quad_width;
+        }
+    }
+}
+
+/// Adds a quad perpendicular to the given axis to the mesh
+/// * `plane` - the position of the quad along the axis
+/// * `min` - the minimum corner of the quad along the other two axes, in the order they follow the axis
+/// * `max` - the maximum corner of the quad along the other two axes
+/// * `facing_forward` - true if the quad is visible from the positive direction of the axis
+fn push_quad(
+    mesh: &mut VoxelMesh,
+    axis: usize,
+    plane: u32,
+    min: [u32; 2],
+    max: [u32; 2],
+    color: Albedo,
+    facing_forward: bool,
+) {
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let corner = |corner_u: u32, corner_v: u32| {
+        let mut position = [0.; 3];
+        position[axis] = plane as f32;
+        position[u] = corner_u as f32;
+        position[v] = corner_v as f32;
+        V3c::new(position[0], position[1], position[2])
+    };
+    let first_vertex = mesh.positions.len() as u32;
+    mesh.positions.extend_from_slice(&[
+        corner(min[0], min[1]),
+        corner(max[0], min[1]),
+        corner(max[0], max[1]),
+        corner(min[0], max[1]),
+    ]);
+    mesh.colors.extend_from_slice(&[color; 4]);
+
+    // u x v points along the axis, so the quad is counter-clockwise seen from the front
+    let triangles = if facing_forward {
+        [0, 1, 2, 0, 2, 3]
+    } else {
+        [0, 2, 1, 0, 3, 2]
+    };
+    mesh.indices
+        .extend(triangles.iter().map(|index| first_vertex + index));
+}
+
+/// The 12 edges of a cell, described by the offsets of their endpoints
+const CELL_EDGES: [((usize, usize, usize), (usize, usize, usize)); 12] = [
+    ((0, 0, 0), (1, 0, 0)),
+    ((0, 1, 0), (1, 1, 0)),
+    ((0, 0, 1), (1, 0, 1)),
+    ((0, 1, 1), (1, 1, 1)),
+    ((0, 0, 0), (0, 1, 0)),
+    ((1, 0, 0), (1, 1, 0)),
+    ((0, 0, 1), (0, 1, 1)),
+    ((1, 0, 1), (1, 1, 1)),
+    ((0, 0, 0), (0, 0, 1)),
+    ((1, 0, 0), (1, 0, 1)),
+    ((0, 1, 0), (0, 1, 1)),
+    ((1, 1, 0), (1, 1, 1)),
+];
@@ -1,9 +1,22 @@
+pub mod meshing;
 pub mod types;
 pub mod update;
 
+mod analytics;
+mod animation;
+mod checksum;
 mod convert;
+mod damage;
 mod detail;
+mod entity_grid;
+mod merge;
 mod node;
+mod physics;
+mod region;
+mod schedule;
+mod sdf;
+mod transform;
+mod world_gen;
 
 #[cfg(test)]
 mod tests;
@@ -11,51 +24,186 @@ mod tests;
 #[cfg(feature = "raytracing")]
 pub mod raytracing;
 
+#[cfg(feature = "rapier")]
+pub mod rapier;
+
+#[cfg(feature = "sim")]
+pub mod sim;
+
+pub use crate::object_pool::PoolGrowth;
 pub use crate::spatial::math::vector::{V3c, V3cf32};
-pub use types::{Albedo, Octree, VoxelData};
+pub use animation::VoxelAnimation;
+#[cfg(feature = "schematic")]
+pub use convert::schematic::{BlockColors, BlockId};
+pub use convert::{
+    edit_log::{EditLog, EditRecord},
+    point_cloud::{PointCloudBinner, PointColorMode},
+    volume::IntensityWindow,
+};
+pub use damage::DamageLayer;
+pub use entity_grid::EntityGrid;
+pub use physics::CollisionHull;
+pub use schedule::TickScheduler;
+pub use types::{
+    Aabb, Albedo, Axis, Contact, MergePolicy, Octree, OctreeHeader, OctreeLoadError, OctreeRegion,
+    VoxelCoverage, VoxelData, VoxelFill,
+};
+pub use world_gen::WorldGen;
 
 use crate::object_pool::{empty_marker, ObjectPool};
 use crate::octree::{
+    convert::bytecode::{current_payload, decompressed, with_format_header},
     detail::{bound_contains, child_octant_for},
     types::{BrickData, NodeChildren, NodeContent, OctreeError},
 };
 use crate::spatial::{math::matrix_index_for, Cube};
 use bendy::{decoding::FromBencode, encoding::ToBencode};
+use std::collections::BTreeMap;
 
 #[cfg(debug_assertions)]
 use crate::spatial::math::position_in_bitmap_64bits;
 
+#[cfg(feature = "compression")]
+use crate::octree::convert::bytecode::compressed;
+
+/// Converts errors of region persistence to io errors
+fn region_error(error: OctreeError) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{error:?}"))
+}
+
+/// The number of children a node has along each dimension
+pub const OCTREE_NODE_DIMENSION: u32 = 2;
+
 impl<T, const DIM: usize> Octree<T, DIM>
 where
     T: Default + Eq + Clone + Copy + VoxelData,
 {
-    /// converts the data structure to a byte representation
+    /// converts the data structure to a byte representation, starting with the format version
     pub fn to_bytes(&self) -> Vec<u8> {
-        self.to_bencode().ok().unwrap()
+        with_format_header(&self.encoded_header(), self.to_bencode().ok().unwrap())
     }
 
-    /// parses the data structure from a byte string
-    pub fn from_bytes(bytes: Vec<u8>) -> Self {
-        Self::from_bencode(&bytes).ok().unwrap()
+    /// converts the data structure to an LZ4 compressed byte representation
+    #[cfg(feature = "compression")]
+    pub fn to_compressed_bytes(&self) -> Vec<u8> {
+        with_format_header(
+            &self.encoded_header(),
+            compressed(&self.to_bencode().ok().unwrap()),
+        )
+    }
+
+    /// parses the data structure from a byte string, which may also be compressed
+    /// Bytes saved by earlier versions of the library are migrated to the current format
+    /// Returns with an error if the bytes are not a valid encoding of a tree, e.g. they are truncated
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, OctreeLoadError> {
+        let bytes = decompressed(&bytes)?;
+        let tree = Self::from_bencode(&current_payload(&bytes)?)
+            .map_err(|error| OctreeLoadError::Decode(format!("{error:?}")))?;
+        tree.validate_structure()
+            .map_err(OctreeLoadError::InvalidStructure)?;
+        Ok(tree)
+    }
+
+    /// parses the data structure from a byte string, panics if the bytes are not a valid tree
+    pub fn from_bytes_unchecked(bytes: Vec<u8>) -> Self {
+        Self::from_bytes(bytes).expect("Expected bytes to be a valid encoding of an octree")
     }
 
     /// saves the data structure to the given file path
+    /// The saved data is compressed if the compression feature is enabled
     pub fn save(&self, path: &str) -> Result<(), std::io::Error> {
         use std::fs::File;
         use std::io::Write;
+        let mut writer = std::io::BufWriter::new(File::create(path)?);
+        #[cfg(feature = "compression")]
+        writer.write_all(&self.to_compressed_bytes())?;
+        #[cfg(not(feature = "compression"))]
+        self.save_to_writer(&mut writer)?;
+        writer.flush()
+    }
+
+    /// loads the data structure from the given file path
+    pub fn load(path: &str) -> Result<Self, OctreeLoadError> {
+        Self::load_from_reader(&mut std::fs::File::open(path)?)
+    }
+
+    /// loads the data structure from the given file path, panics if it can not be loaded
+    pub fn load_unchecked(path: &str) -> Self {
+        Self::load(path).expect("Expected to load a valid octree from the given path")
+    }
+
+    /// saves the voxels inside the given region to the given file path
+    /// Only the region is stored, so it can be loaded into any tree large enough to contain it
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    pub fn save_region(
+        &self,
+        path: &str,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+    ) -> Result<(), std::io::Error> {
+        use std::fs::File;
+        use std::io::Write;
+        let region = self.copy_region(region_min, region_max);
+        let extent = region.size();
+        let region_size = extent.x.max(extent.y).max(extent.z);
+        let region_size = Self::valid_sizes_around(region_size, DIM as u32)
+            .1
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Region is too large to be saved",
+                )
+            })?;
+        let mut region_tree = Self::new(region_size).map_err(region_error)?;
+        region_tree
+            .paste_region(&region, &V3c::unit(0))
+            .map_err(region_error)?;
+
         let mut file = File::create(path)?;
-        file.write_all(&self.to_bytes())?;
+        for component in [extent.x, extent.y, extent.z] {
+            file.write_all(&component.to_le_bytes())?;
+        }
+        file.write_all(&region_tree.to_bytes())?;
         Ok(())
     }
 
-    /// loads the data structure from the given file path
-    pub fn load(path: &str) -> Result<Self, std::io::Error> {
+    /// loads a region saved with `save_region` into the tree, replacing its previous contents
+    /// Parts of the region outside the bounds of the tree are ignored
+    /// * `destination_min` - the position the minimum position of the region is placed at
+    pub fn load_region_into(
+        &mut self,
+        path: &str,
+        destination_min: &V3c<u32>,
+    ) -> Result<(), std::io::Error> {
         use std::fs::File;
         use std::io::Read;
         let mut file = File::open(path)?;
+        let mut extent = [0u32; 3];
+        for component in extent.iter_mut() {
+            let mut bytes = [0u8; 4];
+            file.read_exact(&mut bytes)?;
+            *component = u32::from_le_bytes(bytes);
+        }
         let mut bytes = Vec::new();
         file.read_to_end(&mut bytes)?;
-        Ok(Self::from_bytes(bytes))
+        let region_tree = Self::from_bytes(bytes).map_err(|error| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{error:?}"))
+        })?;
+        if destination_min.x >= self.octree_size
+            || destination_min.y >= self.octree_size
+            || destination_min.z >= self.octree_size
+        {
+            return Ok(());
+        }
+
+        let extent = V3c::new(extent[0], extent[1], extent[2]);
+        let region = region_tree.copy_region(&V3c::unit(0), &extent);
+        self.update_batch(|tree| {
+            tree.clear_region(destination_min, &(*destination_min + extent))?;
+            tree.paste_region(&region, destination_min)
+        })
+        .map_err(region_error)
     }
 
     /// creates an octree with overall size nodes_dimension * DIM
@@ -66,7 +214,13 @@ where
             return Err(OctreeError::InvalidBrickDimension(DIM as u32));
         }
         if DIM > size as usize || 0 == size || (size as f32 / DIM as f32).log(2.0).fract() != 0.0 {
-            return Err(OctreeError::InvalidSize(size));
+            let (smaller_valid, larger_valid) = Self::valid_sizes_around(size, DIM as u32);
+            return Err(OctreeError::InvalidSize {
+                size,
+                brick_dim: DIM as u32,
+                smaller_valid,
+                larger_valid,
+            });
         }
         if DIM >= size as usize {
             return Err(OctreeError::InvalidStructure(
@@ -86,9 +240,46 @@ where
             octree_size: size,
             nodes,
             node_children,
+            metadata: BTreeMap::new(),
+            checksums: None,
+            modified_regions: None,
         })
     }
 
+    /// Provides the valid octree sizes closest to the desired size from below and above
+    /// Valid sizes are `brick_dim * (2^x)`, where x > 0
+    fn valid_sizes_around(desired: u32, brick_dim: u32) -> (Option<u32>, Option<u32>) {
+        let mut smaller = None;
+        let mut candidate = brick_dim.max(1).checked_mul(2);
+        while let Some(size) = candidate {
+            if size >= desired {
+                return (smaller, Some(size));
+            }
+            smaller = Some(size);
+            candidate = size.checked_mul(2);
+        }
+        (smaller, None)
+    }
+
+    /// Provides the valid octree size closest to the desired one for the given brick dimension
+    /// In case the desired size is exactly between two valid sizes, the larger one is provided
+    /// * `desired` - The requested size of the octree
+    /// * `brick_dim` - The dimension of the voxel bricks, must be one of `(2^x)`
+    pub fn nearest_valid_size(desired: u32, brick_dim: u32) -> u32 {
+        match Self::valid_sizes_around(desired, brick_dim) {
+            (Some(smaller), Some(larger)) => {
+                if desired - smaller < larger - desired {
+                    smaller
+                } else {
+                    larger
+                }
+            }
+            (None, Some(larger)) => larger,
+            (Some(smaller), None) => smaller,
+            (None, None) => panic!("Brick dimension {brick_dim} is too large for any valid size"),
+        }
+    }
+
     /// Provides immutable reference to the data, if there is any at the given position
     pub fn get(&self, position: &V3c<u32>) -> Option<&T> {
         let mut current_bounds = Cube::root_bounds(self.octree_size as f32);
@@ -246,13 +437,15 @@ where
     }
 
     /// Provides mutable reference to the data, if there is any at the given position
+    /// Changes made through the reference are included in the brick checksums at the next write or verification
     pub fn get_mut(&mut self, position: &V3c<u32>) -> Option<&mut T> {
         let mut current_bounds = Cube::root_bounds(self.octree_size as f32);
         let mut current_node_key = Self::ROOT_NODE_KEY as usize;
-        let position = V3c::from(*position);
-        if !bound_contains(&current_bounds, &position) {
+        if !bound_contains(&current_bounds, &V3c::from(*position)) {
             return None;
         }
+        self.checksums_before_update(position, 1);
+        let position = V3c::from(*position);
 
         loop {
             match self.nodes.get(current_node_key) {
@@ -308,4 +501,51 @@ where
     pub fn get_size(&self) -> u32 {
         self.octree_size
     }
+
+    /// The size of one voxel brick in each dimension, equals the generic parameter DIM
+    pub fn brick_dim(&self) -> u32 {
+        DIM as u32
+    }
+
+    /// The number of node levels below the root, until the size of a node child equals the brick size
+    /// The size of the tree is always `brick_dim() * OCTREE_NODE_DIMENSION^depth()`
+    pub fn depth(&self) -> u32 {
+        (self.octree_size / DIM as u32).ilog2()
+    }
+
+    /// The number of nodes currently allocated inside the tree, including the root node
+    pub fn node_count(&self) -> usize {
+        self.nodes.reserved_count()
+    }
+
+    /// Reserves space for the given number of additional nodes, e.g. based on the
+    /// `node_count` of a saved header, to avoid reallocations during bulk inserts
+    pub fn reserve_nodes(&mut self, count: usize) {
+        self.nodes.reserve(count);
+        self.node_children.reserve_exact(count);
+    }
+
+    /// Sets how much space is allocated for new nodes once the reserved space runs out
+    pub fn set_node_pool_growth(&mut self, growth: PoolGrowth) {
+        self.nodes.set_growth(growth);
+    }
+
+    /// Provides the minimum position of the brick containing the given position
+    pub fn brick_origin_of(&self, position: &V3c<u32>) -> V3c<u32> {
+        V3c::new(
+            position.x - position.x % DIM as u32,
+            position.y - position.y % DIM as u32,
+            position.z - position.z % DIM as u32,
+        )
+    }
+
+    /// Application defined key-value pairs saved and loaded together with the tree
+    pub fn metadata(&self) -> &BTreeMap<String, Vec<u8>> {
+        &self.metadata
+    }
+
+    /// Mutable access to the key-value pairs saved and loaded together with the tree
+    pub fn metadata_mut(&mut self) -> &mut BTreeMap<String, Vec<u8>> {
+        &mut self.metadata
+    }
 }
@@ -0,0 +1,319 @@
+use crate::octree::{
+    types::{Aabb, Contact},
+    Octree, V3c, VoxelData,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Unit normals of the six sides of a voxel
+const FACE_NORMALS: [V3c<f32>; 6] = [
+    V3c {
+        x: 1.,
+        y: 0.,
+        z: 0.,
+    },
+    V3c {
+        x: -1.,
+        y: 0.,
+        z: 0.,
+    },
+    V3c {
+        x: 0.,
+        y: 1.,
+        z: 0.,
+    },
+    V3c {
+        x: 0.,
+        y: -1.,
+        z: 0.,
+    },
+    V3c {
+        x: 0.,
+        y: 0.,
+        z: 1.,
+    },
+    V3c {
+        x: 0.,
+        y: 0.,
+        z: -1.,
+    },
+];
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Generates contact points between the voxels of the tree and the given axis aligned box
+    /// Each voxel overlapping or touching the box is resolved along the axis of least penetration,
+    /// through one of its faces not covered by a neighbouring voxel,
+    /// and contacts on the same plane with the same normal are merged into one.
+    /// Boxes resting exactly on a surface have contacts with zero depth
+    /// * `aabb_min` - the minimum position of the box
+    /// * `aabb_max` - the maximum position of the box
+    pub fn contacts_with_aabb(&self, aabb_min: &V3c<f32>, aabb_max: &V3c<f32>) -> Vec<Contact> {
+        // Contacts collected per face: (face index, plane coordinate, position sum, count, depth)
+        let mut merged: Vec<(usize, f32, V3c<f32>, f32, f32)> = Vec::new();
+
+        // The region is extended, so cells only touching the box are also visited
+        let region_min = *aabb_min - V3c::unit(1.);
+        let region_max = *aabb_max + V3c::unit(1.);
+        self.for_each_filled_cell_in(&region_min, &region_max, &mut |bounds, _voxel| {
+            let cell_min = bounds.min_position;
+            let cell_max = bounds.min_position + V3c::unit(bounds.size);
+            if cell_max.x < aabb_min.x
+                || cell_max.y < aabb_min.y
+                || cell_max.z < aabb_min.z
+                || aabb_max.x < cell_min.x
+                || aabb_max.y < cell_min.y
+                || aabb_max.z < cell_min.z
+            {
+                return;
+            }
+
+            // Penetration depth in case the box is pushed out through each face of the cell
+            let depths = [
+                cell_max.x - aabb_min.x,
+                aabb_max.x - cell_min.x,
+                cell_max.y - aabb_min.y,
+                aabb_max.y - cell_min.y,
+                cell_max.z - aabb_min.z,
+                aabb_max.z - cell_min.z,
+            ];
+            let planes = [cell_max.x, cell_min.x, cell_max.y, cell_min.y, cell_max.z, cell_min.z];
+
+            // The contact is at the center of the overlapping area
+            let overlap_min = V3c::new(
+                cell_min.x.max(aabb_min.x),
+                cell_min.y.max(aabb_min.y),
+                cell_min.z.max(aabb_min.z),
+            );
+            let overlap_max = V3c::new(
+                cell_max.x.min(aabb_max.x),
+                cell_max.y.min(aabb_max.y),
+                cell_max.z.min(aabb_max.z),
+            );
+            let position = (overlap_min + overlap_max) * 0.5;
+
+            // A cell only touching the box can only be in contact through the faces it touches
+            let touching = depths.iter().any(|depth| *depth <= 0.);
+            let Some(face) = (0..6)
+                .filter(|face| !touching || depths[*face] <= 0.)
+                .filter(|face| self.is_face_open(&cell_min, &cell_max, &position, *face))
+                .min_by(|a, b| depths[*a].total_cmp(&depths[*b]))
+            else {
+                return;
+            };
+
+            if let Some(contact) = merged
+                .iter_mut()
+                .find(|(f, plane, _, _, _)| *f == face && *plane == planes[face])
+            {
+                contact.2 += position;
+                contact.3 += 1.;
+                contact.4 = contact.4.max(depths[face]);
+            } else {
+                merged.push((face, planes[face], position, 1., depths[face]));
+            }
+        });
+
+        merged
+            .into_iter()
+            .map(|(face, _plane, position_sum, count, depth)| Contact {
+                position: position_sum * (1. / count),
+                normal: FACE_NORMALS[face],
+                depth,
+            })
+            .collect()
+    }
+
+    /// Decides if the given face of a cell is not covered by a voxel next to it
+    /// * `cell_min` - the minimum position of the cell
+    /// * `cell_max` - the maximum position of the cell
+    /// * `position` - a point of the cell, the voxel next to the face is checked closest to it
+    /// * `face` - the index of the face, in the order of `FACE_NORMALS`
+    fn is_face_open(
+        &self,
+        cell_min: &V3c<f32>,
+        cell_max: &V3c<f32>,
+        position: &V3c<f32>,
+        face: usize,
+    ) -> bool {
+        let inside = |value: f32, min: f32, max: f32| value.floor().clamp(min, max - 1.);
+        let mut neighbour = V3c::new(
+            inside(position.x, cell_min.x, cell_max.x),
+            inside(position.y, cell_min.y, cell_max.y),
+            inside(position.z, cell_min.z, cell_max.z),
+        );
+        match face {
+            0 => neighbour.x = cell_max.x,
+            1 => neighbour.x = cell_min.x - 1.,
+            2 => neighbour.y = cell_max.y,
+            3 => neighbour.y = cell_min.y - 1.,
+            4 => neighbour.z = cell_max.z,
+            _ => neighbour.z = cell_min.z - 1.,
+        }
+        let size = self.octree_size as f32;
+        if [neighbour.x, neighbour.y, neighbour.z]
+            .iter()
+            .any(|component| *component < 0. || size <= *component)
+        {
+            return true;
+        }
+        self.get(&V3c::<u32>::from(neighbour)).is_none()
+    }
+
+    /// Merges the voxels inside the given region into as few boxes as possible, e.g. to be used as colliders
+    /// Boxes are grown greedily from each voxel not yet covered: first along x, then y, then z
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    pub fn collision_hull(&self, region_min: &V3c<u32>, region_max: &V3c<u32>) -> Vec<Aabb> {
+        let mut region_max = *region_max;
+        region_max.cut_each_component(&self.octree_size);
+        if region_max.x <= region_min.x
+            || region_max.y <= region_min.y
+            || region_max.z <= region_min.z
+        {
+            return Vec::new();
+        }
+        let extent = V3c::<usize>::from(region_max - *region_min);
+        let index = |x: usize, y: usize, z: usize| x + extent.x * (y + extent.y * z);
+        let mut solid = vec![false; extent.x * extent.y * extent.z];
+        self.for_each_in(region_min, &region_max, |position, _voxel| {
+            let position = V3c::<usize>::from(*position - *region_min);
+            solid[index(position.x, position.y, position.z)] = true;
+        });
+
+        // Voxels are unmarked as soon as they are covered by a box
+        let mut boxes = Vec::new();
+        for z in 0..extent.z {
+            for y in 0..extent.y {
+                for x in 0..extent.x {
+                    if !solid[index(x, y, z)] {
+                        continue;
+                    }
+                    let mut width = 1;
+                    while x + width < extent.x && solid[index(x + width, y, z)] {
+                        width += 1;
+                    }
+                    let mut height = 1;
+                    while y + height < extent.y
+                        && (x..x + width).all(|bx| solid[index(bx, y + height, z)])
+                    {
+                        height += 1;
+                    }
+                    let mut depth = 1;
+                    while z + depth < extent.z
+                        && (y..y + height)
+                            .all(|by| (x..x + width).all(|bx| solid[index(bx, by, z + depth)]))
+                    {
+                        depth += 1;
+                    }
+                    for bz in z..z + depth {
+                        for by in y..y + height {
+                            for bx in x..x + width {
+                                solid[index(bx, by, bz)] = false;
+                            }
+                        }
+                    }
+                    let min = *region_min + V3c::new(x as u32, y as u32, z as u32);
+                    boxes.push(Aabb {
+                        min,
+                        max: min + V3c::new(width as u32, height as u32, depth as u32),
+                    });
+                }
+            }
+        }
+        boxes
+    }
+}
+
+/// Keeps the collision hull of a tree up to date, recomputing only the parts changed since the last update
+/// The tree is divided into cubes of equal size, each of them having its own set of boxes
+/// so boxes never span over the boundary of two cubes
+#[derive(Debug, Clone)]
+pub struct CollisionHull {
+    chunk_size: u32,
+    chunks: HashMap<V3c<u32>, Vec<Aabb>>,
+    dirty: HashSet<V3c<u32>>,
+}
+
+impl CollisionHull {
+    /// Creates a hull for the whole tree, with every part of it to be computed in the next update
+    /// * `chunk_size` - the size of the independently updated cubes, at least 1
+    pub fn new<T, const DIM: usize>(tree: &Octree<T, DIM>, chunk_size: u32) -> Self
+    where
+        T: Default + Eq + Clone + Copy + VoxelData,
+    {
+        let mut hull = Self {
+            chunk_size: chunk_size.max(1),
+            chunks: HashMap::new(),
+            dirty: HashSet::new(),
+        };
+        hull.mark_dirty(&V3c::unit(0), &V3c::unit(tree.get_size()));
+        hull
+    }
+
+    /// Marks every part of the hull overlapping with the given region to be recomputed, e.g. after editing it
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    pub fn mark_dirty(&mut self, region_min: &V3c<u32>, region_max: &V3c<u32>) {
+        if region_max.x <= region_min.x
+            || region_max.y <= region_min.y
+            || region_max.z <= region_min.z
+        {
+            return;
+        }
+        let step = self.chunk_size as usize;
+        let first = V3c::new(
+            region_min.x - region_min.x % self.chunk_size,
+            region_min.y - region_min.y % self.chunk_size,
+            region_min.z - region_min.z % self.chunk_size,
+        );
+        for x in (first.x..region_max.x).step_by(step) {
+            for y in (first.y..region_max.y).step_by(step) {
+                for z in (first.z..region_max.z).step_by(step) {
+                    self.dirty.insert(V3c::new(x, y, z));
+                }
+            }
+        }
+    }
+
+    /// Recomputes the parts of the hull marked dirty
+    /// * Returns with true if any part of the hull was recomputed
+    pub fn update<T, const DIM: usize>(&mut self, tree: &Octree<T, DIM>) -> bool
+    where
+        T: Default + Eq + Clone + Copy + VoxelData,
+    {
+        !self.update_chunks(tree).is_empty()
+    }
+
+    /// Recomputes the parts of the hull marked dirty
+    /// * Returns with the minimum positions of the recomputed parts
+    pub fn update_chunks<T, const DIM: usize>(&mut self, tree: &Octree<T, DIM>) -> Vec<V3c<u32>>
+    where
+        T: Default + Eq + Clone + Copy + VoxelData,
+    {
+        let updated = std::mem::take(&mut self.dirty)
+            .into_iter()
+            .collect::<Vec<_>>();
+        for chunk_min in &updated {
+            let boxes = tree.collision_hull(chunk_min, &(*chunk_min + V3c::unit(self.chunk_size)));
+            if boxes.is_empty() {
+                self.chunks.remove(chunk_min);
+            } else {
+                self.chunks.insert(*chunk_min, boxes);
+            }
+        }
+        updated
+    }
+
+    /// Provides the boxes of the part of the hull starting at the given position
+    /// * `chunk_min` - the minimum position of the part, its components are multiples of the chunk size
+    pub fn chunk_boxes(&self, chunk_min: &V3c<u32>) -> &[Aabb] {
+        self.chunks.get(chunk_min).map_or(&[], Vec::as_slice)
+    }
+
+    /// Provides every box of the hull
+    pub fn boxes(&self) -> impl Iterator<Item = &Aabb> {
+        self.chunks.values().flatten()
+    }
+}
@@ -0,0 +1,170 @@
+use crate::octree::{Octree, V3c, VoxelData};
+use rapier3d::prelude::{
+    Aabb, ColliderBuilder, ColliderHandle, ColliderSet, IslandManager, Isometry, RigidBodySet,
+    SharedShape,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Keeps compound colliders in a rapier collider set in line with the contents of the octree.
+/// The tree is split into chunks, each covered by one compound collider built from the merged boxes
+/// of its collision hull. Colliders are only kept for the chunks around the given bodies,
+/// and only the chunks written in the tree since the last sync are rebuilt.
+#[derive(Debug, Clone)]
+pub struct OctreeColliderSync {
+    chunk_size: u32,
+    margin: f32,
+    active: HashSet<V3c<u32>>,
+    handles: HashMap<V3c<u32>, ColliderHandle>,
+}
+
+impl OctreeColliderSync {
+    /// Creates the sync object, and starts tracking the modifications of the tree
+    /// The sync object takes the modified regions of the tree, so it should be their only consumer
+    /// * `tree` - The octree to build the colliders from
+    /// * `chunk_size` - the size of one chunk covered by a single collider in each dimension
+    /// * `margin` - the distance around the bodies, inside which chunks have colliders
+    pub fn new<T, const DIM: usize>(tree: &mut Octree<T, DIM>, chunk_size: u32, margin: f32) -> Self
+    where
+        T: Default + Eq + Clone + Copy + VoxelData,
+    {
+        tree.track_modifications();
+        tree.take_modified_regions();
+        Self {
+            chunk_size: chunk_size.max(1),
+            margin: margin.max(0.),
+            active: HashSet::new(),
+            handles: HashMap::new(),
+        }
+    }
+
+    /// The handles of the managed colliders, with the minimum position of the chunk they cover
+    pub fn handles(&self) -> impl Iterator<Item = (&V3c<u32>, &ColliderHandle)> {
+        self.handles.iter()
+    }
+
+    /// Updates the colliders to cover the chunks around the given bodies
+    /// Chunks getting close to a body, or modified in the tree since the last sync are rebuilt,
+    /// colliders of chunks no longer close to any body, or without any voxels are removed
+    /// * `tree` - The octree to build the colliders from
+    /// * `body_aabbs` - The bounding boxes of the dynamic bodies, in the coordinates of the tree
+    /// * `colliders` - The collider set to store the colliders in
+    /// * `islands` - The island manager of the simulation, needed to remove colliders
+    /// * `bodies` - The rigid body set of the simulation, needed to remove colliders
+    /// * Returns with true if any collider was rebuilt or removed
+    pub fn sync<T, const DIM: usize>(
+        &mut self,
+        tree: &mut Octree<T, DIM>,
+        body_aabbs: &[Aabb],
+        colliders: &mut ColliderSet,
+        islands: &mut IslandManager,
+        bodies: &mut RigidBodySet,
+    ) -> bool
+    where
+        T: Default + Eq + Clone + Copy + VoxelData,
+    {
+        let mut active = HashSet::new();
+        for aabb in body_aabbs {
+            let (region_min, region_max) = self.region_around(tree.get_size(), aabb);
+            active.extend(self.chunks_in(&region_min, &region_max));
+        }
+
+        // Chunks newly close to a body, or modified while close to one
+        let mut dirty = active
+            .difference(&self.active)
+            .copied()
+            .collect::<HashSet<_>>();
+        for (region_min, region_max) in tree.take_modified_regions() {
+            dirty.extend(
+                self.chunks_in(&region_min, &region_max)
+                    .filter(|chunk_min| active.contains(chunk_min)),
+            );
+        }
+
+        let mut changed = false;
+        for chunk_min in self.active.difference(&active) {
+            if let Some(handle) = self.handles.remove(chunk_min) {
+                colliders.remove(handle, islands, bodies, false);
+                changed = true;
+            }
+        }
+        self.active = active;
+
+        for chunk_min in &dirty {
+            let shapes = tree
+                .collision_hull(chunk_min, &(*chunk_min + V3c::unit(self.chunk_size)))
+                .iter()
+                .map(|aabb| {
+                    let half_size = V3c::<f32>::from(aabb.max - aabb.min) / 2.;
+                    let center = V3c::<f32>::from(aabb.min) + half_size;
+                    (
+                        Isometry::translation(center.x, center.y, center.z),
+                        SharedShape::cuboid(half_size.x, half_size.y, half_size.z),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            if shapes.is_empty() {
+                if let Some(handle) = self.handles.remove(chunk_min) {
+                    colliders.remove(handle, islands, bodies, false);
+                    changed = true;
+                }
+                continue;
+            }
+            changed = true;
+            match self
+                .handles
+                .get(chunk_min)
+                .and_then(|handle| colliders.get_mut(*handle))
+            {
+                Some(collider) => collider.set_shape(SharedShape::compound(shapes)),
+                None => {
+                    self.handles.insert(
+                        *chunk_min,
+                        colliders.insert(ColliderBuilder::compound(shapes).build()),
+                    );
+                }
+            }
+        }
+        changed
+    }
+
+    /// The region of the tree inside the margin around the given bounding box
+    /// * Returns with the minimum and the exclusive maximum position of the region
+    fn region_around(&self, tree_size: u32, aabb: &Aabb) -> (V3c<u32>, V3c<u32>) {
+        let to_tree = |value: f32| value.clamp(0., tree_size as f32) as u32;
+        (
+            V3c::new(
+                to_tree((aabb.mins.x - self.margin).floor()),
+                to_tree((aabb.mins.y - self.margin).floor()),
+                to_tree((aabb.mins.z - self.margin).floor()),
+            ),
+            V3c::new(
+                to_tree((aabb.maxs.x + self.margin).ceil()),
+                to_tree((aabb.maxs.y + self.margin).ceil()),
+                to_tree((aabb.maxs.z + self.margin).ceil()),
+            ),
+        )
+    }
+
+    /// The minimum positions of the chunks overlapping with the given region
+    fn chunks_in(
+        &self,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+    ) -> impl Iterator<Item = V3c<u32>> {
+        let step = self.chunk_size as usize;
+        let first = V3c::new(
+            region_min.x - region_min.x % self.chunk_size,
+            region_min.y - region_min.y % self.chunk_size,
+            region_min.z - region_min.z % self.chunk_size,
+        );
+        let max = *region_max;
+        (first.x..max.x).step_by(step).flat_map(move |x| {
+            (first.y..max.y).step_by(step).flat_map(move |y| {
+                (first.z..max.z)
+                    .step_by(step)
+                    .map(move |z| V3c::new(x, y, z))
+            })
+        })
+    }
+}
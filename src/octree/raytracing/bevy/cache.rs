@@ -536,6 +536,27 @@ impl OctreeGPUDataHandler {
     //  ███████████  █████   █████ █████ ░░█████████  █████ ░░████
     // ░░░░░░░░░░░  ░░░░░   ░░░░░ ░░░░░   ░░░░░░░░░  ░░░░░   ░░░░
     //##############################################################################
+    /// Erases every node and brick from the cache, then adds the root node of the given tree with its children
+    /// Colors in the palette are kept, as they are valid for any tree
+    /// Returns with the indices of the bricks added with the root node
+    pub(crate) fn reset<T, const DIM: usize>(&mut self, tree: &Octree<T, DIM>) -> Vec<usize>
+    where
+        T: Default + Copy + Clone + PartialEq + VoxelData + Send + Sync + 'static,
+    {
+        self.render_data.octree_meta.octree_size = tree.octree_size;
+        self.render_data.metadata.fill(0);
+        self.render_data.node_children.fill(empty_marker());
+        self.render_data.node_ocbits.fill(0);
+        self.victim_node = VictimPointer::new(self.render_data.metadata.len());
+        self.victim_brick = 0;
+        self.node_key_vs_meta_index.clear();
+        self.brick_ownership.fill(BrickOwnedBy::NotOwned);
+        self.map_to_brick_maybe_owned_by_node.clear();
+        self.add_node(tree, Octree::<T, DIM>::ROOT_NODE_KEY as usize, true)
+            .map(|(_, _, modified_bricks)| modified_bricks)
+            .unwrap_or_default()
+    }
+
     /// Provides the index of the first brick available to be overwritten, through the second chance algorithm
     fn first_available_brick(&mut self) -> usize {
         let mut brick_index;
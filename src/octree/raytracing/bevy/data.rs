@@ -2,10 +2,10 @@ use crate::object_pool::empty_marker;
 use crate::octree::{
     raytracing::bevy::types::{
         BrickOwnedBy, OctreeGPUDataHandler, OctreeGPUHost, OctreeGPUView, OctreeMetaData,
-        OctreeRenderData, OctreeSpyGlass, SvxRenderPipeline, SvxViewSet, VictimPointer, Viewport,
-        Voxelement,
+        OctreeRenderData, OctreeSpyGlass, SvxAnimation, SvxFrameStats, SvxRenderPipeline,
+        SvxUploadBudget, SvxViewSet, Tonemapper, VictimPointer, Viewport, Voxelement,
     },
-    BrickData, NodeContent, Octree, V3c, VoxelData,
+    BrickData, NodeContent, Octree, V3c, VoxelAnimation, VoxelData,
 };
 use bevy::{
     ecs::system::{Res, ResMut},
@@ -19,6 +19,7 @@ use bevy::{
         },
         renderer::{RenderDevice, RenderQueue},
     },
+    time::Time,
 };
 use bimap::BiHashMap;
 use std::{
@@ -56,6 +57,12 @@ where
     //     ░░███      █████ ██████████    ░░███ ░░███
     //##############################################################################
 
+    /// Provides the largest view size usable in `create_new_view` with a GPU cache
+    /// fitting into the given number of bytes
+    pub fn view_size_for_memory_limit(max_bytes: usize) -> usize {
+        max_bytes / gpu_bytes_per_node::<DIM>()
+    }
+
     /// Creates GPU compatible data renderable on the GPU from an octree
     pub fn create_new_view(
         &mut self,
@@ -70,6 +77,15 @@ where
                 octree_meta: OctreeMetaData {
                     octree_size: self.tree.octree_size,
                     voxel_brick_dim: DIM as u32,
+                    albedo_variation: 0.,
+                    animation_time: 0.,
+                    foliage_mask: 0,
+                    damage_mask: 0,
+                    light_mask: 0,
+                    exposure: 1.,
+                    gamma: 1.,
+                    tonemapper: Tonemapper::None as u32,
+                    collect_stats: 0,
                     ambient_light_color: V3c::new(1., 1., 1.),
                     ambient_light_position: V3c::new(
                         self.tree.octree_size as f32,
@@ -100,10 +116,11 @@ where
 
         gpu_data_handler.add_node(&self.tree, Octree::<T, DIM>::ROOT_NODE_KEY as usize, true);
 
+        // Zero sized textures can not be created, e.g. while the window is minimized
         let mut output_texture = Image::new_fill(
             Extent3d {
-                width: resolution[0],
-                height: resolution[1],
+                width: resolution[0].max(1),
+                height: resolution[1].max(1),
                 depth_or_array_layers: 1,
             },
             TextureDimension::D2,
@@ -115,19 +132,230 @@ where
             | TextureUsages::STORAGE_BINDING
             | TextureUsages::TEXTURE_BINDING;
         let output_texture = images.add(output_texture);
+        let color_grading_lut = images.add(identity_color_grading_lut());
 
         svx_view_set.views.push(Arc::new(Mutex::new(OctreeGPUView {
             data_handler: gpu_data_handler,
+            paused: false,
+            single_frame_requested: false,
+            upload_budget: SvxUploadBudget::default(),
+            upload_backlog: 0,
+            cache_reset_requested: false,
             spyglass: OctreeSpyGlass {
                 node_requests: vec![empty_marker(); 4],
                 output_texture: output_texture.clone(),
                 viewport: viewport,
+                color_grading_lut,
+                frame_stats: SvxFrameStats::default(),
             },
         })));
         output_texture
     }
 }
 
+impl OctreeGPUView {
+    /// Sets the strength of the random per voxel albedo variation used while rendering
+    /// * `amplitude` - 0 disables the variation, values above 1 are clamped
+    pub fn set_albedo_variation(&mut self, amplitude: f32) {
+        self.data_handler.render_data.octree_meta.albedo_variation = amplitude.clamp(0., 1.);
+    }
+
+    /// Sets the voxels animated as foliage swaying in the wind
+    /// * `mask` - voxels with any of these bits set in their user data are animated, 0 disables it
+    pub fn set_foliage_mask(&mut self, mask: u32) {
+        self.data_handler.render_data.octree_meta.foliage_mask = mask;
+    }
+
+    /// Sets the bits of the user data storing the damage of voxels, displayed as cracks
+    /// * `mask` - usually `DamageLayer::mask`, 0 disables displaying damage
+    pub fn set_damage_mask(&mut self, mask: u32) {
+        self.data_handler.render_data.octree_meta.damage_mask = mask;
+    }
+
+    /// Sets the bits of the user data storing the baked light of voxels, see `Octree::bake_lighting`
+    /// * `mask` - usually `LightLayer::mask`, 0 disables baked lighting
+    pub fn set_light_mask(&mut self, mask: u32) {
+        self.data_handler.render_data.octree_meta.light_mask = mask;
+    }
+
+    /// Sets the time driving the animated effects of the view, e.g. the elapsed seconds of the app
+    pub fn set_animation_time(&mut self, seconds: f32) {
+        self.data_handler.render_data.octree_meta.animation_time = seconds;
+    }
+
+    /// Sets the multiplier of the rendered colors before tonemapping
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.data_handler.render_data.octree_meta.exposure = exposure.max(0.);
+    }
+
+    /// Sets the gamma of the display, colors are corrected with its inverse after tonemapping
+    pub fn set_gamma(&mut self, gamma: f32) {
+        debug_assert!(0. < gamma);
+        self.data_handler.render_data.octree_meta.gamma = gamma;
+    }
+
+    /// Sets the operator mapping rendered colors into the displayable range
+    pub fn set_tonemapper(&mut self, tonemapper: Tonemapper) {
+        self.data_handler.render_data.octree_meta.tonemapper = tonemapper as u32;
+    }
+
+    /// Stops or resumes dispatching the voxel pass for the view every frame
+    /// The output texture keeps the last rendered image while the view is paused
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Renders one frame of the paused view, e.g. to step through edits of the tree
+    pub fn render_single_frame(&mut self) {
+        self.single_frame_requested = true;
+    }
+
+    /// Starts or stops collecting the counters of the rendered frames on the GPU
+    /// Collecting them costs some GPU time and a readback every frame, so it is disabled by default
+    pub fn set_frame_stats_enabled(&mut self, enabled: bool) {
+        self.data_handler.render_data.octree_meta.collect_stats = enabled as u32;
+        if !enabled {
+            self.spyglass.frame_stats = SvxFrameStats::default();
+        }
+    }
+
+    pub fn frame_stats_enabled(&self) -> bool {
+        0 != self.data_handler.render_data.octree_meta.collect_stats
+    }
+
+    /// Provides the counters collected by the GPU while rendering the last frame
+    /// All counters are 0 while frame statistics are disabled
+    pub fn frame_stats(&self) -> SvxFrameStats {
+        self.spyglass.frame_stats
+    }
+
+    /// Sets the limits of the data uploaded to the GPU for the view in one frame
+    pub fn set_upload_budget(&mut self, budget: SvxUploadBudget) {
+        self.upload_budget = budget;
+    }
+
+    /// Provides the number of data requests postponed in the last frame because of the upload budget
+    /// A growing backlog means the tree is edited faster, than it can be streamed to the GPU
+    pub fn upload_backlog(&self) -> usize {
+        self.upload_backlog
+    }
+
+    /// Drops every node and brick uploaded to the GPU on the next render, so they are streamed again
+    /// from the tree of the host, e.g. after it is replaced by another tree
+    pub fn reset_cache(&mut self) {
+        self.cache_reset_requested = true;
+    }
+}
+
+impl<T, const DIM: usize> SvxAnimation<T, DIM>
+where
+    T: Default + Clone + PartialEq + VoxelData + Send + Sync + 'static,
+{
+    /// Plays the given animation from its start, once inserted as a resource next to the `OctreeGPUHost`
+    pub fn new(animation: VoxelAnimation<T, DIM>) -> Self {
+        Self {
+            animation,
+            displayed_frame: None,
+            time: 0.,
+            paused: false,
+        }
+    }
+
+    /// The index of the frame displayed in the tree of the host, if any
+    pub fn displayed_frame(&self) -> Option<usize> {
+        self.displayed_frame
+    }
+}
+
+/// Advances the time of the played animation, and swaps its due frame into the tree of the host
+/// The GPU cache of the views is reset when the displayed frame changes,
+/// the last frame stays displayed after the animation is over
+pub(crate) fn play_animation<T, const DIM: usize>(
+    time: Res<Time>,
+    animation: Option<ResMut<SvxAnimation<T, DIM>>>,
+    tree_gpu_host: Option<ResMut<OctreeGPUHost<T, DIM>>>,
+    svx_view_set: Res<SvxViewSet>,
+) where
+    T: Default + Clone + PartialEq + VoxelData + Send + Sync + 'static,
+{
+    let (Some(mut animation), Some(mut tree_host)) = (animation, tree_gpu_host) else {
+        return;
+    };
+    let animation = &mut *animation;
+    if !animation.paused {
+        animation.time += time.delta_secs();
+    }
+    let Some(frame) = animation.animation.frame_index_at(animation.time) else {
+        return;
+    };
+    if Some(frame) == animation.displayed_frame {
+        return;
+    }
+
+    // The displayed frame is put back to its place, in exchange for the tree it replaced
+    if let Some(displayed_frame) = animation.displayed_frame {
+        animation
+            .animation
+            .swap_frame(displayed_frame, &mut tree_host.tree);
+    }
+    animation.animation.swap_frame(frame, &mut tree_host.tree);
+    animation.displayed_frame = Some(frame);
+    for view in &svx_view_set.views {
+        view.lock().unwrap().reset_cache();
+    }
+}
+
+/// The number of bytes of metadata, occupancy bits and child references stored for a node on the GPU
+const GPU_NODE_SIZE_BYTES: usize = 4 + 2 * 4 + 8 * 4;
+
+/// The number of bytes stored on the GPU for a brick of voxels
+fn gpu_brick_size_bytes<const DIM: usize>() -> usize {
+    DIM * DIM * DIM * Voxelement::SHADER_SIZE.get() as usize
+}
+
+/// The number of bytes stored on the GPU for each unit of the view size, a node and its 8 bricks
+fn gpu_bytes_per_node<const DIM: usize>() -> usize {
+    GPU_NODE_SIZE_BYTES + 8 * gpu_brick_size_bytes::<DIM>()
+}
+
+impl SvxUploadBudget {
+    /// Tells if the budget allows no more uploads after the given number of nodes and bricks
+    pub(crate) fn is_exhausted<const DIM: usize>(&self, nodes: usize, bricks: usize) -> bool {
+        self.max_bricks_per_frame
+            .is_some_and(|max_bricks| max_bricks <= bricks)
+            || self.max_bytes_per_frame.is_some_and(|max_bytes| {
+                max_bytes <= nodes * GPU_NODE_SIZE_BYTES + bricks * gpu_brick_size_bytes::<DIM>()
+            })
+    }
+}
+
+/// The number of samples along each dimension of the default color grading LUT
+const IDENTITY_LUT_SIZE: u32 = 16;
+
+/// Creates a color grading LUT which maps every color to itself
+fn identity_color_grading_lut() -> Image {
+    let mut data = Vec::with_capacity((IDENTITY_LUT_SIZE.pow(3) * 4) as usize);
+    let level = |i: u32| (i * 255 / (IDENTITY_LUT_SIZE - 1)) as u8;
+    for b in 0..IDENTITY_LUT_SIZE {
+        for g in 0..IDENTITY_LUT_SIZE {
+            for r in 0..IDENTITY_LUT_SIZE {
+                data.extend_from_slice(&[level(r), level(g), level(b), 255]);
+            }
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: IDENTITY_LUT_SIZE,
+            height: IDENTITY_LUT_SIZE,
+            depth_or_array_layers: IDENTITY_LUT_SIZE,
+        },
+        TextureDimension::D3,
+        data,
+        TextureFormat::Rgba8Unorm,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
 /// Handles data sync between Bevy main(CPU) world and rendering world
 pub(crate) fn sync_with_main_world(// tree_view: Option<ResMut<OctreeGPUView>>,
     // mut world: ResMut<bevy::render::MainWorld>,
@@ -180,11 +408,24 @@ pub(crate) fn handle_gpu_readback<T, const DIM: usize>(
             },
         );
 
+        let mut view = svx_view_set.views[0].lock().unwrap();
+        let frame_stats_buffer_slice = resources.readable_frame_stats_buffer.slice(..);
+        let frame_stats_recv = view.frame_stats_enabled().then(|| {
+            let (s, frame_stats_recv) = crossbeam::channel::unbounded::<()>();
+            frame_stats_buffer_slice.map_async(
+                bevy::render::render_resource::MapMode::Read,
+                move |d| match d {
+                    Ok(_) => s.send(()).expect("Failed to send map update"),
+                    Err(err) => panic!("Couldn't map frame statistics buffer!: {err}"),
+                },
+            );
+            frame_stats_recv
+        });
+
         render_device
             .poll(bevy::render::render_resource::Maintain::wait())
             .panic_on_timeout();
 
-        let mut view = svx_view_set.views[0].lock().unwrap();
         node_requests_recv
             .recv()
             .expect("Failed to receive the map_async message");
@@ -197,6 +438,26 @@ pub(crate) fn handle_gpu_readback<T, const DIM: usize>(
         }
         resources.readable_node_requests_buffer.unmap();
 
+        if let Some(frame_stats_recv) = frame_stats_recv {
+            frame_stats_recv
+                .recv()
+                .expect("Failed to receive the map_async message");
+            {
+                let buffer_view = frame_stats_buffer_slice.get_mapped_range();
+                let counters = buffer_view
+                    .chunks(std::mem::size_of::<u32>())
+                    .map(|chunk| u32::from_ne_bytes(chunk.try_into().expect("should be a u32")))
+                    .collect::<Vec<u32>>();
+                view.spyglass.frame_stats = SvxFrameStats {
+                    rays: counters[0],
+                    node_steps: counters[1],
+                    brick_steps: counters[2],
+                    misses: counters[3],
+                };
+            }
+            resources.readable_frame_stats_buffer.unmap();
+        }
+
         if {
             let mut is_metadata_required_this_loop = false;
             for node_request in &view.spyglass.node_requests {
@@ -290,9 +551,10 @@ pub(crate) fn write_to_gpu<T, const DIM: usize>(
 ) where
     T: Default + Clone + Copy + PartialEq + VoxelData + Send + Sync + 'static,
 {
-    if let (Some(pipeline), Some(tree_host)) = (svx_pipeline, tree_gpu_host) {
+    if let (Some(mut pipeline), Some(tree_host)) = (svx_pipeline, tree_gpu_host) {
+        let pipeline = &mut *pipeline;
         let render_queue = &pipeline.render_queue;
-        let resources = if let Some(resources) = &pipeline.resources {
+        let resources = if let Some(resources) = &mut pipeline.resources {
             resources
         } else {
             // No resources available yet, can't write to them
@@ -304,7 +566,20 @@ pub(crate) fn write_to_gpu<T, const DIM: usize>(
         // Data updates for spyglass viewport
         let mut buffer = UniformBuffer::new(Vec::<u8>::new());
         buffer.write(&view.spyglass.viewport).unwrap();
-        render_queue.write_buffer(&resources.viewport_buffer, 0, &buffer.into_inner());
+        let buffer = buffer.into_inner();
+        if buffer != resources.uploaded_viewport {
+            render_queue.write_buffer(&resources.viewport_buffer, 0, &buffer);
+            resources.uploaded_viewport = buffer;
+        }
+
+        // Data updates for rendering properties of the tree
+        let mut buffer = UniformBuffer::new(Vec::<u8>::new());
+        buffer.write(&view.data_handler.render_data.octree_meta).unwrap();
+        let buffer = buffer.into_inner();
+        if buffer != resources.uploaded_octree_meta {
+            render_queue.write_buffer(&resources.octree_meta_buffer, 0, &buffer);
+            resources.uploaded_octree_meta = buffer;
+        }
 
         // Handle node requests, update cache
         let tree = &tree_host.tree;
@@ -325,14 +600,39 @@ pub(crate) fn write_to_gpu<T, const DIM: usize>(
                 start: view.data_handler.render_data.voxels.len(),
                 end: 0,
             };
+            if view.cache_reset_requested {
+                // Requests of the previous frame refer to erased nodes, every node is uploaded again
+                view.cache_reset_requested = false;
+                view.spyglass.node_requests.fill(empty_marker());
+                let added_bricks = view.data_handler.reset(tree);
+                meta_updated = 0..view.data_handler.render_data.metadata.len();
+                node_children_updated = 0..view.data_handler.render_data.node_children.len();
+                ocbits_updated = 0..view.data_handler.render_data.node_ocbits.len();
+                for brick_index in added_bricks {
+                    voxels_updated.start =
+                        voxels_updated.start.min(brick_index * (DIM * DIM * DIM));
+                    voxels_updated.end = voxels_updated
+                        .end
+                        .max(brick_index * (DIM * DIM * DIM) + (DIM * DIM * DIM));
+                }
+            }
             let mut node_requests = view.spyglass.node_requests.clone();
             let mut modified_nodes = HashSet::<usize>::new();
             let mut modified_bricks = HashSet::<usize>::new();
             let victim_node_loop_count = view.data_handler.victim_node.get_loop_count();
+            let mut upload_backlog = 0;
             for node_request in &mut node_requests {
                 if *node_request == empty_marker() {
                     continue;
                 }
+                if view
+                    .upload_budget
+                    .is_exhausted::<DIM>(modified_nodes.len(), modified_bricks.len())
+                {
+                    // The request is postponed, the GPU requests the data again in the next frame
+                    upload_backlog += 1;
+                    continue;
+                }
                 let requested_parent_meta_index = (*node_request & 0x00FFFFFF) as usize;
                 let requested_child_octant = (*node_request & 0xFF000000) >> 24;
 
@@ -471,6 +771,8 @@ pub(crate) fn write_to_gpu<T, const DIM: usize>(
                 }
             }
 
+            view.upload_backlog = upload_backlog;
+
             debug_assert!(
                 // Either all node requests are empty
                 node_requests
@@ -480,6 +782,8 @@ pub(crate) fn write_to_gpu<T, const DIM: usize>(
                     == node_requests.len()
                     // Or some ndoes were updated this loop
                     || 0 < modified_nodes.len()
+                    // Or the requests were postponed by the upload budget
+                    || 0 < upload_backlog
                     // Or the distance traveled by the victim pointer this loop is small enough
                     || (view.data_handler.victim_node.len() as f32 * 0.5) as usize
                         > (victim_node_loop_count - view.data_handler.victim_node.get_loop_count()),
@@ -509,7 +813,6 @@ pub(crate) fn write_to_gpu<T, const DIM: usize>(
             let host_color_count = view.data_handler.map_to_color_index_in_palette.keys().len();
             let color_palette_size_diff =
                 host_color_count - view.data_handler.uploaded_color_palette_size;
-            let resources = &pipeline.resources.as_ref().unwrap();
 
             debug_assert!(
                 host_color_count >= view.data_handler.uploaded_color_palette_size,
@@ -4,24 +4,26 @@ mod pipeline;
 pub mod types;
 
 pub use crate::octree::raytracing::bevy::types::{
-    OctreeGPUHost, OctreeGPUView, OctreeSpyGlass, RenderBevyPlugin, SvxViewSet, Viewport,
+    OctreeGPUHost, OctreeGPUView, OctreeSpyGlass, RenderBevyPlugin, SvxAnimation, SvxFrameStats,
+    SvxLabel, SvxUploadBudget, SvxViewSet, Tonemapper, Viewport,
 };
 
 use crate::octree::{
     raytracing::bevy::{
-        data::{handle_gpu_readback, sync_with_main_world, write_to_gpu},
+        data::{handle_gpu_readback, play_animation, sync_with_main_world, write_to_gpu},
         pipeline::prepare_bind_groups,
-        types::{SvxLabel, SvxRenderNode, SvxRenderPipeline},
+        types::{SvxRenderNode, SvxRenderPipeline},
     },
     VoxelData,
 };
 
 use bevy::{
-    app::{App, Plugin},
+    app::{App, Plugin, Update},
     prelude::{ExtractSchedule, IntoSystemConfigs},
     render::{
-        extract_resource::ExtractResourcePlugin, render_graph::RenderGraph, Render, RenderApp,
-        RenderSet,
+        extract_resource::ExtractResourcePlugin,
+        render_graph::{RenderGraph, RenderLabel},
+        Render, RenderApp, RenderSet,
     },
 };
 
@@ -33,8 +35,23 @@ where
         RenderBevyPlugin {
             dummy: std::marker::PhantomData,
             resolution,
+            run_after: vec![],
+            run_before: vec![bevy::render::graph::CameraDriverLabel.intern()],
         }
     }
+
+    /// Dispatches the voxel pass after the given render graph node, e.g. after a shadow pre-pass
+    pub fn run_after(mut self, label: impl RenderLabel) -> Self {
+        self.run_after.push(label.intern());
+        self
+    }
+
+    /// Dispatches the voxel pass before the given render graph node, e.g. before post-processing
+    /// By default the pass is dispatched before the camera driver node
+    pub fn run_before(mut self, label: impl RenderLabel) -> Self {
+        self.run_before.push(label.intern());
+        self
+    }
 }
 
 impl<T, const DIM: usize> Plugin for RenderBevyPlugin<T, DIM>
@@ -46,6 +63,7 @@ where
             ExtractResourcePlugin::<OctreeGPUHost<T, DIM>>::default(),
             ExtractResourcePlugin::<SvxViewSet>::default(),
         ));
+        app.add_systems(Update, play_animation::<T, DIM>);
         let render_app = app.sub_app_mut(RenderApp);
         render_app.add_systems(ExtractSchedule, sync_with_main_world);
         render_app.add_systems(
@@ -64,7 +82,12 @@ where
                 resolution: self.resolution,
             },
         );
-        render_graph.add_node_edge(SvxLabel, bevy::render::graph::CameraDriverLabel);
+        for label in &self.run_after {
+            render_graph.add_node_edge(*label, SvxLabel);
+        }
+        for label in &self.run_before {
+            render_graph.add_node_edge(SvxLabel, *label);
+        }
     }
 
     fn finish(&self, app: &mut App) {
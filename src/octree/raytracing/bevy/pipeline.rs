@@ -16,10 +16,12 @@ use bevy::{
         render_graph::{self},
         render_resource::{
             encase::{StorageBuffer, UniformBuffer},
-            BindGroupEntry, BindGroupLayoutEntry, BindingResource, BindingType, BufferBindingType,
-            BufferDescriptor, BufferInitDescriptor, BufferUsages, CachedPipelineState,
-            ComputePassDescriptor, ComputePipelineDescriptor, PipelineCache, ShaderSize,
-            ShaderStages, ShaderType, StorageTextureAccess, TextureFormat, TextureViewDimension,
+            BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutEntry, BindingResource,
+            BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferInitDescriptor,
+            BufferSize, BufferUsages, CachedPipelineState, ComputePassDescriptor,
+            ComputePipelineDescriptor, PipelineCache, ShaderStages, ShaderType,
+            StorageTextureAccess, TextureFormat, TextureSampleType, TextureView,
+            TextureViewDimension,
         },
         renderer::{RenderContext, RenderDevice, RenderQueue},
         texture::GpuImage,
@@ -29,6 +31,9 @@ use std::borrow::Cow;
 
 use super::types::{OctreeRenderDataResources, SvxViewSet};
 
+/// Size of the buffer holding the 4 counters of SvxFrameStats
+const FRAME_STATS_BUFFER_SIZE: u64 = 4 * std::mem::size_of::<u32>() as u64;
+
 impl FromWorld for SvxRenderPipeline {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
@@ -65,6 +70,26 @@ impl FromWorld for SvxRenderPipeline {
                     },
                     count: None,
                 },
+                BindGroupLayoutEntry {
+                    binding: 3u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4u32,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(FRAME_STATS_BUFFER_SIZE),
+                    },
+                    count: None,
+                },
             ],
         );
         let render_data_bind_group_layout = render_device.create_bind_group_layout(
@@ -171,6 +196,19 @@ impl FromWorld for SvxRenderPipeline {
 // ░░░░░   ░░░░░   ░░░░░░░░   ░░░░░    ░░░░░
 //##############################################################################
 const WORKGROUP_SIZE: u32 = 8;
+
+/// The number of workgroups covering the given output resolution
+/// Returns with None in case there is nothing to render, e.g. when the window is minimized
+pub(crate) fn workgroup_count(resolution: [u32; 2]) -> Option<[u32; 2]> {
+    if 0 == resolution[0] || 0 == resolution[1] {
+        return None;
+    }
+    Some([
+        resolution[0].div_ceil(WORKGROUP_SIZE),
+        resolution[1].div_ceil(WORKGROUP_SIZE),
+    ])
+}
+
 impl render_graph::Node for SvxRenderNode {
     fn update(&mut self, world: &mut World) {
         {
@@ -192,14 +230,25 @@ impl render_graph::Node for SvxRenderNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), render_graph::NodeRunError> {
+        let Some(workgroups) = workgroup_count(self.resolution) else {
+            return Ok(());
+        };
         if self.ready {
             let pipeline_cache = world.resource::<PipelineCache>();
             let svx_pipeline = world.resource::<SvxRenderPipeline>();
             let svx_viewset = world.resource::<SvxViewSet>();
-            let current_view = svx_viewset.views[0].lock().unwrap();
+            let mut current_view = svx_viewset.views[0].lock().unwrap();
+            if current_view.paused && !current_view.single_frame_requested {
+                return Ok(());
+            }
+            current_view.single_frame_requested = false;
             let command_encoder = render_context.command_encoder();
             let data_handler = &current_view.data_handler;
             let resources = svx_pipeline.resources.as_ref().unwrap();
+            let collect_frame_stats = current_view.frame_stats_enabled();
+            if collect_frame_stats {
+                command_encoder.clear_buffer(&resources.frame_stats_buffer, 0, None);
+            }
             {
                 let mut pass =
                     command_encoder.begin_compute_pass(&ComputePassDescriptor::default());
@@ -210,11 +259,7 @@ impl render_graph::Node for SvxRenderNode {
                     .get_compute_pipeline(svx_pipeline.update_pipeline)
                     .unwrap();
                 pass.set_pipeline(pipeline);
-                pass.dispatch_workgroups(
-                    self.resolution[0] / WORKGROUP_SIZE,
-                    self.resolution[1] / WORKGROUP_SIZE,
-                    1,
-                );
+                pass.dispatch_workgroups(workgroups[0], workgroups[1], 1);
             }
 
             command_encoder.copy_buffer_to_buffer(
@@ -238,6 +283,16 @@ impl render_graph::Node for SvxRenderNode {
                 (std::mem::size_of_val(&current_view.spyglass.node_requests[0])
                     * current_view.spyglass.node_requests.len()) as u64,
             );
+
+            if collect_frame_stats {
+                command_encoder.copy_buffer_to_buffer(
+                    &resources.frame_stats_buffer,
+                    0,
+                    &resources.readable_frame_stats_buffer,
+                    0,
+                    FRAME_STATS_BUFFER_SIZE,
+                );
+            }
         }
         Ok(())
     }
@@ -278,18 +333,46 @@ pub(crate) fn prepare_bind_groups<T, const DIM: usize>(
 ) where
     T: Default + Clone + PartialEq + VoxelData + Send + Sync + 'static,
 {
+    let tree_view = &svx_viewset.views[0].lock().unwrap();
+    let Some(color_grading_lut) = gpu_images.get(&tree_view.spyglass.color_grading_lut) else {
+        // Keep the current bindings until the color grading LUT is available
+        return;
+    };
+
+    // Rebind the spyglass group in case the color grading LUT of the view changed
+    let pipeline = &mut *pipeline;
+    if let Some(resources) = &mut pipeline.resources {
+        if resources.bound_color_grading_lut != tree_view.spyglass.color_grading_lut.id() {
+            resources.spyglass_bind_group = create_spyglass_bind_group(
+                &render_device,
+                &pipeline.spyglass_bind_group_layout,
+                &gpu_images
+                    .get(&tree_view.spyglass.output_texture)
+                    .unwrap()
+                    .texture_view,
+                &color_grading_lut.texture_view,
+                &resources.viewport_buffer,
+                &resources.node_requests_buffer,
+                &resources.frame_stats_buffer,
+            );
+            resources.bound_color_grading_lut = tree_view.spyglass.color_grading_lut.id();
+        }
+    }
+
     if pipeline.resources.is_some() && !pipeline.update_tree {
         return;
     }
 
-    let tree_view = &svx_viewset.views[0].lock().unwrap();
     let render_data = &tree_view.data_handler.render_data;
-    if let Some(resources) = &pipeline.resources {
+    if let Some(resources) = &mut pipeline.resources {
         let mut buffer = UniformBuffer::new(Vec::<u8>::new());
         buffer.write(&render_data.octree_meta).unwrap();
-        pipeline
-            .render_queue
-            .write_buffer(&resources.metadata_buffer, 0, &buffer.into_inner());
+        resources.uploaded_octree_meta = buffer.into_inner();
+        pipeline.render_queue.write_buffer(
+            &resources.octree_meta_buffer,
+            0,
+            &resources.uploaded_octree_meta,
+        );
 
         let mut buffer = StorageBuffer::new(Vec::<u8>::new());
         buffer.write(&render_data.metadata).unwrap();
@@ -351,9 +434,10 @@ pub(crate) fn prepare_bind_groups<T, const DIM: usize>(
 
         let mut buffer = UniformBuffer::new(Vec::<u8>::new());
         buffer.write(&render_data.octree_meta).unwrap();
+        let uploaded_octree_meta = buffer.into_inner();
         let octree_meta_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
             label: Some("Octree Tree Metadata Buffer"),
-            contents: &buffer.into_inner(),
+            contents: &uploaded_octree_meta,
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
 
@@ -454,11 +538,12 @@ pub(crate) fn prepare_bind_groups<T, const DIM: usize>(
         // ░░███  ░░███  ░███    ░███ ░░███     ███  ░███   ░███  ░███
         //  ░░█████████  █████   █████ ░░░███████░   ░░████████   █████
         //##############################################################################
-        let mut buffer = UniformBuffer::new([0u8; Viewport::SHADER_SIZE.get() as usize]);
+        let mut buffer = UniformBuffer::new(Vec::<u8>::new());
         buffer.write(&tree_view.spyglass.viewport).unwrap();
+        let uploaded_viewport = buffer.into_inner();
         let viewport_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
             label: Some("Octree Viewport Buffer"),
-            contents: &buffer.into_inner(),
+            contents: &uploaded_viewport,
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
 
@@ -483,35 +568,41 @@ pub(crate) fn prepare_bind_groups<T, const DIM: usize>(
             usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
         });
 
-        let output_texture_view = gpu_images
-            .get(&tree_view.spyglass.output_texture)
-            .unwrap()
-            .texture_view
-            .clone();
-        let spyglass_bind_group = render_device.create_bind_group(
-            "OctreeSpyGlass",
+        let frame_stats_buffer = render_device.create_buffer(&BufferDescriptor {
+            mapped_at_creation: false,
+            size: FRAME_STATS_BUFFER_SIZE,
+            label: Some("Octree Frame statistics Buffer"),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+        });
+
+        let readable_frame_stats_buffer = render_device.create_buffer(&BufferDescriptor {
+            mapped_at_creation: false,
+            size: FRAME_STATS_BUFFER_SIZE,
+            label: Some("Octree Frame statistics staging Buffer"),
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        });
+
+        let spyglass_bind_group = create_spyglass_bind_group(
+            &render_device,
             &pipeline.spyglass_bind_group_layout,
-            &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&output_texture_view.clone()),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: viewport_buffer.as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 2,
-                    resource: node_requests_buffer.as_entire_binding(),
-                },
-            ],
+            &gpu_images
+                .get(&tree_view.spyglass.output_texture)
+                .unwrap()
+                .texture_view,
+            &color_grading_lut.texture_view,
+            &viewport_buffer,
+            &node_requests_buffer,
+            &frame_stats_buffer,
         );
 
         pipeline.resources = Some(OctreeRenderDataResources {
             node_requests_buffer,
+            frame_stats_buffer,
             spyglass_bind_group,
+            bound_color_grading_lut: tree_view.spyglass.color_grading_lut.id(),
             tree_bind_group,
             viewport_buffer,
+            octree_meta_buffer,
             metadata_buffer,
             node_children_buffer,
             node_ocbits_buffer,
@@ -519,8 +610,70 @@ pub(crate) fn prepare_bind_groups<T, const DIM: usize>(
             color_palette_buffer,
             readable_node_requests_buffer,
             readable_metadata_buffer,
+            readable_frame_stats_buffer,
+            uploaded_viewport,
+            uploaded_octree_meta,
         });
     }
 
     pipeline.update_tree = false;
 }
+
+/// Creates the bind group for the view dependent resources of the given spyglass
+fn create_spyglass_bind_group(
+    render_device: &RenderDevice,
+    layout: &BindGroupLayout,
+    output_texture_view: &TextureView,
+    color_grading_lut_view: &TextureView,
+    viewport_buffer: &Buffer,
+    node_requests_buffer: &Buffer,
+    frame_stats_buffer: &Buffer,
+) -> BindGroup {
+    render_device.create_bind_group(
+        "OctreeSpyGlass",
+        layout,
+        &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(output_texture_view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: viewport_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: node_requests_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::TextureView(color_grading_lut_view),
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: frame_stats_buffer.as_entire_binding(),
+            },
+        ],
+    )
+}
+
+#[cfg(test)]
+mod pipeline_dispatch_tests {
+    use super::{workgroup_count, WORKGROUP_SIZE};
+
+    #[test]
+    fn test_workgroup_count_through_resize_sequence() {
+        for (resolution, expected) in [
+            ([1024, 768], Some([128, 96])),
+            ([1023, 769], Some([128, 97])),
+            ([1, 1], Some([1, 1])),
+            ([0, 0], None),
+            ([0, 768], None),
+            ([1024, 0], None),
+            ([WORKGROUP_SIZE - 1, WORKGROUP_SIZE + 1], Some([1, 2])),
+            ([1920, 1080], Some([240, 135])),
+        ] {
+            assert_eq!(workgroup_count(resolution), expected);
+        }
+    }
+}
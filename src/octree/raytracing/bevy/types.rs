@@ -1,13 +1,15 @@
-use crate::octree::{Albedo, Octree, V3cf32, VoxelData};
+pub use crate::octree::raytracing::Viewport;
+
+use crate::octree::{Albedo, Octree, V3cf32, VoxelAnimation, VoxelData};
 use bevy::{
-    asset::Handle,
+    asset::{AssetId, Handle},
     ecs::system::Resource,
     math::Vec4,
     prelude::Image,
     reflect::TypePath,
     render::{
         extract_resource::ExtractResource,
-        render_graph::RenderLabel,
+        render_graph::{InternedRenderLabel, RenderLabel},
         render_resource::{
             AsBindGroup, BindGroup, BindGroupLayout, Buffer, CachedComputePipelineId, ShaderType,
         },
@@ -32,13 +34,45 @@ pub struct OctreeMetaData {
     pub ambient_light_position: V3cf32,
     pub(crate) octree_size: u32,
     pub(crate) voxel_brick_dim: u32,
+
+    /// Strength of the random per voxel variation applied to the albedo of hits
+    /// 0 disables the variation, 1 is the strongest recommended value
+    pub albedo_variation: f32,
+
+    /// Time in seconds driving the animated effects of the shader
+    pub(crate) animation_time: f32,
+
+    /// Voxels with any of these bits set in their user data sway as foliage in the wind
+    pub(crate) foliage_mask: u32,
+
+    /// The bits of the user data storing the damage of voxels, see `DamageLayer`
+    pub(crate) damage_mask: u32,
+
+    /// The bits of the user data storing the baked light of voxels, see `LightLayer`
+    pub(crate) light_mask: u32,
+
+    /// Multiplier of the rendered colors before tonemapping
+    pub(crate) exposure: f32,
+
+    /// Gamma correction applied to the tonemapped colors
+    pub(crate) gamma: f32,
+
+    /// The tonemapping operator, as the discriminant of `Tonemapper`
+    pub(crate) tonemapper: u32,
+
+    /// 1 if the shader collects `SvxFrameStats`, 0 otherwise
+    pub(crate) collect_stats: u32,
 }
 
-#[derive(Debug, Clone, Copy, ShaderType)]
-pub struct Viewport {
-    pub origin: V3cf32,
-    pub direction: V3cf32,
-    pub w_h_fov: V3cf32,
+/// Maps the rendered colors into the displayable range
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Tonemapper {
+    /// Colors are clamped into the displayable range
+    #[default]
+    None = 0,
+    Reinhard = 1,
+    /// Filmic curve fitted to the ACES reference by Krzysztof Narkowicz
+    Aces = 2,
 }
 
 pub struct RenderBevyPlugin<T, const DIM: usize>
@@ -47,6 +81,10 @@ where
 {
     pub(crate) dummy: std::marker::PhantomData<T>,
     pub(crate) resolution: [u32; 2],
+    /// Render graph nodes the voxel pass is dispatched after
+    pub(crate) run_after: Vec<InternedRenderLabel>,
+    /// Render graph nodes the voxel pass is dispatched before
+    pub(crate) run_before: Vec<InternedRenderLabel>,
 }
 
 #[derive(Resource, Clone, TypePath, ExtractResource)]
@@ -58,6 +96,26 @@ where
     pub tree: Octree<T, DIM>,
 }
 
+/// Plays a `VoxelAnimation` in the tree of the `OctreeGPUHost`
+/// The due frame is swapped into the host without copying it, and the views stream it to the GPU
+/// through their regular upload budget
+#[derive(Resource)]
+pub struct SvxAnimation<T, const DIM: usize>
+where
+    T: Default + Clone + PartialEq + VoxelData + Send + Sync + 'static,
+{
+    pub(crate) animation: VoxelAnimation<T, DIM>,
+
+    /// The frame swapped into the host, its place in the animation holds the tree it replaced
+    pub(crate) displayed_frame: Option<usize>,
+
+    /// Seconds elapsed since the start of the animation
+    pub time: f32,
+
+    /// The time of the animation is not advanced while paused
+    pub paused: bool,
+}
+
 #[derive(Default, Resource, Clone, TypePath, ExtractResource)]
 #[type_path = "shocovox::gpu::SvxViewSet"]
 pub struct SvxViewSet {
@@ -68,6 +126,33 @@ pub struct SvxViewSet {
 pub struct OctreeGPUView {
     pub spyglass: OctreeSpyGlass,
     pub(crate) data_handler: OctreeGPUDataHandler,
+
+    /// The voxel pass is not dispatched for paused views
+    pub(crate) paused: bool,
+
+    /// A single frame is dispatched for the paused view on the next render
+    pub(crate) single_frame_requested: bool,
+
+    /// Limits of the data uploaded to the GPU in one frame
+    pub(crate) upload_budget: SvxUploadBudget,
+
+    /// The number of data requests of the view postponed in the last frame due to the upload budget
+    pub(crate) upload_backlog: usize,
+
+    /// The GPU cache of the view is emptied and streamed again from the root on the next render
+    pub(crate) cache_reset_requested: bool,
+}
+
+/// Limits of the data uploaded for a view in a single frame, so edits and camera movement
+/// are streamed to the GPU over multiple frames instead of causing frame spikes
+/// Requests above the budget are postponed, as the GPU requests missing data again in the next frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SvxUploadBudget {
+    /// The maximum number of voxel bricks uploaded in one frame, unlimited if None
+    pub max_bricks_per_frame: Option<usize>,
+
+    /// The maximum number of bytes of node and brick data uploaded in one frame, unlimited if None
+    pub max_bytes_per_frame: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
@@ -101,11 +186,14 @@ pub struct OctreeGPUDataHandler {
 pub(crate) struct OctreeRenderDataResources {
     // Spyglass group
     pub(crate) spyglass_bind_group: BindGroup,
+    pub(crate) bound_color_grading_lut: AssetId<Image>,
     pub(crate) viewport_buffer: Buffer,
     pub(crate) node_requests_buffer: Buffer,
+    pub(crate) frame_stats_buffer: Buffer,
 
     // Octree render data group
     pub(crate) tree_bind_group: BindGroup,
+    pub(crate) octree_meta_buffer: Buffer,
     pub(crate) metadata_buffer: Buffer,
     pub(crate) node_children_buffer: Buffer,
     pub(crate) node_ocbits_buffer: Buffer,
@@ -115,13 +203,40 @@ pub(crate) struct OctreeRenderDataResources {
     // Staging buffers for data reads
     pub(crate) readable_node_requests_buffer: Buffer,
     pub(crate) readable_metadata_buffer: Buffer,
+    pub(crate) readable_frame_stats_buffer: Buffer,
+
+    // Contents of the uniform buffers last uploaded, to skip writes without changes
+    pub(crate) uploaded_viewport: Vec<u8>,
+    pub(crate) uploaded_octree_meta: Vec<u8>,
 }
 
 #[derive(Clone)]
 pub struct OctreeSpyGlass {
     pub output_texture: Handle<Image>,
     pub viewport: Viewport,
+
+    /// 3D texture mapping each rendered color to its graded color, applied after tonemapping
+    /// An identity mapping is used by default, which leaves the colors unchanged
+    pub color_grading_lut: Handle<Image>,
     pub(crate) node_requests: Vec<u32>,
+    pub(crate) frame_stats: SvxFrameStats,
+}
+
+/// Counters accumulated by the GPU while rendering the last frame of a view
+/// Only collected for views with frame statistics enabled, see `OctreeGPUView::set_frame_stats_enabled`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SvxFrameStats {
+    /// The number of primary rays cast
+    pub rays: u32,
+
+    /// The number of steps taken through the nodes of the octree by all rays
+    pub node_steps: u32,
+
+    /// The number of steps taken inside voxel bricks by all rays
+    pub brick_steps: u32,
+
+    /// The number of rays not hitting any voxel
+    pub misses: u32,
 }
 
 #[derive(Clone, TypePath)]
@@ -203,8 +318,9 @@ pub(crate) struct SvxRenderPipeline {
     pub(crate) resources: Option<OctreeRenderDataResources>,
 }
 
+/// Render graph label of the voxel raytracing pass
 #[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
-pub(crate) struct SvxLabel;
+pub struct SvxLabel;
 
 pub(crate) struct SvxRenderNode {
     pub(crate) ready: bool,
@@ -0,0 +1,243 @@
+use crate::{
+    octree::{types::OctreeError, Octree, V3c, VoxelData},
+    spatial::raytracing::{Ray, VoxelFace},
+};
+
+/// A light source considered while baking the lighting of a tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BakeLight {
+    /// Light arriving from infinitely far away, e.g. the sun
+    /// * `direction` - the direction the light travels in
+    Directional { direction: V3c<f32>, intensity: f32 },
+    /// Light emitted from a point, its intensity falls off with the square of the distance
+    Point { position: V3c<f32>, intensity: f32 },
+    /// Light arriving uniformly from every direction, e.g. an overcast sky
+    Sky { intensity: f32 },
+}
+
+/// Describes the bits of the user data storing the baked light of a voxel
+/// The remaining bits of the user data are kept intact, so other attributes can be stored next to it
+/// The light is stored inverted, so voxels without baked light, e.g. inside solid bricks
+/// whose user data is not uploaded to the GPU, are displayed fully lit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LightLayer {
+    shift: u32,
+    bits: u32,
+}
+
+impl LightLayer {
+    /// Creates a layer stored in the given bits of the user data
+    /// * `shift` - the index of the lowest bit of the layer
+    /// * `bits` - the number of bits in the layer, at least 1, reaching at most the highest bit of the user data
+    pub fn new(shift: u32, bits: u32) -> Self {
+        debug_assert!(0 < bits && shift + bits <= u32::BITS);
+        Self { shift, bits }
+    }
+
+    /// The bits of the user data the layer is stored in, to be used in the renderer
+    pub fn mask(&self) -> u32 {
+        self.max_level() << self.shift
+    }
+
+    /// The number of distinct levels of light the layer can store above 0
+    pub fn max_level(&self) -> u32 {
+        u32::MAX >> (u32::BITS - self.bits)
+    }
+
+    /// Provides the light stored in the given user data, in range 0..=1
+    pub fn irradiance_of(&self, user_data: u32) -> f32 {
+        let darkness = (user_data & self.mask()) >> self.shift;
+        1. - darkness as f32 / self.max_level() as f32
+    }
+
+    /// Provides the given user data with its light replaced, clamped to range 0..=1
+    pub fn with_irradiance(&self, user_data: u32, irradiance: f32) -> u32 {
+        let darkness = ((1. - irradiance.clamp(0., 1.)) * self.max_level() as f32).round() as u32;
+        (user_data & !self.mask()) | (darkness << self.shift)
+    }
+}
+
+/// Offset of the shadow ray origins from the surface, to not hit the voxel they start from
+const SHADOW_RAY_OFFSET: f32 = 0.001;
+
+/// The angle between consecutive samples on the hemisphere, in radians
+pub(crate) const GOLDEN_ANGLE: f32 = 2.399_963;
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Calculates the light arriving at each voxel on the surface of the contents of the tree,
+    /// and stores it in the user data of the voxel, to be displayed by the renderer
+    /// The irradiance of a voxel is the average of the irradiance of its faces not covered
+    /// by other voxels. Lights are blocked by the voxels of the tree, light bouncing
+    /// off of surfaces is not simulated.
+    /// The type of the voxels needs to store their user data for the light to be kept
+    /// * `layer` - the bits of the user data storing the light
+    /// * `lights` - the light sources to consider
+    /// * `samples` - the number of rays to sample the sky lights with on each face
+    pub fn bake_lighting(
+        &mut self,
+        layer: &LightLayer,
+        lights: &[BakeLight],
+        samples: u32,
+    ) -> Result<(), OctreeError> {
+        // Only the voxels on the boundary of a filled cell may have uncovered faces
+        let mut surface_voxels = Vec::new();
+        self.for_each_filled_cell_in(
+            &V3c::unit(0.),
+            &V3c::unit(self.octree_size as f32),
+            &mut |bounds, voxel| {
+                let min = V3c::<u32>::from(bounds.min_position);
+                let max = min + V3c::unit(bounds.size as u32 - 1);
+                for x in min.x..=max.x {
+                    for y in min.y..=max.y {
+                        let on_boundary = x == min.x || x == max.x || y == min.y || y == max.y;
+                        let z_step = if on_boundary { 1 } else { max.z - min.z };
+                        for z in (min.z..=max.z).step_by(z_step as usize) {
+                            surface_voxels.push((V3c::new(x, y, z), *voxel));
+                        }
+                    }
+                }
+            },
+        );
+
+        let mut lit_voxels = Vec::new();
+        for (position, voxel) in surface_voxels {
+            let mut exposed_faces = 0;
+            let mut voxel_irradiance = 0.;
+            for face in [
+                VoxelFace::PositiveX,
+                VoxelFace::NegativeX,
+                VoxelFace::PositiveY,
+                VoxelFace::NegativeY,
+                VoxelFace::PositiveZ,
+                VoxelFace::NegativeZ,
+            ] {
+                let normal = face.normal();
+                let neighbor = V3c::<i32>::from(position) + V3c::<i32>::from(normal);
+                if self.is_filled_at(&neighbor) {
+                    continue;
+                }
+                exposed_faces += 1;
+                let face_center = V3c::<f32>::from(position) + V3c::unit(0.5) + normal * 0.5;
+                voxel_irradiance += self.irradiance_at(&face_center, &normal, lights, samples);
+            }
+            if 0 < exposed_faces {
+                let user_data = layer
+                    .with_irradiance(voxel.user_data(), voxel_irradiance / exposed_faces as f32);
+                if user_data != voxel.user_data() {
+                    lit_voxels.push((position, T::new(voxel.albedo(), user_data)));
+                }
+            }
+        }
+        self.insert_batch(lit_voxels)
+    }
+
+    /// True if the given position is inside the tree and contains a voxel
+    fn is_filled_at(&self, position: &V3c<i32>) -> bool {
+        let size = self.octree_size as i32;
+        if position.x < 0
+            || position.y < 0
+            || position.z < 0
+            || position.x >= size
+            || position.y >= size
+            || position.z >= size
+        {
+            return false;
+        }
+        self.get(&V3c::<u32>::from(*position)).is_some()
+    }
+
+    /// True if there are voxels along the ray closer, than the given distance
+    pub(crate) fn is_occluded(&self, ray: &Ray, distance: f32) -> bool {
+        self.get_by_ray(ray)
+            .is_some_and(|(_, impact_point, _)| (impact_point - ray.origin).length() < distance)
+    }
+
+    /// The light arriving at the given surface point
+    fn irradiance_at(
+        &self,
+        point: &V3c<f32>,
+        normal: &V3c<f32>,
+        lights: &[BakeLight],
+        samples: u32,
+    ) -> f32 {
+        let origin = *point + *normal * SHADOW_RAY_OFFSET;
+        let mut irradiance = 0.;
+        for light in lights {
+            match light {
+                BakeLight::Directional {
+                    direction,
+                    intensity,
+                } => {
+                    let to_light = (*direction * -1.).normalized();
+                    let cosine = normal.dot(&to_light);
+                    let ray = Ray {
+                        origin,
+                        direction: to_light,
+                    };
+                    if 0. < cosine && !self.is_occluded(&ray, f32::MAX) {
+                        irradiance += intensity * cosine;
+                    }
+                }
+                BakeLight::Point {
+                    position,
+                    intensity,
+                } => {
+                    let to_light = *position - origin;
+                    let distance = to_light.length();
+                    if 0. == distance {
+                        continue;
+                    }
+                    let to_light = to_light / distance;
+                    let cosine = normal.dot(&to_light);
+                    let ray = Ray {
+                        origin,
+                        direction: to_light,
+                    };
+                    if 0. < cosine && !self.is_occluded(&ray, distance) {
+                        irradiance += intensity * cosine / (distance * distance).max(1.);
+                    }
+                }
+                BakeLight::Sky { intensity } => {
+                    if 0 == samples {
+                        continue;
+                    }
+                    let visible_samples = hemisphere_samples(normal, samples)
+                        .filter(|direction| {
+                            let ray = Ray {
+                                origin,
+                                direction: *direction,
+                            };
+                            !self.is_occluded(&ray, f32::MAX)
+                        })
+                        .count();
+                    irradiance += intensity * visible_samples as f32 / samples as f32;
+                }
+            }
+        }
+        irradiance
+    }
+}
+
+/// Provides evenly distributed, cosine weighted directions on the hemisphere around the normal
+fn hemisphere_samples(normal: &V3c<f32>, samples: u32) -> impl Iterator<Item = V3c<f32>> {
+    let normal = *normal;
+    let tangent = if 0.5 < normal.x.abs() {
+        V3c::new(0., 1., 0.)
+    } else {
+        V3c::new(1., 0., 0.)
+    };
+    let bitangent = normal.cross(tangent).normalized();
+    let tangent = bitangent.cross(normal);
+    (0..samples).map(move |i| {
+        let height = (i as f32 + 0.5) / samples as f32;
+        let radius = height.sqrt();
+        let angle = i as f32 * GOLDEN_ANGLE;
+        (tangent * (radius * angle.cos())
+            + bitangent * (radius * angle.sin())
+            + normal * (1. - height).sqrt())
+        .normalized()
+    })
+}
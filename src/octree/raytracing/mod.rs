@@ -1,13 +1,19 @@
+mod lighting;
 pub mod raytracing_on_cpu;
 mod tests;
+mod viewport;
+mod visibility;
 
 #[cfg(feature = "bevy_wgpu")]
 pub mod bevy;
 
-pub use crate::spatial::raytracing::Ray;
+pub use crate::spatial::raytracing::{Ray, VoxelFace};
+pub use lighting::{BakeLight, LightLayer};
+pub use raytracing_on_cpu::RayHit;
+pub use viewport::Viewport;
 
 #[cfg(feature = "bevy_wgpu")]
 pub use bevy::types::{
-    OctreeGPUHost, OctreeGPUView, OctreeRenderData, OctreeSpyGlass, RenderBevyPlugin, SvxViewSet,
-    Viewport,
+    OctreeGPUHost, OctreeGPUView, OctreeRenderData, OctreeSpyGlass, RenderBevyPlugin, SvxLabel,
+    SvxViewSet, Tonemapper,
 };
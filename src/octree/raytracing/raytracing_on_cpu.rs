@@ -9,10 +9,32 @@ use crate::{
             RAY_TO_NODE_OCCUPANCY_BITMASK_LUT,
         },
         math::{hash_direction, hash_region, BITMAP_DIMENSION},
-        raytracing::{cube_impact_normal, step_octant, Ray, FLOAT_ERROR_TOLERANCE},
+        raytracing::{
+            cube_impact_normal, float_error_tolerance_for, step_octant, Ray, VoxelFace,
+            FLOAT_ERROR_TOLERANCE,
+        },
     },
 };
 
+/// Detailed information about the intersection of a ray and the voxel it hit first
+#[derive(Debug, Clone)]
+pub struct RayHit<'a, T> {
+    /// The data of the voxel hit by the ray
+    pub data: &'a T,
+    /// The position of the hit voxel
+    pub voxel_position: V3c<u32>,
+    /// The point where the ray hit the voxel
+    pub impact_point: V3c<f32>,
+    /// The distance along the ray from its origin to the impact point
+    pub impact_distance: f32,
+    /// The normal of the surface at the impact point
+    pub impact_normal: V3c<f32>,
+    /// The side of the voxel which was hit
+    pub face: VoxelFace,
+    /// The coordinates inside the hit face, each component in range 0..=1
+    pub face_uv: [f32; 2],
+}
+
 #[derive(Debug)]
 pub(crate) struct NodeStack<T, const SIZE: usize = 4> {
     data: [T; SIZE],
@@ -107,11 +129,13 @@ where
     /// * `ray_current_distance` - The distance the ray iteration is currently at
     /// * `current_bounds` - The cell which boundaries the current ray iteration intersects
     /// * `ray_scale_factors` - Pre-computed dda values for the ray
+    /// * `tolerance` - The float error tolerance to use when comparing distances
     pub(crate) fn dda_step_to_next_sibling(
         ray: &Ray,
         ray_current_distance: &mut f32,
         current_bounds: &Cube,
         ray_scale_factors: &V3c<f32>,
+        tolerance: f32,
     ) -> V3c<f32> {
         let p = ray.point_at(*ray_current_distance);
         let diff_from_min = p - current_bounds.min_position;
@@ -132,17 +156,17 @@ where
         *ray_current_distance = d_x.min(d_y).min(d_z);
 
         V3c::new(
-            if (*ray_current_distance - d_x).abs() < FLOAT_ERROR_TOLERANCE {
+            if (*ray_current_distance - d_x).abs() < tolerance {
                 signum_vec.x
             } else {
                 0.
             },
-            if (*ray_current_distance - d_y).abs() < FLOAT_ERROR_TOLERANCE {
+            if (*ray_current_distance - d_y).abs() < tolerance {
                 signum_vec.y
             } else {
                 0.
             },
-            if (*ray_current_distance - d_z).abs() < FLOAT_ERROR_TOLERANCE {
+            if (*ray_current_distance - d_z).abs() < tolerance {
                 signum_vec.z
             } else {
                 0.
@@ -157,6 +181,7 @@ where
         brick: &[[[T; DIM]; DIM]; DIM],
         brick_bounds: &Cube,
         ray_scale_factors: &V3c<f32>,
+        tolerance: f32,
     ) -> Option<V3c<usize>> {
         // Decide the starting index inside the brick
         let position_in_brick = (ray.point_at(*ray_current_distance) - brick_bounds.min_position)
@@ -200,6 +225,7 @@ where
                 ray_current_distance,
                 &current_bounds,
                 ray_scale_factors,
+                tolerance,
             );
             current_bounds.min_position += step * brick_unit;
             current_index += V3c::<i32>::from(step);
@@ -209,12 +235,12 @@ where
                 let relative_point =
                     ray.point_at(*ray_current_distance) - current_bounds.min_position;
                 debug_assert!(
-                    (relative_point.x < FLOAT_ERROR_TOLERANCE
-                        || (relative_point.x - current_bounds.size) < FLOAT_ERROR_TOLERANCE)
-                        || (relative_point.y < FLOAT_ERROR_TOLERANCE
-                            || (relative_point.y - current_bounds.size) < FLOAT_ERROR_TOLERANCE)
-                        || (relative_point.z < FLOAT_ERROR_TOLERANCE
-                            || (relative_point.z - current_bounds.size) < FLOAT_ERROR_TOLERANCE)
+                    (relative_point.x < tolerance
+                        || (relative_point.x - current_bounds.size) < tolerance)
+                        || (relative_point.y < tolerance
+                            || (relative_point.y - current_bounds.size) < tolerance)
+                        || (relative_point.z < tolerance
+                            || (relative_point.z - current_bounds.size) < tolerance)
                 );
             }
         }
@@ -227,6 +253,7 @@ where
         brick: &'a BrickData<T, DIM>,
         brick_bounds: &Cube,
         ray_scale_factors: &V3c<f32>,
+        tolerance: f32,
     ) -> Option<(&'a T, V3c<f32>, V3c<f32>)> {
         match brick {
             BrickData::Empty => {
@@ -248,6 +275,7 @@ where
                     brick,
                     brick_bounds,
                     ray_scale_factors,
+                    tolerance,
                 ) {
                     let hit_bounds = Cube {
                         size: brick_bounds.size / DIM as f32,
@@ -274,6 +302,7 @@ where
         // Pre-calculated optimization variables
         let ray_scale_factors = Self::get_dda_scale_factors(ray);
         let direction_lut_index = hash_direction(&ray.direction) as usize;
+        let tolerance = float_error_tolerance_for(self.octree_size as f32);
 
         let mut node_stack: NodeStack<u32> = NodeStack::default();
         let mut current_bounds = Cube::root_bounds(self.octree_size as f32);
@@ -318,6 +347,7 @@ where
                                 brick,
                                 &current_bounds,
                                 &ray_scale_factors,
+                                tolerance,
                             ) {
                                 return Some(hit);
                             }
@@ -334,6 +364,7 @@ where
                                 &bricks[target_octant as usize],
                                 &current_bounds.child_bounds_for(target_octant),
                                 &ray_scale_factors,
+                                tolerance,
                             ) {
                                 return Some(hit);
                             }
@@ -371,6 +402,7 @@ where
                         &mut ray_current_distance,
                         &current_bounds,
                         &ray_scale_factors,
+                        tolerance,
                     );
                     if let Some(parent) = node_stack.last_mut() {
                         current_node_key = *parent as usize;
@@ -422,6 +454,7 @@ where
                             &mut ray_current_distance,
                             &target_bounds,
                             &ray_scale_factors,
+                            tolerance,
                         );
                         target_octant = step_octant(target_octant, step_vec);
                         if OOB_OCTANT != target_octant {
@@ -486,4 +519,49 @@ where
         }
         None
     }
+    /// provides detailed information of the first voxel the ray hits, should there be any
+    pub fn cast_ray(&self, ray: &Ray) -> Option<RayHit<T>> {
+        let (data, impact_point, impact_normal) = self.get_by_ray(ray)?;
+        let face = VoxelFace::from_normal(&impact_normal);
+
+        // The hit voxel is directly behind the impact point, opposite to the face normal
+        let inside_point = impact_point - face.normal() * 0.5;
+        let max_coordinate = (self.octree_size - 1) as f32;
+        let voxel_position = V3c::new(
+            inside_point.x.floor().clamp(0., max_coordinate),
+            inside_point.y.floor().clamp(0., max_coordinate),
+            inside_point.z.floor().clamp(0., max_coordinate),
+        );
+        let position_in_voxel = impact_point - voxel_position;
+        let face_uv = face.uv_of(&position_in_voxel);
+        Some(RayHit {
+            data,
+            voxel_position: voxel_position.into(),
+            impact_point,
+            impact_distance: (impact_point - ray.origin).length(),
+            impact_normal,
+            face,
+            face_uv: [face_uv[0].clamp(0., 1.), face_uv[1].clamp(0., 1.)],
+        })
+    }
+    /// Provides the position of the voxel the ray hits, and the empty position in front of the hit face
+    /// Returns with None if there's no hit, or the adjacent position is outside the tree or occupied
+    pub fn placement_target(&self, ray: &Ray) -> Option<(V3c<u32>, V3c<u32>)> {
+        let hit = self.cast_ray(ray)?;
+        let adjacent = V3c::<i32>::from(hit.voxel_position) + V3c::<i32>::from(hit.face.normal());
+        if adjacent.x < 0
+            || adjacent.y < 0
+            || adjacent.z < 0
+            || adjacent.x >= self.octree_size as i32
+            || adjacent.y >= self.octree_size as i32
+            || adjacent.z >= self.octree_size as i32
+        {
+            return None;
+        }
+        let adjacent = V3c::<u32>::from(adjacent);
+        if self.get(&adjacent).is_some() {
+            return None;
+        }
+        Some((hit.voxel_position, adjacent))
+    }
 }
@@ -70,7 +70,7 @@ mod wgpu_tests {
 #[cfg(test)]
 mod octree_raytracing_tests {
     use crate::octree::{raytracing::tests::get_step_to_next_sibling, Albedo, Cube, Octree, V3c};
-    use crate::spatial::raytracing::{Ray, FLOAT_ERROR_TOLERANCE};
+    use crate::spatial::raytracing::{Ray, VoxelFace, FLOAT_ERROR_TOLERANCE};
 
     use rand::{rngs::ThreadRng, Rng};
 
@@ -108,7 +108,8 @@ mod octree_raytracing_tests {
                             &ray,
                             &mut current_d,
                             &cube,
-                            &scale_factors
+                            &scale_factors,
+                            FLOAT_ERROR_TOLERANCE,
                         ))
                     .length()
             );
@@ -745,6 +746,328 @@ mod octree_raytracing_tests {
         let hit = tree.get_by_ray(&ray);
         assert!(hit.is_some());
     }
+
+    /// Casts a ray from outside the tree onto the voxel at its far corner, from a distance of the tree size
+    /// * Returns with the distance between the impact point and where the ray enters the voxel,
+    /// relative to the size of the tree
+    fn far_corner_hit_relative_error(tree_size: u32) -> f64 {
+        let mut tree = Octree::<Albedo>::new(tree_size).ok().unwrap();
+        let corner = tree_size - 1;
+        tree.insert(&V3c::new(corner, corner, corner), 0xFF0000FF.into())
+            .ok()
+            .unwrap();
+
+        let target = V3c::unit(tree_size as f32 - 0.5);
+        let direction = V3c::new(-1., -1.1, -0.9).normalized();
+        let ray = Ray {
+            origin: target - direction * tree_size as f32,
+            direction,
+        };
+        let (data, impact_point, _normal) = tree.get_by_ray(&ray).unwrap();
+        assert!(*data == 0xFF0000FF.into());
+
+        // The ray enters the voxel through the last of its faces it crosses
+        let origin = [ray.origin.x, ray.origin.y, ray.origin.z].map(|c| c as f64);
+        let direction = [direction.x, direction.y, direction.z].map(|c| c as f64);
+        let entry_distance = (0..3)
+            .map(|axis| (tree_size as f64 - origin[axis]) / direction[axis])
+            .fold(f64::MIN, f64::max);
+        let impact_point = [impact_point.x, impact_point.y, impact_point.z];
+        (0..3)
+            .map(|axis| {
+                let expected = origin[axis] + direction[axis] * entry_distance;
+                (impact_point[axis] as f64 - expected).abs() / tree_size as f64
+            })
+            .fold(0., f64::max)
+    }
+
+    #[test]
+    fn test_cast_ray_face_and_uv() {
+        let mut tree = Octree::<Albedo>::new(8).ok().unwrap();
+        tree.insert(&V3c::new(3, 3, 3), 0xFF0000FF.into())
+            .ok()
+            .unwrap();
+
+        let target = V3c::new(3.5, 3.25, 3.);
+        let direction = V3c::new(0.1, 0.05, 1.).normalized();
+        let ray = Ray {
+            origin: target - direction * 5.,
+            direction,
+        };
+        let hit = tree.cast_ray(&ray).unwrap();
+        assert!(*hit.data == 0xFF0000FF.into());
+        assert!(hit.voxel_position == V3c::new(3, 3, 3));
+        assert!(hit.face == VoxelFace::NegativeZ);
+        assert!((hit.impact_distance - 5.).abs() < 0.01);
+        assert!((hit.face_uv[0] - 0.5).abs() < 0.01);
+        assert!((hit.face_uv[1] - 0.25).abs() < 0.01);
+
+        let target = V3c::new(4., 3.5, 3.75);
+        let direction = V3c::new(-1., 0.05, -0.1).normalized();
+        let ray = Ray {
+            origin: target - direction * 5.,
+            direction,
+        };
+        let hit = tree.cast_ray(&ray).unwrap();
+        assert!(hit.voxel_position == V3c::new(3, 3, 3));
+        assert!(hit.face == VoxelFace::PositiveX);
+        assert!((hit.face_uv[0] - 0.75).abs() < 0.01);
+        assert!((hit.face_uv[1] - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_placement_target() {
+        let mut tree = Octree::<Albedo>::new(8).ok().unwrap();
+        tree.insert(&V3c::new(3, 0, 3), 0xFF0000FF.into())
+            .ok()
+            .unwrap();
+
+        // Looking down onto the top of the voxel
+        let target = V3c::new(3.5, 1., 3.5);
+        let direction = V3c::new(0.1, -1., 0.05).normalized();
+        let ray = Ray {
+            origin: target - direction * 10.,
+            direction,
+        };
+        assert!(tree.placement_target(&ray) == Some((V3c::new(3, 0, 3), V3c::new(3, 1, 3))));
+
+        // Looking up at the bottom of the voxel, the adjacent position is outside the tree
+        let target = V3c::new(3.5, 0., 3.5);
+        let direction = V3c::new(0.1, 1., 0.05).normalized();
+        let ray = Ray {
+            origin: target - direction * 10.,
+            direction,
+        };
+        assert!(tree.placement_target(&ray).is_none());
+    }
+
+    #[test]
+    fn test_get_by_ray_relative_accuracy_across_tree_sizes() {
+        // The same ray, scaled to the smallest and a huge tree
+        let smallest_tree_error = far_corner_hit_relative_error(2);
+        let huge_tree_error = far_corner_hit_relative_error(65536);
+        assert!(
+            smallest_tree_error < 0.0001 && huge_tree_error < 0.0001,
+            "Relative errors of impact points: {smallest_tree_error} and {huge_tree_error}"
+        );
+        assert!(
+            (huge_tree_error - smallest_tree_error).abs() < 0.00001,
+            "Relative errors of impact points: {smallest_tree_error} and {huge_tree_error}"
+        );
+    }
+
+    #[test]
+    fn test_bake_lighting() {
+        use crate::octree::{
+            raytracing::{BakeLight, LightLayer},
+            VoxelData,
+        };
+
+        /// Voxel keeping its user data, so light can be stored in it
+        #[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+        struct Block {
+            albedo: Albedo,
+            user_data: u32,
+        }
+        impl VoxelData for Block {
+            fn new(albedo: Albedo, user_data: u32) -> Self {
+                Self { albedo, user_data }
+            }
+            fn albedo(&self) -> Albedo {
+                self.albedo
+            }
+            fn user_data(&self) -> u32 {
+                self.user_data
+            }
+            fn clear(&mut self) {
+                *self = Self::default();
+            }
+        }
+
+        // 8 bits of light above a material id
+        let layer = LightLayer::new(8, 8);
+        assert!(layer.mask() == 0xFF00);
+        assert!(layer.irradiance_of(0x12) == 1.);
+        assert!(layer.with_irradiance(0x12, 0.) == 0xFF12);
+        assert!(layer.with_irradiance(0x12, 2.) == 0x12);
+        let tolerance = 1. / layer.max_level() as f32;
+
+        let red = Block::new(0xFF0000FF.into(), 0x12);
+        let mut tree = Octree::<Block>::new(8).ok().unwrap();
+        tree.insert(&V3c::new(2, 2, 2), red).ok().unwrap();
+        let light_at = |tree: &Octree<Block>, position: V3c<u32>| {
+            let voxel = tree.get(&position).unwrap();
+            assert!(0x12 == voxel.user_data() & !layer.mask());
+            layer.irradiance_of(voxel.user_data())
+        };
+
+        // A lonely voxel sees the whole sky on each face
+        tree.bake_lighting(&layer, &[BakeLight::Sky { intensity: 0.5 }], 16)
+            .ok()
+            .unwrap();
+        assert!((light_at(&tree, V3c::new(2, 2, 2)) - 0.5).abs() < tolerance);
+
+        let sun = [BakeLight::Directional {
+            direction: V3c::new(0.1, -1., 0.1).normalized(),
+            intensity: 1.,
+        }];
+        tree.bake_lighting(&layer, &sun, 0).ok().unwrap();
+        let unshadowed = light_at(&tree, V3c::new(2, 2, 2));
+        assert!(0. < unshadowed);
+
+        // A voxel above casts a shadow onto the top face of the one below
+        tree.insert(&V3c::new(2, 5, 2), red).ok().unwrap();
+        tree.bake_lighting(&layer, &sun, 0).ok().unwrap();
+        assert!(light_at(&tree, V3c::new(2, 2, 2)) < unshadowed);
+        assert!((light_at(&tree, V3c::new(2, 5, 2)) - unshadowed).abs() < tolerance);
+
+        // Only the surface of a solid block is lit, its inside keeps its user data
+        let mut tree = Octree::<Block>::new(8).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(0, 0, 0), 4, red).ok().unwrap();
+        tree.bake_lighting(&layer, &[BakeLight::Sky { intensity: 0.5 }], 16)
+            .ok()
+            .unwrap();
+        assert!(tree.get(&V3c::new(1, 1, 1)).unwrap().user_data() == 0x12);
+        assert!((light_at(&tree, V3c::new(3, 3, 3)) - 0.5).abs() < tolerance);
+        assert!((light_at(&tree, V3c::new(1, 3, 1)) - 0.5).abs() < tolerance);
+        assert!((light_at(&tree, V3c::new(0, 1, 2)) - 0.5).abs() < tolerance);
+    }
+
+    #[test]
+    fn test_visible_set() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo>::new(16).ok().unwrap();
+
+        // A wall in front of the origin hides the voxel behind it
+        for y in 0..16 {
+            for z in 0..16 {
+                tree.insert(&V3c::new(10, y, z), red).ok().unwrap();
+            }
+        }
+        tree.insert(&V3c::new(14, 8, 8), red).ok().unwrap();
+        tree.insert(&V3c::new(0, 8, 8), red).ok().unwrap();
+
+        let origin = V3c::new(6.5, 8.5, 8.5);
+        let visible = tree.visible_set(&origin, 100., 64);
+        assert!(visible.contains(&V3c::new(10, 8, 8)));
+        assert!(visible.contains(&V3c::new(0, 8, 8)));
+        assert!(!visible.contains(&V3c::new(14, 8, 8)));
+
+        // Voxels further than the maximum distance are not visible
+        let visible = tree.visible_set(&origin, 4., 64);
+        assert!(!visible.contains(&V3c::new(0, 8, 8)));
+        assert!(visible.contains(&V3c::new(10, 8, 8)));
+    }
+
+    #[test]
+    fn test_occlusion_factor() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo>::new(16).ok().unwrap();
+        let (from, to) = (V3c::new(2.5, 8.5, 8.5), V3c::new(13.5, 8.5, 8.5));
+        assert!(tree.occlusion_factor(&from, &to, 16) == 0.);
+
+        // A wall between the two points blocks every ray
+        for y in 0..16 {
+            for z in 0..16 {
+                tree.insert(&V3c::new(8, y, z), red).ok().unwrap();
+            }
+        }
+        assert!(tree.occlusion_factor(&from, &to, 16) == 1.);
+        assert!(tree.occlusion_factor(&from, &to, 0) == 1.);
+
+        // A voxel at the edge of the target only blocks some of the rays
+        let mut tree = Octree::<Albedo>::new(16).ok().unwrap();
+        tree.insert(&V3c::new(12, 9, 8), red).ok().unwrap();
+        let factor = tree.occlusion_factor(&from, &V3c::new(13.5, 9., 8.5), 16);
+        assert!(0. < factor && factor < 1.);
+    }
+
+    #[test]
+    fn test_viewport_rays() {
+        use crate::octree::raytracing::Viewport;
+        let mut viewport = Viewport::default();
+        viewport.look_at(
+            V3c::new(0., 0., 10.),
+            V3c::new(0., 0., 0.),
+            V3c::new(0., 2., 0.),
+        );
+        assert!(viewport.up == V3c::new(0., 1., 0.));
+
+        let center = viewport.ray_for((0.5, 0.5));
+        assert!(center.origin == V3c::new(0., 0., 10.));
+        assert!((center.direction - V3c::new(0., 0., -1.)).length() < FLOAT_ERROR_TOLERANCE);
+
+        // The top left corner of the view is up and to the left of the direction
+        let top_left = viewport.ray_for((0., 0.));
+        assert!(0. < top_left.direction.y);
+        let right = viewport.up.cross(viewport.direction);
+        assert!(top_left.direction.dot(&right) < 0.);
+
+        // Pixel jitter offsets the rays inside their pixels
+        assert!(viewport.ray_for_pixel([0, 0], [2, 2]).direction == top_left.direction);
+        viewport.set_pixel_jitter([0.5, 0.5]);
+        assert!(viewport.pixel_jitter() == [0.5, 0.5]);
+        let jittered = viewport.ray_for_pixel([0, 0], [2, 2]);
+        assert!(jittered.direction == viewport.ray_for((0.25, 0.25)).direction);
+
+        let fov = Viewport::fov_from_focal_length(50., 36., viewport.w_h_fov.x);
+        assert!((fov - 10. * 50. / 36.).abs() < FLOAT_ERROR_TOLERANCE);
+
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+        tree.insert(&V3c::new(0, 0, 0), red).ok().unwrap();
+        viewport.look_at(
+            V3c::new(0.5, 0.5, 10.),
+            V3c::new(0.5, 0.5, 0.),
+            V3c::new(0., 1., 0.),
+        );
+        assert!(tree.get_by_ray(&viewport.ray_for((0.5, 0.5))).is_some());
+    }
+
+    #[test]
+    fn test_viewport_look_at_orthogonalizes() {
+        use crate::octree::raytracing::Viewport;
+        let mut viewport = Viewport::default();
+
+        // Camera pitched down towards the target, with the world up direction given
+        viewport.look_at(
+            V3c::new(0., 10., 10.),
+            V3c::new(0., 0., 0.),
+            V3c::new(0., 1., 0.),
+        );
+        let expected_direction = V3c::new(0., -1., -1.).normalized();
+        assert!((viewport.direction - expected_direction).length() < FLOAT_ERROR_TOLERANCE);
+        assert!(viewport.up.dot(&viewport.direction).abs() < FLOAT_ERROR_TOLERANCE);
+        assert!((viewport.up.length() - 1.).abs() < FLOAT_ERROR_TOLERANCE);
+        assert!(
+            (viewport.up - V3c::new(0., 1., -1.).normalized()).length() < FLOAT_ERROR_TOLERANCE
+        );
+
+        // The center of the view is still the target
+        let center = viewport.ray_for((0.5, 0.5));
+        assert!((center.direction - expected_direction).length() < FLOAT_ERROR_TOLERANCE);
+
+        // Corners of the view are symmetric around the view direction
+        let top_left = viewport.ray_for((0., 0.));
+        let bottom_right = viewport.ray_for((1., 1.));
+        assert!(
+            (top_left.direction.dot(&expected_direction)
+                - bottom_right.direction.dot(&expected_direction))
+            .abs()
+                < FLOAT_ERROR_TOLERANCE
+        );
+
+        // Looking straight down along the up direction still provides a valid view
+        viewport.look_at(
+            V3c::new(0., 10., 0.),
+            V3c::new(0., 0., 0.),
+            V3c::new(0., 1., 0.),
+        );
+        assert!((viewport.direction - V3c::new(0., -1., 0.)).length() < FLOAT_ERROR_TOLERANCE);
+        assert!(viewport.up.dot(&viewport.direction).abs() < FLOAT_ERROR_TOLERANCE);
+        assert!((viewport.up.length() - 1.).abs() < FLOAT_ERROR_TOLERANCE);
+        assert!(viewport.ray_for((0., 0.)).direction.x.is_finite());
+    }
 }
 
 #[cfg(test)]
@@ -844,3 +1167,147 @@ mod node_stack_tests {
         );
     }
 }
+
+/// Renders canonical scenes with the CPU raytracer and compares them to stored reference images
+/// Missing references are created on the first run, set SHOCOVOX_BLESS to overwrite existing ones
+#[cfg(all(test, feature = "raytracing", feature = "dot_vox_support"))]
+mod reference_image_tests {
+    use crate::octree::{Albedo, Octree, V3c};
+    use crate::spatial::raytracing::Ray;
+    use image::{ImageBuffer, Rgb, RgbImage};
+
+    const RESOLUTION: u32 = 64;
+
+    /// The largest difference in any color channel for a pixel to be considered unchanged
+    const CHANNEL_TOLERANCE: u8 = 8;
+
+    /// The ratio of changed pixels allowed before the render is considered a regression
+    const PIXEL_TOLERANCE: f32 = 0.01;
+
+    /// Renders the tree from the given camera position looking at the center of the tree
+    /// with the same shading the cpu_render example uses
+    fn render<const DIM: usize>(tree: &Octree<Albedo, DIM>, origin: V3c<f32>) -> RgbImage {
+        let center = V3c::unit(tree.get_size() as f32 / 2.);
+        let direction = (center - origin).normalized();
+        let up_direction = V3c::new(0., 1., 0.);
+        let right_direction = up_direction.cross(direction).normalized();
+        let (viewport_width, viewport_height, viewport_fov) = (4., 4., 3.);
+        let viewport_bottom_left = origin + (direction * viewport_fov)
+            - (up_direction * (viewport_height / 2.))
+            - (right_direction * (viewport_width / 2.));
+        let diffuse_light_normal = V3c::new(0., -1., 1.).normalized();
+
+        let mut img = ImageBuffer::new(RESOLUTION, RESOLUTION);
+        for x in 0..RESOLUTION {
+            for y in 0..RESOLUTION {
+                let glass_point = viewport_bottom_left
+                    + right_direction * x as f32 * viewport_width / RESOLUTION as f32
+                    + up_direction * y as f32 * viewport_height / RESOLUTION as f32;
+                let ray = Ray {
+                    origin,
+                    direction: (glass_point - origin).normalized(),
+                };
+                let pixel = if let Some((data, _, normal)) = tree.get_by_ray(&ray) {
+                    let light = 1. - (normal.dot(&diffuse_light_normal) / 2. + 0.5);
+                    Rgb([
+                        (data.r as f32 * light) as u8,
+                        (data.g as f32 * light) as u8,
+                        (data.b as f32 * light) as u8,
+                    ])
+                } else {
+                    Rgb([128, 128, 128])
+                };
+                img.put_pixel(x, RESOLUTION - y - 1, pixel);
+            }
+        }
+        img
+    }
+
+    fn assert_matches_reference(name: &str, rendered: &RgbImage) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets/tests/reference")
+            .join(format!("{name}.png"));
+        if !path.exists() || std::env::var_os("SHOCOVOX_BLESS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap())
+                .ok()
+                .unwrap();
+            rendered.save(&path).ok().unwrap();
+            return;
+        }
+        let reference = image::open(&path).ok().unwrap().into_rgb8();
+        assert_eq!(reference.dimensions(), rendered.dimensions());
+        let changed_pixels = reference
+            .pixels()
+            .zip(rendered.pixels())
+            .filter(|(expected, actual)| {
+                expected
+                    .0
+                    .iter()
+                    .zip(actual.0.iter())
+                    .any(|(e, a)| e.abs_diff(*a) > CHANNEL_TOLERANCE)
+            })
+            .count();
+        assert!(
+            (changed_pixels as f32 / (RESOLUTION * RESOLUTION) as f32) <= PIXEL_TOLERANCE,
+            "Render of {name} differs from its reference in {changed_pixels} pixels"
+        );
+    }
+
+    #[test]
+    fn test_reference_checkerboard_cube() {
+        const TREE_SIZE: u32 = 16;
+        let mut tree: Octree<Albedo, 2> = Octree::new(TREE_SIZE).ok().unwrap();
+        for x in 0..TREE_SIZE {
+            for y in 0..TREE_SIZE {
+                for z in 0..TREE_SIZE {
+                    let color = if 0 == (x + y + z) % 2 {
+                        0xFF0000FF
+                    } else {
+                        0x00FF00FF
+                    };
+                    tree.insert(&V3c::new(x, y, z), color.into()).ok().unwrap();
+                }
+            }
+        }
+        let origin = V3c::new(
+            2. * TREE_SIZE as f32,
+            1.5 * TREE_SIZE as f32,
+            -(TREE_SIZE as f32),
+        );
+        assert_matches_reference("checkerboard_cube", &render(&tree, origin));
+    }
+
+    #[test]
+    fn test_reference_voxel_sphere() {
+        const TREE_SIZE: u32 = 32;
+        let mut tree: Octree<Albedo, 4> = Octree::new(TREE_SIZE).ok().unwrap();
+        let center = V3c::unit(TREE_SIZE as f32 / 2.);
+        for x in 0..TREE_SIZE {
+            for y in 0..TREE_SIZE {
+                for z in 0..TREE_SIZE {
+                    let position = V3c::new(x as f32, y as f32, z as f32) + V3c::unit(0.5);
+                    if (position - center).length() < TREE_SIZE as f32 / 3. {
+                        tree.insert(&V3c::new(x, y, z), 0x645097FF.into())
+                            .ok()
+                            .unwrap();
+                    }
+                }
+            }
+        }
+        let origin = V3c::new(-(TREE_SIZE as f32), TREE_SIZE as f32, -(TREE_SIZE as f32));
+        assert_matches_reference("voxel_sphere", &render(&tree, origin));
+    }
+
+    #[test]
+    fn test_reference_vox_asset() {
+        let tree = Octree::<Albedo, 8>::load_vox_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/models/navigate.vox"
+        ))
+        .ok()
+        .unwrap();
+        let size = tree.get_size() as f32;
+        let origin = V3c::new(1.5 * size, 1.5 * size, -0.5 * size);
+        assert_matches_reference("navigate_vox", &render(&tree, origin));
+    }
+}
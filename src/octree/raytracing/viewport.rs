@@ -0,0 +1,111 @@
+use crate::{
+    octree::V3cf32,
+    spatial::raytracing::{Ray, FLOAT_ERROR_TOLERANCE},
+};
+
+/// The camera the tree is rendered from, shared by the CPU and GPU renderers
+/// Rays start from the origin and go through a rectangle in front of it
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "bevy_wgpu",
+    derive(bevy::render::render_resource::ShaderType)
+)]
+pub struct Viewport {
+    pub origin: V3cf32,
+    /// The direction the viewport is looking at, normalized
+    pub direction: V3cf32,
+    /// The vertical direction of the viewport, normalized
+    pub up: V3cf32,
+    /// Width and height of the rectangle rays go through, and its distance from the origin
+    pub w_h_fov: V3cf32,
+    /// Offset of the rays inside their pixels, horizontally
+    pixel_jitter_x: f32,
+    /// Offset of the rays inside their pixels, vertically
+    pixel_jitter_y: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            origin: V3cf32::new(0., 0., 0.),
+            direction: V3cf32::new(0., 0., -1.),
+            up: V3cf32::new(0., 1., 0.),
+            w_h_fov: V3cf32::new(10., 10., 3.),
+            pixel_jitter_x: 0.,
+            pixel_jitter_y: 0.,
+        }
+    }
+}
+
+impl Viewport {
+    /// Points the viewport from the given position towards the target
+    /// * `eye` - the new origin of the viewport
+    /// * `target` - the point in the middle of the view
+    /// * `up` - the approximate vertical direction of the view, doesn't need to be normalized
+    ///   nor perpendicular to the view direction; if it is parallel to it, a world axis is used instead
+    /// The direction is kept if the eye is at the target
+    pub fn look_at(&mut self, eye: V3cf32, target: V3cf32, up: V3cf32) {
+        self.origin = eye;
+        if FLOAT_ERROR_TOLERANCE < (target - eye).length() {
+            self.direction = (target - eye).normalized();
+        }
+        let mut right = up.cross(self.direction);
+        if right.length() < FLOAT_ERROR_TOLERANCE {
+            // Use the world axis least aligned with the direction
+            let axis = if self.direction.y.abs() < 0.9 {
+                V3cf32::new(0., 1., 0.)
+            } else {
+                V3cf32::new(0., 0., -1.)
+            };
+            right = axis.cross(self.direction);
+        }
+        let right = right.normalized();
+        self.up = self.direction.cross(right);
+    }
+
+    /// Provides the distance of the viewport rectangle from its origin to match the given camera lens
+    /// * `focal_length` - the focal length of the lens, in the same unit as `sensor_width`
+    /// * `sensor_width` - the width of the camera sensor, e.g. 36mm for full frame
+    /// * `viewport_width` - the width of the viewport rectangle, i.e. `w_h_fov.x`
+    pub fn fov_from_focal_length(focal_length: f32, sensor_width: f32, viewport_width: f32) -> f32 {
+        viewport_width * focal_length / sensor_width
+    }
+
+    /// Sets the offset of the generated rays inside their pixels, e.g. to be used in temporal anti-aliasing
+    /// * `jitter` - horizontal and vertical offset in pixels, usually in range -0.5..0.5
+    pub fn set_pixel_jitter(&mut self, jitter: [f32; 2]) {
+        self.pixel_jitter_x = jitter[0];
+        self.pixel_jitter_y = jitter[1];
+    }
+
+    pub fn pixel_jitter(&self) -> [f32; 2] {
+        [self.pixel_jitter_x, self.pixel_jitter_y]
+    }
+
+    /// Provides the ray going through the given pixel of the view, offset by the pixel jitter
+    /// The ray generation of the GPU renderer matches this function
+    /// * `pixel` - horizontal and vertical position of the pixel, (0,0) being the top left corner
+    /// * `resolution` - the number of pixels in the view horizontally and vertically
+    pub fn ray_for_pixel(&self, pixel: [u32; 2], resolution: [u32; 2]) -> Ray {
+        self.ray_for((
+            (pixel[0] as f32 + self.pixel_jitter_x) / resolution[0] as f32,
+            (pixel[1] as f32 + self.pixel_jitter_y) / resolution[1] as f32,
+        ))
+    }
+
+    /// Provides the ray going through the given point of the view
+    /// * `uv` - horizontal and vertical position on the view in range 0..1, (0,0) being the top left corner
+    pub fn ray_for(&self, uv: (f32, f32)) -> Ray {
+        let right = self.up.cross(self.direction).normalized();
+        let bottom_left = self.origin + (self.direction * self.w_h_fov.z)
+            - (right * (self.w_h_fov.x / 2.))
+            - (self.up * (self.w_h_fov.y / 2.));
+        let glass_point = bottom_left
+            + (right * self.w_h_fov.x * uv.0)
+            + (self.up * self.w_h_fov.y * (1. - uv.1));
+        Ray {
+            origin: self.origin,
+            direction: (glass_point - self.origin).normalized(),
+        }
+    }
+}
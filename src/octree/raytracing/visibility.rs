@@ -0,0 +1,123 @@
+use crate::{
+    octree::{raytracing::lighting::GOLDEN_ANGLE, Octree, V3c, VoxelData},
+    spatial::{raytracing::Ray, Cube},
+};
+use std::collections::HashSet;
+
+/// The radius of the area around the target point the occlusion rays are spread in
+const OCCLUSION_JITTER_RADIUS: f32 = 0.5;
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Collects the voxels visible from the given point, e.g. for AI perception or sound occlusion
+    /// Rays are cast evenly in every direction, rays not reaching any occupied part of the tree are skipped
+    /// * `origin` - the point the voxels are seen from
+    /// * `max_distance` - voxels further away than this are not collected
+    /// * `angular_resolution` - the number of rays cast along a half circle, from straight up to straight down
+    /// * Returns with the positions of the visible voxels, each only once
+    pub fn visible_set(
+        &self,
+        origin: &V3c<f32>,
+        max_distance: f32,
+        angular_resolution: u32,
+    ) -> Vec<V3c<u32>> {
+        // Only the occupied octants of the root node are considered for culling rays
+        let root_bounds = Cube::root_bounds(self.octree_size as f32);
+        let occupied_octants = (0..8)
+            .map(|octant| root_bounds.child_bounds_for(octant))
+            .filter(|bounds| {
+                let min = V3c::<u32>::from(bounds.min_position);
+                !self.is_region_empty(&min, &(min + V3c::unit(bounds.size as u32)))
+            })
+            .collect::<Vec<Cube>>();
+        let is_in_reach = |bounds: &Cube, ray: &Ray| {
+            bounds
+                .intersect_ray(ray)
+                .is_some_and(|hit| hit.impact_distance.unwrap_or(0.) <= max_distance)
+        };
+
+        let mut visible = Vec::new();
+        let mut visited = HashSet::new();
+        for direction in sphere_directions(angular_resolution) {
+            let ray = Ray {
+                origin: *origin,
+                direction,
+            };
+            if !occupied_octants
+                .iter()
+                .any(|bounds| is_in_reach(bounds, &ray))
+            {
+                continue;
+            }
+            if let Some(hit) = self.cast_ray(&ray) {
+                if hit.impact_distance <= max_distance && visited.insert(hit.voxel_position) {
+                    visible.push(hit.voxel_position);
+                }
+            }
+        }
+        visible
+    }
+
+    /// Estimates how much of the path between the given points is blocked by voxels, e.g. to attenuate sounds
+    /// Rays are cast towards points spread around the target, the result is the ratio of the blocked rays
+    /// * `from` - the point the rays are cast from, e.g. the listener
+    /// * `to` - the point the rays are cast towards, e.g. the sound source
+    /// * `samples` - the number of rays to cast, at least one ray is cast
+    /// * Returns with a value in range 0..=1, 0 meaning the path is clear, 1 meaning it is fully blocked
+    pub fn occlusion_factor(&self, from: &V3c<f32>, to: &V3c<f32>, samples: u32) -> f32 {
+        let samples = samples.max(1);
+        let mut blocked = 0;
+        for i in 0..samples {
+            // Targets are spread evenly inside a sphere around the target point
+            let target = if 1 == samples {
+                *to
+            } else {
+                let height = 1. - 2. * (i as f32 + 0.5) / samples as f32;
+                let radius = (1. - height * height).sqrt();
+                let angle = i as f32 * GOLDEN_ANGLE;
+                let offset = V3c::new(radius * angle.cos(), height, radius * angle.sin());
+                let spread = ((i as f32 + 0.5) / samples as f32).cbrt() * OCCLUSION_JITTER_RADIUS;
+                *to + offset * spread
+            };
+            let distance = (target - *from).length();
+            if distance <= 0. {
+                continue;
+            }
+            let ray = Ray {
+                origin: *from,
+                direction: (target - *from).normalized(),
+            };
+            if self.is_occluded(&ray, distance) {
+                blocked += 1;
+            }
+        }
+        blocked as f32 / samples as f32
+    }
+}
+
+/// Provides evenly distributed unit vectors pointing in every direction
+/// * `angular_resolution` - the number of rings of directions from straight up to straight down
+///   each ring has a number of directions proportional to its circumference
+pub(crate) fn sphere_directions(angular_resolution: u32) -> Vec<V3c<f32>> {
+    let mut directions = Vec::new();
+    for ring in 0..angular_resolution {
+        let polar = std::f32::consts::PI * (ring as f32 + 0.5) / angular_resolution as f32;
+        let ring_size = ((2 * angular_resolution) as f32 * polar.sin())
+            .round()
+            .max(1.) as u32;
+        for step in 0..ring_size {
+            let azimuth = std::f32::consts::TAU * step as f32 / ring_size as f32;
+            directions.push(
+                V3c::new(
+                    polar.sin() * azimuth.cos(),
+                    polar.cos(),
+                    polar.sin() * azimuth.sin(),
+                )
+                .normalized(),
+            );
+        }
+    }
+    directions
+}
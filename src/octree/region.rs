@@ -0,0 +1,196 @@
+use crate::octree::{
+    detail::bound_contains,
+    types::{BrickData, OctreeError, OctreeRegion},
+    Octree, V3c, VoxelData,
+};
+use crate::spatial::{math::flat_projection, Cube};
+
+impl<T, const DIM: usize> OctreeRegion<T, DIM>
+where
+    T: Clone + PartialEq + VoxelData,
+{
+    /// The extent of the copied box
+    pub fn size(&self) -> V3c<u32> {
+        self.size
+    }
+
+    /// True if the copied box contains no voxels
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty() && self.bricks.is_empty()
+    }
+}
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Copies the voxels inside the given box, so they can be pasted with `paste_region`
+    /// Filled cells and parted bricks completely inside the box are copied as a whole
+    /// * `region_min` - the minimum position of the box
+    /// * `region_max` - the maximum position of the box, exclusive, cut to the bounds of the tree
+    pub fn copy_region(
+        &self,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+    ) -> OctreeRegion<T, DIM> {
+        let mut region_max = *region_max;
+        region_max.cut_each_component(&self.octree_size);
+        let size = V3c::new(
+            region_max.x.saturating_sub(region_min.x),
+            region_max.y.saturating_sub(region_min.y),
+            region_max.z.saturating_sub(region_min.z),
+        );
+        let is_inside = |cell_min: &V3c<u32>, cell_size: u32| {
+            let cell_max = *cell_min + V3c::unit(cell_size);
+            region_min.x <= cell_min.x
+                && region_min.y <= cell_min.y
+                && region_min.z <= cell_min.z
+                && cell_max.x <= region_max.x
+                && cell_max.y <= region_max.y
+                && cell_max.z <= region_max.z
+        };
+
+        let mut filled_cells = Vec::new();
+        let mut bricks = Vec::new();
+        let (visited_min, visited_max) =
+            (V3c::<f32>::from(*region_min), V3c::<f32>::from(region_max));
+        self.for_each_filled_brick_in(&visited_min, &visited_max, &mut |bounds, brick| {
+            let brick_min = V3c::<u32>::from(bounds.min_position);
+            if matches!(brick, BrickData::Parted(_))
+                && DIM == bounds.size as usize
+                && is_inside(&brick_min, DIM as u32)
+            {
+                bricks.push((brick_min - *region_min, brick.clone()));
+                return;
+            }
+            Self::visit_filled_cells_of_brick(
+                brick,
+                bounds,
+                &visited_min,
+                &visited_max,
+                &mut |bounds, voxel| filled_cells.push((*bounds, *voxel)),
+            );
+        });
+
+        let mut cells = Vec::new();
+        for (bounds, voxel) in filled_cells {
+            let cell_min = V3c::<u32>::from(bounds.min_position);
+            let cell_size = bounds.size as u32;
+            if is_inside(&cell_min, cell_size) {
+                cells.push((cell_min - *region_min, cell_size, voxel));
+                continue;
+            }
+
+            // Only the voxels of the cell inside the box are copied
+            let cell_max = cell_min + V3c::unit(cell_size);
+            for x in cell_min.x.max(region_min.x)..cell_max.x.min(region_max.x) {
+                for y in cell_min.y.max(region_min.y)..cell_max.y.min(region_max.y) {
+                    for z in cell_min.z.max(region_min.z)..cell_max.z.min(region_max.z) {
+                        cells.push((V3c::new(x, y, z) - *region_min, 1, voxel));
+                    }
+                }
+            }
+        }
+        OctreeRegion {
+            size,
+            cells,
+            bricks,
+        }
+    }
+
+    /// Inserts the voxels of a copied region into the tree, the tree is simplified only once
+    /// Copied cells are inserted as a whole where the destination is aligned to their size,
+    /// copied bricks are inserted as a whole where the destination is aligned to DIM,
+    /// otherwise they are inserted voxel by voxel, which is slower
+    /// Empty parts of the region leave the tree unchanged, parts outside the tree are ignored
+    /// * `region` - the voxels copied with `copy_region`
+    /// * `destination_min` - the position the minimum position of the region is placed at
+    pub fn paste_region(
+        &mut self,
+        region: &OctreeRegion<T, DIM>,
+        destination_min: &V3c<u32>,
+    ) -> Result<(), OctreeError> {
+        let root_bounds = Cube::root_bounds(self.octree_size as f32);
+        self.update_batch(|tree| {
+            for (offset, cell_size, voxel) in &region.cells {
+                let cell_min = *destination_min + *offset;
+                if !bound_contains(&root_bounds, &V3c::<f32>::from(cell_min)) {
+                    continue;
+                }
+                if 1 < *cell_size
+                    && *cell_size <= tree.octree_size
+                    && 0 == cell_min.x % cell_size
+                    && 0 == cell_min.y % cell_size
+                    && 0 == cell_min.z % cell_size
+                {
+                    tree.insert_at_lod(&cell_min, *cell_size, *voxel)?;
+                    continue;
+                }
+                let cell_max = cell_min + V3c::unit(*cell_size);
+                tree.fill_region(&cell_min, &cell_max, *voxel)?;
+            }
+            for (offset, brick) in &region.bricks {
+                let BrickData::Parted(brick) = brick else {
+                    continue;
+                };
+                let brick_min = *destination_min + *offset;
+                if !bound_contains(&root_bounds, &V3c::<f32>::from(brick_min)) {
+                    continue;
+                }
+                if 0 != brick_min.x % DIM as u32
+                    || 0 != brick_min.y % DIM as u32
+                    || 0 != brick_min.z % DIM as u32
+                {
+                    for x in 0..DIM {
+                        for y in 0..DIM {
+                            for z in 0..DIM {
+                                let position = brick_min + V3c::new(x as u32, y as u32, z as u32);
+                                if !brick[x][y][z].is_empty()
+                                    && bound_contains(&root_bounds, &V3c::<f32>::from(position))
+                                {
+                                    tree.insert(&position, brick[x][y][z])?;
+                                }
+                            }
+                        }
+                    }
+                    continue;
+                }
+
+                // Empty voxels of the copied brick keep the contents of the tree
+                let mut data = vec![T::default(); DIM * DIM * DIM];
+                let brick_max = brick_min + V3c::unit(DIM as u32);
+                tree.for_each_filled_cell_in(
+                    &V3c::<f32>::from(brick_min),
+                    &V3c::<f32>::from(brick_max),
+                    &mut |bounds, voxel| {
+                        let cell_min = V3c::<u32>::from(bounds.min_position);
+                        let cell_max = cell_min + V3c::unit(bounds.size as u32);
+                        for x in cell_min.x.max(brick_min.x)..cell_max.x.min(brick_max.x) {
+                            for y in cell_min.y.max(brick_min.y)..cell_max.y.min(brick_max.y) {
+                                for z in cell_min.z.max(brick_min.z)..cell_max.z.min(brick_max.z) {
+                                    data[flat_projection(
+                                        (x - brick_min.x) as usize,
+                                        (y - brick_min.y) as usize,
+                                        (z - brick_min.z) as usize,
+                                        DIM,
+                                    )] = *voxel;
+                                }
+                            }
+                        }
+                    },
+                );
+                for x in 0..DIM {
+                    for y in 0..DIM {
+                        for z in 0..DIM {
+                            if !brick[x][y][z].is_empty() {
+                                data[flat_projection(x, y, z, DIM)] = brick[x][y][z];
+                            }
+                        }
+                    }
+                }
+                tree.insert_brick(&brick_min, &data)?;
+            }
+            Ok(())
+        })
+    }
+}
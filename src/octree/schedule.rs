@@ -0,0 +1,123 @@
+use crate::octree::{types::OctreeError, Octree, V3c, VoxelData};
+use std::collections::HashSet;
+
+/// Keeps track of the active parts of a tree, so simulations e.g. water flow or fire spread
+/// only need to visit the recently changed regions each tick, instead of scanning the whole tree
+/// The tree is divided into cubes of equal size, usually the brick dimension of the tree
+#[derive(Debug, Clone)]
+pub struct TickScheduler {
+    region_size: u32,
+    active: HashSet<V3c<u32>>,
+}
+
+impl TickScheduler {
+    /// Creates a scheduler without any active regions
+    /// * `region_size` - the size of the tracked regions in each dimension, at least 1
+    pub fn new(region_size: u32) -> Self {
+        Self {
+            region_size: region_size.max(1),
+            active: HashSet::new(),
+        }
+    }
+
+    pub fn region_size(&self) -> u32 {
+        self.region_size
+    }
+
+    /// The number of regions to be visited in the next tick
+    pub fn active_count(&self) -> usize {
+        self.active.len()
+    }
+
+    /// Provides the minimum position of the region containing the given position
+    fn region_of(&self, position: &V3c<u32>) -> V3c<u32> {
+        V3c::new(
+            position.x - position.x % self.region_size,
+            position.y - position.y % self.region_size,
+            position.z - position.z % self.region_size,
+        )
+    }
+
+    /// Marks the region containing the given position to be visited in the next tick
+    pub fn mark_active(&mut self, position: &V3c<u32>) {
+        let region = self.region_of(position);
+        self.active.insert(region);
+    }
+
+    /// Marks every region overlapping with the given area to be visited in the next tick
+    /// * `min` - the minimum position of the area
+    /// * `max` - the maximum position of the area, exclusive
+    pub fn mark_area_active(&mut self, min: &V3c<u32>, max: &V3c<u32>) {
+        if max.x <= min.x || max.y <= min.y || max.z <= min.z {
+            return;
+        }
+        let first = self.region_of(min);
+        for x in (first.x..max.x).step_by(self.region_size as usize) {
+            for y in (first.y..max.y).step_by(self.region_size as usize) {
+                for z in (first.z..max.z).step_by(self.region_size as usize) {
+                    self.active.insert(V3c::new(x, y, z));
+                }
+            }
+        }
+    }
+
+    pub fn is_active(&self, position: &V3c<u32>) -> bool {
+        self.active.contains(&self.region_of(position))
+    }
+
+    /// Marks every region containing voxels matching the given tag as active
+    /// e.g. to start the simulation of water or fire already present in a loaded tree
+    /// * `is_tagged` - decides if a voxel takes part in the simulation
+    pub fn activate_tagged<T, const DIM: usize, F>(&mut self, tree: &Octree<T, DIM>, is_tagged: F)
+    where
+        T: Default + Eq + Clone + Copy + VoxelData,
+        F: Fn(&T) -> bool,
+    {
+        let size = tree.get_size();
+        tree.for_each_in(&V3c::unit(0), &V3c::unit(size), |position, voxel| {
+            if is_tagged(voxel) {
+                self.mark_active(position);
+            }
+        });
+    }
+
+    /// Inserts the given data into the tree, and marks its region active
+    pub fn insert<T, const DIM: usize>(
+        &mut self,
+        tree: &mut Octree<T, DIM>,
+        position: &V3c<u32>,
+        data: T,
+    ) -> Result<(), OctreeError>
+    where
+        T: Default + Eq + Clone + Copy + VoxelData,
+    {
+        tree.insert(position, data)?;
+        self.mark_active(position);
+        Ok(())
+    }
+
+    /// Visits every active region once, in a deterministic order
+    /// Regions are deactivated before being visited, so they are only visited in the next tick
+    /// if the callback marks them active again, e.g. because it changed something inside or next to them
+    /// * `callback` - called with the tree, the minimum and maximum(exclusive) position of the region
+    ///   and the scheduler to mark regions active for the next tick
+    pub fn tick<T, const DIM: usize, F>(&mut self, tree: &mut Octree<T, DIM>, mut callback: F)
+    where
+        T: Default + Eq + Clone + Copy + VoxelData,
+        F: FnMut(&mut Octree<T, DIM>, &V3c<u32>, &V3c<u32>, &mut Self),
+    {
+        let mut regions = std::mem::take(&mut self.active)
+            .into_iter()
+            .collect::<Vec<V3c<u32>>>();
+        regions.sort_by_key(|region| (region.z, region.y, region.x));
+        let size = tree.get_size();
+        for region_min in regions {
+            let region_max = V3c::new(
+                (region_min.x + self.region_size).min(size),
+                (region_min.y + self.region_size).min(size),
+                (region_min.z + self.region_size).min(size),
+            );
+            callback(tree, &region_min, &region_max, self);
+        }
+    }
+}
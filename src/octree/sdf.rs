@@ -0,0 +1,161 @@
+use crate::octree::{types::OctreeError, Octree, V3c, VoxelData};
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Bakes a signed distance field from the contents of the given region using jump flooding
+    /// The region is sampled in cells of `resolution` voxels, the occupancy of a cell
+    /// is decided by the voxel at its center. Distances are measured in voxels,
+    /// and are negative inside the content. In case the region has no occupied cells
+    /// the distances are f32::MAX, in case it has no empty cells they are -f32::MAX
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region, exclusive
+    /// * `resolution` - size of one distance field cell in voxels, must be larger, than 0
+    /// * Returns with the distance values and the number of cells in each dimension.
+    ///   The values are ordered with x being the fastest changing coordinate, and z the slowest
+    pub fn bake_sdf(
+        &self,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+        resolution: u32,
+    ) -> Result<(Vec<f32>, V3c<usize>), OctreeError> {
+        if 0 == resolution {
+            return Err(OctreeError::InvalidStructure(
+                "SDF resolution must be larger, than 0".into(),
+            ));
+        }
+        let mut region_max = *region_max;
+        region_max.cut_each_component(&self.octree_size);
+        if region_min.x >= region_max.x
+            || region_min.y >= region_max.y
+            || region_min.z >= region_max.z
+        {
+            return Err(OctreeError::InvalidPosition {
+                x: region_min.x,
+                y: region_min.y,
+                z: region_min.z,
+            });
+        }
+        let cells = V3c::new(
+            (region_max.x - region_min.x).div_ceil(resolution) as usize,
+            (region_max.y - region_min.y).div_ceil(resolution) as usize,
+            (region_max.z - region_min.z).div_ceil(resolution) as usize,
+        );
+
+        let mut occupancy = Vec::with_capacity(cells.x * cells.y * cells.z);
+        for z in 0..cells.z {
+            for y in 0..cells.y {
+                for x in 0..cells.x {
+                    let sample_position = (*region_min
+                        + V3c::new(x as u32, y as u32, z as u32) * resolution
+                        + V3c::unit(resolution / 2))
+                    .cut_each_component(&(self.octree_size - 1));
+                    occupancy.push(self.get(&sample_position).is_some());
+                }
+            }
+        }
+
+        // Distance to the closest occupied cell for empty cells, and vice versa
+        let to_occupied = jump_flood(&occupancy, &cells, true);
+        let to_empty = jump_flood(&occupancy, &cells, false);
+        let distances = occupancy
+            .iter()
+            .enumerate()
+            .map(|(i, occupied)| {
+                // The surface is between the cell centers
+                if *occupied {
+                    -to_empty[i].map_or(f32::MAX, |d| (d - 0.5) * resolution as f32)
+                } else {
+                    to_occupied[i].map_or(f32::MAX, |d| (d - 0.5) * resolution as f32)
+                }
+            })
+            .collect();
+        Ok((distances, cells))
+    }
+}
+
+/// Calculates the distance of each cell to the closest cell with the given occupancy
+/// Returns with None for cells where there are no seeds to measure distance from
+fn jump_flood(occupancy: &[bool], cells: &V3c<usize>, seed_occupancy: bool) -> Vec<Option<f32>> {
+    let mut closest_seed: Vec<Option<V3c<i32>>> = Vec::with_capacity(occupancy.len());
+    for z in 0..cells.z {
+        for y in 0..cells.y {
+            for x in 0..cells.x {
+                closest_seed.push(
+                    if occupancy[flat_projection_3d(x, y, z, cells)] == seed_occupancy {
+                        Some(V3c::new(x as i32, y as i32, z as i32))
+                    } else {
+                        None
+                    },
+                );
+            }
+        }
+    }
+
+    let distance = |a: &V3c<i32>, b: &V3c<i32>| V3c::<f32>::from(*a - *b).length();
+    let mut step = (cells.x.max(cells.y).max(cells.z) as i32 / 2).max(1);
+    loop {
+        let mut next_seed = closest_seed.clone();
+        for z in 0..cells.z as i32 {
+            for y in 0..cells.y as i32 {
+                for x in 0..cells.x as i32 {
+                    let cell = V3c::new(x, y, z);
+                    let cell_index = flat_projection_3d(x as usize, y as usize, z as usize, cells);
+                    for offset_z in [-step, 0, step] {
+                        for offset_y in [-step, 0, step] {
+                            for offset_x in [-step, 0, step] {
+                                let neighbor = cell + V3c::new(offset_x, offset_y, offset_z);
+                                if neighbor.x < 0
+                                    || neighbor.y < 0
+                                    || neighbor.z < 0
+                                    || neighbor.x >= cells.x as i32
+                                    || neighbor.y >= cells.y as i32
+                                    || neighbor.z >= cells.z as i32
+                                {
+                                    continue;
+                                }
+                                let Some(candidate) = closest_seed[flat_projection_3d(
+                                    neighbor.x as usize,
+                                    neighbor.y as usize,
+                                    neighbor.z as usize,
+                                    cells,
+                                )] else {
+                                    continue;
+                                };
+                                if next_seed[cell_index].map_or(true, |current| {
+                                    distance(&candidate, &cell) < distance(&current, &cell)
+                                }) {
+                                    next_seed[cell_index] = Some(candidate);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        closest_seed = next_seed;
+        if 1 == step {
+            break;
+        }
+        step /= 2;
+    }
+
+    closest_seed
+        .iter()
+        .enumerate()
+        .map(|(i, seed)| {
+            let cell = V3c::new(
+                (i % cells.x) as i32,
+                ((i / cells.x) % cells.y) as i32,
+                (i / (cells.x * cells.y)) as i32,
+            );
+            seed.map(|seed| distance(&seed, &cell))
+        })
+        .collect()
+}
+
+/// Index of the given cell inside a grid with possibly different sizes in each dimension
+fn flat_projection_3d(x: usize, y: usize, z: usize, cells: &V3c<usize>) -> usize {
+    x + (y * cells.x) + (z * cells.x * cells.y)
+}
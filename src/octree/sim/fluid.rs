@@ -0,0 +1,192 @@
+use crate::octree::{types::OctreeError, Octree, TickScheduler, V3c, VoxelData};
+use std::collections::{HashMap, HashSet};
+
+/// Simple cellular water simulation: water falls down, and spreads horizontally by levels
+/// Only the regions where water moved recently are simulated each step
+/// Cells containing water are set to the water voxel in the tree, every other non-empty voxel
+/// is considered solid, so the water voxel should differ from the solid voxels of the tree
+#[derive(Debug, Clone)]
+pub struct FluidSimulation<T> {
+    water: T,
+    max_level: u8,
+    levels: HashMap<V3c<u32>, u8>,
+    scheduler: TickScheduler,
+}
+
+impl<T> FluidSimulation<T>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Creates a simulation without any water
+    /// * `water` - the voxel displaying water inside the tree
+    /// * `max_level` - the amount of water a single cell can hold, at least 1
+    /// * `region_size` - the size of the regions the simulation is scheduled in, usually the brick dimension
+    pub fn new(water: T, max_level: u8, region_size: u32) -> Self {
+        Self {
+            water,
+            max_level: max_level.max(1),
+            levels: HashMap::new(),
+            scheduler: TickScheduler::new(region_size),
+        }
+    }
+
+    /// The amount of water in the cell at the given position, 0 if there is none
+    pub fn level_at(&self, position: &V3c<u32>) -> u8 {
+        self.levels.get(position).copied().unwrap_or(0)
+    }
+
+    /// The amount of water inside the simulation
+    pub fn total_volume(&self) -> u32 {
+        self.levels.values().map(|level| *level as u32).sum()
+    }
+
+    /// The number of regions to be simulated in the next step, 0 if the water is settled
+    pub fn active_region_count(&self) -> usize {
+        self.scheduler.active_count()
+    }
+
+    /// Adds water into the cell at the given position, up to the maximum level of the cell
+    /// The previous content of the cell inside the tree is replaced by water
+    /// * Returns with error if the position is outside the tree
+    pub fn add_water<const DIM: usize>(
+        &mut self,
+        tree: &mut Octree<T, DIM>,
+        position: &V3c<u32>,
+        amount: u8,
+    ) -> Result<(), OctreeError> {
+        if 0 == amount {
+            return Ok(());
+        }
+        self.scheduler.insert(tree, position, self.water)?;
+        let level = self.levels.entry(*position).or_insert(0);
+        *level = level.saturating_add(amount).min(self.max_level);
+        Ok(())
+    }
+
+    /// Simulates one step of the water inside the active regions, and updates the tree accordingly
+    pub fn step<const DIM: usize>(&mut self, tree: &mut Octree<T, DIM>) -> Result<(), OctreeError> {
+        let Self {
+            water,
+            max_level,
+            levels,
+            scheduler,
+        } = self;
+        let mut changed = HashSet::new();
+        scheduler.tick(tree, |tree, region_min, region_max, _| {
+            let mut cells = Vec::new();
+            tree.for_each_in(region_min, region_max, |position, voxel| {
+                if *voxel == *water && levels.contains_key(position) {
+                    cells.push(*position);
+                }
+            });
+            // Lower cells are simulated first, so water above can fall into the space freed up
+            cells.sort_by_key(|cell| (cell.y, cell.z, cell.x));
+            for cell in cells {
+                flow(tree, *water, *max_level, levels, &cell, &mut changed);
+            }
+        });
+
+        // Changes are applied to the tree in bulk after every region is simulated
+        let (filled, emptied): (Vec<V3c<u32>>, Vec<V3c<u32>>) = changed
+            .iter()
+            .copied()
+            .partition(|position| levels.contains_key(position));
+        tree.insert_batch(filled.into_iter().map(|position| (position, *water)))?;
+        tree.clear_batch(emptied)?;
+
+        // Water may continue to move where it moved, or next to it
+        let size = tree.get_size();
+        for position in changed {
+            scheduler.mark_area_active(
+                &V3c::new(
+                    position.x.saturating_sub(1),
+                    position.y.saturating_sub(1),
+                    position.z.saturating_sub(1),
+                ),
+                &V3c::new(
+                    (position.x + 2).min(size),
+                    (position.y + 2).min(size),
+                    (position.z + 2).min(size),
+                ),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Decides if water can flow into the cell at the given position
+fn is_open<T, const DIM: usize>(
+    tree: &Octree<T, DIM>,
+    water: T,
+    levels: &HashMap<V3c<u32>, u8>,
+    position: &V3c<u32>,
+) -> bool
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    let size = tree.get_size();
+    if position.x >= size || position.y >= size || position.z >= size {
+        return false;
+    }
+    // Cells emptied in the current step still contain water inside the tree
+    levels.contains_key(position)
+        || tree
+            .get(position)
+            .map_or(true, |voxel| voxel.is_empty() || *voxel == water)
+}
+
+/// Moves the water of the given cell: first down as much as possible, then one unit
+/// to each horizontal neighbour with at least 2 units less water
+fn flow<T, const DIM: usize>(
+    tree: &Octree<T, DIM>,
+    water: T,
+    max_level: u8,
+    levels: &mut HashMap<V3c<u32>, u8>,
+    cell: &V3c<u32>,
+    changed: &mut HashSet<V3c<u32>>,
+) where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    let mut level = levels.get(cell).copied().unwrap_or(0);
+    let mut move_water = |levels: &mut HashMap<V3c<u32>, u8>, target: V3c<u32>, amount: u8| {
+        *levels.entry(target).or_insert(0) += amount;
+        changed.insert(*cell);
+        changed.insert(target);
+    };
+
+    if cell.y > 0 {
+        let below = *cell - V3c::new(0, 1, 0);
+        if is_open(tree, water, levels, &below) {
+            let room = max_level - levels.get(&below).copied().unwrap_or(0);
+            let amount = room.min(level);
+            if 0 < amount {
+                move_water(levels, below, amount);
+                level -= amount;
+            }
+        }
+    }
+
+    let neighbours = [
+        V3c::new(cell.x + 1, cell.y, cell.z),
+        V3c::new(cell.x.wrapping_sub(1), cell.y, cell.z),
+        V3c::new(cell.x, cell.y, cell.z + 1),
+        V3c::new(cell.x, cell.y, cell.z.wrapping_sub(1)),
+    ];
+    for neighbour in neighbours {
+        if level <= 1 {
+            break;
+        }
+        if is_open(tree, water, levels, &neighbour)
+            && levels.get(&neighbour).copied().unwrap_or(0) < level - 1
+        {
+            move_water(levels, neighbour, 1);
+            level -= 1;
+        }
+    }
+
+    if 0 == level {
+        levels.remove(cell);
+    } else {
+        levels.insert(*cell, level);
+    }
+}
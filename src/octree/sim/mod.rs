@@ -0,0 +1,6 @@
+//! Reference simulations running over the active regions of a tree with `TickScheduler`
+
+pub mod fluid;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,36 @@
+mod fluid_tests {
+    use crate::octree::sim::fluid::FluidSimulation;
+    use crate::octree::{Albedo, Octree, V3c};
+
+    #[test]
+    fn test_water_falls_and_spreads() {
+        let stone: Albedo = 0x808080FF.into();
+        let water: Albedo = 0x0000FFFF.into();
+        let mut tree = Octree::<Albedo>::new(8).ok().unwrap();
+        tree.fill_region(&V3c::new(0, 0, 0), &V3c::new(8, 1, 8), stone)
+            .ok()
+            .unwrap();
+
+        let mut simulation = FluidSimulation::new(water, 4, 2);
+        simulation
+            .add_water(&mut tree, &V3c::new(4, 5, 4), 4)
+            .ok()
+            .unwrap();
+        assert!(simulation.total_volume() == 4);
+        assert!(simulation.active_region_count() == 1);
+
+        for _ in 0..32 {
+            simulation.step(&mut tree).ok().unwrap();
+        }
+
+        // The water fell to the ground, spread out, and settled without losing any of it
+        assert!(simulation.total_volume() == 4);
+        assert!(simulation.active_region_count() == 0);
+        assert!(simulation.level_at(&V3c::new(4, 5, 4)) == 0);
+        assert!(tree.get(&V3c::new(4, 5, 4)).is_none());
+        assert!(simulation.level_at(&V3c::new(4, 1, 4)) > 0);
+        assert!(simulation.level_at(&V3c::new(4, 1, 4)) < 4);
+        assert!(tree.get(&V3c::new(4, 1, 4)) == Some(&water));
+        assert!(tree.get(&V3c::new(4, 0, 4)) == Some(&stone));
+    }
+}
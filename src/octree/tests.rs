@@ -1,6 +1,12 @@
 mod octree_tests {
-    use crate::octree::types::{Albedo, Octree, VoxelData};
-    use crate::spatial::{lut::OCTANT_OFFSET_REGION_LUT, math::vector::V3c};
+    use crate::octree::types::{Albedo, Axis, MergePolicy, Octree, VoxelData};
+    use crate::octree::{
+        Aabb, CollisionHull, DamageLayer, EntityGrid, PoolGrowth, TickScheduler, VoxelAnimation,
+    };
+    use crate::spatial::{
+        lut::OCTANT_OFFSET_REGION_LUT,
+        math::{flat_projection, vector::V3c},
+    };
 
     #[test]
     fn test_simple_insert_and_get() {
@@ -1089,4 +1095,1474 @@ mod octree_tests {
         let item = tree.get(&V3c::new(3, 0, 0));
         assert!(item.is_none(), "Item shouldn't exist: {:?}", item);
     }
+
+    #[test]
+    fn test_paint_keeps_occupancy() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+
+        let mut tree = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        tree.insert(&V3c::new(1, 0, 0), red).ok().unwrap();
+        tree.insert(&V3c::new(5, 5, 5), red).ok().unwrap();
+
+        tree.paint(&V3c::new(1, 0, 0), green).ok().unwrap();
+        tree.paint(&V3c::new(0, 0, 0), green).ok().unwrap();
+        assert!(tree.get(&V3c::new(1, 0, 0)).is_some_and(|v| *v == green));
+        assert!(tree.get(&V3c::new(0, 0, 0)).is_none());
+        assert!(tree.get(&V3c::new(5, 5, 5)).is_some_and(|v| *v == red));
+        assert!(tree.paint(&V3c::new(8, 0, 0), green).is_err());
+    }
+
+    #[test]
+    fn test_paint_at_lod_only_recolors_occupied_voxels() {
+        let red: Albedo = 0xFF0000FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+
+        let mut tree = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(0, 0, 0), 4, red).ok().unwrap();
+        tree.clear(&V3c::new(1, 1, 1)).ok().unwrap();
+
+        tree.paint_at_lod(&V3c::new(0, 0, 0), 8, blue).ok().unwrap();
+        for x in 0..8 {
+            for y in 0..8 {
+                for z in 0..8 {
+                    let voxel = tree.get(&V3c::new(x, y, z));
+                    if x < 4 && y < 4 && z < 4 && !(x == 1 && y == 1 && z == 1) {
+                        assert!(voxel.is_some_and(|v| *v == blue));
+                    } else {
+                        assert!(voxel.is_none());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_paint_at_lod_in_place() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+
+        let mut tree = Octree::<Albedo, 4>::new(32).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(0, 0, 0), 16, red)
+            .ok()
+            .unwrap();
+        tree.insert_at_lod(&V3c::new(16, 16, 16), 4, green)
+            .ok()
+            .unwrap();
+        tree.insert(&V3c::new(5, 6, 7), green).ok().unwrap();
+        tree.clear(&V3c::new(9, 9, 9)).ok().unwrap();
+        tree.insert(&V3c::new(20, 25, 30), red).ok().unwrap();
+        tree.enable_checksums();
+
+        let mut reference = std::collections::HashMap::new();
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    if let Some(voxel) = tree.get(&V3c::new(x, y, z)) {
+                        reference.insert(V3c::new(x, y, z), *voxel);
+                    }
+                }
+            }
+        }
+
+        let region_min = V3c::new(3, 5, 7);
+        let region_size = 19;
+        tree.paint_at_lod(&region_min, region_size, blue)
+            .ok()
+            .unwrap();
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let position = V3c::new(x, y, z);
+                    let inside = (region_min.x..region_min.x + region_size).contains(&x)
+                        && (region_min.y..region_min.y + region_size).contains(&y)
+                        && (region_min.z..region_min.z + region_size).contains(&z);
+                    let expected = reference
+                        .get(&position)
+                        .map(|voxel| if inside { blue } else { *voxel });
+                    assert_eq!(tree.get(&position).copied(), expected, "at {:?}", position);
+                }
+            }
+        }
+        assert!(tree.verify_stored_checksums().is_empty());
+    }
+
+    #[test]
+    fn test_insert_brick_where_dim_is_2() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+
+        let mut tree = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        tree.insert(&V3c::new(2, 2, 2), green).ok().unwrap();
+        let brick = [red, Albedo::default(), red, red, red, red, red, red];
+        tree.insert_brick(&V3c::new(2, 2, 2), &brick).ok().unwrap();
+        assert!(tree.get(&V3c::new(2, 2, 2)).is_some_and(|v| *v == red));
+        assert!(tree.get(&V3c::new(3, 2, 2)).is_none());
+        assert!(tree.get(&V3c::new(2, 3, 2)).is_some_and(|v| *v == red));
+        assert!(tree.get(&V3c::new(3, 3, 3)).is_some_and(|v| *v == red));
+
+        tree.insert_brick(&V3c::new(2, 2, 2), &[green; 8]).ok().unwrap();
+        for x in 2..4 {
+            for y in 2..4 {
+                for z in 2..4 {
+                    assert!(tree.get(&V3c::new(x, y, z)).is_some_and(|v| *v == green));
+                }
+            }
+        }
+
+        tree.insert_brick(&V3c::new(2, 2, 2), &[Albedo::default(); 8])
+            .ok()
+            .unwrap();
+        assert!(tree.get(&V3c::new(2, 2, 2)).is_none());
+
+        assert!(tree.insert_brick(&V3c::new(1, 0, 0), &[red; 8]).is_err());
+        assert!(tree.insert_brick(&V3c::new(0, 0, 0), &[red; 7]).is_err());
+    }
+
+    #[test]
+    fn test_insert_brick_into_existing_nodes() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let brick = (0..64)
+            .map(|i| match i % 3 {
+                0 => Albedo::default(),
+                1 => red,
+                _ => green,
+            })
+            .collect::<Vec<_>>();
+
+        // Into a solid leaf, next to a parted brick and into an empty part of the tree
+        for brick_origin in [V3c::new(0, 0, 0), V3c::new(28, 0, 0), V3c::new(16, 24, 8)] {
+            let mut tree = Octree::<Albedo, 4>::new(32).ok().unwrap();
+            let mut reference = Octree::<Albedo, 4>::new(32).ok().unwrap();
+            for tree in [&mut tree, &mut reference] {
+                tree.insert_at_lod(&V3c::new(0, 0, 0), 16, green)
+                    .ok()
+                    .unwrap();
+                tree.insert(&V3c::new(30, 1, 1), red).ok().unwrap();
+            }
+            tree.enable_checksums();
+            tree.insert_brick(&brick_origin, &brick).ok().unwrap();
+            reference.clear_at_lod(&brick_origin, 4).ok().unwrap();
+            for x in 0..4 {
+                for y in 0..4 {
+                    for z in 0..4 {
+                        let voxel = brick[flat_projection(x, y, z, 4)];
+                        if !voxel.is_empty() {
+                            let position = brick_origin + V3c::new(x as u32, y as u32, z as u32);
+                            reference.insert(&position, voxel).ok().unwrap();
+                        }
+                    }
+                }
+            }
+
+            for x in 0..32 {
+                for y in 0..32 {
+                    for z in 0..32 {
+                        let position = V3c::new(x, y, z);
+                        assert!(tree.get(&position) == reference.get(&position));
+                    }
+                }
+            }
+            for depth in 0..=tree.depth() {
+                assert!(
+                    tree.occupied_bits_at(&brick_origin, depth)
+                        == reference.occupied_bits_at(&brick_origin, depth)
+                );
+            }
+            assert!(tree.verify_stored_checksums().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_insert_and_clear_batch() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        let mut reference = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        let positions = (0..4)
+            .flat_map(|x| (0..4).flat_map(move |y| (0..4).map(move |z| V3c::new(x, y, z))))
+            .collect::<Vec<_>>();
+
+        tree.insert_batch(positions.iter().map(|p| (*p, red)))
+            .ok()
+            .unwrap();
+        for position in &positions {
+            reference.insert(position, red).ok().unwrap();
+        }
+        assert!(tree.auto_simplify);
+        for position in &positions {
+            assert!(tree.get(position) == reference.get(position));
+        }
+        assert!(tree.get(&V3c::new(4, 0, 0)).is_none());
+
+        tree.clear_batch(positions.iter().filter(|p| 0 == p.x).copied())
+            .ok()
+            .unwrap();
+        for position in &positions {
+            assert!(tree.get(position).is_some() == (0 != position.x));
+        }
+
+        // Updates before an invalid position are kept
+        assert!(tree
+            .insert_batch([(V3c::new(0, 0, 0), red), (V3c::new(8, 0, 0), red)])
+            .is_err());
+        assert!(tree.get(&V3c::new(0, 0, 0)).is_some_and(|v| *v == red));
+        assert!(tree.auto_simplify);
+    }
+
+    #[test]
+    fn test_insert_batch_simplifies_like_single_inserts() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        let mut reference = Octree::<Albedo, 2>::new(16).ok().unwrap();
+
+        // The node of the first octant can't be simplified, while the one of the last octant can
+        let positions =
+            std::iter::once(V3c::new(0, 0, 0))
+                .chain((8..16).flat_map(|x| {
+                    (8..16).flat_map(move |y| (8..16).map(move |z| V3c::new(x, y, z)))
+                }))
+                .collect::<Vec<_>>();
+        tree.insert_batch(positions.iter().map(|p| (*p, red)))
+            .ok()
+            .unwrap();
+        for position in &positions {
+            reference.insert(position, red).ok().unwrap();
+        }
+        assert_eq!(tree.node_count(), reference.node_count());
+        assert_eq!(
+            tree.stored_occupied_bits(Octree::<Albedo, 2>::ROOT_NODE_KEY as usize),
+            reference.stored_occupied_bits(Octree::<Albedo, 2>::ROOT_NODE_KEY as usize)
+        );
+
+        // Emptied nodes are freed up, leaving the tree as if only the first voxel was inserted
+        tree.clear_batch(positions.iter().skip(1).copied())
+            .ok()
+            .unwrap();
+        let mut reference = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        reference.insert(&V3c::new(0, 0, 0), red).ok().unwrap();
+        assert!(tree.get(&V3c::new(0, 0, 0)).is_some_and(|v| *v == red));
+        assert!(tree.get(&V3c::new(8, 8, 8)).is_none());
+        assert_eq!(tree.node_count(), reference.node_count());
+        assert_eq!(
+            tree.stored_occupied_bits(Octree::<Albedo, 2>::ROOT_NODE_KEY as usize),
+            reference.stored_occupied_bits(Octree::<Albedo, 2>::ROOT_NODE_KEY as usize)
+        );
+    }
+
+    #[test]
+    fn test_fill_region() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        let (region_min, region_max) = (V3c::new(1, 3, 0), V3c::new(11, 8, 9));
+        tree.fill_region(&region_min, &region_max, red).ok().unwrap();
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    let inside = (region_min.x..region_max.x).contains(&x)
+                        && (region_min.y..region_max.y).contains(&y)
+                        && (region_min.z..region_max.z).contains(&z);
+                    assert!(
+                        tree.get(&V3c::new(x, y, z)).is_some() == inside,
+                        "Unexpected content at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+
+        // The region is cut to the bounds of the tree
+        tree.fill_region(&V3c::new(12, 12, 12), &V3c::new(20, 20, 20), red)
+            .ok()
+            .unwrap();
+        assert!(tree.get(&V3c::new(15, 15, 15)).is_some_and(|v| *v == red));
+        assert!(tree
+            .fill_region(&V3c::new(16, 0, 0), &V3c::new(20, 1, 1), red)
+            .is_err());
+    }
+
+    #[test]
+    fn test_clear_region() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(0, 0, 0), 16, red)
+            .ok()
+            .unwrap();
+        let (region_min, region_max) = (V3c::new(1, 3, 0), V3c::new(11, 8, 9));
+        tree.clear_region(&region_min, &region_max).ok().unwrap();
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    let inside = (region_min.x..region_max.x).contains(&x)
+                        && (region_min.y..region_max.y).contains(&y)
+                        && (region_min.z..region_max.z).contains(&z);
+                    assert!(
+                        tree.get(&V3c::new(x, y, z)).is_none() == inside,
+                        "Unexpected content at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+        assert!(tree
+            .clear_region(&V3c::new(16, 0, 0), &V3c::new(20, 1, 1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_reserve_nodes() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        tree.reserve_nodes(500);
+        assert!(tree.nodes.capacity() >= tree.nodes.len() + 500);
+        assert!(tree.node_children.capacity() >= tree.node_children.len() + 500);
+
+        tree.set_node_pool_growth(PoolGrowth::Chunked(8));
+        for x in 0..16 {
+            tree.insert(&V3c::new(x, x, x), red).ok().unwrap();
+        }
+        for x in 0..16 {
+            assert!(tree.get(&V3c::new(x, x, x)).is_some_and(|v| *v == red));
+        }
+    }
+
+    #[test]
+    fn test_trim_after_clear() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        tree.insert(&V3c::new(0, 0, 0), red).ok().unwrap();
+        let populated_len = {
+            tree.insert(&V3c::new(15, 15, 15), green).ok().unwrap();
+            tree.nodes.len()
+        };
+        tree.clear(&V3c::new(15, 15, 15)).ok().unwrap();
+        tree.trim();
+        assert!(tree.nodes.len() <= populated_len);
+        assert!(tree.node_children.len() == tree.nodes.len());
+        assert!(tree.get(&V3c::new(0, 0, 0)).is_some_and(|v| *v == red));
+        assert!(tree.get(&V3c::new(15, 15, 15)).is_none());
+
+        // The trimmed tree can be updated again
+        tree.insert(&V3c::new(15, 15, 15), green).ok().unwrap();
+        assert!(tree.get(&V3c::new(15, 15, 15)).is_some_and(|v| *v == green));
+        assert!(tree.node_children.len() >= tree.nodes.len());
+    }
+
+    #[test]
+    fn test_alignment_helpers_where_dim_is_4() {
+        let mut tree = Octree::<Albedo, 4>::new(32).ok().unwrap();
+        assert!(tree.brick_dim() == 4);
+        assert!(tree.depth() == 3);
+        assert!(
+            tree.get_size()
+                == tree.brick_dim() * crate::octree::OCTREE_NODE_DIMENSION.pow(tree.depth())
+        );
+        assert!(tree.brick_origin_of(&V3c::new(5, 3, 31)) == V3c::new(4, 0, 28));
+
+        assert!(tree.node_count() == 1);
+        tree.insert(&V3c::new(0, 0, 0), 0xFF0000FF.into())
+            .ok()
+            .unwrap();
+        assert!(tree.node_count() > 1);
+    }
+
+    #[test]
+    fn test_nearest_valid_size() {
+        assert!(Octree::<Albedo>::nearest_valid_size(0, 1) == 2);
+        assert!(Octree::<Albedo>::nearest_valid_size(5, 1) == 4);
+        assert!(Octree::<Albedo>::nearest_valid_size(6, 1) == 8);
+        assert!(Octree::<Albedo>::nearest_valid_size(64, 8) == 64);
+        assert!(Octree::<Albedo>::nearest_valid_size(100, 8) == 128);
+        assert!(Octree::<Albedo>::nearest_valid_size(8, 8) == 16);
+        assert!(Octree::<Albedo>::nearest_valid_size(u32::MAX, 1) == 1 << 31);
+    }
+
+    #[test]
+    fn test_invalid_size_suggestions() {
+        use crate::octree::types::OctreeError;
+        match Octree::<Albedo, 4>::new(50) {
+            Err(OctreeError::InvalidSize {
+                size,
+                brick_dim,
+                smaller_valid,
+                larger_valid,
+            }) => {
+                assert!(size == 50);
+                assert!(brick_dim == 4);
+                assert!(smaller_valid == Some(32));
+                assert!(larger_valid == Some(64));
+            }
+            _ => panic!("Expected InvalidSize error"),
+        }
+    }
+
+    #[test]
+    fn test_grow() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+
+        let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+        tree.insert(&V3c::new(1, 2, 3), red).ok().unwrap();
+        tree.insert(&V3c::new(0, 0, 0), green).ok().unwrap();
+
+        let offset = tree.grow(7).ok().unwrap();
+        assert!(offset == V3c::new(4, 4, 4));
+        assert!(tree.get_size() == 8);
+        assert!(tree.get(&V3c::new(5, 6, 7)).is_some_and(|v| *v == red));
+        assert!(tree.get(&V3c::new(4, 4, 4)).is_some_and(|v| *v == green));
+        assert!(tree.get(&V3c::new(1, 2, 3)).is_none());
+
+        tree.insert(&V3c::new(0, 0, 0), red).ok().unwrap();
+        assert!(tree.get(&V3c::new(0, 0, 0)).is_some_and(|v| *v == red));
+        tree.clear(&V3c::new(5, 6, 7)).ok().unwrap();
+        assert!(tree.get(&V3c::new(5, 6, 7)).is_none());
+        assert!(tree.grow(8).is_err());
+    }
+
+    #[test]
+    fn test_grow_to() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(4).ok().unwrap();
+        tree.insert(&V3c::new(3, 1, 2), red).ok().unwrap();
+        tree.grow_to(32).ok().unwrap();
+        assert!(tree.get_size() == 32);
+        assert!(tree.get(&V3c::new(3, 1, 2)).is_some_and(|v| *v == red));
+        tree.insert(&V3c::new(31, 31, 31), red).ok().unwrap();
+        assert!(tree.get(&V3c::new(31, 31, 31)).is_some_and(|v| *v == red));
+
+        assert!(tree.grow_to(48).is_err());
+        assert!(tree.grow_to(16).is_err());
+        assert!(tree.grow_to(32).is_ok());
+    }
+
+    #[test]
+    fn test_grow_where_dim_is_2() {
+        let red: Albedo = 0xFF0000FF.into();
+
+        let mut tree = Octree::<Albedo, 2>::new(4).ok().unwrap();
+        tree.insert(&V3c::new(3, 3, 3), red).ok().unwrap();
+        let offset = tree.grow(0).ok().unwrap();
+        assert!(offset == V3c::new(0, 0, 0));
+        assert!(tree.get_size() == 8);
+        assert!(tree.get(&V3c::new(3, 3, 3)).is_some_and(|v| *v == red));
+
+        tree.insert(&V3c::new(7, 7, 7), red).ok().unwrap();
+        assert!(tree.get(&V3c::new(7, 7, 7)).is_some_and(|v| *v == red));
+
+        let mut empty_tree = Octree::<Albedo, 2>::new(4).ok().unwrap();
+        assert!(empty_tree.grow(1).ok().unwrap() == V3c::new(4, 0, 0));
+        assert!(empty_tree.get_size() == 8);
+        assert!(empty_tree.get(&V3c::new(5, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+
+        let mut tree = Octree::<Albedo>::new(16).ok().unwrap();
+        tree.insert(&V3c::new(13, 9, 12), red).ok().unwrap();
+        tree.insert(&V3c::new(12, 8, 13), green).ok().unwrap();
+
+        let offset = tree.shrink_to_fit();
+        assert!(offset == V3c::new(12, 8, 12));
+        assert!(tree.get_size() == 2);
+        assert!(tree.get(&V3c::new(1, 1, 0)).is_some_and(|v| *v == red));
+        assert!(tree.get(&V3c::new(0, 0, 1)).is_some_and(|v| *v == green));
+        assert!(tree.get(&V3c::new(0, 0, 0)).is_none());
+
+        // Growing back should restore the original layout
+        tree.grow(0).ok().unwrap();
+        tree.grow(3).ok().unwrap();
+        tree.grow(7).ok().unwrap();
+        assert!(tree.get_size() == 16);
+        assert!(tree.get(&V3c::new(13, 9, 12)).is_some_and(|v| *v == red));
+        assert!(tree.get(&V3c::new(12, 8, 13)).is_some_and(|v| *v == green));
+    }
+
+    #[test]
+    fn test_shrink_to_fit_where_dim_is_2() {
+        let red: Albedo = 0xFF0000FF.into();
+
+        let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        tree.insert(&V3c::new(1, 1, 1), red).ok().unwrap();
+        tree.insert(&V3c::new(9, 1, 1), red).ok().unwrap();
+
+        // Content spans multiple octants of the root, nothing to do
+        assert!(tree.shrink_to_fit() == V3c::new(0, 0, 0));
+        assert!(tree.get_size() == 16);
+
+        let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        tree.insert(&V3c::new(9, 1, 1), red).ok().unwrap();
+        let offset = tree.shrink_to_fit();
+        assert!(offset == V3c::new(8, 0, 0));
+        assert!(tree.get_size() == 4);
+        assert!(tree.get(&V3c::new(1, 1, 1)).is_some_and(|v| *v == red));
+    }
+    #[test]
+    fn test_histogram_where_dim_is_2() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+
+        let mut tree = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(0, 0, 0), 4, red).ok().unwrap();
+        tree.insert(&V3c::new(7, 7, 7), green).ok().unwrap();
+        tree.insert(&V3c::new(6, 7, 7), green).ok().unwrap();
+        tree.clear(&V3c::new(1, 1, 1)).ok().unwrap();
+
+        let histogram = tree.histogram();
+        assert!(histogram.len() == 2);
+        assert!(histogram[&red] == 63);
+        assert!(histogram[&green] == 2);
+
+        let histogram = tree.histogram_in(&V3c::new(2, 2, 2), &V3c::new(7, 8, 8));
+        assert!(histogram.len() == 2);
+        assert!(histogram[&red] == 8);
+        assert!(histogram[&green] == 1);
+
+        assert!(tree
+            .histogram_in(&V3c::new(4, 0, 0), &V3c::new(6, 6, 6))
+            .is_empty());
+    }
+    #[test]
+    fn test_contacts_with_aabb() {
+        let mut tree = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        // A floor of height 1
+        for x in 0..8 {
+            for z in 0..8 {
+                tree.insert(&V3c::new(x, 0, z), 0xFF0000FF.into())
+                    .ok()
+                    .unwrap();
+            }
+        }
+
+        // A box sinking slightly into the floor
+        let contacts = tree.contacts_with_aabb(&V3c::new(2.5, 0.75, 2.5), &V3c::new(4.5, 2.5, 4.5));
+        assert!(contacts.len() == 1);
+        assert!(contacts[0].normal == V3c::new(0., 1., 0.));
+        assert!((contacts[0].depth - 0.25).abs() < 0.0001);
+        assert!((contacts[0].position.y - 0.875).abs() < 0.0001);
+        assert!((contacts[0].position.x - 3.5).abs() < 0.0001);
+        assert!((contacts[0].position.z - 3.5).abs() < 0.0001);
+
+        // A box resting exactly on the floor has a contact with zero depth
+        let contacts = tree.contacts_with_aabb(&V3c::new(2.5, 1., 2.5), &V3c::new(4.5, 2.5, 4.5));
+        assert!(contacts.len() == 1);
+        assert!(contacts[0].normal == V3c::new(0., 1., 0.));
+        assert!(contacts[0].depth == 0.);
+        assert!((contacts[0].position.y - 1.).abs() < 0.0001);
+
+        // A wall next to the box, on top of the floor
+        tree.insert(&V3c::new(5, 1, 3), 0xFF0000FF.into())
+            .ok()
+            .unwrap();
+        let contacts = tree.contacts_with_aabb(&V3c::new(4.25, 1., 3.), &V3c::new(5.1, 2., 4.));
+        assert!(contacts.len() == 2);
+        let wall = contacts
+            .iter()
+            .find(|contact| contact.normal == V3c::new(-1., 0., 0.))
+            .unwrap();
+        assert!((wall.depth - 0.1).abs() < 0.0001);
+
+        // The floor under the wall is covered, so it is not pushing the box out downwards
+        assert!(contacts
+            .iter()
+            .all(|contact| contact.normal != V3c::new(0., -1., 0.)));
+        let floor = contacts
+            .iter()
+            .find(|contact| contact.normal == V3c::new(0., 1., 0.))
+            .unwrap();
+        assert!(floor.depth == 0.);
+    }
+    #[test]
+    fn test_smooth_mesh_of_single_voxel() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+        tree.insert(&V3c::new(1, 1, 1), red).ok().unwrap();
+
+        let mesh = tree.extract_smooth_mesh(&V3c::new(0, 0, 0), &V3c::new(3, 3, 3));
+        assert!(mesh.positions.len() == 8);
+        assert!(mesh.colors.iter().all(|c| *c == red));
+        assert!(mesh.indices.len() == 6 * 2 * 3);
+        for position in mesh.positions.iter() {
+            for component in [position.x, position.y, position.z] {
+                assert!(component > 1. && component < 2.);
+            }
+        }
+
+        let empty_mesh = tree.extract_smooth_mesh(&V3c::new(3, 3, 3), &V3c::new(4, 4, 4));
+        assert!(empty_mesh.positions.is_empty());
+        assert!(empty_mesh.indices.is_empty());
+    }
+    #[test]
+    fn test_greedy_mesh() {
+        let red: Albedo = 0xFF0000FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree = Octree::<Albedo>::new(4).ok().unwrap();
+        tree.insert(&V3c::new(1, 1, 1), red).ok().unwrap();
+        tree.insert(&V3c::new(2, 1, 1), red).ok().unwrap();
+
+        // The faces of the two voxels are merged into the 6 sides of a box
+        let mesh = tree.extract_greedy_mesh(&V3c::new(0, 0, 0), &V3c::new(4, 4, 4));
+        assert!(mesh.positions.len() == 6 * 4);
+        assert!(mesh.colors.iter().all(|c| *c == red));
+        assert!(mesh.indices.len() == 6 * 2 * 3);
+        for position in mesh.positions.iter() {
+            assert!(position.x == 1. || position.x == 3.);
+            assert!(position.y == 1. || position.y == 2.);
+            assert!(position.z == 1. || position.z == 2.);
+        }
+
+        // Faces of different colors are not merged
+        tree.insert(&V3c::new(2, 1, 1), blue).ok().unwrap();
+        let mesh = tree.extract_greedy_mesh(&V3c::new(0, 0, 0), &V3c::new(4, 4, 4));
+        assert!(mesh.positions.len() == 10 * 4);
+        assert!(mesh.colors.iter().filter(|c| **c == blue).count() == 5 * 4);
+
+        // Only the voxels inside the region are meshed
+        let mesh = tree.extract_greedy_mesh(&V3c::new(0, 0, 0), &V3c::new(2, 4, 4));
+        assert!(mesh.positions.len() == 6 * 4);
+        assert!(mesh.colors.iter().all(|c| *c == red));
+
+        let empty_mesh = tree.extract_greedy_mesh(&V3c::new(3, 3, 3), &V3c::new(4, 4, 4));
+        assert!(empty_mesh.positions.is_empty());
+        assert!(empty_mesh.indices.is_empty());
+
+        // Large solid parts are meshed without visiting their voxels one by one,
+        // only the parts of their faces hidden by other voxels are left out
+        let mut tree = Octree::<Albedo>::new(64).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(0, 0, 0), 32, red)
+            .ok()
+            .unwrap();
+        tree.insert(&V3c::new(32, 0, 0), blue).ok().unwrap();
+        let mesh = tree.extract_greedy_mesh(&V3c::new(0, 0, 0), &V3c::new(64, 64, 64));
+        assert!(mesh.positions.len() == (7 + 5) * 4);
+        assert!(mesh.colors.iter().filter(|c| **c == blue).count() == 5 * 4);
+        assert!(mesh.indices.len() == (7 + 5) * 2 * 3);
+    }
+    #[test]
+    fn test_tick_scheduler() {
+        let red: Albedo = 0xFF0000FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree = Octree::<Albedo>::new(16).ok().unwrap();
+        tree.insert(&V3c::new(9, 1, 1), blue).ok().unwrap();
+        let mut scheduler = TickScheduler::new(4);
+        assert!(scheduler.active_count() == 0);
+
+        // Regions containing tagged voxels and edited regions are active
+        scheduler.activate_tagged(&tree, |voxel| *voxel == blue);
+        assert!(scheduler.is_active(&V3c::new(8, 0, 0)));
+        scheduler
+            .insert(&mut tree, &V3c::new(1, 1, 1), red)
+            .ok()
+            .unwrap();
+        assert!(scheduler.is_active(&V3c::new(3, 3, 3)));
+        assert!(!scheduler.is_active(&V3c::new(4, 3, 3)));
+        assert!(scheduler.active_count() == 2);
+
+        // Only active regions are visited, and only those marked again stay active
+        let mut visited = Vec::new();
+        scheduler.tick(&mut tree, |tree, min, max, scheduler| {
+            visited.push((*min, *max));
+            if tree.get(&V3c::new(1, 1, 1)).is_some() && *min == V3c::new(0, 0, 0) {
+                scheduler
+                    .insert(tree, &V3c::new(5, 1, 1), red)
+                    .ok()
+                    .unwrap();
+            }
+        });
+        assert!(
+            visited
+                == vec![
+                    (V3c::new(0, 0, 0), V3c::new(4, 4, 4)),
+                    (V3c::new(8, 0, 0), V3c::new(12, 4, 4)),
+                ]
+        );
+        assert!(scheduler.active_count() == 1);
+        assert!(scheduler.is_active(&V3c::new(4, 0, 0)));
+
+        scheduler.tick(&mut tree, |_, _, _, _| {});
+        assert!(scheduler.active_count() == 0);
+
+        scheduler.mark_area_active(&V3c::new(3, 0, 0), &V3c::new(5, 1, 1));
+        assert!(scheduler.active_count() == 2);
+    }
+    #[test]
+    fn test_world_gen() {
+        let red: Albedo = 0xFF0000FF.into();
+        assert!(WorldGen::<Albedo, 1>::new(3, 1, |_, _| {}).is_err());
+
+        let mut world = Octree::<Albedo>::new(32).ok().unwrap();
+        let mut world_gen =
+            WorldGen::new(8, 1, move |_sector_min, staging: &mut Octree<Albedo>| {
+                staging.insert(&V3c::new(1, 1, 1), red).ok().unwrap();
+            })
+            .ok()
+            .unwrap();
+
+        // Only sectors with their center inside the radius are requested
+        world_gen.request_around(&world, &V3c::new(4., 4., 4.), 10.);
+        assert!(world_gen.pending_count() == 4);
+        assert!(world_gen.is_requested(&V3c::new(13, 2, 2)));
+        assert!(!world_gen.is_requested(&V3c::new(13, 13, 2)));
+
+        // The sector nearest to the focus is generated first, and each is merged at its position
+        let merged = world_gen.finish(&mut world).ok().unwrap();
+        assert!(merged.len() == 4);
+        assert!(merged[0] == V3c::new(0, 0, 0));
+        assert!(world_gen.pending_count() == 0);
+        assert!(world.get(&V3c::new(1, 1, 1)) == Some(&red));
+        assert!(world.get(&V3c::new(9, 1, 1)) == Some(&red));
+        assert!(world.get(&V3c::new(1, 9, 1)) == Some(&red));
+        assert!(world.get(&V3c::new(1, 1, 9)) == Some(&red));
+        assert!(world.get(&V3c::new(9, 9, 1)).is_none());
+
+        // Sectors are only generated once
+        world_gen.request_around(&world, &V3c::new(4., 4., 4.), 10.);
+        assert!(world_gen.pending_count() == 0);
+        assert!(world_gen.update(&mut world, 8).ok().unwrap().is_empty());
+
+        // A panicking generator fails its sector, the others are still generated
+        let mut world = Octree::<Albedo>::new(32).ok().unwrap();
+        let mut world_gen = WorldGen::new(
+            8,
+            1,
+            move |sector_min: &V3c<u32>, staging: &mut Octree<Albedo>| {
+                assert!(0 == sector_min.x, "Sector can not be generated");
+                staging.insert(&V3c::new(1, 1, 1), red).ok().unwrap();
+            },
+        )
+        .ok()
+        .unwrap();
+        world_gen.request_around(&world, &V3c::new(4., 4., 4.), 10.);
+        assert!(world_gen.finish(&mut world).is_err());
+        world_gen.finish(&mut world).ok().unwrap();
+        assert!(world_gen.pending_count() == 0);
+        assert!(world.get(&V3c::new(1, 1, 1)) == Some(&red));
+        assert!(world.get(&V3c::new(9, 1, 1)).is_none());
+        assert!(world.get(&V3c::new(1, 9, 1)) == Some(&red));
+        assert!(world.get(&V3c::new(1, 1, 9)) == Some(&red));
+    }
+    #[test]
+    fn test_apply_damage() {
+        /// Voxel keeping its user data, so damage can be stored in it
+        #[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+        struct Block {
+            albedo: Albedo,
+            user_data: u32,
+        }
+        impl VoxelData for Block {
+            fn new(albedo: Albedo, user_data: u32) -> Self {
+                Self { albedo, user_data }
+            }
+            fn albedo(&self) -> Albedo {
+                self.albedo
+            }
+            fn user_data(&self) -> u32 {
+                self.user_data
+            }
+            fn clear(&mut self) {
+                *self = Self::default();
+            }
+        }
+
+        // 2 bits of damage above a material id
+        let layer = DamageLayer::new(8, 2);
+        assert!(layer.mask() == 0x300);
+        assert!(layer.max_damage() == 3);
+        assert!(layer.damage_of(layer.with_damage(0x1F, 2)) == 2);
+        assert!(layer.with_damage(0x1F, 7) == 0x31F);
+        assert!(layer.health_of(0x1F) == 1.);
+
+        let stone = Block::new(0x808080FF.into(), 0x1F);
+        let mut tree = Octree::<Block>::new(4).ok().unwrap();
+        tree.insert(&V3c::new(0, 0, 0), stone).ok().unwrap();
+        tree.insert(&V3c::new(1, 0, 0), stone).ok().unwrap();
+        tree.insert(&V3c::new(3, 3, 3), stone).ok().unwrap();
+
+        let destroyed = tree
+            .apply_damage(&layer, &V3c::new(0, 0, 0), &V3c::new(2, 2, 2), 2)
+            .ok()
+            .unwrap();
+        assert!(destroyed.is_empty());
+        let damaged = tree.get(&V3c::new(1, 0, 0)).unwrap();
+        assert!(damaged.albedo == stone.albedo);
+        assert!(damaged.user_data == 0x21F);
+        assert!(tree.get(&V3c::new(3, 3, 3)) == Some(&stone));
+
+        // Voxels reaching the maximum damage are cleared and reported
+        let mut destroyed = tree
+            .apply_damage(&layer, &V3c::new(0, 0, 0), &V3c::new(4, 4, 4), 1)
+            .ok()
+            .unwrap();
+        destroyed.sort_by_key(|(position, _)| position.x);
+        assert!(destroyed.len() == 2);
+        assert!(destroyed[0].0 == V3c::new(0, 0, 0));
+        assert!(destroyed[1].0 == V3c::new(1, 0, 0));
+        assert!(destroyed[1].1.user_data == 0x21F);
+        assert!(tree.get(&V3c::new(0, 0, 0)).is_none());
+        assert!(tree.get(&V3c::new(1, 0, 0)).is_none());
+        assert!(tree.get(&V3c::new(3, 3, 3)).unwrap().user_data == 0x11F);
+    }
+    #[test]
+    fn test_entity_grid() {
+        let mut grid = EntityGrid::<u32>::new(4);
+        grid.insert(1, V3c::new(1., 1., 1.), V3c::new(2., 2., 2.));
+        grid.insert(2, V3c::new(3., 0., 0.), V3c::new(6., 1., 1.));
+        grid.insert(3, V3c::new(-2., 9., 9.), V3c::new(-1., 10., 10.));
+        assert!(grid.len() == 3);
+
+        // Entities possibly touching an edited region
+        let mut touching = grid.entities_touching(&V3c::new(0, 0, 0), &V3c::new(4, 4, 4));
+        touching.sort();
+        assert!(touching == vec![1, 2]);
+        assert!(grid
+            .entities_touching(&V3c::new(5, 0, 2), &V3c::new(8, 4, 4))
+            .is_empty());
+        assert!(grid.entities_in(&V3c::new(-3., 8., 8.), &V3c::new(0., 12., 12.)) == vec![3]);
+
+        // Regions of the tree an entity overlaps
+        let mut cells = grid.cells_of(&2);
+        cells.sort_by_key(|cell| cell.x);
+        assert!(cells == vec![V3c::new(0, 0, 0), V3c::new(4, 0, 0)]);
+        assert!(grid.cells_of(&3).is_empty());
+
+        // Moved and removed entities are no longer found at their previous place
+        grid.insert(1, V3c::new(9., 9., 9.), V3c::new(10., 10., 10.));
+        assert!(grid.len() == 3);
+        assert!(grid
+            .entities_touching(&V3c::new(0, 0, 0), &V3c::new(2, 2, 2))
+            .is_empty());
+        assert!(grid.remove(&2));
+        assert!(!grid.remove(&2));
+        assert!(grid.bounds_of(&2).is_none());
+        assert!(grid
+            .entities_touching(&V3c::new(0, 0, 0), &V3c::new(4, 4, 4))
+            .is_empty());
+    }
+    #[test]
+    fn test_collision_hull() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo>::new(8).ok().unwrap();
+        tree.fill_region(&V3c::new(0, 0, 0), &V3c::new(4, 2, 3), red)
+            .ok()
+            .unwrap();
+        tree.insert(&V3c::new(6, 6, 6), red).ok().unwrap();
+
+        let hull = tree.collision_hull(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8));
+        assert!(hull.len() == 2);
+        assert!(hull.contains(&Aabb {
+            min: V3c::new(0, 0, 0),
+            max: V3c::new(4, 2, 3)
+        }));
+        assert!(hull.contains(&Aabb {
+            min: V3c::new(6, 6, 6),
+            max: V3c::new(7, 7, 7)
+        }));
+
+        // Boxes are cut to the region
+        let hull = tree.collision_hull(&V3c::new(1, 1, 1), &V3c::new(3, 8, 8));
+        assert!(
+            hull == vec![Aabb {
+                min: V3c::new(1, 1, 1),
+                max: V3c::new(3, 2, 3)
+            }]
+        );
+
+        // The hull is recomputed only where it was marked dirty
+        let mut cached_hull = CollisionHull::new(&tree, 4);
+        assert!(cached_hull.update(&tree));
+        assert!(!cached_hull.update(&tree));
+        assert!(cached_hull.boxes().count() == 2);
+        tree.insert(&V3c::new(0, 2, 0), red).ok().unwrap();
+        tree.insert(&V3c::new(6, 2, 6), red).ok().unwrap();
+        cached_hull.mark_dirty(&V3c::new(0, 2, 0), &V3c::new(1, 3, 1));
+        assert!(cached_hull.update_chunks(&tree) == vec![V3c::new(0, 0, 0)]);
+        assert!(cached_hull.chunk_boxes(&V3c::new(0, 0, 0)).len() == 2);
+        assert!(cached_hull.chunk_boxes(&V3c::new(4, 0, 4)).is_empty());
+        assert!(cached_hull.boxes().count() == 3);
+        let volume = cached_hull
+            .boxes()
+            .map(|aabb| {
+                let size = aabb.max - aabb.min;
+                size.x * size.y * size.z
+            })
+            .sum::<u32>();
+        assert!(volume == 4 * 2 * 3 + 2);
+    }
+
+    #[test]
+    fn test_track_modifications() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo>::new(8).ok().unwrap();
+        tree.insert(&V3c::new(1, 1, 1), red).ok().unwrap();
+        assert!(tree.take_modified_regions().is_empty());
+
+        let contains = |regions: &Vec<(V3c<u32>, V3c<u32>)>, position: V3c<u32>| {
+            regions.iter().any(|(min, max)| {
+                min.x <= position.x
+                    && min.y <= position.y
+                    && min.z <= position.z
+                    && position.x < max.x
+                    && position.y < max.y
+                    && position.z < max.z
+            })
+        };
+        tree.track_modifications();
+        tree.insert(&V3c::new(6, 2, 6), red).ok().unwrap();
+        tree.clear(&V3c::new(1, 1, 1)).ok().unwrap();
+        let regions = tree.take_modified_regions();
+        assert!(contains(&regions, V3c::new(6, 2, 6)));
+        assert!(contains(&regions, V3c::new(1, 1, 1)));
+        assert!(tree.take_modified_regions().is_empty());
+
+        // Rearranging the whole tree modifies every part of it
+        tree.mirror(Axis::X);
+        assert!(tree.take_modified_regions() == vec![(V3c::unit(0), V3c::unit(8))]);
+    }
+
+    #[test]
+    fn test_bake_sdf() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo>::new(8).ok().unwrap();
+        assert!(tree
+            .bake_sdf(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8), 1)
+            .ok()
+            .unwrap()
+            .0
+            .iter()
+            .all(|d| *d == f32::MAX));
+
+        tree.insert(&V3c::new(4, 4, 4), red).ok().unwrap();
+        let (sdf, cells) = tree
+            .bake_sdf(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8), 1)
+            .ok()
+            .unwrap();
+        assert!(cells == V3c::new(8, 8, 8));
+        assert!(sdf.len() == 8 * 8 * 8);
+        assert!((sdf[flat_projection(4, 4, 4, 8)] + 0.5).abs() < 0.0001);
+        assert!((sdf[flat_projection(6, 4, 4, 8)] - 1.5).abs() < 0.0001);
+        assert!((sdf[flat_projection(0, 0, 0, 8)] - (48_f32.sqrt() - 0.5)).abs() < 0.0001);
+
+        // Region and resolution is taken into account
+        tree.insert_at_lod(&V3c::new(4, 4, 4), 2, red).ok().unwrap();
+        let (sdf, cells) = tree
+            .bake_sdf(&V3c::new(0, 4, 4), &V3c::new(8, 6, 6), 2)
+            .ok()
+            .unwrap();
+        assert!(cells == V3c::new(4, 1, 1));
+        assert!((sdf[2] + 1.).abs() < 0.0001);
+        assert!((sdf[0] - 3.).abs() < 0.0001);
+
+        assert!(tree
+            .bake_sdf(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8), 0)
+            .is_err());
+    }
+    #[test]
+    fn test_voxel_animation_frames() {
+        let mut animation = VoxelAnimation::<Albedo>::new(false);
+        assert!(animation.frame_at(0.).is_none());
+
+        let mut first = Octree::<Albedo>::new(4).ok().unwrap();
+        first.insert(&V3c::new(0, 0, 0), 0xFF0000FF.into())
+            .ok()
+            .unwrap();
+        let mut second = Octree::<Albedo>::new(4).ok().unwrap();
+        second.insert(&V3c::new(1, 0, 0), 0xFF0000FF.into())
+            .ok()
+            .unwrap();
+
+        animation.add_frame(first, 0.5).ok().unwrap();
+        animation.add_frame(second, 1.).ok().unwrap();
+        assert!(animation
+            .add_frame(Octree::<Albedo>::new(8).ok().unwrap(), 1.)
+            .is_err());
+        assert!(animation
+            .add_frame(Octree::<Albedo>::new(4).ok().unwrap(), 0.)
+            .is_err());
+
+        assert!(animation.frame_count() == 2);
+        assert!(animation.duration() == 1.5);
+        assert!(animation.frame_index_at(0.25) == Some(0));
+        assert!(animation.frame_index_at(0.5) == Some(1));
+        assert!(animation.frame_index_at(1.6).is_none());
+        assert!(animation
+            .frame_at(1.)
+            .unwrap()
+            .get(&V3c::new(1, 0, 0))
+            .is_some());
+
+        animation.looping = true;
+        assert!(animation.frame_index_at(1.6) == Some(0));
+        assert!(animation.frame_index_at(2.5) == Some(1));
+
+        // Frames are swapped into the displayed tree without copying them
+        let mut displayed = Octree::<Albedo>::new(4).ok().unwrap();
+        animation.swap_frame(1, &mut displayed);
+        assert!(displayed.get(&V3c::new(1, 0, 0)).is_some());
+        assert!(animation
+            .frame_at(0.5)
+            .unwrap()
+            .get(&V3c::new(1, 0, 0))
+            .is_none());
+        animation.swap_frame(1, &mut displayed);
+        assert!(displayed.get(&V3c::new(1, 0, 0)).is_none());
+        assert!(animation
+            .frame_at(0.5)
+            .unwrap()
+            .get(&V3c::new(1, 0, 0))
+            .is_some());
+    }
+    #[test]
+    fn test_merge_with_policies() {
+        let red: Albedo = 0xFF0000FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut base = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        base.insert(&V3c::new(0, 0, 0), red).ok().unwrap();
+
+        let mut other = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        other.insert_at_lod(&V3c::new(0, 0, 0), 2, blue).ok().unwrap();
+        other.insert_at_lod(&V3c::new(4, 4, 4), 4, blue).ok().unwrap();
+
+        let mut merged = base.clone();
+        merged.merge(&other, MergePolicy::KeepExisting).ok().unwrap();
+        assert!(*merged.get(&V3c::new(0, 0, 0)).unwrap() == red);
+        assert!(*merged.get(&V3c::new(1, 1, 1)).unwrap() == blue);
+        assert!(*merged.get(&V3c::new(7, 7, 7)).unwrap() == blue);
+
+        let mut merged = base.clone();
+        merged.merge(&other, MergePolicy::Overwrite).ok().unwrap();
+        assert!(*merged.get(&V3c::new(0, 0, 0)).unwrap() == blue);
+
+        let mut merged = base.clone();
+        merged.merge(&other, MergePolicy::Blend).ok().unwrap();
+        assert!(*merged.get(&V3c::new(0, 0, 0)).unwrap() == red.lerp(&blue, 0.5));
+        assert!(*merged.get(&V3c::new(0, 1, 0)).unwrap() == blue);
+        assert!(merged.get(&V3c::new(3, 3, 3)).is_none());
+
+        let larger = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        assert!(base.merge(&larger, MergePolicy::Overwrite).is_err());
+    }
+
+    #[test]
+    fn test_merge_into_larger_tree() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut base = Octree::<Albedo, 2>::new(32).ok().unwrap();
+        base.insert(&V3c::new(1, 1, 1), red).ok().unwrap();
+        base.insert(&V3c::new(20, 20, 20), red).ok().unwrap();
+
+        let mut other = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        other
+            .insert_at_lod(&V3c::new(0, 0, 0), 2, blue)
+            .ok()
+            .unwrap();
+        other
+            .insert_at_lod(&V3c::new(4, 4, 4), 4, green)
+            .ok()
+            .unwrap();
+        other.insert(&V3c::new(6, 0, 3), green).ok().unwrap();
+
+        let mut merged = base.clone();
+        merged
+            .merge(&other, MergePolicy::KeepExisting)
+            .ok()
+            .unwrap();
+        for x in 0..8 {
+            for y in 0..8 {
+                for z in 0..8 {
+                    let position = V3c::new(x, y, z);
+                    let expected = base.get(&position).or(other.get(&position));
+                    assert!(merged.get(&position) == expected);
+                }
+            }
+        }
+        assert!(*merged.get(&V3c::new(20, 20, 20)).unwrap() == red);
+        assert!(merged.iter().count() == 2 * 2 * 2 + 4 * 4 * 4 + 2);
+        assert!(merged.occupied_bits_at(&V3c::new(0, 0, 0), 0).unwrap() & 1 == 1);
+
+        // Nodes are copied over as a whole into the empty part of the tree
+        let mut merged = Octree::<Albedo, 2>::new(32).ok().unwrap();
+        merged.merge(&other, MergePolicy::Overwrite).ok().unwrap();
+        assert!(
+            merged.iter().collect::<std::collections::HashMap<_, _>>()
+                == other.iter().collect::<std::collections::HashMap<_, _>>()
+        );
+    }
+    #[test]
+    fn test_occupancy_bitmap_accessors() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        tree.insert(&V3c::new(7, 7, 7), red).ok().unwrap();
+
+        // The voxel is in the last part of the 4x4x4 bitmaps both in the root and its child
+        assert!(tree.occupied_bits_at(&V3c::new(0, 0, 0), 0) == Some(1 << 63));
+        assert!(tree.occupied_bits_at(&V3c::new(7, 7, 7), 1) == Some(1 << 63));
+        assert!(tree.occupied_bits_at(&V3c::new(0, 0, 0), 1).is_none());
+        assert!(tree.occupied_bits_at(&V3c::new(7, 7, 7), 2).is_none());
+        assert!(tree.occupied_bits_at(&V3c::new(8, 0, 0), 0).is_none());
+
+        let pyramid = tree.occupancy_pyramid(5);
+        assert!(pyramid.len() == 2);
+        assert!(pyramid[0].len() == 1 && pyramid[0][&V3c::new(0, 0, 0)] == 1 << 63);
+        assert!(pyramid[1].len() == 1 && pyramid[1][&V3c::new(4, 4, 4)] == 1 << 63);
+        assert!(tree.occupancy_pyramid(0).len() == 1);
+    }
+
+    #[test]
+    fn test_iter() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        assert!(tree.iter().next().is_none());
+
+        tree.insert(&V3c::new(3, 5, 1), green).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(8, 8, 8), 4, red).ok().unwrap();
+        let voxels = tree.iter().collect::<std::collections::HashMap<_, _>>();
+        assert!(voxels.len() == 1 + 4 * 4 * 4);
+        assert!(voxels[&V3c::new(3, 5, 1)] == green);
+        for x in 8..12 {
+            for y in 8..12 {
+                for z in 8..12 {
+                    assert!(voxels[&V3c::new(x, y, z)] == red);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_iter_region() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        tree.insert(&V3c::new(3, 5, 1), green).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(8, 8, 8), 4, red).ok().unwrap();
+
+        let voxels = tree
+            .iter_region(&V3c::new(0, 0, 0), &V3c::new(10, 9, 10))
+            .collect::<std::collections::HashMap<_, _>>();
+        // The solid area is cut to 2x1x2 voxels by the region
+        assert!(voxels.len() == 1 + 2 * 2);
+        assert!(voxels[&V3c::new(3, 5, 1)] == green);
+        assert!(voxels[&V3c::new(9, 8, 9)] == red);
+        assert!(!voxels.contains_key(&V3c::new(10, 8, 8)));
+
+        let mut visited = 0;
+        tree.for_each_in(
+            &V3c::new(4, 0, 0),
+            &V3c::new(16, 16, 16),
+            |position, voxel| {
+                assert!(8 <= position.x && *voxel == red);
+                visited += 1;
+            },
+        );
+        assert!(visited == 4 * 4 * 4);
+    }
+
+    #[test]
+    fn test_is_region_empty() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo, 4>::new(32).ok().unwrap();
+        assert!(tree.is_region_empty(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32)));
+
+        tree.insert(&V3c::new(5, 6, 7), red).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(16, 16, 16), 8, red)
+            .ok()
+            .unwrap();
+        assert!(!tree.is_region_empty(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32)));
+        assert!(!tree.is_region_empty(&V3c::new(5, 6, 7), &V3c::new(6, 7, 8)));
+        assert!(tree.is_region_empty(&V3c::new(6, 6, 7), &V3c::new(8, 8, 8)));
+        assert!(tree.is_region_empty(&V3c::new(0, 0, 0), &V3c::new(5, 32, 32)));
+        assert!(!tree.is_region_empty(&V3c::new(23, 23, 23), &V3c::new(40, 40, 40)));
+        assert!(tree.is_region_empty(&V3c::new(24, 0, 0), &V3c::new(32, 32, 32)));
+
+        // Occupied parts of a leaf completely inside the region are decided by the bitmap
+        tree.insert(&V3c::new(1, 1, 1), red).ok().unwrap();
+        assert!(!tree.is_region_empty(&V3c::new(4, 6, 6), &V3c::new(6, 8, 8)));
+        tree.clear(&V3c::new(5, 6, 7)).ok().unwrap();
+        assert!(tree.is_region_empty(&V3c::new(4, 6, 6), &V3c::new(6, 8, 8)));
+        assert!(!tree.is_region_empty(&V3c::new(1, 0, 0), &V3c::new(3, 3, 3)));
+        tree.clear(&V3c::new(1, 1, 1)).ok().unwrap();
+        assert!(tree.is_region_empty(&V3c::new(0, 0, 0), &V3c::new(16, 16, 16)));
+    }
+
+    #[test]
+    fn test_scan_axis() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo, 4>::new(32).ok().unwrap();
+        tree.insert(&V3c::new(5, 2, 7), red).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(16, 16, 16), 8, red)
+            .ok()
+            .unwrap();
+
+        // Falling down from above the voxel
+        let down = V3c::new(0, -1, 0);
+        assert!(tree.scan_axis(&V3c::new(5, 30, 7), &down, 32) == Some(28));
+        assert!(tree.scan_axis(&V3c::new(5, 30, 7), &down, 28).is_none());
+        assert!(tree.scan_axis(&V3c::new(5, 2, 7), &down, 1) == Some(0));
+        assert!(tree.scan_axis(&V3c::new(5, 1, 7), &down, 32).is_none());
+
+        // Scanning into the solid area
+        assert!(tree.scan_axis(&V3c::new(0, 20, 20), &V3c::new(1, 0, 0), 32) == Some(16));
+        assert!(tree.scan_axis(&V3c::new(31, 20, 20), &V3c::new(-1, 0, 0), 32) == Some(8));
+        assert!(tree.scan_axis(&V3c::new(20, 20, 0), &V3c::new(0, 0, 1), 32) == Some(16));
+        let forward = V3c::new(0, 0, 1);
+        assert!(tree.scan_axis(&V3c::new(0, 0, 0), &forward, 32).is_none());
+    }
+
+    #[test]
+    fn test_copy_and_paste_region() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut source = Octree::<Albedo, 2>::new(16).ok().unwrap();
+        source
+            .insert_at_lod(&V3c::new(4, 4, 4), 4, red)
+            .ok()
+            .unwrap();
+        source.insert(&V3c::new(1, 2, 3), green).ok().unwrap();
+        source.insert(&V3c::new(9, 9, 9), green).ok().unwrap();
+
+        let region = source.copy_region(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8));
+        assert!(region.size() == V3c::new(8, 8, 8));
+        assert!(!region.is_empty());
+        assert!(region.bricks.len() == 1);
+        assert!(source
+            .copy_region(&V3c::new(12, 0, 0), &V3c::new(16, 4, 4))
+            .is_empty());
+
+        // Brick aligned and unaligned destinations, empty voxels of pasted bricks keep the tree contents
+        let blue: Albedo = 0x0000FFFF.into();
+        for destination in [V3c::new(8, 8, 8), V3c::new(3, 1, 2)] {
+            let mut target = Octree::<Albedo, 2>::new(16).ok().unwrap();
+            target
+                .insert(&(destination + V3c::new(0, 2, 2)), blue)
+                .ok()
+                .unwrap();
+            target.paste_region(&region, &destination).ok().unwrap();
+            for x in 0..16 {
+                for y in 0..16 {
+                    for z in 0..16 {
+                        let position = V3c::new(x, y, z);
+                        let in_block = (4..8).contains(&(x as i32 - destination.x as i32))
+                            && (4..8).contains(&(y as i32 - destination.y as i32))
+                            && (4..8).contains(&(z as i32 - destination.z as i32));
+                        let expected = if in_block {
+                            Some(red)
+                        } else if position == destination + V3c::new(1, 2, 3) {
+                            Some(green)
+                        } else if position == destination + V3c::new(0, 2, 2) {
+                            Some(blue)
+                        } else {
+                            None
+                        };
+                        assert!(target.get(&position).copied() == expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mirror_rotate_and_translate() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree = Octree::<Albedo, 4>::new(16).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(8, 0, 4), 4, red).ok().unwrap();
+        tree.insert(&V3c::new(1, 2, 3), green).ok().unwrap();
+        tree.insert(&V3c::new(14, 13, 0), green).ok().unwrap();
+        let original = tree.iter().collect::<std::collections::HashMap<_, _>>();
+        let transformed = |mapping: fn(V3c<u32>) -> V3c<u32>| {
+            original
+                .iter()
+                .map(|(position, voxel)| (mapping(*position), *voxel))
+                .collect::<std::collections::HashMap<_, _>>()
+        };
+
+        let mut mirrored = tree.clone();
+        mirrored.mirror(Axis::X);
+        let voxels = mirrored.iter().collect::<std::collections::HashMap<_, _>>();
+        assert!(voxels == transformed(|p| V3c::new(15 - p.x, p.y, p.z)));
+        mirrored.mirror(Axis::X);
+        assert!(mirrored.iter().collect::<std::collections::HashMap<_, _>>() == original);
+
+        let mut rotated = tree.clone();
+        rotated.rotate_90(Axis::Y);
+        let voxels = rotated.iter().collect::<std::collections::HashMap<_, _>>();
+        assert!(voxels == transformed(|p| V3c::new(p.z, p.y, 15 - p.x)));
+        for _ in 0..3 {
+            rotated.rotate_90(Axis::Y);
+        }
+        assert!(rotated.iter().collect::<std::collections::HashMap<_, _>>() == original);
+
+        let mut translated = tree.clone();
+        translated.translate(&V3c::new(-1, 4, 0)).ok().unwrap();
+        let voxels = translated
+            .iter()
+            .collect::<std::collections::HashMap<_, _>>();
+        assert!(voxels.len() == original.len() - 1); // (14, 13, 0) is moved out of the tree
+        assert!(voxels[&V3c::new(0, 6, 3)] == green);
+        assert!(voxels[&V3c::new(7, 4, 4)] == red);
+        assert!(voxels[&V3c::new(10, 7, 7)] == red);
+
+        // Offsets aligned to nodes or bricks move them as a whole
+        for offset in [V3c::new(8, 0, -8), V3c::new(0, 4, 4), V3c::new(0, 0, 0)] {
+            let mut translated = tree.clone();
+            translated.translate(&offset).ok().unwrap();
+            let expected = original
+                .iter()
+                .map(|(position, voxel)| (V3c::<i32>::from(*position) + offset, *voxel))
+                .filter(|(position, _)| {
+                    (0..16).contains(&position.x)
+                        && (0..16).contains(&position.y)
+                        && (0..16).contains(&position.z)
+                })
+                .map(|(position, voxel)| (V3c::<u32>::from(position), voxel))
+                .collect::<std::collections::HashMap<_, _>>();
+            assert!(
+                translated
+                    .iter()
+                    .collect::<std::collections::HashMap<_, _>>()
+                    == expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_brick_checksums() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(0, 0, 0), 4, red).ok().unwrap();
+        tree.insert(&V3c::new(7, 7, 7), red).ok().unwrap();
+
+        // Checksums don't depend on the structure of the tree
+        let mut voxel_by_voxel = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    voxel_by_voxel.insert(&V3c::new(x, y, z), red).ok().unwrap();
+                }
+            }
+        }
+        voxel_by_voxel.insert(&V3c::new(7, 7, 7), red).ok().unwrap();
+
+        let checksums = tree.brick_checksums();
+        assert!(checksums.len() == 8 + 1);
+        assert!(voxel_by_voxel.verify_checksums(&checksums).is_empty());
+
+        // Checksums don't depend on the position of the brick, the platform or the version of the library
+        assert!(checksums[&V3c::new(0, 0, 0)] == 0xEC09_CBD1_BE1E_B9A2);
+        assert!(checksums[&V3c::new(2, 2, 2)] == checksums[&V3c::new(0, 0, 0)]);
+        assert!(checksums[&V3c::new(6, 6, 6)] != checksums[&V3c::new(0, 0, 0)]);
+
+        voxel_by_voxel
+            .insert(&V3c::new(3, 2, 1), 0x00FF00FF.into())
+            .ok()
+            .unwrap();
+        voxel_by_voxel.clear(&V3c::new(7, 7, 7)).ok().unwrap();
+        assert!(
+            voxel_by_voxel.verify_checksums(&checksums)
+                == vec![V3c::new(2, 2, 0), V3c::new(6, 6, 6)]
+        );
+    }
+
+    #[test]
+    fn test_maintained_checksums() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree = Octree::<Albedo, 2>::new(8).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(0, 0, 0), 4, red).ok().unwrap();
+        assert!(!tree.checksums_enabled());
+        assert!(tree.verify_stored_checksums().is_empty());
+
+        tree.enable_checksums();
+        assert!(tree.checksums_enabled());
+        tree.insert(&V3c::new(7, 7, 7), green).ok().unwrap();
+        tree.clear(&V3c::new(1, 1, 1)).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(4, 0, 0), 4, green)
+            .ok()
+            .unwrap();
+        tree.insert_batch((0..8).map(|x| (V3c::new(x, 5, 5), red)))
+            .ok()
+            .unwrap();
+        *tree.get_mut(&V3c::new(0, 0, 0)).unwrap() = green;
+        assert!(tree.verify_stored_checksums().is_empty());
+        assert!(tree
+            .verify_checksums(&tree.checksums.as_ref().unwrap().values.clone())
+            .is_empty());
+
+        // Corruption in written bricks is detected by the write, in other bricks by the verification
+        let checksums = tree.checksums.as_mut().unwrap();
+        checksums.values.insert(V3c::new(2, 2, 2), 0);
+        checksums.values.insert(V3c::new(6, 0, 0), 0);
+        tree.insert(&V3c::new(3, 3, 3), green).ok().unwrap();
+        assert!(tree.verify_stored_checksums() == vec![V3c::new(2, 2, 2), V3c::new(6, 0, 0)]);
+        assert!(tree.verify_stored_checksums() == vec![V3c::new(6, 0, 0)]);
+        tree.insert(&V3c::new(6, 0, 0), red).ok().unwrap();
+        assert!(tree.verify_stored_checksums() == vec![V3c::new(6, 0, 0)]);
+        assert!(tree.verify_stored_checksums().is_empty());
+
+        // Checksums follow the contents of the tree when it is rearranged
+        tree.grow(7).ok().unwrap();
+        assert!(tree.verify_stored_checksums().is_empty());
+        tree.shrink_to_fit();
+        assert!(tree.verify_stored_checksums().is_empty());
+        tree.mirror(Axis::X);
+        assert!(tree.verify_stored_checksums().is_empty());
+
+        tree.disable_checksums();
+        assert!(!tree.checksums_enabled());
+    }
+}
+
+mod albedo_tests {
+    use crate::octree::types::Albedo;
+
+    #[test]
+    fn test_from_hsv() {
+        assert!(Albedo::from_hsv(0., 1., 1.) == 0xFF0000FF.into());
+        assert!(Albedo::from_hsv(120., 1., 1.) == 0x00FF00FF.into());
+        assert!(Albedo::from_hsv(240., 1., 1.) == 0x0000FFFF.into());
+        assert!(Albedo::from_hsv(360., 1., 1.) == 0xFF0000FF.into());
+        assert!(Albedo::from_hsv(60., 1., 1.) == 0xFFFF00FF.into());
+        assert!(Albedo::from_hsv(200., 0., 1.) == 0xFFFFFFFF.into());
+        assert!(Albedo::from_hsv(200., 1., 0.) == 0x000000FF.into());
+    }
+
+    #[test]
+    fn test_lerp_and_gradient() {
+        let black: Albedo = 0x00000000.into();
+        let white: Albedo = 0xFFFFFFFF.into();
+        assert!(black.lerp(&white, 0.) == black);
+        assert!(black.lerp(&white, 1.) == white);
+        assert!(black.lerp(&white, 2.) == white);
+        assert!(black.lerp(&white, 0.5) == 0x80808080.into());
+
+        let gradient = Albedo::gradient(&black, &white, 5);
+        assert!(gradient.len() == 5);
+        assert!(gradient[0] == black);
+        assert!(gradient[4] == white);
+        assert!(gradient.windows(2).all(|w| w[0].r < w[1].r));
+        assert!(Albedo::gradient(&black, &white, 1) == vec![black]);
+        assert!(Albedo::gradient(&black, &white, 0).is_empty());
+    }
+
+    #[test]
+    fn test_luminance_and_palette() {
+        let black: Albedo = 0x000000FF.into();
+        let white: Albedo = 0xFFFFFFFF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        assert!(black.luminance() == 0.);
+        assert!((white.luminance() - 1.).abs() < 0.0001);
+        assert!(green.luminance() > blue.luminance());
+
+        let palette = Albedo::hue_palette(6, 1., 1.);
+        assert!(palette.len() == 6);
+        assert!(palette[0] == 0xFF0000FF.into());
+        assert!(palette[2] == green);
+        assert!(palette[4] == blue);
+    }
 }
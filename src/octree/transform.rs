@@ -0,0 +1,308 @@
+use crate::object_pool::empty_marker;
+use crate::octree::{
+    detail::child_octant_for,
+    types::{
+        Axis, BrickData, NodeChildren, NodeChildrenArray, NodeContent, OctreeError, OctreeRegion,
+    },
+    Octree, V3c, VoxelData,
+};
+use crate::spatial::{
+    lut::OCTANT_OFFSET_REGION_LUT,
+    math::{flat_projection, hash_region, BITMAP_DIMENSION},
+    Cube,
+};
+use std::collections::HashMap;
+
+/// Provides the index an element is moved to inside a matrix of the given size
+type IndexMapping = dyn Fn(V3c<usize>, usize) -> V3c<usize>;
+
+impl<T, const DIM: usize> Octree<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData,
+{
+    /// Mirrors the contents of the tree along the given axis
+    /// Child nodes, bricks and occupancy bitmaps are reordered in place, voxels are not re-inserted
+    pub fn mirror(&mut self, axis: Axis) {
+        self.transform_nodes(&move |index, size| match axis {
+            Axis::X => V3c::new(size - 1 - index.x, index.y, index.z),
+            Axis::Y => V3c::new(index.x, size - 1 - index.y, index.z),
+            Axis::Z => V3c::new(index.x, index.y, size - 1 - index.z),
+        });
+        self.rebuild_checksums();
+    }
+
+    /// Rotates the contents of the tree by 90 degrees counter-clockwise around the given axis,
+    /// looking from the positive end of the axis towards the origin
+    /// Child nodes, bricks and occupancy bitmaps are reordered in place, voxels are not re-inserted
+    pub fn rotate_90(&mut self, axis: Axis) {
+        self.transform_nodes(&move |index, size| match axis {
+            Axis::X => V3c::new(index.x, size - 1 - index.z, index.y),
+            Axis::Y => V3c::new(index.z, index.y, size - 1 - index.x),
+            Axis::Z => V3c::new(size - 1 - index.y, index.x, index.z),
+        });
+        self.rebuild_checksums();
+    }
+
+    /// Moves the contents of the tree by the given offset, voxels moved outside the tree are removed
+    /// Nodes and bricks are moved as a whole where the offset is aligned to their size,
+    /// the rest of the voxels are re-inserted one cell at a time, which is slower
+    pub fn translate(&mut self, offset: &V3c<i32>) -> Result<(), OctreeError> {
+        let mut translated = Self::new(self.octree_size)?;
+        translated.auto_simplify = self.auto_simplify;
+        let mut touched_nodes = HashMap::new();
+        let mut remainder = OctreeRegion {
+            size: V3c::unit(self.octree_size),
+            cells: Vec::new(),
+            bricks: Vec::new(),
+        };
+        self.translate_node(
+            &mut translated,
+            Self::ROOT_NODE_KEY as usize,
+            &Cube::root_bounds(self.octree_size as f32),
+            offset,
+            &mut touched_nodes,
+            &mut remainder,
+        );
+        translated.refresh_touched_nodes(touched_nodes);
+        translated.paste_region(&remainder, &V3c::unit(0))?;
+        self.nodes = translated.nodes;
+        self.node_children = translated.node_children;
+        self.rebuild_checksums();
+        Ok(())
+    }
+
+    /// Moves the given node of this tree into the translated tree
+    /// The node is copied as a whole if the offset is aligned to its size, otherwise its children are moved one by one
+    /// * `touched_nodes` - collects the nodes of the translated tree the copied nodes were attached to, with their depth
+    /// * `remainder` - collects the bricks and cells which can't be moved as a whole, by their translated position
+    fn translate_node(
+        &self,
+        translated: &mut Self,
+        node_key: usize,
+        node_bounds: &Cube,
+        offset: &V3c<i32>,
+        touched_nodes: &mut HashMap<u32, usize>,
+        remainder: &mut OctreeRegion<T, DIM>,
+    ) {
+        if !self.nodes.key_is_valid(node_key) {
+            return;
+        }
+        let node_size = node_bounds.size as i32;
+        if 0 == offset.x % node_size && 0 == offset.y % node_size && 0 == offset.z % node_size {
+            // An aligned node is moved either completely inside or completely outside the tree
+            let destination = V3c::<i32>::from(node_bounds.min_position) + *offset;
+            if self.contains_translated(&destination, node_size) {
+                translated.attach_node_from(
+                    self,
+                    node_key,
+                    &destination.into(),
+                    node_size as u32,
+                    touched_nodes,
+                );
+            }
+            return;
+        }
+        match self.nodes.get(node_key) {
+            NodeContent::Nothing => {}
+            NodeContent::Internal(_) => {
+                for octant in 0..8u8 {
+                    self.translate_node(
+                        translated,
+                        self.node_children[node_key][octant as u32] as usize,
+                        &node_bounds.child_bounds_for(octant),
+                        offset,
+                        touched_nodes,
+                        remainder,
+                    );
+                }
+            }
+            NodeContent::Leaf(bricks) => {
+                for (octant, brick) in bricks.iter().enumerate() {
+                    self.translate_brick(
+                        brick,
+                        &node_bounds.child_bounds_for(octant as u8),
+                        offset,
+                        remainder,
+                    );
+                }
+            }
+            NodeContent::UniformLeaf(brick) => {
+                self.translate_brick(brick, node_bounds, offset, remainder);
+            }
+        }
+    }
+
+    /// Collects the given brick into the remainder of the translation, by its translated position
+    /// Parted bricks are kept whole if the offset is aligned to DIM, everything else is collected cell by cell
+    fn translate_brick(
+        &self,
+        brick: &BrickData<T, DIM>,
+        brick_bounds: &Cube,
+        offset: &V3c<i32>,
+        remainder: &mut OctreeRegion<T, DIM>,
+    ) {
+        let dim = DIM as i32;
+        if matches!(brick, BrickData::Parted(_))
+            && DIM == brick_bounds.size as usize
+            && 0 == offset.x % dim
+            && 0 == offset.y % dim
+            && 0 == offset.z % dim
+        {
+            let destination = V3c::<i32>::from(brick_bounds.min_position) + *offset;
+            if self.contains_translated(&destination, dim) {
+                remainder.bricks.push((destination.into(), brick.clone()));
+            }
+            return;
+        }
+
+        let brick_max = brick_bounds.min_position + V3c::unit(brick_bounds.size);
+        Self::visit_filled_cells_of_brick(
+            brick,
+            brick_bounds,
+            &brick_bounds.min_position,
+            &brick_max,
+            &mut |bounds, voxel| {
+                let cell_size = bounds.size as i32;
+                let destination = V3c::<i32>::from(bounds.min_position) + *offset;
+                if self.contains_translated(&destination, cell_size) {
+                    remainder
+                        .cells
+                        .push((destination.into(), cell_size as u32, *voxel));
+                    return;
+                }
+
+                // Only the voxels of the cell moved inside the tree are kept
+                let size = self.octree_size as i32;
+                for x in destination.x.max(0)..(destination.x + cell_size).min(size) {
+                    for y in destination.y.max(0)..(destination.y + cell_size).min(size) {
+                        for z in destination.z.max(0)..(destination.z + cell_size).min(size) {
+                            remainder.cells.push((
+                                V3c::new(x as u32, y as u32, z as u32),
+                                1,
+                                *voxel,
+                            ));
+                        }
+                    }
+                }
+            },
+        );
+    }
+
+    /// True if the cube at the given translated position is completely inside the tree
+    fn contains_translated(&self, cube_min: &V3c<i32>, cube_size: i32) -> bool {
+        let size = self.octree_size as i32;
+        0 <= cube_min.x
+            && 0 <= cube_min.y
+            && 0 <= cube_min.z
+            && cube_min.x + cube_size <= size
+            && cube_min.y + cube_size <= size
+            && cube_min.z + cube_size <= size
+    }
+
+    /// Replaces the node at the given position and size with a copy of the given node of the other tree
+    /// Empty nodes on the way are made internal, their occupied bits need to be refreshed afterwards
+    /// * `touched_nodes` - collects the nodes on the way, with their depth
+    fn attach_node_from(
+        &mut self,
+        other: &Self,
+        other_key: usize,
+        node_min: &V3c<u32>,
+        node_size: u32,
+        touched_nodes: &mut HashMap<u32, usize>,
+    ) {
+        let mut node_key = Self::ROOT_NODE_KEY as usize;
+        let mut node_bounds = Cube::root_bounds(self.octree_size as f32);
+        let mut depth = 0;
+        while node_bounds.size > node_size as f32 {
+            if matches!(self.nodes.get(node_key), NodeContent::Nothing) {
+                *self.nodes.get_mut(node_key) = NodeContent::Internal(0);
+                self.node_children[node_key] = NodeChildren::new(empty_marker());
+            }
+            touched_nodes.insert(node_key as u32, depth);
+            let octant = child_octant_for(&node_bounds, &V3c::<f32>::from(*node_min));
+            node_key = self.child_for_merge(node_key, octant as u32);
+            node_bounds = node_bounds.child_bounds_for(octant);
+            depth += 1;
+        }
+        self.copy_node_from(other, node_key, other_key);
+    }
+
+    /// Moves every child, brick, voxel and occupancy bit of each node based on the given mapping
+    /// The mapping has to be a symmetry of the cube, so it can be applied on every level independently
+    fn transform_nodes(&mut self, mapping: &IndexMapping) {
+        for node_key in 0..self.nodes.len() {
+            if !self.nodes.key_is_valid(node_key) {
+                continue;
+            }
+            match self.nodes.get_mut(node_key) {
+                NodeContent::Nothing => {}
+                NodeContent::Internal(occupied_bits) => {
+                    *occupied_bits = transform_bitmap(*occupied_bits, mapping);
+                }
+                NodeContent::UniformLeaf(brick) => {
+                    transform_brick(brick, mapping);
+                }
+                NodeContent::Leaf(bricks) => {
+                    for brick in bricks.iter_mut() {
+                        transform_brick(brick, mapping);
+                    }
+                    *bricks = transform_octants(bricks, mapping);
+                }
+            }
+            match &mut self.node_children[node_key].content {
+                NodeChildrenArray::NoChildren => {}
+                NodeChildrenArray::Children(children) => {
+                    *children = transform_octants(children, mapping);
+                }
+                NodeChildrenArray::OccupancyBitmap(occupied_bits) => {
+                    *occupied_bits = transform_bitmap(*occupied_bits, mapping);
+                }
+            }
+        }
+    }
+}
+
+/// Reorders the given items stored by octant based on the mapping
+fn transform_octants<U: Clone>(items: &[U; 8], mapping: &IndexMapping) -> [U; 8] {
+    let mut result = items.clone();
+    for (octant, item) in items.iter().enumerate() {
+        let target = mapping(V3c::<usize>::from(OCTANT_OFFSET_REGION_LUT[octant]), 2);
+        result[hash_region(&V3c::<f32>::from(target), 1.) as usize] = item.clone();
+    }
+    result
+}
+
+/// Moves the bits of the given occupancy bitmap based on the mapping
+fn transform_bitmap(bitmap: u64, mapping: &IndexMapping) -> u64 {
+    let mut result = 0;
+    for x in 0..BITMAP_DIMENSION {
+        for y in 0..BITMAP_DIMENSION {
+            for z in 0..BITMAP_DIMENSION {
+                if 0 == bitmap & (0x01 << flat_projection(x, y, z, BITMAP_DIMENSION)) {
+                    continue;
+                }
+                let target = mapping(V3c::new(x, y, z), BITMAP_DIMENSION);
+                result |= 0x01 << flat_projection(target.x, target.y, target.z, BITMAP_DIMENSION);
+            }
+        }
+    }
+    result
+}
+
+/// Moves the voxels inside the given brick based on the mapping
+fn transform_brick<T, const DIM: usize>(brick: &mut BrickData<T, DIM>, mapping: &IndexMapping)
+where
+    T: VoxelData + PartialEq + Clone + Copy + Default,
+{
+    if let BrickData::Parted(voxels) = brick {
+        let original = voxels.clone();
+        for x in 0..DIM {
+            for y in 0..DIM {
+                for z in 0..DIM {
+                    let target = mapping(V3c::new(x, y, z), DIM);
+                    voxels[target.x][target.y][target.z] = original[x][y][z];
+                }
+            }
+        }
+    }
+}
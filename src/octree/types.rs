@@ -1,5 +1,9 @@
 use crate::object_pool::ObjectPool;
-use std::error::Error;
+use crate::spatial::math::vector::V3c;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+};
 
 #[cfg(feature = "serialization")]
 use serde::{Deserialize, Serialize};
@@ -31,12 +35,38 @@ where
 /// error types during usage or creation of the octree
 #[derive(Debug)]
 pub enum OctreeError {
-    InvalidSize(u32),
+    /// The size of the tree must be `brick_dim * (2^x)` and larger, than brick_dim
+    /// The closest valid sizes below and above the requested one are provided, if there are any
+    InvalidSize {
+        size: u32,
+        brick_dim: u32,
+        smaller_valid: Option<u32>,
+        larger_valid: Option<u32>,
+    },
     InvalidBrickDimension(u32),
     InvalidStructure(Box<dyn Error>),
     InvalidPosition { x: u32, y: u32, z: u32 },
 }
 
+/// error types while loading a saved octree
+#[derive(Debug)]
+pub enum OctreeLoadError {
+    /// The saved data could not be read
+    Io(std::io::Error),
+    /// The saved data is not a valid encoding of an octree, e.g. it is truncated
+    Decode(String),
+    /// The data was saved in a format version this version of the library can not read
+    VersionMismatch { found: u32, supported: u32 },
+    /// The decoded data does not form a valid octree
+    InvalidStructure(String),
+}
+
+impl From<std::io::Error> for OctreeLoadError {
+    fn from(error: std::io::Error) -> Self {
+        OctreeLoadError::Io(error)
+    }
+}
+
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
 #[cfg_attr(test, derive(Eq))]
 #[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
@@ -70,6 +100,15 @@ pub trait VoxelData {
     }
     /// Implementation to clear the contained data, as well as albedo
     fn clear(&mut self);
+    /// The density of the voxel used by smooth surface extraction, in range 0..=1
+    /// Surfaces are placed where the density crosses 0.5
+    fn density(&self) -> f32 {
+        if self.is_empty() {
+            0.
+        } else {
+            1.
+        }
+    }
 }
 
 /// Sparse Octree of Nodes, where each node contains a brick of voxels.
@@ -86,6 +125,138 @@ where
     pub(crate) octree_size: u32,
     pub(crate) nodes: ObjectPool<NodeContent<T, DIM>>,
     pub(crate) node_children: Vec<NodeChildren<u32>>, // Children index values of each Node
+
+    /// Application defined key-value pairs stored together with the tree, e.g. author or seed
+    pub(crate) metadata: BTreeMap<String, Vec<u8>>,
+
+    /// Checksums of the bricks, maintained on every write while enabled
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub(crate) checksums: Option<BrickChecksums>,
+
+    /// Regions written since they were last taken, recorded while modifications are tracked
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub(crate) modified_regions: Option<Vec<(V3c<u32>, V3c<u32>)>>,
+}
+
+/// Stored checksums of the bricks inside a tree, updated together with the bricks
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct BrickChecksums {
+    /// The checksum of every brick containing voxels, by the minimum position of the brick
+    pub(crate) values: HashMap<V3c<u32>, u64>,
+
+    /// Bricks not matching their stored checksum right before they were written
+    pub(crate) corrupted: HashSet<V3c<u32>>,
+
+    /// Written bricks with outdated checksums, updated after the current write or batch of writes
+    pub(crate) pending: HashSet<V3c<u32>>,
+
+    /// True while a batch of updates is running, checksums are updated once it is done
+    pub(crate) deferred: bool,
+}
+
+/// Properties of a saved octree, readable without loading its nodes
+/// Statistics only stored by format version 2 or later are None for trees saved before
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OctreeHeader {
+    /// The size of the tree in each dimension
+    pub size: u32,
+
+    /// The size of the voxel bricks in each dimension, i.e. DIM of the tree
+    pub brick_dim: Option<u32>,
+
+    /// True if the tree simplifies its nodes automatically after each update
+    pub auto_simplify: bool,
+
+    /// The number of voxels inside the tree
+    pub voxel_count: Option<u64>,
+
+    /// The number of nodes allocated inside the tree, including the root node
+    pub node_count: usize,
+
+    /// The number of distinct colors of the voxels
+    pub color_palette_size: Option<usize>,
+
+    /// The number of distinct user data values of the voxels
+    pub user_data_palette_size: Option<usize>,
+
+    /// The version of the byte layout the tree was saved with
+    pub format_version: u32,
+
+    /// Application defined key-value pairs stored together with the tree
+    pub metadata: BTreeMap<String, Vec<u8>>,
+}
+
+/// Decides the result where both merged trees contain a voxel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// The voxel of the merged tree replaces the existing one
+    Overwrite,
+    /// The existing voxel is kept
+    KeepExisting,
+    /// The albedo of the two voxels is averaged, user data is taken from the merged tree
+    Blend,
+}
+
+/// A copy of the voxels inside a box of a tree, which can be pasted into any tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct OctreeRegion<T, const DIM: usize = 1>
+where
+    T: Clone + PartialEq + VoxelData,
+{
+    /// The extent of the copied box
+    pub(crate) size: V3c<u32>,
+
+    /// The filled cubes of the box: their minimum position relative to the box, their size and data
+    pub(crate) cells: Vec<(V3c<u32>, u32, T)>,
+
+    /// The parted bricks completely inside the box: their minimum position relative to the box, and their data
+    pub(crate) bricks: Vec<(V3c<u32>, BrickData<T, DIM>)>,
+}
+
+/// One of the axes of the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// Decides which voxels are filled by a voxelized mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoxelFill {
+    /// Only the voxels on the surface of the mesh
+    Shell,
+    /// The surface and every voxel enclosed by the mesh
+    Solid,
+}
+
+/// Decides which voxels are counted as part of a voxelized surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoxelCoverage {
+    /// Every voxel touching the surface
+    Conservative,
+    /// A thinner surface, which still can not be passed through along the axes
+    SixSeparating,
+}
+
+/// A contact point between the voxels of the tree and another shape
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    /// The center of the contact area
+    pub position: V3c<f32>,
+    /// Unit vector pointing from the voxel surface towards the other shape
+    pub normal: V3c<f32>,
+    /// The distance the other shape needs to move along the normal to resolve the contact
+    pub depth: f32,
+}
+
+/// An axis aligned box of voxels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Aabb {
+    /// The minimum position of the box
+    pub min: V3c<u32>,
+    /// The maximum position of the box, exclusive
+    pub max: V3c<u32>,
 }
 
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -120,4 +291,67 @@ impl Albedo {
     pub fn is_transparent(&self) -> bool {
         self.a == 0
     }
+
+    /// Creates an opaque color from the given HSV components
+    /// * `hue` - in degrees, wraps around 360
+    /// * `saturation` - in range 0..=1
+    /// * `value` - in range 0..=1
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let hue = hue.rem_euclid(360.) / 60.;
+        let saturation = saturation.clamp(0., 1.);
+        let value = value.clamp(0., 1.);
+        let chroma = value * saturation;
+        let x = chroma * (1. - (hue % 2. - 1.).abs());
+        let (r, g, b) = match hue as u32 {
+            0 => (chroma, x, 0.),
+            1 => (x, chroma, 0.),
+            2 => (0., chroma, x),
+            3 => (0., x, chroma),
+            4 => (x, 0., chroma),
+            _ => (chroma, 0., x),
+        };
+        let m = value - chroma;
+        Albedo {
+            r: ((r + m) * 255.).round() as u8,
+            g: ((g + m) * 255.).round() as u8,
+            b: ((b + m) * 255.).round() as u8,
+            a: 255,
+        }
+    }
+
+    /// Linear interpolation between the two colors, alpha included
+    /// * `t` - The weight of the other color, clamped to 0..=1
+    pub fn lerp(&self, other: &Albedo, t: f32) -> Self {
+        let t = t.clamp(0., 1.);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Albedo {
+            r: mix(self.r, other.r),
+            g: mix(self.g, other.g),
+            b: mix(self.b, other.b),
+            a: mix(self.a, other.a),
+        }
+    }
+
+    /// The relative luminance of the color in range 0..=1, alpha is not considered
+    pub fn luminance(&self) -> f32 {
+        (0.2126 * self.r as f32 + 0.7152 * self.g as f32 + 0.0722 * self.b as f32) / 255.
+    }
+
+    /// Provides the given number of colors evenly interpolated between the two colors, both ends included
+    pub fn gradient(from: &Albedo, to: &Albedo, steps: usize) -> Vec<Albedo> {
+        match steps {
+            0 => vec![],
+            1 => vec![*from],
+            _ => (0..steps)
+                .map(|i| from.lerp(to, i as f32 / (steps - 1) as f32))
+                .collect(),
+        }
+    }
+
+    /// Provides the given number of opaque colors with evenly distributed hues
+    pub fn hue_palette(count: usize, saturation: f32, value: f32) -> Vec<Albedo> {
+        (0..count)
+            .map(|i| Albedo::from_hsv(360. * i as f32 / count as f32, saturation, value))
+            .collect()
+    }
 }
@@ -3,16 +3,17 @@ use crate::octree::types::{BrickData, NodeChildrenArray};
 use crate::octree::{
     detail::{bound_contains, child_octant_for},
     types::{NodeChildren, NodeContent, OctreeError},
-    Octree, VoxelData,
+    Albedo, Octree, VoxelData,
 };
 use crate::spatial::{
-    lut::OCTANT_OFFSET_REGION_LUT,
+    lut::{BITMAP_MASK_FOR_OCTANT_LUT, OCTANT_OFFSET_REGION_LUT},
     math::{
-        hash_region, matrix_index_for, set_occupancy_in_bitmap_64bits, vector::V3c,
-        BITMAP_DIMENSION,
+        flat_projection, hash_region, matrix_index_for, position_in_bitmap_64bits,
+        set_occupancy_in_bitmap_64bits, vector::V3c, BITMAP_DIMENSION,
     },
     Cube,
 };
+use std::collections::HashMap;
 
 impl<T, const DIM: usize> Octree<T, DIM>
 where
@@ -316,6 +317,77 @@ where
         insert_size: u32,
         data: T,
     ) -> Result<(), OctreeError> {
+        let (node_stack, actual_update_size) =
+            self.insert_without_refresh(position, insert_size, data)?;
+        let position = V3c::<f32>::from(*position);
+
+        // post-processing operations
+        let mut simplifyable = self.auto_simplify; // Don't even start to simplify if it's disabled
+        for (node_key, node_bounds) in node_stack.into_iter().rev() {
+            if !self.nodes.key_is_valid(node_key as usize) {
+                continue;
+            }
+
+            // Update Node occupied bits in case of internal nodes
+            if let NodeContent::Internal(ref mut occupied_bits) =
+                self.nodes.get_mut(node_key as usize)
+            {
+                let corrected_update_size = ((actual_update_size as f32 * BITMAP_DIMENSION as f32)
+                    / node_bounds.size)
+                    .ceil() as usize;
+                set_occupancy_in_bitmap_64bits(
+                    &matrix_index_for(&node_bounds, &(position.into()), BITMAP_DIMENSION),
+                    corrected_update_size,
+                    BITMAP_DIMENSION,
+                    true,
+                    occupied_bits,
+                );
+            } else {
+                // Update the occupied bits of the leaf node
+                let mut new_occupied_bits = self.stored_occupied_bits(node_key as usize);
+                if node_bounds.size as usize == actual_update_size {
+                    new_occupied_bits = u64::MAX;
+                } else {
+                    let corrected_update_size = ((node_bounds.size * actual_update_size as f32)
+                        / (DIM as f32 * 2.))
+                        .ceil() as usize;
+                    set_occupancy_in_bitmap_64bits(
+                        &matrix_index_for(&node_bounds, &(position.into()), DIM * 2),
+                        corrected_update_size,
+                        DIM * 2,
+                        true,
+                        &mut new_occupied_bits,
+                    );
+                }
+                self.store_occupied_bits(node_key as usize, new_occupied_bits);
+            }
+            if matches!(
+                self.nodes.get(node_key as usize),
+                NodeContent::Leaf(_) | NodeContent::UniformLeaf(_)
+            ) {
+                // In case of leaf nodes, just try to simplify and continue
+                simplifyable = self.simplify(node_key as usize);
+                continue;
+            }
+
+            if simplifyable {
+                simplifyable = self.simplify(node_key as usize); // If any Nodes fail to simplify, no need to continue because their parents can not be simplified because of it
+            }
+        }
+        self.update_pending_checksums();
+        Ok(())
+    }
+
+    /// Inserts the given data at the given position and lod size without updating the occupied bits
+    /// of the nodes on the way, and without simplifying them
+    /// * Returns with the keys and bounds of the nodes the update went through starting from the root,
+    ///   and the size of the actual update
+    fn insert_without_refresh(
+        &mut self,
+        position: &V3c<u32>,
+        insert_size: u32,
+        data: T,
+    ) -> Result<(Vec<(u32, Cube)>, usize), OctreeError> {
         let root_bounds = Cube::root_bounds(self.octree_size as f32);
         let position = V3c::<f32>::from(*position);
         if !bound_contains(&root_bounds, &position) {
@@ -328,8 +400,9 @@ where
 
         // Nothing to do when data is empty
         if data.is_empty() {
-            return Ok(());
+            return Ok((Vec::new(), 0));
         }
+        self.checksums_before_update(&V3c::from(position), insert_size);
 
         // A CPU stack does not consume significant relevant resources, e.g. a 4096*4096*4096 chunk has depth of 12
         let mut node_stack = vec![(Self::ROOT_NODE_KEY, root_bounds)];
@@ -460,63 +533,407 @@ where
                 break;
             }
         }
+        Ok((node_stack, actual_update_size))
+    }
 
-        // post-processing operations
-        let mut simplifyable = self.auto_simplify; // Don't even start to simplify if it's disabled
-        for (node_key, node_bounds) in node_stack.into_iter().rev() {
-            if !self.nodes.key_is_valid(node_key as usize) {
-                continue;
+    /// Overwrites a whole brick sized block of the tree with the given data
+    /// * `brick_origin` - the minimum position of the brick, each component must be divisible by DIM
+    /// * `data` - DIM * DIM * DIM elements, with x being the fastest changing component, and z the slowest
+    pub fn insert_brick(
+        &mut self,
+        brick_origin: &V3c<u32>,
+        data: &[T],
+    ) -> Result<(), OctreeError> {
+        if !bound_contains(
+            &Cube::root_bounds(self.octree_size as f32),
+            &V3c::<f32>::from(*brick_origin),
+        ) || 0 != brick_origin.x % DIM as u32
+            || 0 != brick_origin.y % DIM as u32
+            || 0 != brick_origin.z % DIM as u32
+        {
+            return Err(OctreeError::InvalidPosition {
+                x: brick_origin.x,
+                y: brick_origin.y,
+                z: brick_origin.z,
+            });
+        }
+        if data.len() != DIM * DIM * DIM {
+            return Err(OctreeError::InvalidStructure(
+                format!(
+                    "Brick data must contain {} elements instead of {}",
+                    DIM * DIM * DIM,
+                    data.len()
+                )
+                .into(),
+            ));
+        }
+
+        // Homogeneous data can be set in one operation
+        if data.iter().all(|voxel| *voxel == data[0]) {
+            if data[0].is_empty() {
+                return self.clear_at_lod(brick_origin, DIM as u32);
             }
+            return self.insert_at_lod(brick_origin, DIM as u32, data[0]);
+        }
 
-            // Update Node occupied bits in case of internal nodes
-            if let NodeContent::Internal(ref mut occupied_bits) =
-                self.nodes.get_mut(node_key as usize)
-            {
-                let corrected_update_size = ((actual_update_size as f32 * BITMAP_DIMENSION as f32)
-                    / node_bounds.size)
-                    .ceil() as usize;
-                set_occupancy_in_bitmap_64bits(
-                    &matrix_index_for(&node_bounds, &(position.into()), BITMAP_DIMENSION),
-                    corrected_update_size,
-                    BITMAP_DIMENSION,
-                    true,
-                    occupied_bits,
-                );
-            } else {
-                // Update the occupied bits of the leaf node
-                let mut new_occupied_bits = self.stored_occupied_bits(node_key as usize);
-                if node_bounds.size as usize == actual_update_size {
-                    new_occupied_bits = u64::MAX;
-                } else {
-                    let corrected_update_size = ((node_bounds.size * actual_update_size as f32)
-                        / (DIM as f32 * 2.))
-                        .ceil() as usize;
-                    set_occupancy_in_bitmap_64bits(
-                        &matrix_index_for(&node_bounds, &(position.into()), DIM * 2),
-                        corrected_update_size,
-                        DIM * 2,
-                        true,
-                        &mut new_occupied_bits,
-                    );
+        let mut brick = Box::new([[[T::default(); DIM]; DIM]; DIM]);
+        for x in 0..DIM {
+            for y in 0..DIM {
+                for z in 0..DIM {
+                    brick[x][y][z] = data[flat_projection(x, y, z, DIM)];
                 }
-                self.store_occupied_bits(node_key as usize, new_occupied_bits);
             }
-            if matches!(
-                self.nodes.get(node_key as usize),
-                NodeContent::Leaf(_) | NodeContent::UniformLeaf(_)
-            ) {
-                // In case of leaf nodes, just try to simplify and continue
-                simplifyable = self.simplify(node_key as usize);
+        }
+        let brick_occupied_bits = BrickData::<T, DIM>::calculate_brick_occupied_bits(&brick);
+        if 0 == brick_occupied_bits {
+            // Empty voxels are not stored
+            return self.clear_at_lod(brick_origin, DIM as u32);
+        }
+        self.checksums_before_update(brick_origin, DIM as u32);
+
+        // Locate or create the leaf node containing the brick
+        let position = V3c::<f32>::from(*brick_origin);
+        let mut node_stack = vec![(
+            Self::ROOT_NODE_KEY as usize,
+            Cube::root_bounds(self.octree_size as f32),
+        )];
+        loop {
+            let (node_key, node_bounds) = *node_stack.last().unwrap();
+            if node_bounds.size <= (DIM * 2) as f32 {
+                break;
+            }
+            let target_octant = child_octant_for(&node_bounds, &position);
+            let child_key = self.node_children[node_key][target_octant as u32] as usize;
+            if !self.nodes.key_is_valid(child_key) {
+                match self.nodes.get(node_key) {
+                    NodeContent::Leaf(_) | NodeContent::UniformLeaf(_) => {
+                        // The new children of the leaf keep its contents
+                        self.subdivide_leaf_to_nodes(node_key, target_octant as usize);
+                    }
+                    NodeContent::Nothing | NodeContent::Internal(_) => {
+                        if let NodeContent::Nothing = self.nodes.get(node_key) {
+                            *self.nodes.get_mut(node_key) = NodeContent::Internal(0);
+                            self.node_children[node_key] = NodeChildren::new(empty_marker());
+                        }
+                        let new_child_key = self.nodes.push(NodeContent::Nothing);
+                        self.node_children.resize(
+                            self.node_children.len().max(self.nodes.len()),
+                            NodeChildren::new(empty_marker()),
+                        );
+                        self.node_children[node_key][target_octant as u32] = new_child_key as u32;
+                    }
+                }
+            }
+            node_stack.push((
+                self.node_children[node_key][target_octant as u32] as usize,
+                node_bounds.child_bounds_for(target_octant),
+            ));
+        }
+
+        // Write the brick into the leaf directly
+        let (leaf_key, leaf_bounds) = *node_stack.last().unwrap();
+        let brick_octant = child_octant_for(&leaf_bounds, &position) as usize;
+        let mut bricks = match std::mem::take(self.nodes.get_mut(leaf_key)) {
+            NodeContent::Nothing => std::array::from_fn(|_| BrickData::Empty),
+            NodeContent::Leaf(bricks) => bricks,
+            NodeContent::UniformLeaf(brick) => Self::divide_uniform_brick(brick),
+            NodeContent::Internal(_) => panic!("Node containing the brick expected to be a leaf!"),
+        };
+        bricks[brick_octant] = BrickData::Parted(brick);
+        *self.nodes.get_mut(leaf_key) = NodeContent::Leaf(bricks);
+
+        // The brick takes up 2x2x2 bits of the occupancy bitmap of the leaf
+        let mut leaf_occupied_bits = self.stored_occupied_bits(leaf_key);
+        let bitmap_offset =
+            V3c::<usize>::from(OCTANT_OFFSET_REGION_LUT[brick_octant]) * (BITMAP_DIMENSION / 2);
+        for octant in 0..8 {
+            set_occupancy_in_bitmap_64bits(
+                &(bitmap_offset + V3c::<usize>::from(OCTANT_OFFSET_REGION_LUT[octant])),
+                1,
+                BITMAP_DIMENSION,
+                0 != (brick_occupied_bits & BITMAP_MASK_FOR_OCTANT_LUT[octant]),
+                &mut leaf_occupied_bits,
+            );
+        }
+        self.store_occupied_bits(leaf_key, leaf_occupied_bits);
+
+        // The brick is inside one bit of the occupancy bitmap of every node above the leaf
+        for (node_key, node_bounds) in node_stack.iter().rev().skip(1) {
+            let mut occupied_bits = self.stored_occupied_bits(*node_key);
+            set_occupancy_in_bitmap_64bits(
+                &matrix_index_for(node_bounds, brick_origin, BITMAP_DIMENSION),
+                1,
+                BITMAP_DIMENSION,
+                true,
+                &mut occupied_bits,
+            );
+            self.store_occupied_bits(*node_key, occupied_bits);
+        }
+
+        if self.auto_simplify {
+            for (node_key, _) in node_stack.into_iter().rev() {
+                if !self.simplify(node_key) {
+                    break;
+                }
+            }
+        }
+        self.update_pending_checksums();
+        Ok(())
+    }
+
+    /// Provides the bricks of a leaf covering the same area as the given brick of a uniform leaf
+    fn divide_uniform_brick(brick: BrickData<T, DIM>) -> [BrickData<T, DIM>; 8] {
+        match brick {
+            BrickData::Empty => std::array::from_fn(|_| BrickData::Empty),
+            BrickData::Solid(voxel) => std::array::from_fn(|_| BrickData::Solid(voxel)),
+            BrickData::Parted(brick) => std::array::from_fn(|octant| {
+                // Each new brick is upscaled from one octant of the current brick
+                let brick_offset = V3c::<usize>::from(OCTANT_OFFSET_REGION_LUT[octant]) * (DIM / 2);
+                let mut new_brick = Box::new([[[T::default(); DIM]; DIM]; DIM]);
+                for x in 0..DIM {
+                    for y in 0..DIM {
+                        for z in 0..DIM {
+                            new_brick[x][y][z] = brick[brick_offset.x + x / 2]
+                                [brick_offset.y + y / 2][brick_offset.z + z / 2];
+                        }
+                    }
+                }
+                BrickData::Parted(new_brick)
+            }),
+        }
+    }
+
+    /// Inserts every given voxel, updating the occupied bits of the modified nodes
+    /// and simplifying the tree only once after all of them are inserted
+    /// In case of an invalid position the voxels before it remain inserted
+    pub fn insert_batch(
+        &mut self,
+        voxels: impl IntoIterator<Item = (V3c<u32>, T)>,
+    ) -> Result<(), OctreeError> {
+        self.update_batch(|tree| {
+            let mut touched_nodes = HashMap::new();
+            let result = voxels.into_iter().try_for_each(|(position, data)| {
+                let (node_stack, _) = tree.insert_without_refresh(&position, 1, data)?;
+                record_touched_nodes(&mut touched_nodes, node_stack);
+                Ok(())
+            });
+            tree.refresh_touched_nodes(touched_nodes);
+            result
+        })
+    }
+
+    /// Clears every given voxel, updating the occupied bits of the modified nodes
+    /// and simplifying the tree only once after all of them are cleared
+    /// In case of an invalid position the voxels before it remain cleared
+    pub fn clear_batch(
+        &mut self,
+        positions: impl IntoIterator<Item = V3c<u32>>,
+    ) -> Result<(), OctreeError> {
+        self.update_batch(|tree| {
+            let mut touched_nodes = HashMap::new();
+            let result = positions.into_iter().try_for_each(|position| {
+                let (node_stack, _) = tree.clear_without_refresh(&position, 1)?;
+                record_touched_nodes(&mut touched_nodes, node_stack);
+                Ok(())
+            });
+            tree.refresh_touched_nodes(touched_nodes);
+            result
+        })
+    }
+
+    /// Updates the occupied bits of the given nodes after they were modified without it, the deepest nodes first
+    /// Children left without any voxels are freed up, and internal nodes without any voxels become empty
+    /// * `touched_nodes` - the keys of the modified nodes, with their depth inside the tree
+    pub(crate) fn refresh_touched_nodes(&mut self, touched_nodes: HashMap<u32, usize>) {
+        let mut touched_nodes = touched_nodes.into_iter().collect::<Vec<_>>();
+        touched_nodes.sort_by(|(_, depth_a), (_, depth_b)| depth_b.cmp(depth_a));
+        for (node_key, _) in touched_nodes {
+            let node_key = node_key as usize;
+            if !self.nodes.key_is_valid(node_key) {
                 continue;
             }
+            match self.nodes.get(node_key) {
+                NodeContent::Nothing => {}
+                NodeContent::Leaf(_) | NodeContent::UniformLeaf(_) => {
+                    self.refresh_occupied_bits(node_key);
+                }
+                NodeContent::Internal(_) => {
+                    // Children are refreshed before their parents, so their occupied bits are up to date
+                    for octant in 0..8 {
+                        let child_key = self.node_children[node_key][octant as u32] as usize;
+                        if self.nodes.key_is_valid(child_key)
+                            && 0 == self.stored_occupied_bits(child_key)
+                        {
+                            self.deallocate_children_of(child_key as u32);
+                            self.nodes.free(child_key);
+                            self.node_children[node_key].clear(octant);
+                        }
+                    }
+                    self.refresh_occupied_bits(node_key);
+                    if 0 == self.stored_occupied_bits(node_key) {
+                        self.deallocate_children_of(node_key as u32);
+                        *self.nodes.get_mut(node_key) = NodeContent::Nothing;
+                    }
+                }
+            }
+        }
+    }
 
-            if simplifyable {
-                simplifyable = self.simplify(node_key as usize); // If any Nodes fail to simplify, no need to continue because their parents can not be simplified because of it
+    /// Fills every voxel of the given axis aligned box with the given data
+    /// The box is decomposed into the largest aligned cubes fitting inside it, which are set with
+    /// one update each, only the parts of the bricks at its boundaries are set voxel by voxel
+    /// * `region_min` - the minimum position of the box
+    /// * `region_max` - the maximum position of the box, exclusive, cut to the bounds of the tree
+    pub fn fill_region(
+        &mut self,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+        data: T,
+    ) -> Result<(), OctreeError> {
+        self.update_region(region_min, region_max, Some(data))
+    }
+
+    /// Clears every voxel of the given axis aligned box, the same way `fill_region` sets them
+    /// * `region_min` - the minimum position of the box
+    /// * `region_max` - the maximum position of the box, exclusive, cut to the bounds of the tree
+    pub fn clear_region(
+        &mut self,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+    ) -> Result<(), OctreeError> {
+        self.update_region(region_min, region_max, None)
+    }
+
+    fn update_region(
+        &mut self,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+        data: Option<T>,
+    ) -> Result<(), OctreeError> {
+        if !bound_contains(
+            &Cube::root_bounds(self.octree_size as f32),
+            &V3c::<f32>::from(*region_min),
+        ) {
+            return Err(OctreeError::InvalidPosition {
+                x: region_min.x,
+                y: region_min.y,
+                z: region_min.z,
+            });
+        }
+        let mut region_max = *region_max;
+        region_max.cut_each_component(&self.octree_size);
+        self.update_batch(|tree| {
+            tree.fill_cube_in_region(
+                &V3c::unit(0),
+                tree.octree_size,
+                region_min,
+                &region_max,
+                data,
+            )
+        })
+    }
+
+    fn fill_cube_in_region(
+        &mut self,
+        cube_min: &V3c<u32>,
+        cube_size: u32,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+        data: Option<T>,
+    ) -> Result<(), OctreeError> {
+        let cube_max = *cube_min + V3c::unit(cube_size);
+        let overlaps = cube_min.x < region_max.x
+            && cube_min.y < region_max.y
+            && cube_min.z < region_max.z
+            && region_min.x < cube_max.x
+            && region_min.y < cube_max.y
+            && region_min.z < cube_max.z;
+        if !overlaps {
+            return Ok(());
+        }
+        let contained = region_min.x <= cube_min.x
+            && region_min.y <= cube_min.y
+            && region_min.z <= cube_min.z
+            && cube_max.x <= region_max.x
+            && cube_max.y <= region_max.y
+            && cube_max.z <= region_max.z;
+        if contained && DIM as u32 <= cube_size {
+            return match data {
+                Some(data) => self.insert_at_lod(cube_min, cube_size, data),
+                None => self.clear_at_lod(cube_min, cube_size),
+            };
+        }
+        if cube_size <= DIM as u32 {
+            // Partially covered brick
+            for x in cube_min.x.max(region_min.x)..cube_max.x.min(region_max.x) {
+                for y in cube_min.y.max(region_min.y)..cube_max.y.min(region_max.y) {
+                    for z in cube_min.z.max(region_min.z)..cube_max.z.min(region_max.z) {
+                        let position = V3c::new(x, y, z);
+                        match data {
+                            Some(data) => self.insert(&position, data)?,
+                            None => self.clear(&position)?,
+                        }
+                    }
+                }
             }
+            return Ok(());
+        }
+        let child_size = cube_size / 2;
+        for offset in OCTANT_OFFSET_REGION_LUT.iter() {
+            self.fill_cube_in_region(
+                &(*cube_min + V3c::<u32>::from(*offset) * child_size),
+                child_size,
+                region_min,
+                region_max,
+                data,
+            )?;
         }
         Ok(())
     }
 
+    /// Runs the given updates with simplification disabled, then simplifies the whole tree once
+    /// Brick checksums are also updated only once, after every update is done
+    pub(crate) fn update_batch(
+        &mut self,
+        updates: impl FnOnce(&mut Self) -> Result<(), OctreeError>,
+    ) -> Result<(), OctreeError> {
+        let auto_simplify = self.auto_simplify;
+        self.auto_simplify = false;
+        let deferred_checksums = self.defer_checksums(true);
+        let result = updates(self);
+        self.defer_checksums(deferred_checksums);
+        self.auto_simplify = auto_simplify;
+        if auto_simplify {
+            self.simplify(Self::ROOT_NODE_KEY as usize);
+        }
+        self.update_pending_checksums();
+        result
+    }
+
+    /// Starts recording the regions written in the tree, to be collected by `take_modified_regions`
+    /// Recorded regions may be larger, than the written area, but they always contain it
+    pub fn track_modifications(&mut self) {
+        self.modified_regions.get_or_insert_with(Vec::new);
+    }
+
+    /// Collects the regions written since modifications are tracked, or since this was last called
+    /// * Returns with `(min_position, max_position)` pairs, the maximum position being exclusive
+    pub fn take_modified_regions(&mut self) -> Vec<(V3c<u32>, V3c<u32>)> {
+        match &mut self.modified_regions {
+            Some(regions) => std::mem::take(regions),
+            None => Vec::new(),
+        }
+    }
+
+    /// Records the given region as modified, while modifications are tracked
+    pub(crate) fn record_modified_region(&mut self, region_min: &V3c<u32>, region_max: &V3c<u32>) {
+        if let Some(regions) = &mut self.modified_regions {
+            if regions.last() != Some(&(*region_min, *region_max)) {
+                regions.push((*region_min, *region_max));
+            }
+        }
+    }
+
     /// clears the voxel at the given position
     pub fn clear(&mut self, position: &V3c<u32>) -> Result<(), OctreeError> {
         self.clear_at_lod(position, 1)
@@ -530,6 +947,117 @@ where
         position: &V3c<u32>,
         clear_size: u32,
     ) -> Result<(), OctreeError> {
+        let (mut node_stack, actual_update_size) =
+            self.clear_without_refresh(position, clear_size)?;
+        let position = V3c::<f32>::from(*position);
+
+        // post-processing operations
+        // If a whole node was removed in the operation, it has to be cleaned up properly
+        let mut removed_node = if let Some((child_key, child_bounds)) = node_stack.pop() {
+            if child_bounds.size as usize <= actual_update_size {
+                Some((child_key, child_bounds))
+            } else {
+                // The occupied bits of the updated leaf are not set by the update itself
+                if matches!(
+                    self.nodes.get(child_key as usize),
+                    NodeContent::Leaf(_) | NodeContent::UniformLeaf(_)
+                ) {
+                    self.refresh_occupied_bits(child_key as usize);
+                }
+                None
+            }
+        } else {
+            None
+        };
+        let mut simplifyable = self.auto_simplify; // Don't even start to simplify if it's disabled
+        for (node_key, node_bounds) in node_stack.into_iter().rev() {
+            let previous_occupied_bits = self.stored_occupied_bits(node_key as usize);
+            let mut new_occupied_bits = previous_occupied_bits;
+            *self.nodes.get_mut(node_key as usize) = if 0 != new_occupied_bits {
+                NodeContent::Internal(new_occupied_bits)
+            } else {
+                NodeContent::Nothing
+            };
+            if let Some((child_key, child_bounds)) = removed_node {
+                // If the child of this node was set to NodeContent::Nothing during this clear operation
+                // it needs to be freed up, and the child index of this node needs to be updated as well
+                let child_octant = hash_region(
+                    &((child_bounds.min_position - node_bounds.min_position)
+                        + V3c::unit(child_bounds.size / 2.)),
+                    node_bounds.size / 2.,
+                ) as usize;
+                self.node_children[node_key as usize].clear(child_octant);
+                self.nodes.free(child_key as usize);
+                removed_node = None;
+            };
+
+            if let NodeContent::Nothing = self.nodes.get(node_key as usize) {
+                debug_assert!(self.node_children[node_key as usize].is_empty());
+                removed_node = Some((node_key, node_bounds));
+            }
+
+            if node_bounds.size as usize == actual_update_size {
+                new_occupied_bits = 0;
+            } else {
+                // Calculate the new occupied bits of the node
+                let start_in_matrix =
+                    matrix_index_for(&node_bounds, &position.into(), (DIM * 2).max(4));
+                let bitmap_update_size = ((node_bounds.size * actual_update_size as f32)
+                    / (DIM as f32 * 2.).max(4.))
+                .ceil() as usize;
+                for x in start_in_matrix.x
+                    ..(start_in_matrix.x + bitmap_update_size).min(BITMAP_DIMENSION)
+                {
+                    for y in start_in_matrix.y
+                        ..(start_in_matrix.y + bitmap_update_size).min(BITMAP_DIMENSION)
+                    {
+                        for z in start_in_matrix.z
+                            ..(start_in_matrix.z + bitmap_update_size).min(BITMAP_DIMENSION)
+                        {
+                            if self.should_bitmap_be_empty_at_index(
+                                node_key as usize,
+                                &V3c::new(x, y, z),
+                            ) {
+                                set_occupancy_in_bitmap_64bits(
+                                    &V3c::new(x, y, z),
+                                    1,
+                                    (DIM * 2).max(4),
+                                    false,
+                                    &mut new_occupied_bits,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            debug_assert!(
+                0 != new_occupied_bits
+                    || matches!(self.nodes.get(node_key as usize), NodeContent::Nothing)
+            );
+            self.store_occupied_bits(node_key as usize, new_occupied_bits);
+
+            if simplifyable {
+                // If any Nodes fail to simplify, no need to continue because their parents can not be simplified further
+                simplifyable = self.simplify(node_key as usize);
+            }
+            if previous_occupied_bits == new_occupied_bits {
+                // In case the occupied bits were not modified, there's no need to continue
+                break;
+            }
+        }
+        self.update_pending_checksums();
+        Ok(())
+    }
+
+    /// Clears the data at the given position and lod size without updating the occupied bits
+    /// of the nodes on the way, and without freeing or simplifying them
+    /// * Returns with the keys and bounds of the nodes the update went through starting from the root,
+    ///   and the size of the actual update
+    fn clear_without_refresh(
+        &mut self,
+        position: &V3c<u32>,
+        clear_size: u32,
+    ) -> Result<(Vec<(u32, Cube)>, usize), OctreeError> {
         let position = V3c::<f32>::from(*position);
         let root_bounds = Cube::root_bounds(self.octree_size as f32);
         if !bound_contains(&root_bounds, &position) {
@@ -540,6 +1068,8 @@ where
             });
         }
 
+        self.checksums_before_update(&V3c::from(position), clear_size);
+
         // A CPU stack does not consume significant relevant resources, e.g. a 4096*4096*4096 chunk has depth of 12
         let mut node_stack = vec![(Self::ROOT_NODE_KEY, root_bounds)];
         let mut actual_update_size = 0;
@@ -650,97 +1180,326 @@ where
                 break;
             }
         }
+        Ok((node_stack, actual_update_size))
+    }
 
-        // post-processing operations
-        // If a whole node was removed in the operation, it has to be cleaned up properly
-        let mut removed_node = if let Some((child_key, child_bounds)) = node_stack.pop() {
-            if child_bounds.size as usize <= actual_update_size {
-                Some((child_key, child_bounds))
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-        let mut simplifyable = self.auto_simplify; // Don't even start to simplify if it's disabled
-        for (node_key, node_bounds) in node_stack.into_iter().rev() {
-            let previous_occupied_bits = self.stored_occupied_bits(node_key as usize);
-            let mut new_occupied_bits = previous_occupied_bits;
-            *self.nodes.get_mut(node_key as usize) = if 0 != new_occupied_bits {
-                NodeContent::Internal(new_occupied_bits)
-            } else {
-                NodeContent::Nothing
-            };
-            if let Some((child_key, child_bounds)) = removed_node {
-                // If the child of this node was set to NodeContent::Nothing during this clear operation
-                // it needs to be freed up, and the child index of this node needs to be updated as well
-                let child_octant = hash_region(
-                    &((child_bounds.min_position - node_bounds.min_position)
-                        + V3c::unit(child_bounds.size / 2.)),
-                    node_bounds.size / 2.,
-                ) as usize;
-                self.node_children[node_key as usize].clear(child_octant);
-                self.nodes.free(child_key as usize);
-                removed_node = None;
-            };
+    /// Changes the color of the voxel at the given position, if there is any.
+    /// Empty positions are left untouched, and the user data of the voxel is preserved,
+    /// so the occupancy of the tree doesn't change in the operation.
+    pub fn paint(&mut self, position: &V3c<u32>, albedo: Albedo) -> Result<(), OctreeError> {
+        self.paint_at_lod(position, 1, albedo)
+    }
 
-            if let NodeContent::Nothing = self.nodes.get(node_key as usize) {
-                debug_assert!(self.node_children[node_key as usize].is_empty());
-                removed_node = Some((node_key, node_bounds));
+    /// Changes the color of every occupied voxel inside the given region
+    /// * `position` - the minimum position of the region, must be contained within the tree
+    /// * `paint_size` - The size of the region to recolor in each dimension
+    /// * `albedo` - The color to set for the occupied voxels
+    pub fn paint_at_lod(
+        &mut self,
+        position: &V3c<u32>,
+        paint_size: u32,
+        albedo: Albedo,
+    ) -> Result<(), OctreeError> {
+        if !bound_contains(
+            &Cube::root_bounds(self.octree_size as f32),
+            &V3c::<f32>::from(*position),
+        ) {
+            return Err(OctreeError::InvalidPosition {
+                x: position.x,
+                y: position.y,
+                z: position.z,
+            });
+        }
+
+        let region_max =
+            (*position + V3c::unit(paint_size.max(1))).cut_each_component(&self.octree_size);
+        self.checksums_before_update_in(position, &region_max);
+        self.update_batch(|tree| {
+            let mut partially_painted = Vec::new();
+            tree.paint_node(
+                Self::ROOT_NODE_KEY as usize,
+                &Cube::root_bounds(tree.octree_size as f32),
+                position,
+                &region_max,
+                albedo,
+                &mut partially_painted,
+            );
+
+            // Cells only partially inside the region need to be divided, which regular updates do
+            for (cell_min, cell_max) in partially_painted {
+                for x in cell_min.x..cell_max.x {
+                    for y in cell_min.y..cell_max.y {
+                        for z in cell_min.z..cell_max.z {
+                            let voxel_position = V3c::new(x, y, z);
+                            let Some(voxel) = tree.get(&voxel_position) else {
+                                continue;
+                            };
+                            if voxel.albedo() == albedo {
+                                // Already the requested color, no need to touch the structure
+                                continue;
+                            }
+                            let painted = T::new(albedo, voxel.user_data());
+                            tree.insert(&voxel_position, painted)?;
+                        }
+                    }
+                }
             }
+            Ok(())
+        })
+    }
 
-            if node_bounds.size as usize == actual_update_size {
-                new_occupied_bits = 0;
-            } else {
-                // Calculate the new occupied bits of the node
-                let start_in_matrix =
-                    matrix_index_for(&node_bounds, &position.into(), (DIM * 2).max(4));
-                let bitmap_update_size = ((node_bounds.size * actual_update_size as f32)
-                    / (DIM as f32 * 2.).max(4.))
-                .ceil() as usize;
-                for x in start_in_matrix.x
-                    ..(start_in_matrix.x + bitmap_update_size).min(BITMAP_DIMENSION)
+    /// Recolors the voxels of the given node inside the given region in place
+    /// Cells only partially inside the region can not be recolored without dividing them,
+    /// their part inside the region is collected instead
+    fn paint_node(
+        &mut self,
+        node_key: usize,
+        node_bounds: &Cube,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+        albedo: Albedo,
+        partially_painted: &mut Vec<(V3c<u32>, V3c<u32>)>,
+    ) {
+        if let NodeContent::Internal(_) = self.nodes.get(node_key) {
+            for octant in 0..8 {
+                let child_key = self.node_children[node_key][octant as u32] as usize;
+                let child_bounds = node_bounds.child_bounds_for(octant);
+                if self.nodes.key_is_valid(child_key)
+                    && cell_overlap(&child_bounds, region_min, region_max).is_some()
                 {
-                    for y in start_in_matrix.y
-                        ..(start_in_matrix.y + bitmap_update_size).min(BITMAP_DIMENSION)
-                    {
-                        for z in start_in_matrix.z
-                            ..(start_in_matrix.z + bitmap_update_size).min(BITMAP_DIMENSION)
-                        {
-                            if self.should_bitmap_be_empty_at_index(
-                                node_key as usize,
-                                &V3c::new(x, y, z),
-                            ) {
-                                set_occupancy_in_bitmap_64bits(
-                                    &V3c::new(x, y, z),
-                                    1,
-                                    (DIM * 2).max(4),
-                                    false,
-                                    &mut new_occupied_bits,
-                                );
+                    self.paint_node(
+                        child_key,
+                        &child_bounds,
+                        region_min,
+                        region_max,
+                        albedo,
+                        partially_painted,
+                    );
+                }
+            }
+            return;
+        }
+
+        match self.nodes.get_mut(node_key) {
+            NodeContent::Nothing | NodeContent::Internal(_) => {}
+            NodeContent::UniformLeaf(brick) => Self::paint_brick(
+                brick,
+                node_bounds,
+                region_min,
+                region_max,
+                albedo,
+                partially_painted,
+            ),
+            NodeContent::Leaf(bricks) => {
+                for (octant, brick) in bricks.iter_mut().enumerate() {
+                    Self::paint_brick(
+                        brick,
+                        &node_bounds.child_bounds_for(octant as u8),
+                        region_min,
+                        region_max,
+                        albedo,
+                        partially_painted,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Recolors the voxels of the given brick inside the given region in place
+    /// Voxels covering multiple positions, which are only partially inside the region are collected instead
+    fn paint_brick(
+        brick: &mut BrickData<T, DIM>,
+        brick_bounds: &Cube,
+        region_min: &V3c<u32>,
+        region_max: &V3c<u32>,
+        albedo: Albedo,
+        partially_painted: &mut Vec<(V3c<u32>, V3c<u32>)>,
+    ) {
+        let Some((overlap_min, overlap_max)) = cell_overlap(brick_bounds, region_min, region_max)
+        else {
+            return;
+        };
+        let brick_min = V3c::<u32>::from(brick_bounds.min_position);
+        let brick_size = brick_bounds.size as u32;
+        match brick {
+            BrickData::Empty => {}
+            BrickData::Solid(voxel) => {
+                if overlap_min == brick_min && overlap_max == brick_min + V3c::unit(brick_size) {
+                    if let Some(painted) = painted_voxel(voxel, albedo) {
+                        *voxel = painted;
+                    }
+                } else if brick_size != DIM as u32 {
+                    partially_painted.push((overlap_min, overlap_max));
+                } else if painted_voxel(voxel, albedo).is_some_and(|painted| painted != *voxel) {
+                    // Each voxel of the brick covers one position, so they can be recolored one by one
+                    *brick = BrickData::Parted(Box::new([[[*voxel; DIM]; DIM]; DIM]));
+                    Self::paint_brick(
+                        brick,
+                        brick_bounds,
+                        region_min,
+                        region_max,
+                        albedo,
+                        partially_painted,
+                    );
+                }
+            }
+            BrickData::Parted(voxels) => {
+                let cell_size = brick_size / DIM as u32;
+                let first = (overlap_min - brick_min) / cell_size;
+                let last = V3c::new(
+                    (overlap_max.x - brick_min.x).div_ceil(cell_size),
+                    (overlap_max.y - brick_min.y).div_ceil(cell_size),
+                    (overlap_max.z - brick_min.z).div_ceil(cell_size),
+                );
+                for x in first.x..last.x {
+                    for y in first.y..last.y {
+                        for z in first.z..last.z {
+                            let cell_min = brick_min + V3c::new(x, y, z) * cell_size;
+                            let cell_max = cell_min + V3c::unit(cell_size);
+                            let inside_min = V3c::new(
+                                cell_min.x.max(region_min.x),
+                                cell_min.y.max(region_min.y),
+                                cell_min.z.max(region_min.z),
+                            );
+                            let inside_max = V3c::new(
+                                cell_max.x.min(region_max.x),
+                                cell_max.y.min(region_max.y),
+                                cell_max.z.min(region_max.z),
+                            );
+                            let voxel = &mut voxels[x as usize][y as usize][z as usize];
+                            if inside_min != cell_min || inside_max != cell_max {
+                                if !voxel.is_empty() {
+                                    partially_painted.push((inside_min, inside_max));
+                                }
+                            } else if let Some(painted) = painted_voxel(voxel, albedo) {
+                                *voxel = painted;
                             }
                         }
                     }
                 }
             }
-            debug_assert!(
-                0 != new_occupied_bits
-                    || matches!(self.nodes.get(node_key as usize), NodeContent::Nothing)
-            );
-            self.store_occupied_bits(node_key as usize, new_occupied_bits);
+        }
+    }
+    /// Doubles the size of the octree, placing the current contents into the given octant of the new root
+    /// * `octant` - The octant of the new root node to contain the current data, must be smaller, than 8
+    /// * Returns with the offset applied to the positions of every already contained voxel
+    pub fn grow(&mut self, octant: u8) -> Result<V3c<u32>, OctreeError> {
+        if 8 <= octant {
+            return Err(OctreeError::InvalidStructure(
+                format!("Invalid octant to place the current root into: {octant}").into(),
+            ));
+        }
+        let Some(new_size) = self.octree_size.checked_mul(2) else {
+            return Err(OctreeError::InvalidStructure(
+                "Octree size can not be increased any further".into(),
+            ));
+        };
+        let offset = V3c::<u32>::from(OCTANT_OFFSET_REGION_LUT[octant as usize]) * self.octree_size;
+        self.octree_size = new_size;
+        self.move_checksums(&offset, false);
+        if let NodeContent::Nothing = self.nodes.get(Self::ROOT_NODE_KEY as usize) {
+            // Nothing to move, the empty root node stays as is
+            return Ok(offset);
+        }
 
-            if simplifyable {
-                // If any Nodes fail to simplify, no need to continue because their parents can not be simplified further
-                simplifyable = self.simplify(node_key as usize);
-            }
-            if previous_occupied_bits == new_occupied_bits {
-                // In case the occupied bits were not modified, there's no need to continue
-                break;
+        // Move the current root into a new node, and make it the only child of the root
+        let old_occupied_bits = self.stored_occupied_bits(Self::ROOT_NODE_KEY as usize);
+        let old_root_content = std::mem::take(self.nodes.get_mut(Self::ROOT_NODE_KEY as usize));
+        let old_root_children = self.node_children[Self::ROOT_NODE_KEY as usize];
+        let new_child_key = self.nodes.push(old_root_content);
+        self.node_children.resize(
+            self.node_children.len().max(new_child_key + 1),
+            NodeChildren::new(empty_marker()),
+        );
+        self.node_children[new_child_key] = old_root_children;
+        self.node_children[Self::ROOT_NODE_KEY as usize] = NodeChildren::new(empty_marker());
+        self.node_children[Self::ROOT_NODE_KEY as usize][octant as u32] = new_child_key as u32;
+
+        // Each bit in the new occupancy bitmap covers 2x2x2 bits of the old one
+        let bitmap_offset =
+            V3c::<usize>::from(OCTANT_OFFSET_REGION_LUT[octant as usize]) * (BITMAP_DIMENSION / 2);
+        let mut new_occupied_bits = 0;
+        for x in 0..BITMAP_DIMENSION {
+            for y in 0..BITMAP_DIMENSION {
+                for z in 0..BITMAP_DIMENSION {
+                    let old_bit = position_in_bitmap_64bits(&V3c::new(x, y, z), BITMAP_DIMENSION);
+                    if 0 != (old_occupied_bits & (0x01 << old_bit)) {
+                        set_occupancy_in_bitmap_64bits(
+                            &(bitmap_offset + V3c::new(x / 2, y / 2, z / 2)),
+                            1,
+                            BITMAP_DIMENSION,
+                            true,
+                            &mut new_occupied_bits,
+                        );
+                    }
+                }
             }
         }
+        *self.nodes.get_mut(Self::ROOT_NODE_KEY as usize) =
+            NodeContent::Internal(new_occupied_bits);
+        Ok(offset)
+    }
+
+    /// Increases the size of the octree to the given size, keeping the current contents at the origin
+    /// The existing nodes are placed under new root nodes, no voxels are re-inserted
+    /// * `new_size` - must be a valid size of the tree, not smaller, than the current size
+    pub fn grow_to(&mut self, new_size: u32) -> Result<(), OctreeError> {
+        let (smaller_valid, larger_valid) = Self::valid_sizes_around(new_size, DIM as u32);
+        if new_size < self.octree_size || larger_valid != Some(new_size) {
+            return Err(OctreeError::InvalidSize {
+                size: new_size,
+                brick_dim: DIM as u32,
+                smaller_valid,
+                larger_valid,
+            });
+        }
+        while self.octree_size < new_size {
+            self.grow(0)?;
+        }
         Ok(())
     }
 
+    /// Reduces the size of the octree while every contained voxel fits inside one octant of the root node
+    /// The size of the tree is kept above DIM, and empty trees are not modified
+    /// * Returns with the offset subtracted from the positions of every contained voxel
+    pub fn shrink_to_fit(&mut self) -> V3c<u32> {
+        let mut offset = V3c::new(0, 0, 0);
+        while DIM < (self.octree_size / 2) as usize
+            && self.is_node_internal(Self::ROOT_NODE_KEY as usize)
+        {
+            let mut valid_children = self.node_children[Self::ROOT_NODE_KEY as usize]
+                .iter()
+                .into_iter()
+                .flatten()
+                .enumerate()
+                .filter(|(_octant, child_key)| self.nodes.key_is_valid(**child_key as usize));
+            let (Some((octant, child_key)), None) = (valid_children.next(), valid_children.next())
+            else {
+                break;
+            };
+            let child_key = *child_key as usize;
+
+            // Move the only child into the root node
+            let child_content = self.nodes.pop(child_key).unwrap();
+            *self.nodes.get_mut(Self::ROOT_NODE_KEY as usize) = child_content;
+            self.node_children[Self::ROOT_NODE_KEY as usize] = self.node_children[child_key];
+            self.node_children[child_key] = NodeChildren::new(empty_marker());
+
+            self.octree_size /= 2;
+            offset += V3c::<u32>::from(OCTANT_OFFSET_REGION_LUT[octant]) * self.octree_size;
+        }
+        self.move_checksums(&offset, true);
+        offset
+    }
+
+    /// Releases the memory of unused nodes at the end of the node storage, e.g. after mass clears
+    /// Node keys in use are not changed, so unused nodes between them are kept for reuse
+    pub fn trim(&mut self) {
+        self.nodes.trim();
+        self.node_children.truncate(self.nodes.len());
+        self.node_children.shrink_to_fit();
+    }
+
     /// Updates the given node recursively to collapse nodes with uniform children into a leaf
     /// Returns with true if the given node was simplified
     pub(crate) fn simplify(&mut self, node_key: usize) -> bool {
@@ -861,19 +1620,16 @@ where
                         return false;
                     };
 
-                    // Try to simplify each child of the node
-                    self.simplify(child_keys[0] as usize);
+                    // Try to simplify each child of the node, even if the node itself can't be simplified
+                    for child_key in child_keys.iter() {
+                        self.simplify(*child_key as usize);
+                    }
 
                     if !self.nodes.key_is_valid(child_keys[0] as usize) {
-                        // At least try to simplify the siblings
-                        for child_key in child_keys.iter().skip(1) {
-                            self.simplify(*child_key as usize);
-                        }
                         return false;
                     }
 
                     for octant in 1..8 {
-                        self.simplify(child_keys[octant] as usize);
                         if !self.nodes.key_is_valid(child_keys[octant] as usize)
                             || (self.nodes.get(child_keys[0] as usize)
                                 != self.nodes.get(child_keys[octant] as usize))
@@ -905,3 +1661,41 @@ where
         }
     }
 }
+
+/// Provides the part of the given cell inside the given region, if they overlap
+fn cell_overlap(
+    cell: &Cube,
+    region_min: &V3c<u32>,
+    region_max: &V3c<u32>,
+) -> Option<(V3c<u32>, V3c<u32>)> {
+    let cell_min = V3c::<u32>::from(cell.min_position);
+    let cell_max = cell_min + V3c::unit(cell.size as u32);
+    let overlap_min = V3c::new(
+        cell_min.x.max(region_min.x),
+        cell_min.y.max(region_min.y),
+        cell_min.z.max(region_min.z),
+    );
+    let overlap_max = V3c::new(
+        cell_max.x.min(region_max.x),
+        cell_max.y.min(region_max.y),
+        cell_max.z.min(region_max.z),
+    );
+    (overlap_min.x < overlap_max.x
+        && overlap_min.y < overlap_max.y
+        && overlap_min.z < overlap_max.z)
+        .then_some((overlap_min, overlap_max))
+}
+
+/// Provides the given voxel with the given color, keeping its user data
+/// * Returns with None if the voxel is empty, or it would become empty, so the occupancy of the tree is kept
+fn painted_voxel<T: VoxelData>(voxel: &T, albedo: Albedo) -> Option<T> {
+    let painted = T::new(albedo, voxel.user_data());
+    (!voxel.is_empty() && !painted.is_empty()).then_some(painted)
+}
+
+/// Stores the nodes an update went through, with their depth inside the tree
+fn record_touched_nodes(touched_nodes: &mut HashMap<u32, usize>, node_stack: Vec<(u32, Cube)>) {
+    for (depth, (node_key, _)) in node_stack.into_iter().enumerate() {
+        touched_nodes.insert(node_key, depth);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::octree::{types::OctreeError, Octree, V3c, VoxelData};
+use std::{
+    collections::HashSet,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+/// The generator filling the staging tree of a sector, with the minimum position of the sector
+type SectorGenerator<T, const DIM: usize> = dyn Fn(&V3c<u32>, &mut Octree<T, DIM>) + Send + Sync;
+
+/// Generates the sectors of a world tree on worker threads, nearest to the focus point first
+/// Each sector is generated into its own staging tree, which is merged into the world tree by `update`
+/// Merged sectors are edits like any other, so a renderer sharing the world tree streams them
+/// to the GPU through its regular upload budget
+pub struct WorldGen<T, const DIM: usize>
+where
+    T: Default + Eq + Clone + Copy + VoxelData + Send + 'static,
+{
+    sector_size: u32,
+    focus: V3c<f32>,
+
+    /// Sectors waiting for a worker, the nearest to the focus point being the last
+    queue: Vec<V3c<u32>>,
+
+    /// Sectors generated or being generated, and the number of those being generated
+    requested: HashSet<V3c<u32>>,
+    in_flight: usize,
+    worker_count: usize,
+
+    jobs: Option<mpsc::Sender<V3c<u32>>>,
+    results: mpsc::Receiver<(V3c<u32>, Option<Octree<T, DIM>>)>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl<T, const DIM: usize> WorldGen<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData + Send + 'static,
+{
+    /// Creates the driver and starts its worker threads
+    /// * `sector_size` - the size of the generated sectors in each dimension, needs to be a valid tree size
+    /// * `worker_count` - the number of worker threads, at least 1
+    /// * `generator` - fills the empty staging tree of the sector starting at the given position,
+    /// if it panics, merging the sector fails with an error
+    pub fn new<F>(sector_size: u32, worker_count: usize, generator: F) -> Result<Self, OctreeError>
+    where
+        F: Fn(&V3c<u32>, &mut Octree<T, DIM>) + Send + Sync + 'static,
+    {
+        Octree::<T, DIM>::new(sector_size)?;
+        let worker_count = worker_count.max(1);
+        let generator: Arc<SectorGenerator<T, DIM>> = Arc::new(generator);
+        let (job_sender, job_receiver) = mpsc::channel::<V3c<u32>>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let workers = (0..worker_count)
+            .map(|_| {
+                let job_receiver = job_receiver.clone();
+                let result_sender = result_sender.clone();
+                let generator = generator.clone();
+                thread::spawn(move || loop {
+                    let job = job_receiver.lock().unwrap().recv();
+                    let Ok(sector_min) = job else {
+                        break;
+                    };
+                    // A panicking generator only fails its own sector, the worker keeps running
+                    let staging = Octree::new(sector_size).ok().and_then(|mut staging| {
+                        panic::catch_unwind(AssertUnwindSafe(|| {
+                            generator(&sector_min, &mut staging)
+                        }))
+                        .ok()?;
+                        Some(staging)
+                    });
+                    if result_sender.send((sector_min, staging)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            sector_size,
+            focus: V3c::unit(0.),
+            queue: Vec::new(),
+            requested: HashSet::new(),
+            in_flight: 0,
+            worker_count,
+            jobs: Some(job_sender),
+            results,
+            workers,
+        })
+    }
+
+    pub fn sector_size(&self) -> u32 {
+        self.sector_size
+    }
+
+    /// The number of requested sectors not yet merged into the world tree
+    pub fn pending_count(&self) -> usize {
+        self.queue.len() + self.in_flight
+    }
+
+    /// True if the sector containing the given position was requested, even if it is not yet merged
+    pub fn is_requested(&self, position: &V3c<u32>) -> bool {
+        self.requested.contains(&self.sector_of(position))
+    }
+
+    /// Provides the minimum position of the sector containing the given position
+    fn sector_of(&self, position: &V3c<u32>) -> V3c<u32> {
+        V3c::new(
+            position.x - position.x % self.sector_size,
+            position.y - position.y % self.sector_size,
+            position.z - position.z % self.sector_size,
+        )
+    }
+
+    /// Squared distance between the focus point and the center of the given sector
+    fn priority_of(&self, sector_min: &V3c<u32>) -> f32 {
+        let center = V3c::<f32>::from(*sector_min) + V3c::unit(self.sector_size as f32 / 2.);
+        let offset = center - self.focus;
+        offset.dot(&offset)
+    }
+
+    /// Requests every ungenerated sector of the world tree with its center inside the given sphere
+    /// Sectors still waiting for a worker are reordered based on the new focus point
+    /// * `world` - the tree the sectors are merged into
+    /// * `focus` - the point to generate around, e.g. the position of the camera
+    /// * `radius` - the radius of the generated area around the focus point
+    pub fn request_around(&mut self, world: &Octree<T, DIM>, focus: &V3c<f32>, radius: f32) {
+        self.focus = *focus;
+        let world_size = world.get_size() as f32;
+        let lower = |component: f32| (component - radius).clamp(0., world_size - 1.) as u32;
+        let upper = |component: f32| (component + radius).clamp(0., world_size).ceil() as u32;
+        let first = self.sector_of(&V3c::new(lower(focus.x), lower(focus.y), lower(focus.z)));
+        let max = V3c::new(upper(focus.x), upper(focus.y), upper(focus.z));
+        for x in (first.x..max.x).step_by(self.sector_size as usize) {
+            for y in (first.y..max.y).step_by(self.sector_size as usize) {
+                for z in (first.z..max.z).step_by(self.sector_size as usize) {
+                    let sector_min = V3c::new(x, y, z);
+                    if self.priority_of(&sector_min) <= radius * radius
+                        && self.requested.insert(sector_min)
+                    {
+                        self.queue.push(sector_min);
+                    }
+                }
+            }
+        }
+        let mut queue = std::mem::take(&mut self.queue);
+        queue.sort_by(|a, b| self.priority_of(b).total_cmp(&self.priority_of(a)));
+        self.queue = queue;
+        self.dispatch();
+    }
+
+    /// Hands out the nearest queued sectors to the idle workers
+    fn dispatch(&mut self) {
+        let Some(jobs) = &self.jobs else {
+            return;
+        };
+        while self.in_flight < self.worker_count {
+            let Some(sector_min) = self.queue.pop() else {
+                break;
+            };
+            if jobs.send(sector_min).is_err() {
+                self.queue.push(sector_min);
+                break;
+            }
+            self.in_flight += 1;
+        }
+    }
+
+    /// Merges the given staging tree into the world tree at the position of its sector
+    fn merge_sector(
+        &mut self,
+        world: &mut Octree<T, DIM>,
+        sector_min: V3c<u32>,
+        staging: Option<Octree<T, DIM>>,
+    ) -> Result<(), OctreeError> {
+        self.in_flight -= 1;
+        let Some(staging) = staging else {
+            return Err(OctreeError::InvalidStructure(
+                format!("Staging tree of sector {sector_min:?} could not be generated").into(),
+            ));
+        };
+        let region = staging.copy_region(&V3c::unit(0), &V3c::unit(self.sector_size));
+        world.paste_region(&region, &sector_min)
+    }
+
+    /// Merges every finished sector into the world tree without waiting for the workers,
+    /// then hands out the next sectors in the order of their distance from the focus point
+    /// * `world` - the tree the sectors are merged into
+    /// * `budget` - the maximum number of sectors to merge in this call
+    /// * Returns with the minimum positions of the merged sectors, e.g. to mark them dirty in other structures
+    pub fn update(
+        &mut self,
+        world: &mut Octree<T, DIM>,
+        budget: usize,
+    ) -> Result<Vec<V3c<u32>>, OctreeError> {
+        let mut merged = Vec::new();
+        while merged.len() < budget {
+            let Ok((sector_min, staging)) = self.results.try_recv() else {
+                break;
+            };
+            self.merge_sector(world, sector_min, staging)?;
+            merged.push(sector_min);
+        }
+        self.dispatch();
+        Ok(merged)
+    }
+
+    /// Waits until every requested sector is generated and merged into the world tree
+    /// * Returns with the minimum positions of the merged sectors
+    pub fn finish(&mut self, world: &mut Octree<T, DIM>) -> Result<Vec<V3c<u32>>, OctreeError> {
+        let mut merged = Vec::new();
+        while 0 < self.pending_count() {
+            self.dispatch();
+            let Ok((sector_min, staging)) = self.results.recv() else {
+                return Err(OctreeError::InvalidStructure(
+                    "World generation workers stopped unexpectedly".into(),
+                ));
+            };
+            self.merge_sector(world, sector_min, staging)?;
+            merged.push(sector_min);
+        }
+        Ok(merged)
+    }
+}
+
+impl<T, const DIM: usize> Drop for WorldGen<T, DIM>
+where
+    T: Default + Eq + Clone + Copy + VoxelData + Send + 'static,
+{
+    fn drop(&mut self) {
+        // Closing the job channel stops the workers after their current sector
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
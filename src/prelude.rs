@@ -0,0 +1,12 @@
+//! The commonly used types of the library, to be imported with `use shocovox_rs::prelude::*;`
+
+pub use crate::octree::{Albedo, Octree, V3c, VoxelData};
+
+#[cfg(feature = "raytracing")]
+pub use crate::octree::raytracing::{Ray, Viewport};
+
+#[cfg(feature = "bevy_wgpu")]
+pub use crate::octree::raytracing::{
+    OctreeGPUHost, OctreeGPUView, OctreeRenderData, OctreeSpyGlass, RenderBevyPlugin, SvxLabel,
+    SvxViewSet, Tonemapper,
+};
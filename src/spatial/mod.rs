@@ -3,8 +3,9 @@ pub mod lut;
 
 pub mod math;
 
+/// Ray geometry shared by the raytracing implementations, publicly available through `octree::raytracing`
 #[cfg(feature = "raytracing")]
-pub mod raytracing;
+pub(crate) mod raytracing;
 
 mod tests;
 
@@ -37,4 +38,30 @@ impl Cube {
             size: child_size,
         }
     }
+
+    /// Calculates the volume of the intersection of the cube and the given axis aligned region
+    /// * `region_min` - the minimum position of the region
+    /// * `region_max` - the maximum position of the region
+    pub(crate) fn overlap_volume(&self, region_min: &V3c<f32>, region_max: &V3c<f32>) -> f32 {
+        let max_position = self.min_position + V3c::unit(self.size);
+        let overlap = |min: f32, max: f32, other_min: f32, other_max: f32| {
+            (max.min(other_max) - min.max(other_min)).max(0.)
+        };
+        overlap(
+            self.min_position.x,
+            max_position.x,
+            region_min.x,
+            region_max.x,
+        ) * overlap(
+            self.min_position.y,
+            max_position.y,
+            region_min.y,
+            region_max.y,
+        ) * overlap(
+            self.min_position.z,
+            max_position.z,
+            region_min.z,
+            region_max.z,
+        )
+    }
 }
@@ -4,6 +4,13 @@ mod tests;
 
 pub(crate) const FLOAT_ERROR_TOLERANCE: f32 = 0.00001;
 
+/// Provides the tolerance to use while comparing distances inside an area of the given size
+/// The precision of floating point numbers decreases with their magnitude,
+/// so a fixed tolerance is too strict for large trees
+pub(crate) fn float_error_tolerance_for(size: f32) -> f32 {
+    FLOAT_ERROR_TOLERANCE.max(size * f32::EPSILON * 4.)
+}
+
 #[derive(Debug)]
 pub struct Ray {
     pub origin: V3c<f32>,
@@ -20,6 +27,69 @@ impl Ray {
     }
 }
 
+/// One of the six sides of a voxel
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VoxelFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl VoxelFace {
+    /// Provides the face the given normal is pointing out of, based on its dominant component
+    pub fn from_normal(normal: &V3c<f32>) -> Self {
+        let abs_normal = V3c::new(normal.x.abs(), normal.y.abs(), normal.z.abs());
+        if abs_normal.x >= abs_normal.y && abs_normal.x >= abs_normal.z {
+            if 0. <= normal.x {
+                VoxelFace::PositiveX
+            } else {
+                VoxelFace::NegativeX
+            }
+        } else if abs_normal.y >= abs_normal.z {
+            if 0. <= normal.y {
+                VoxelFace::PositiveY
+            } else {
+                VoxelFace::NegativeY
+            }
+        } else if 0. <= normal.z {
+            VoxelFace::PositiveZ
+        } else {
+            VoxelFace::NegativeZ
+        }
+    }
+
+    /// The unit vector pointing out of the face
+    pub fn normal(&self) -> V3c<f32> {
+        match self {
+            VoxelFace::PositiveX => V3c::new(1., 0., 0.),
+            VoxelFace::NegativeX => V3c::new(-1., 0., 0.),
+            VoxelFace::PositiveY => V3c::new(0., 1., 0.),
+            VoxelFace::NegativeY => V3c::new(0., -1., 0.),
+            VoxelFace::PositiveZ => V3c::new(0., 0., 1.),
+            VoxelFace::NegativeZ => V3c::new(0., 0., -1.),
+        }
+    }
+
+    /// Projects the given position relative to the voxel onto the 2D coordinates of the face
+    /// X faces are mapped to (z, y), Y faces to (x, z) and Z faces to (x, y)
+    pub fn uv_of(&self, position_in_voxel: &V3c<f32>) -> [f32; 2] {
+        match self {
+            VoxelFace::PositiveX | VoxelFace::NegativeX => {
+                [position_in_voxel.z, position_in_voxel.y]
+            }
+            VoxelFace::PositiveY | VoxelFace::NegativeY => {
+                [position_in_voxel.x, position_in_voxel.z]
+            }
+            VoxelFace::PositiveZ | VoxelFace::NegativeZ => {
+                [position_in_voxel.x, position_in_voxel.y]
+            }
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Default)]
 pub struct CubeRayIntersection {
     pub(crate) impact_distance: Option<f32>,
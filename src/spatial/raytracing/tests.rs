@@ -1,6 +1,32 @@
 #[cfg(test)]
 mod raytracing_tests {
-    use crate::spatial::{raytracing::plane_line_intersection, raytracing::Ray, Cube, V3c};
+    use crate::spatial::{
+        raytracing::{
+            float_error_tolerance_for, plane_line_intersection, Ray, VoxelFace,
+            FLOAT_ERROR_TOLERANCE,
+        },
+        Cube, V3c,
+    };
+
+    #[test]
+    fn test_voxel_face_from_normal() {
+        assert!(VoxelFace::from_normal(&V3c::new(1., 0., 0.)) == VoxelFace::PositiveX);
+        assert!(VoxelFace::from_normal(&V3c::new(0., -1., 0.)) == VoxelFace::NegativeY);
+        assert!(VoxelFace::from_normal(&V3c::new(0.2, 0.3, -0.9)) == VoxelFace::NegativeZ);
+        assert!(VoxelFace::from_normal(&V3c::new(0., 0., 1.)).normal() == V3c::new(0., 0., 1.));
+        assert!(
+            VoxelFace::PositiveY.uv_of(&V3c::new(0.1, 0.2, 0.3)) == [0.1, 0.3],
+            "Y faces should be mapped to (x, z)"
+        );
+    }
+
+    #[test]
+    fn test_float_error_tolerance_scales_with_size() {
+        assert!(float_error_tolerance_for(2.) == FLOAT_ERROR_TOLERANCE);
+        assert!(float_error_tolerance_for(65536.) > FLOAT_ERROR_TOLERANCE);
+        assert!(float_error_tolerance_for(65536.) < 1.);
+        assert!(float_error_tolerance_for(1024.) <= float_error_tolerance_for(65536.));
+    }
 
     #[test]
     fn test_plane_line_intersection() {
@@ -92,3 +92,21 @@ mod bitmask_tests {
         assert!(42 == position_in_bitmap_64bits(&V3c::new(1, 1, 1), 2));
     }
 }
+
+#[cfg(test)]
+mod cube_tests {
+    use crate::spatial::{Cube, V3c};
+
+    #[test]
+    fn test_overlap_volume() {
+        let cube = Cube {
+            min_position: V3c::new(2., 2., 2.),
+            size: 2.,
+        };
+        assert!(cube.overlap_volume(&V3c::unit(0.), &V3c::unit(8.)) == 8.);
+        assert!(cube.overlap_volume(&V3c::unit(3.), &V3c::unit(8.)) == 1.);
+        assert!(cube.overlap_volume(&V3c::unit(0.), &V3c::new(3., 8., 8.)) == 4.);
+        assert!(cube.overlap_volume(&V3c::unit(4.), &V3c::unit(8.)) == 0.);
+        assert!(cube.overlap_volume(&V3c::unit(0.), &V3c::unit(1.)) == 0.);
+    }
+}